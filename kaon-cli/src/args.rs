@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use clap::{Arg, Command};
 use termcolor::ColorChoice;
 
+use crate::history::DEFAULT_HISTORY_SIZE;
+
 #[derive(Debug)]
 pub enum Coloring {
     Auto,
@@ -13,6 +15,8 @@ pub enum Coloring {
 pub struct Args {
     pub file: Option<PathBuf>,
     pub color: Coloring,
+    /// The maximum number of entries kept in the REPL history.
+    pub history_size: usize,
 }
 
 impl Default for Args {
@@ -44,6 +48,14 @@ impl Args {
                     .require_equals(true)
                     .default_missing_value("always")
                     .help("Coloring: auto, always or never"),
+            )
+            .arg(
+                Arg::new("history-size")
+                    .long("history-size")
+                    .value_name("SIZE")
+                    .takes_value(true)
+                    .validator(|size| size.parse::<usize>())
+                    .help("Number of REPL history entries to keep"),
             );
 
         let matches = app.get_matches();
@@ -57,7 +69,16 @@ impl Args {
             _ => Coloring::Always,
         };
 
-        Self { file, color }
+        let history_size = matches
+            .value_of("history-size")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_SIZE);
+
+        Self {
+            file,
+            color,
+            history_size,
+        }
     }
 
     pub fn color_preference(&self) -> ColorChoice {
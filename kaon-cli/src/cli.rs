@@ -1,12 +1,19 @@
 //! CLI for the Kaon language.
 
 use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 pub use crate::args::Args;
-use rustyline::{error::ReadlineError, Editor};
+use crate::history;
+use rustyline::{error::ReadlineError, Config, Editor};
 use termcolor::{self, Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-use kaon::{common::Source, error::Error, Kaon, KaonError, Value, Scope};
+use kaon::common::{Source, Span};
+use kaon::compiler::typecheck::Symbol as TypeSymbol;
+use kaon::compiler::{ASTNode, Resolver, Stmt, Symbol, Type, TypeChecker, AST};
+use kaon::error::Errors;
+use kaon::{Kaon, KaonError, Scope, Value};
 
 #[derive(Default)]
 pub struct Styles {
@@ -52,12 +59,17 @@ pub struct ReplConfig {
     /// Sets the color preference of output.
     preference: ColorChoice,
     styles: Styles,
+    /// The file history is loaded from and saved to, if any.
+    history_path: Option<PathBuf>,
+    /// The maximum number of history entries to keep.
+    history_size: usize,
 }
 
 pub struct Repl {
     kaon: Kaon,
     config: ReplConfig,
-    continued_lines: Vec<String>,
+    /// Globals declared earlier in the session.
+    scope: Scope,
 }
 
 impl Repl {
@@ -65,153 +77,60 @@ impl Repl {
         Self {
             kaon: Kaon::default(),
             config,
-            continued_lines: Vec::new(),
+            scope: Scope::new(),
         }
     }
 
-    pub fn read_multiline(&mut self, editor: &mut Editor<()>) -> Result<String, String> {
-        loop {
-            let readline = editor.readline("... ");
-            match readline {
+    /// Keep reading continuation lines until the input is complete.
+    ///
+    /// An empty line submits the input as it is. Returns `None` if reading was interrupted.
+    fn read_multiline(&mut self, editor: &mut Editor<()>, mut input: String) -> Option<String> {
+        while !is_input_complete(&input) {
+            match editor.readline("... ") {
+                Ok(line) if line.trim().is_empty() => break,
                 Ok(line) => {
-                    editor.add_history_entry(line.to_string());
-
-                    if line.is_empty() {
-                        break;
-                    } else {
-                        self.continued_lines.push(line);
-                    }
+                    input.push('\n');
+                    input.push_str(&line);
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("CTRL-C");
-                    break;
-                }
-                Err(ReadlineError::Eof) => {
-                    println!("CTRL-D");
-                    break;
+                    return None;
                 }
+                Err(ReadlineError::Eof) => break,
                 Err(err) => {
                     println!("{}", err);
-                    break;
+                    return None;
                 }
             }
         }
 
-        let script = self.continued_lines.join("\n");
-
-        Ok(script)
+        Some(input)
     }
 
     pub fn run_repl(&mut self) -> Result<(), String> {
-        let mut editor = Editor::<()>::new();
-        let mut stdout = StandardStream::stdout(self.config.preference);
+        let config = Config::builder()
+            .max_history_size(self.config.history_size)
+            .build();
+        let mut editor = Editor::<()>::with_config(config);
 
-        //let scope = core::prelude();//.unwrap();
+        if let Some(path) = &self.config.history_path {
+            match history::load_history(path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        editor.add_history_entry(entry);
+                    }
+                }
+                Err(err) => println!("Could not load history: {err}"),
+            }
+        }
+        let mut stdout = StandardStream::stdout(self.config.preference);
 
         println!("Welcome to Kaon v{}", self.config.version);
-        println!("Type \".help\" for more information");
+        println!("Type \":help\" for more information");
 
         loop {
-            let readline = editor.readline("> ");
-
-            match readline {
-                Ok(line) => match &line[..] {
-                    ".help" => {
-                        println!("Welcome to Kaon!");
-                        println!();
-                        println!("Press CTRL-D to exit the REPL");
-                    }
-                    line => {
-                        editor.add_history_entry(line);
-
-                        let source = Source::new(line, "REPL");
-                        let tokens = match self.kaon.tokenize(source) {
-                            Ok(tokens) => tokens,
-                            Err(err) => {
-                                println!("{err}");
-                                continue;
-                            }
-                        };
-
-                        let ast = match self.kaon.parse(tokens) {
-                            Ok(ast) => ast,
-                            Err(KaonError::ParserError(err)) => match err {
-                                Error::UnexpectedEOF(_) => {
-                                    self.continued_lines.push(line.to_string());
-                                    let input = self.read_multiline(&mut editor)?;
-
-                                    match self.kaon.parse_from_script(&input) {
-                                        Ok(ast) => ast,
-                                        Err(err) => {
-                                            println!("{err}");
-                                            continue;
-                                        }
-                                    }
-                                }
-                                error => {
-                                    println!("{error}");
-                                    continue;
-                                }
-                            },
-                            Err(err) => {
-                                println!("{err}");
-                                continue;
-                            }
-                        };
-
-                        let mut scope = Scope::new();
-
-                        match self.kaon.compile_ast(ast, &mut scope) {
-                            Ok((_, _)) => {
-                                //scope = globals;
-                            }
-                            Err(err) => {
-                                println!("{err}");
-                                continue;
-                            }
-                        }
-
-                        match self.kaon.run() {
-                            Ok(result) => {
-                                match result {
-                                    Value::Unit => continue,
-                                    Value::Float(_) | Value::Integer(_) => {
-                                        stdout.set_color(&self.config.styles.number).unwrap();
-                                    }
-                                    Value::Boolean(_) => {
-                                        stdout.set_color(&self.config.styles.boolean).unwrap();
-                                    }
-                                    Value::String(_) => {
-                                        stdout.set_color(&self.config.styles.string).unwrap();
-                                    }
-                                    Value::List(_) | Value::Tuple(_) | Value::Map(_) => {
-                                        stdout.set_color(&self.config.styles.white).unwrap()
-                                    }
-                                    Value::NativeFun(_)
-                                    | Value::Function(_)
-                                    | Value::Closure(_)
-                                    | Value::Class(_)
-                                    | Value::Instance(_)
-                                    | Value::Constructor(_)
-                                    | Value::Method(_) => {
-                                        stdout.set_color(&self.config.styles.fun).unwrap();
-                                    }
-                                    Value::Nil => {
-                                        stdout.set_color(&self.config.styles.nil).unwrap();
-                                    }
-                                }
-
-                                writeln!(&mut stdout, "{result}").unwrap();
-
-                                stdout.set_color(&self.config.styles.white).unwrap();
-                            }
-                            Err(err) => {
-                                println!("{err}");
-                                continue;
-                            }
-                        }
-                    }
-                },
+            let line = match editor.readline("> ") {
+                Ok(line) => line,
                 Err(ReadlineError::Interrupted) => {
                     println!("CTRL-C");
                     break;
@@ -224,11 +143,305 @@ impl Repl {
                     println!("{}", err);
                     break;
                 }
+            };
+
+            match parse_command(&line) {
+                Ok(Some(command)) => {
+                    editor.add_history_entry(&line);
+
+                    if command == Command::Quit {
+                        break;
+                    }
+
+                    self.run_command(command);
+                    continue;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    println!("{err}");
+                    continue;
+                }
+            }
+
+            let input = match self.read_multiline(&mut editor, line) {
+                Some(input) => input,
+                None => continue,
+            };
+
+            editor.add_history_entry(&input);
+
+            match self.eval(Source::from_string("repl", &input)) {
+                Ok((value, true)) => {
+                    self.print_value(&mut stdout, &value);
+                    self.set_previous_result(value);
+                }
+                Ok((_, false)) => {}
+                Err(err) => println!("{err}"),
+            }
+        }
+
+        if let Some(path) = &self.config.history_path {
+            let entries = editor.history().iter().cloned().collect::<Vec<_>>();
+            if let Err(err) = history::save_history(path, &entries, self.config.history_size) {
+                println!("Could not save history: {err}");
             }
         }
 
         Ok(())
     }
+
+    /// Run a REPL command, other than [Command::Quit].
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Help => {
+                println!("Welcome to Kaon!");
+                println!();
+                println!("Commands:");
+                println!("  :help          Show this message");
+                println!("  :load <file>   Run a file in the current session");
+                println!("  :type <expr>   Show the type of an expression, without running it");
+                println!("  :reset         Clear all globals");
+                println!("  :quit          Exit the REPL");
+                println!();
+                println!("Press CTRL-D to exit the REPL");
+            }
+            Command::Load(path) => {
+                let result = self
+                    .kaon
+                    .read_file(PathBuf::from(path))
+                    .map_err(|err| err.to_string())
+                    .and_then(|source| self.eval(source));
+
+                if let Err(err) = result {
+                    println!("{err}");
+                }
+            }
+            Command::Type(expr) => match self.type_of(expr) {
+                Ok(typ) => println!("{typ}"),
+                Err(err) => println!("{err}"),
+            },
+            Command::Reset => {
+                self.kaon = Kaon::default();
+                self.scope = Scope::new();
+            }
+            Command::Quit => {}
+        }
+    }
+
+    /// Compile and run a script in the current session.
+    ///
+    /// Returns the result, and whether it should be echoed back to the user.
+    fn eval(&mut self, source: Rc<Source>) -> Result<(Value, bool), String> {
+        let tokens = self.kaon.tokenize(source).map_err(|err| err.to_string())?;
+        let ast = self.kaon.parse(tokens).map_err(|err| err.to_string())?;
+        let echo = should_echo(&ast);
+
+        let (_, scope) = self
+            .kaon
+            .compile_ast(ast, &mut self.scope)
+            .map_err(|err| err.to_string())?;
+        self.scope = scope;
+
+        let value = self.kaon.run().map_err(|err| err.to_string())?;
+
+        Ok((value, echo))
+    }
+
+    /// Infer the type of an expression without running it, so that calls in it
+    /// have no side effects.
+    ///
+    /// Globals of the session are typed by their current values. Anything the
+    /// typechecker can't infer, such as the result of a call, is `any`.
+    fn type_of(&mut self, expr: &str) -> Result<Type, String> {
+        let tokens = self
+            .kaon
+            .tokenize(Source::from_string("repl", expr))
+            .map_err(|err| err.to_string())?;
+        let ast = self.kaon.parse(tokens).map_err(|err| err.to_string())?;
+
+        let expr = match &ast.nodes[..] {
+            [ASTNode::Expr(expr)] | [ASTNode::Stmt(Stmt::Expr(expr))] => expr,
+            _ => return Err("`:type` expects a single expression".to_string()),
+        };
+
+        let mut resolver = Resolver::with_scope(&mut self.scope);
+        resolver.resolve_ast(&ast);
+        if !resolver.errors.is_empty() {
+            let errors = Errors::from(resolver.errors);
+            return Err(KaonError::MultipleErrors(errors).to_string());
+        }
+
+        let mut typechecker = TypeChecker::new();
+        self.kaon.vm.visit_globals(|name, value| {
+            typechecker.env[0].insert(TypeSymbol::new(name), value_type(value));
+        });
+
+        typechecker.check_expr(expr).map_err(|err| {
+            KaonError::MultipleErrors(Errors::from(vec![err])).to_string()
+        })
+    }
+
+    /// Bind the result of the last expression to `_`.
+    fn set_previous_result(&mut self, value: Value) {
+        if !self.scope.has_symbol("_") {
+            self.scope.insert(Symbol("_".to_string(), Span::empty()));
+        }
+
+        self.kaon
+            .vm
+            .context
+            .borrow_mut()
+            .globals
+            .insert("_".to_string(), value);
+    }
+
+    fn print_value(&mut self, stdout: &mut StandardStream, value: &Value) {
+        match value {
+            Value::Unit => return,
+            Value::Float(_) | Value::Integer(_) => {
+                stdout.set_color(&self.config.styles.number).unwrap();
+            }
+            Value::Boolean(_) => {
+                stdout.set_color(&self.config.styles.boolean).unwrap();
+            }
+            Value::String(_) | Value::Bytes(_) => {
+                stdout.set_color(&self.config.styles.string).unwrap();
+            }
+            Value::List(_) | Value::Tuple(_) | Value::Map(_) => {
+                stdout.set_color(&self.config.styles.white).unwrap()
+            }
+            Value::NativeFun(_)
+            | Value::Function(_)
+            | Value::Closure(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::Constructor(_)
+            | Value::Method(_)
+            | Value::External(_)
+            | Value::Enum(_)
+            | Value::Variant(_) => {
+                stdout.set_color(&self.config.styles.fun).unwrap();
+            }
+            Value::Nil => {
+                stdout.set_color(&self.config.styles.nil).unwrap();
+            }
+        }
+
+        match self.kaon.vm.display_value(value) {
+            Ok(text) => writeln!(stdout, "{text}").unwrap(),
+            Err(err) => writeln!(stdout, "{err}").unwrap(),
+        }
+
+        stdout.set_color(&self.config.styles.white).unwrap();
+    }
+}
+
+/// Check if the input ends with a bare expression, whose value should be
+/// printed, rather than a statement like `var x = 1`.
+pub fn should_echo(ast: &AST) -> bool {
+    matches!(
+        ast.nodes.last(),
+        Some(ASTNode::Expr(_) | ASTNode::Stmt(Stmt::Expr(_)))
+    )
+}
+
+/// The type of a value held by the session, as far as the typechecker knows it.
+fn value_type(value: &Value) -> Type {
+    match value {
+        Value::Float(_) => Type::Float,
+        Value::Integer(_) => Type::Int,
+        Value::Boolean(_) => Type::Bool,
+        Value::String(_) => Type::String,
+        Value::List(_) => Type::List(Box::new(Type::Any)),
+        Value::Class(_) => Type::Class,
+        Value::Unit => Type::Void,
+        _ => Type::Any,
+    }
+}
+
+/// A colon-prefixed REPL command.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    Help,
+    Load(&'a str),
+    Type(&'a str),
+    Reset,
+    Quit,
+}
+
+/// Parse a REPL command, returning `None` if the input isn't a command.
+pub fn parse_command(input: &str) -> Result<Option<Command<'_>>, String> {
+    let command = match input.trim().strip_prefix(':') {
+        Some(command) => command,
+        None => return Ok(None),
+    };
+
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+
+    let command = match (name, arg) {
+        ("help", "") => Command::Help,
+        ("reset", "") => Command::Reset,
+        ("quit", "") => Command::Quit,
+        ("help" | "reset" | "quit", _) => {
+            return Err(format!("`:{name}` doesn't take an argument"));
+        }
+        ("load" | "type", "") => return Err(format!("`:{name}` expects an argument")),
+        ("load", path) => Command::Load(path),
+        ("type", expr) => Command::Type(expr),
+        _ => {
+            return Err(format!(
+                "unknown command `:{name}`, type \":help\" for a list of commands"
+            ))
+        }
+    };
+
+    Ok(Some(command))
+}
+
+/// Check if the REPL input forms a complete statement.
+///
+/// Input is incomplete if it has unclosed brackets or strings, or ends with an
+/// operator that expects another operand.
+pub fn is_input_complete(input: &str) -> bool {
+    let mut depth = 0;
+    let mut last = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => loop {
+                match chars.next() {
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some('"') => break,
+                    Some(_) => {}
+                    None => return false,
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+                continue;
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+
+        if !c.is_whitespace() {
+            last = Some(c);
+        }
+    }
+
+    let trailing_operator = matches!(
+        last,
+        Some('+' | '-' | '*' | '/' | '%' | '=' | '<' | '>' | '&' | '|' | ',' | '.')
+    );
+
+    depth <= 0 && !trailing_operator
 }
 
 pub fn run(args: Args) -> ! {
@@ -237,6 +450,8 @@ pub fn run(args: Args) -> ! {
             version: env!("CARGO_PKG_VERSION").to_string(),
             preference: args.color_preference(),
             styles: Styles::new(),
+            history_path: history::default_history_path(),
+            history_size: args.history_size,
         })
         .run_repl()
         {
@@ -245,3 +460,86 @@ pub fn run(args: Args) -> ! {
         },
     )
 }
+
+#[cfg(test)]
+mod test {
+    use kaon::common::Source;
+    use kaon::compiler::Type;
+    use kaon::{Kaon, Value};
+    use termcolor::ColorChoice;
+
+    use super::{is_input_complete, parse_command, should_echo, Command, Repl, ReplConfig, Styles};
+
+    #[test]
+    fn multi_line_function() {
+        let lines = ["fun add(a, b) {", "    return a +", "        b", "}"];
+
+        for end in 1..lines.len() {
+            assert!(!is_input_complete(&lines[..end].join("\n")));
+        }
+        assert!(is_input_complete(&lines.join("\n")));
+    }
+
+    #[test]
+    fn brackets_in_strings_and_comments() {
+        assert!(is_input_complete("print(\"{\")"));
+        assert!(is_input_complete("var x = 1 // {"));
+        assert!(!is_input_complete("var x = \"unterminated"));
+        assert!(!is_input_complete("var list = ["));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command(":help"), Ok(Some(Command::Help)));
+        assert_eq!(parse_command("  :quit  "), Ok(Some(Command::Quit)));
+        assert_eq!(parse_command(":reset"), Ok(Some(Command::Reset)));
+        assert_eq!(
+            parse_command(":load examples/fib.kaon"),
+            Ok(Some(Command::Load("examples/fib.kaon")))
+        );
+        assert_eq!(
+            parse_command(":type 1 + 2"),
+            Ok(Some(Command::Type("1 + 2")))
+        );
+
+        assert_eq!(parse_command("1 + 2"), Ok(None));
+        assert!(parse_command(":load").is_err());
+        assert!(parse_command(":quit now").is_err());
+        assert!(parse_command(":exit").is_err());
+    }
+
+    #[test]
+    fn echo_expressions() {
+        let echo = |input| should_echo(&Kaon::new().parse_from_script(input).unwrap());
+
+        assert!(echo("1 + 2"));
+        assert!(echo("var x = 1\nx"));
+        assert!(echo("print(\"hello\")"));
+
+        assert!(!echo("var x = 1"));
+        assert!(!echo("fun f() {}"));
+        assert!(!echo("if true { 1 }"));
+    }
+
+    #[test]
+    fn type_without_running() {
+        let mut repl = Repl::with_config(ReplConfig {
+            version: String::new(),
+            preference: ColorChoice::Never,
+            styles: Styles::default(),
+            history_path: None,
+            history_size: 0,
+        });
+
+        let script = "var count = 0\nfun bump() {\n    count = count + 1\n    return count\n}";
+        repl.eval(Source::from_string("repl", script)).unwrap();
+
+        assert_eq!(repl.type_of("1 + 2"), Ok(Type::Float));
+        assert_eq!(repl.type_of("count == 0"), Ok(Type::Bool));
+        assert_eq!(repl.type_of("bump()"), Ok(Type::Any));
+        assert_eq!(repl.kaon.vm.get_global("count"), Some(Value::Float(0.0)));
+
+        assert!(repl.type_of("missing").is_err());
+        assert!(repl.type_of("var x = 1").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Persistent REPL history.
+//!
+//! History is stored one entry per line. Newlines and backslashes inside an
+//! entry are escaped, so multi-line input is recalled as a single entry.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The default number of entries kept in the history file.
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// The default location of the history file, `~/.kaon_history`.
+pub fn default_history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".kaon_history"))
+}
+
+/// Load history entries from `path`.
+///
+/// A missing file is treated as an empty history.
+pub fn load_history(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().map(unescape).collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Save the most recent `max_size` entries to `path`.
+pub fn save_history(path: &Path, entries: &[String], max_size: usize) -> io::Result<()> {
+    let start = entries.len().saturating_sub(max_size);
+
+    let mut contents = String::new();
+    for entry in &entries[start..] {
+        contents.push_str(&escape(entry));
+        contents.push('\n');
+    }
+
+    fs::write(path, contents)
+}
+
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            (c, _) => entry.push(c),
+        }
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load_history, save_history};
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("kaon_history_{}", std::process::id()));
+
+        let entries = vec![
+            "var x = 1".to_string(),
+            "fun f() {\n    return \"a\\nb\"\n}".to_string(),
+            "print(x)".to_string(),
+        ];
+
+        save_history(&path, &entries, 10).unwrap();
+        assert_eq!(load_history(&path).unwrap(), entries);
+
+        save_history(&path, &entries, 2).unwrap();
+        assert_eq!(load_history(&path).unwrap(), entries[1..]);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_history(&path).unwrap().is_empty());
+    }
+}
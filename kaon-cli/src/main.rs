@@ -1,6 +1,8 @@
 mod args;
 mod cli;
+mod history;
 
+use kaon::error::Emitter;
 use kaon::{Kaon, KaonError, Scope};
 
 fn main() -> Result<(), KaonError> {
@@ -12,7 +14,13 @@ fn main() -> Result<(), KaonError> {
             let mut prelude = Scope::new();//core::prelude();//.map_err(|e| KaonError::ParserError(e))?; //kaon.read_file("kaon/src/core/core.kaon".into())?;
             let source = kaon.read_file(path)?;
 
-            kaon.run_with_scope(&mut prelude, source)?;
+            kaon.compile_with_scope(&mut prelude, source)?;
+
+            for warning in kaon.warnings() {
+                warning.emit(&[warning.report()]);
+            }
+
+            kaon.run()?;
         }
         None => cli::run(args),
     }
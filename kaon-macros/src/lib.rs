@@ -2,7 +2,7 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{self, punctuated::Punctuated, token::Comma, DataEnum, Variant};
+use syn::{self, punctuated::Punctuated, token::Comma, DataEnum, ImplItem, ItemImpl, Variant};
 
 #[derive(Debug)]
 struct Value {
@@ -78,3 +78,48 @@ fn parse_variants(variants: &Punctuated<Variant, Comma>) -> Result<Vec<Value>, S
 
     Ok(variants)
 }
+
+/// Register the associated functions of an `impl` block as the functions of a
+/// Kaon module.
+///
+/// Adds a `module()` function to the type, returning a
+/// `kaon::common::ModuleBuilder` with each associated function that doesn't
+/// take `self` registered under its own name. Methods taking `self` are left
+/// out, since there is no receiver to call them with.
+#[proc_macro_attribute]
+pub fn kaon_module(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ast: ItemImpl = match syn::parse(item) {
+        Ok(ast) => ast,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    module_impl(&ast)
+}
+
+fn module_impl(ast: &ItemImpl) -> TokenStream {
+    let self_ty = &ast.self_ty;
+    let (impl_generics, _, where_clause) = ast.generics.split_for_impl();
+
+    let functions = ast.items.iter().filter_map(|item| match item {
+        ImplItem::Method(method) if method.sig.receiver().is_none() => {
+            let ident = &method.sig.ident;
+            let name = ident.to_string();
+            Some(quote! { .function(#name, Self::#ident) })
+        }
+        _ => None,
+    });
+
+    let gen = quote! {
+        #ast
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Build a module from this type's associated functions.
+            pub fn module() -> ::kaon::common::ModuleBuilder {
+                ::kaon::common::ModuleBuilder::new()
+                    #(#functions)*
+            }
+        }
+    };
+
+    gen.into()
+}
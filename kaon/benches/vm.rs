@@ -0,0 +1,35 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use kaon::{Kaon, Scope, Source};
+
+/// A loop that loads lists, strings and constants from locals and globals on
+/// every iteration, which is dominated by pushing values onto the stack.
+const LIST_LOOP: &str = "\
+var list = [1, 2, 3, 4, 5, 6, 7, 8]
+var total = 0
+
+fun work() {
+    var local = list
+    var i = 0
+    while i < 2000 {
+        var alias = local
+        var name = \"kaon\"
+        if name == \"kaon\" {
+            total = total + alias[i % 8] + 0.5
+        }
+        i = i + 1
+    }
+    return total
+}
+
+work()";
+
+fn list_loop(c: &mut Criterion) {
+    let mut kaon = Kaon::new();
+    kaon.compile_with_scope(&mut Scope::new(), Source::contents(LIST_LOOP))
+        .unwrap();
+
+    c.bench_function("list loop", |b| b.iter(|| kaon.run().unwrap()));
+}
+
+criterion_group!(benches, list_loop);
+criterion_main!(benches);
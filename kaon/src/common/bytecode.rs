@@ -1,27 +1,53 @@
-use crate::common::{Span, Value};
+use std::rc::Rc;
+
+use crate::common::{Opcode, Span, Value};
 
 use super::Function;
 
+/// Maps opcode offsets back to the [Span] they were compiled from.
+///
+/// The map is run-length encoded: each entry holds the offset of the first
+/// opcode compiled from a span, and covers every opcode up to the offset of
+/// the next entry. Consecutive opcodes from the same span share one entry.
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct DebugInfo {
+    /// A list of `(offset, span)` pairs, sorted by offset.
     pub source_map: Vec<(usize, Span)>,
 }
 
 impl DebugInfo {
+    /// Record that the opcodes starting at `ip` originate from `span`.
+    ///
+    /// Offsets must be pushed in increasing order.
     pub fn push(&mut self, ip: usize, span: Span) {
+        if let Some((_, last)) = self.source_map.last() {
+            if last.start == span.start
+                && last.length == span.length
+                && Rc::ptr_eq(&last.source, &span.source)
+            {
+                return;
+            }
+        }
+
         self.source_map.push((ip, span));
     }
 
-    pub fn get_source(&self, ip: usize) -> Option<Span> {
-        let mut result = None;
+    /// Get the [Span] of the opcode at the given offset.
+    ///
+    /// Returns `None` for an offset before the first recorded opcode.
+    pub fn span_at(&self, ip: usize) -> Option<Span> {
+        let index = self.source_map.partition_point(|(offset, _)| *offset <= ip);
 
-        for entry in self.source_map.iter() {
-            if ip <= entry.0 {
-                result = Some(entry.1.clone());
-            }
+        match index {
+            0 => None,
+            _ => Some(self.source_map[index - 1].1.clone()),
         }
+    }
 
-        result
+    /// Get the [Span] of the opcode at the given offset.
+    #[deprecated(note = "use `DebugInfo::span_at` instead")]
+    pub fn get_source(&self, ip: usize) -> Option<Span> {
+        self.span_at(ip)
     }
 }
 
@@ -62,6 +88,40 @@ impl Chunk {
     pub fn emit_span(&mut self, span: Span) {
         self.debug_info.push(self.opcodes.len(), span)
     }
+
+    /// Iterate over the instructions in the chunk.
+    ///
+    /// Each instruction is an `(offset, opcode, operands)` tuple, where the
+    /// operands are the raw bytes following the opcode. The operands of a
+    /// truncated final instruction are cut short rather than read out of bounds,
+    /// and iteration stops at the first byte that isn't a valid opcode.
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            opcodes: &self.opcodes,
+            offset: 0,
+        }
+    }
+}
+
+/// An iterator over the instructions of a [Chunk], created by [Chunk::instructions].
+pub struct Instructions<'a> {
+    opcodes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = (usize, Opcode, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        let opcode = Opcode::try_from(*self.opcodes.get(offset)?).ok()?;
+
+        let start = offset + 1;
+        let end = (start + opcode.operand_width()).min(self.opcodes.len());
+        self.offset = start + opcode.operand_width();
+
+        Some((offset, opcode, &self.opcodes[start..end]))
+    }
 }
 
 pub enum ChunkConstant {
@@ -1,5 +1,11 @@
-use crate::common::{Chunk, Opcode};
+use std::fmt::Write;
 
+use crate::common::{Chunk, Opcode, Value};
+
+/// Prints a human readable listing of a [Chunk].
+///
+/// Each line shows the byte offset, the opcode name, its decoded operand
+/// and, where the operand refers to a constant or identifier, its value.
 pub struct Disassembler<'a> {
     name: &'a str,
     chunk: &'a Chunk,
@@ -10,117 +16,135 @@ impl<'a> Disassembler<'a> {
         Disassembler { name, chunk }
     }
 
+    /// Disassemble the chunk to stdout.
     pub fn disassemble(&self) {
-        println!("Disassembling {}", self.name);
-        let mut offset = 0;
+        print!("{}", self.disassemble_to_string());
+    }
 
-        println!("Code");
-        while offset < self.chunk.opcodes.len() {
-            offset = self.disassemble_instruction(offset);
-        }
+    /// Disassemble the chunk into a [String].
+    pub fn disassemble_to_string(&self) -> String {
+        let mut output = String::new();
+        self.write_chunk(&mut output, 0);
+        output
+    }
 
-        println!("Constants");
-        for con in &self.chunk.constants {
-            print!("[ {} ]", con);
-        }
+    fn write_chunk(&self, output: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+        writeln!(output, "{indent}== {} ==", self.name).unwrap();
 
-        println!();
+        for (offset, opcode, operands) in self.chunk.instructions() {
+            self.write_instruction(output, offset, opcode, operands, depth);
+        }
     }
 
-    fn disassemble_instruction(&self, offset: usize) -> usize {
-        let byte = self.chunk.opcodes[offset];
-        match Opcode::from(byte) {
-            Opcode::Const => self.byte_instruction("Const", offset),
-            Opcode::True => self.simple_instruction("True", offset),
-            Opcode::False => self.simple_instruction("False", offset),
-            Opcode::Nil => self.simple_instruction("Nil", offset),
-            Opcode::Add => self.simple_instruction("Add", offset),
-            Opcode::Sub => self.simple_instruction("Subtract", offset),
-            Opcode::Mul => self.simple_instruction("Multiply", offset),
-            Opcode::Div => self.simple_instruction("Divide", offset),
-            Opcode::Mod => self.simple_instruction("Modulo", offset),
-            Opcode::Negate => self.simple_instruction("Negate", offset),
-            Opcode::Equal => self.simple_instruction("EqualsTo", offset),
-            Opcode::NotEqual => self.simple_instruction("NotEqual", offset),
-            Opcode::Gte => self.simple_instruction("GreaterThanEquals", offset),
-            Opcode::Lte => self.simple_instruction("LessThanEquals", offset),
-            Opcode::Gt => self.simple_instruction("GreaterThan", offset),
-            Opcode::Lt => self.simple_instruction("LessThan", offset),
-            Opcode::Not => self.simple_instruction("Not", offset),
-            Opcode::DefGlobal => self.byte_instruction("DefGlobal", offset),
-            Opcode::SetGlobal => self.byte_instruction("SetGlobal", offset),
-            Opcode::GetGlobal => self.byte_instruction("GetGlobal", offset),
-            Opcode::LoadLocal => self.byte_instruction("LoadLocal", offset),
-            Opcode::SaveLocal => self.byte_instruction("SaveLocal", offset),
-            Opcode::LoadUpValue => self.byte_instruction("LoadUpValue", offset),
-            Opcode::SaveUpValue => self.byte_instruction("SaveUpValue", offset),
-            Opcode::CloseUpValue => self.simple_instruction("CloseUpValue", offset),
-            Opcode::Jump => self.short_instruction("Jump", offset),
-            Opcode::JumpIfFalse => self.short_instruction("JumpIfFalse", offset),
-            Opcode::JumpIfTrue => self.short_instruction("JumpIfTrue", offset),
-            Opcode::Call => self.simple_instruction("Call", offset),
-            Opcode::Closure => self.closure(offset),
-            Opcode::Return => self.simple_instruction("Return", offset),
-            Opcode::Pop => self.simple_instruction("Del", offset),
-            Opcode::Class => self.byte_instruction("Class", offset),
-            Opcode::List => self.byte_instruction("List", offset),
-            Opcode::Tuple => self.byte_instruction("Tuple", offset),
-            Opcode::Map => self.byte_instruction("Map", offset),
-            Opcode::GetIndex => self.byte_instruction("GetIndex", offset),
-            Opcode::Get => self.byte_instruction("Get", offset),
-            Opcode::Set => self.byte_instruction("Set", offset),
-            Opcode::Loop => self.short_instruction("Loop", offset),
-            Opcode::Halt => self.simple_instruction("Halt", offset),
-            _ => unimplemented!()
+    fn write_instruction(
+        &self,
+        output: &mut String,
+        offset: usize,
+        opcode: Opcode,
+        operands: &[u8],
+        depth: usize,
+    ) {
+        let name = format!("{opcode:?}");
+        let indent = "    ".repeat(depth);
+
+        let mut line = format!("{indent}{offset:04} {name:<14}");
+
+        match opcode {
+            Opcode::Const => {
+                let index = operand(operands, 0);
+                self.write_operand(&mut line, index, self.constant(index));
+            }
+            Opcode::String
+            | Opcode::DefGlobal
+            | Opcode::SetGlobal
+            | Opcode::GetGlobal
+            | Opcode::Get
+            | Opcode::Set
+            | Opcode::GetSuper
+            | Opcode::IsVariant => {
+                let index = operand(operands, 0);
+                self.write_operand(&mut line, index, self.variable(index));
+            }
+            Opcode::LoadLocal
+            | Opcode::SaveLocal
+            | Opcode::LoadUpValue
+            | Opcode::SaveUpValue
+            | Opcode::Call
+            | Opcode::PopN
+            | Opcode::List
+            | Opcode::Tuple
+            | Opcode::Map => {
+                write!(line, " {:>4}", operand(operands, 0)).unwrap();
+            }
+            Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::JumpIfTrue
+            | Opcode::JumpIfStop
+            | Opcode::Try => {
+                let jump = short(operands);
+                let target = format!("-> {:04}", offset + 3 + jump);
+                self.write_operand(&mut line, jump, target);
+            }
+            Opcode::Loop => {
+                let jump = short(operands);
+                let target = format!("-> {:04}", (offset + 3).saturating_sub(jump));
+                self.write_operand(&mut line, jump, target);
+            }
+            Opcode::Class => {
+                let index = operand(operands, 0);
+                let comment = format!(
+                    "{} (methods: {}, constructors: {}, statics: {}, fields: {})",
+                    self.variable(index),
+                    operand(operands, 1),
+                    operand(operands, 2),
+                    operand(operands, 3),
+                    operand(operands, 4),
+                );
+                self.write_operand(&mut line, index, comment);
+            }
+            Opcode::Closure => {
+                let index = operand(operands, 0);
+                self.write_operand(&mut line, index, self.constant(index));
+                writeln!(output, "{}", line.trim_end()).unwrap();
+
+                if let Some(Value::Function(fun)) = self.chunk.constants.get(index).map(|c| &**c) {
+                    Disassembler::new(&fun.name, &fun.chunk).write_chunk(output, depth + 1);
+                }
+
+                return;
+            }
+            _ => {}
         }
-    }
 
-    fn simple_instruction(&self, name: &str, offset: usize) -> usize {
-        self.write_instruction(name, offset);
-        println!();
-        offset + 1
+        writeln!(output, "{}", line.trim_end()).unwrap();
     }
 
-    fn byte_instruction(&self, name: &str, offset: usize) -> usize {
-        self.write_instruction(name, offset);
-        let index = &self.chunk.opcodes[offset + 1];
-        let i_padding =
-            " ".repeat(self.chunk.constants.len().to_string().len() - index.to_string().len());
-        print!("{}{}", i_padding, index);
-
-        self.write_value(*index as usize);
-
-        offset + 2
+    fn write_operand(&self, line: &mut String, operand: usize, comment: String) {
+        write!(line, " {operand:>4} ; {comment}").unwrap();
     }
 
-    fn short_instruction(&self, name: &str, offset: usize) -> usize {
-        self.write_instruction(name, offset);
-        let index = &self.chunk.opcodes[offset + 1];
-        let i_padding =
-            " ".repeat(self.chunk.constants.len().to_string().len() - index.to_string().len());
-        print!("{}{}", i_padding, index);
-
-        self.write_value(*index as usize);
-
-        offset + 3
+    fn constant(&self, index: usize) -> String {
+        match self.chunk.constants.get(index) {
+            Some(constant) => constant.to_string(),
+            None => "<invalid constant>".to_string(),
+        }
     }
 
-    fn closure(&self, offset: usize) -> usize {
-        let constant = self.chunk.opcodes[offset + 2];
-        println!("{}", constant);
-
-        self.write_value(constant as usize);
-
-        offset + 2
+    fn variable(&self, index: usize) -> String {
+        match self.chunk.variables.get(index) {
+            Some(variable) => format!("{variable:?}"),
+            None => "<invalid identifier>".to_string(),
+        }
     }
+}
 
-    fn write_instruction(&self, name: &str, offset: usize) {
-        let padding =
-            " ".repeat(self.chunk.opcodes.len().to_string().len() - offset.to_string().len());
-        print!("{}{}: {:<14}", padding, offset, name);
-    }
+/// Read the operand byte at `index`, or zero if the instruction was truncated.
+fn operand(operands: &[u8], index: usize) -> usize {
+    operands.get(index).copied().unwrap_or_default() as usize
+}
 
-    fn write_value(&self, index: usize) {
-        println!(" ; {}", self.chunk.constants[index]);
-    }
+/// Read a big-endian `u16` jump offset.
+fn short(operands: &[u8]) -> usize {
+    (operand(operands, 0) << 8) | operand(operands, 1)
 }
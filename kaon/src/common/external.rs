@@ -0,0 +1,171 @@
+//! Rust objects exposed to scripts.
+//!
+//! An [External] wraps arbitrary Rust data together with a [MetaMap] of native
+//! methods, so that a script can call `object.method(...)` on it:
+//!
+//! ```
+//! # use kaon::common::External;
+//! struct Counter(i64);
+//!
+//! let counter = External::builder(Counter(0))
+//!     .method("increment", |counter: &mut Counter, _args| {
+//!         counter.0 += 1;
+//!         counter.0
+//!     })
+//!     .build();
+//! ```
+//!
+//! A method named `to_string` is used to display the object when it's printed.
+
+use std::any::{self, Any};
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+
+use fnv::FnvHashMap;
+
+use crate::common::{NativeFun, NativeResult, Value};
+use crate::runtime::Vm;
+
+/// The data owned by an [External].
+pub type ExternalData = Rc<RefCell<dyn Any>>;
+
+/// A table of the methods callable on an [External], keyed by name.
+#[derive(Default)]
+pub struct MetaMap {
+    methods: FnvHashMap<Box<str>, Rc<NativeFun>>,
+}
+
+impl MetaMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a method to the table, replacing any method with the same name.
+    pub fn insert(&mut self, fun: NativeFun) {
+        self.methods.insert(fun.name.clone(), Rc::new(fun));
+    }
+
+    /// Look up a method by name.
+    pub fn get(&self, name: &str) -> Option<Rc<NativeFun>> {
+        self.methods.get(name).cloned()
+    }
+}
+
+/// A Rust object that can be passed to and called from scripts.
+pub struct External {
+    /// The name of the wrapped Rust type, unless the embedder gave it another.
+    pub name: &'static str,
+    pub data: ExternalData,
+    pub meta_map: MetaMap,
+}
+
+impl External {
+    /// Start building an [External] wrapping `data`.
+    pub fn builder<T: Any>(data: T) -> ExternalBuilder<T> {
+        ExternalBuilder {
+            data,
+            name: any::type_name::<T>(),
+            meta_map: MetaMap::new(),
+        }
+    }
+
+    /// Borrow the wrapped data, if it is of type `T`.
+    ///
+    /// This is how a native function gets back the Rust value it was passed.
+    /// Returns `None` if the data is of another type, or is already mutably
+    /// borrowed by one of its own methods.
+    pub fn downcast_ref<T: Any>(&self) -> Option<Ref<'_, T>> {
+        let data = self.data.try_borrow().ok()?;
+        Ref::filter_map(data, |data| data.downcast_ref::<T>()).ok()
+    }
+
+    /// Mutably borrow the wrapped data, if it is of type `T`.
+    pub fn borrow_mut<T: Any>(&self) -> Option<RefMut<'_, T>> {
+        RefMut::filter_map(self.data.borrow_mut(), |data| data.downcast_mut::<T>()).ok()
+    }
+
+    /// Look up a method by name.
+    pub fn method(&self, name: &str) -> Option<Rc<NativeFun>> {
+        self.meta_map.get(name)
+    }
+}
+
+impl fmt::Debug for External {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("External")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl PartialEq for External {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for External {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self == other).then_some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl fmt::Display for External {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<external {}>", self.name)
+    }
+}
+
+/// A builder for registering methods on an [External].
+pub struct ExternalBuilder<T> {
+    data: T,
+    name: &'static str,
+    meta_map: MetaMap,
+}
+
+impl<T: Any> ExternalBuilder<T> {
+    /// Register a method.
+    ///
+    /// The method is passed a mutable reference to the wrapped data, followed by
+    /// the arguments it was called with.
+    pub fn method<R, F>(mut self, name: &str, fun: F) -> Self
+    where
+        R: NativeResult,
+        F: Fn(&mut T, Vec<Value>) -> R + 'static,
+    {
+        let native = move |_vm: &mut Vm, mut args: Vec<Value>| {
+            let receiver = match args.first() {
+                Some(Value::External(external)) => external.clone(),
+                _ => return Err("expected an external receiver".into()),
+            };
+            args.remove(0);
+
+            let mut data = receiver.borrow_mut::<T>().ok_or_else(|| {
+                format!("expected a receiver of type `{}`", any::type_name::<T>())
+            })?;
+
+            fun(&mut data, args).into_result()
+        };
+
+        self.meta_map
+            .insert(NativeFun::new(name, Box::new([]), Rc::new(native), true));
+        self
+    }
+
+    /// Set the name the [External] is displayed with when it has no `to_string`
+    /// method, in place of the name of the wrapped Rust type.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Finish building the [External].
+    pub fn build(self) -> Rc<External> {
+        Rc::new(External {
+            name: self.name,
+            data: Rc::new(RefCell::new(self.data)),
+            meta_map: self.meta_map,
+        })
+    }
+}
@@ -21,6 +21,11 @@ pub trait KaonRead {
     fn read_to_string(&self) -> Result<String, String> {
         Err("cannot write to specified file type".to_string())
     }
+
+    /// Returns the raw bytes of the file
+    fn read_bytes(&self) -> Result<Vec<u8>, String> {
+        Err("cannot read bytes from specified file type".to_string())
+    }
 }
 
 /// trait that defines the write operations of [KaonFile]
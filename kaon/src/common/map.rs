@@ -1,19 +1,23 @@
 use core::{borrow, fmt};
 use std::{
-    collections::HashMap,
     hash::{self, BuildHasherDefault},
     rc::Rc,
 };
 
+use indexmap::IndexMap;
+
 use super::{FromValue, Named, ToValue};
 use crate::Value;
 
-pub type Keys<'a> = std::collections::hash_map::Keys<'a, String, Value>;
+pub type Keys<'a> = indexmap::map::Keys<'a, String, Value>;
 
-pub type Values<'a> = std::collections::hash_map::Values<'a, String, Value>;
+pub type Values<'a> = indexmap::map::Values<'a, String, Value>;
 
 /// A struct representing a map at runtime.
 ///
+/// Entries are kept in insertion order, so iterating or printing a map always
+/// visits its keys in the order they were first inserted.
+///
 /// # Examples
 ///
 /// ```rust
@@ -33,29 +37,50 @@ pub type Values<'a> = std::collections::hash_map::Values<'a, String, Value>;
 /// ```
 #[derive(Default, Clone)]
 pub struct Map {
-    inner: Rc<HashMap<String, Value, BuildHasherDefault<ahash::AHasher>>>,
+    inner: Rc<Inner>,
+}
+
+/// The contents of a [Map], shared between copies of it until one is changed.
+#[derive(Default, Clone)]
+struct Inner {
+    entries: IndexMap<String, Value, BuildHasherDefault<ahash::AHasher>>,
+    /// Whether the map holds the members of a native module.
+    module: bool,
 }
 
 impl Map {
     /// Create a new empty [`Map`].
     pub fn new() -> Self {
-        Self {
-            inner: Rc::new(HashMap::default()),
-        }
+        Self::default()
     }
 
     /// Create a new empty [`Map`] with the specified capacity.
     pub fn with_capacity(cap: usize) -> Self {
         Self {
-            inner: Rc::new(HashMap::with_capacity_and_hasher(
-                cap,
-                BuildHasherDefault::default(),
-            )),
+            inner: Rc::new(Inner {
+                entries: IndexMap::with_capacity_and_hasher(cap, BuildHasherDefault::default()),
+                module: false,
+            }),
         }
     }
 
-    pub fn make_mut(&mut self) -> &mut HashMap<String, Value, BuildHasherDefault<ahash::AHasher>> {
-        Rc::make_mut(&mut self.inner)
+    pub fn make_mut(&mut self) -> &mut IndexMap<String, Value, BuildHasherDefault<ahash::AHasher>> {
+        &mut Rc::make_mut(&mut self.inner).entries
+    }
+
+    /// Mark the map as the members of a native module, built by a
+    /// [ModuleBuilder](crate::common::ModuleBuilder).
+    ///
+    /// Scripts look up the entries of a module before the builtin map methods,
+    /// so a member such as `len` isn't shadowed by the method of the same name.
+    pub(crate) fn into_module(mut self) -> Self {
+        Rc::make_mut(&mut self.inner).module = true;
+        self
+    }
+
+    /// Whether the map holds the members of a native module.
+    pub(crate) fn is_module(&self) -> bool {
+        self.inner.module
     }
 
     /// Return a reference to the value corresponding to the key.
@@ -64,7 +89,7 @@ impl Map {
         String: borrow::Borrow<Q>,
         Q: hash::Hash + std::cmp::Eq + std::cmp::Ord,
     {
-        self.inner.get(k)
+        self.inner.entries.get(k)
     }
 
     /// Return a mutable reference to the value corresponding to the key
@@ -84,7 +109,7 @@ impl Map {
         Q: hash::Hash + std::cmp::Eq + std::cmp::Ord,
         T: FromValue,
     {
-        let v = match self.inner.get(k) {
+        let v = match self.inner.entries.get(k) {
             Some(v) => v.clone(),
             None => return None,
         };
@@ -93,6 +118,8 @@ impl Map {
     }
 
     /// Insert a key-value pair into the [`Map`].
+    ///
+    /// Replacing the value of an existing key keeps its original position.
     pub fn insert(&mut self, k: String, v: Value) {
         self.make_mut().insert(k, v);
     }
@@ -107,32 +134,40 @@ impl Map {
     }
 
     /// Removes a key from the [`Map`], returning the value at the key.
+    ///
+    /// The order of the remaining entries is preserved.
     pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<Value>
     where
         String: borrow::Borrow<Q>,
         Q: hash::Hash + std::cmp::Eq + std::cmp::Ord,
     {
-        self.make_mut().remove(k)
+        self.make_mut().shift_remove(k)
     }
 
     /// Return the number of key-value pairs in the [`Map`].
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.inner.entries.len()
     }
 
-    /// Returns `true` if the inner [`HashMap`] is empty, otherwise `false`.
+    /// Returns `true` if the [`Map`] is empty, otherwise `false`.
     pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+        self.inner.entries.is_empty()
     }
 
-    /// An iterator visiting all keys in an arbitrary order.
+    /// An iterator visiting all keys in insertion order.
     pub fn keys(&self) -> Keys<'_> {
-        self.inner.keys()
+        self.inner.entries.keys()
     }
 
-    /// An iterator visiting all values in an arbitrary order.
+    /// An iterator visiting all values in insertion order.
     pub fn values(&self) -> Values<'_> {
-        self.inner.values()
+        self.inner.entries.values()
+    }
+
+    /// The address of the entries, which are shared between copies of the map
+    /// until one of them is changed.
+    pub(crate) fn as_ptr(&self) -> *const () {
+        Rc::as_ptr(&self.inner) as *const ()
     }
 
     pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
@@ -140,7 +175,7 @@ impl Map {
         String: borrow::Borrow<Q>,
         Q: hash::Hash + std::cmp::Eq + std::cmp::Ord,
     {
-        self.inner.contains_key(k)
+        self.inner.entries.contains_key(k)
     }
 }
 
@@ -163,7 +198,7 @@ impl ToValue for Map {
 
 impl fmt::Debug for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_map().entries(self.inner.iter()).finish()
+        f.debug_map().entries(self.inner.entries.iter()).finish()
     }
 }
 
@@ -171,10 +206,10 @@ impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("{")?;
 
-        for (pos, (key, value)) in self.inner.iter().enumerate() {
+        for (pos, (key, value)) in self.inner.entries.iter().enumerate() {
             f.write_fmt(format_args!("{key}: {value}"))?;
 
-            if pos != self.inner.len() {
+            if pos != self.inner.entries.len() - 1 {
                 f.write_str(", ")?;
             }
         }
@@ -184,7 +219,7 @@ impl fmt::Display for Map {
 
 impl PartialEq for Map {
     fn eq(&self, other: &Self) -> bool {
-        self.len() == other.len() && self.inner == other.inner
+        self.len() == other.len() && self.inner.entries == other.inner.entries
     }
 }
 
@@ -197,3 +232,26 @@ impl PartialOrd for Map {
 impl Named for Map {
     const NAME: &'static str = "Map";
 }
+
+#[cfg(test)]
+mod test {
+    use super::Map;
+    use crate::Value;
+
+    #[test]
+    fn insertion_order() {
+        let mut map = Map::new();
+        for (key, value) in [("zeta", 1.0), ("alpha", 2.0), ("mu", 3.0), ("beta", 4.0)] {
+            map.insert(key.to_string(), Value::Float(value));
+        }
+        assert_eq!(map.to_string(), "{zeta: 1, alpha: 2, mu: 3, beta: 4}");
+
+        map.insert("alpha".to_string(), Value::Float(5.0));
+        map.remove("mu");
+        assert_eq!(map.to_string(), "{zeta: 1, alpha: 5, beta: 4}");
+        assert_eq!(
+            map.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["zeta", "alpha", "beta"]
+        );
+    }
+}
@@ -3,27 +3,33 @@
 pub mod args;
 pub mod bytecode;
 pub mod disassembler;
+pub mod external;
 pub mod file;
 pub mod immutable_string;
 pub mod opcode;
+pub mod serialize;
 pub mod source;
 pub mod span;
 pub mod state;
 pub mod value;
 mod hash;
 pub mod map;
+pub mod module;
 
 pub use args::{Args, FromArgs, ToArgs, Varidic};
-pub use bytecode::{Chunk, DebugInfo};
+pub use bytecode::{Chunk, DebugInfo, Instructions};
 pub use disassembler::Disassembler;
+pub use external::{External, ExternalBuilder, ExternalData, MetaMap};
 pub use file::{KaonFile, KaonRead, KaonWrite};
 pub use immutable_string::ImmutableString;
 pub use opcode::Opcode;
-pub use source::Source;
+pub use source::{Origin, Source};
 pub use span::{Span, Spanned};
 pub use value::{
-    BoundMethod, Captured, Class, Closure, Constructor, Function, Instance, NativeFun, Upvalue,
-    Value, Named
+    BoundMethod, Captured, Class, Closure, Constructor, Enum, Function, Instance, NativeFun,
+    TotalOrd, Upvalue, UpvalueLocation, Value, Variant, Named
 };
-pub use value::{FromValue, ToValue};
+pub use value::{FromValue, NativeResult, ToValue};
 pub use map::Map;
+pub use module::ModuleBuilder;
+pub use kaon_macros::kaon_module;
@@ -0,0 +1,80 @@
+//! Native modules exposed to scripts.
+//!
+//! A [ModuleBuilder] collects native functions and constants into a [Map],
+//! which is installed as a global so that scripts access its members with dot
+//! syntax:
+//!
+//! ```
+//! # use kaon::{common::ModuleBuilder, runtime::Vm};
+//! let mut vm = Vm::new();
+//!
+//! ModuleBuilder::new()
+//!     .function("square", |x: f64| x * x)
+//!     .constant("tau", std::f64::consts::TAU)
+//!     .install(&mut vm, "geometry");
+//! ```
+//!
+//! The members of a struct's `impl` block can be registered all at once with
+//! the [kaon_module](crate::common::kaon_module) attribute.
+
+use std::rc::Rc;
+
+use crate::common::value::RegisterFunction;
+use crate::common::{Map, NativeFun, ToValue, Value};
+use crate::runtime::Vm;
+
+/// A builder for a module of native functions and constants.
+#[derive(Default)]
+pub struct ModuleBuilder {
+    members: Map,
+}
+
+impl ModuleBuilder {
+    /// Create a builder for an empty module.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a native function as a member of the module.
+    ///
+    /// Arguments are converted the same way as for
+    /// [Vm::register_function].
+    pub fn function<A, R, F: RegisterFunction<A, R> + Copy>(
+        mut self,
+        name: &str,
+        fun: F,
+    ) -> Self {
+        let fun = NativeFun::new(
+            name,
+            fun.arity(),
+            fun.to_native_function(),
+            fun.is_varidic(),
+        );
+
+        self.members
+            .insert(name.to_string(), Value::NativeFun(Rc::new(fun)));
+        self
+    }
+
+    /// Add a constant as a member of the module.
+    pub fn constant<V: ToValue>(mut self, name: &str, value: V) -> Self {
+        self.members.insert(name.to_string(), value.to_value());
+        self
+    }
+
+    /// Finish building the module, returning its members.
+    ///
+    /// Unlike the entries of other maps, the members of a module are looked up
+    /// before the builtin map methods, so a member may shadow a method.
+    pub fn build(self) -> Map {
+        self.members.into_module()
+    }
+
+    /// Finish building the module and install it as the global `name`.
+    ///
+    /// See [Vm::set_global] for how scripts are compiled against injected
+    /// globals.
+    pub fn install(self, vm: &mut Vm, name: &str) {
+        vm.set_global(name, Value::Map(self.build()));
+    }
+}
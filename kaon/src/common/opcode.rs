@@ -1,5 +1,5 @@
 #[repr(u8)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     /// Load a constant on to the stack.
     Const, 
@@ -13,6 +13,8 @@ pub enum Opcode {
     Mul,
     Div,
     Mod,
+    /// Raise the topmost value to the power of the value below it.
+    Pow,
     Negate,
     Equal,
     NotEqual,
@@ -20,10 +22,19 @@ pub enum Opcode {
     Lte,
     Gt,
     Lt,
+    /// Check if the topmost value is an instance of the class below it.
+    Is,
+    /// Check if the value below the top of the stack is the named variant of
+    /// the enum on top.
+    IsVariant,
     Not,
     BitAnd,
     BitOr,
     BitXor,
+    /// Shift the topmost value left by the value below it.
+    Shl,
+    /// Shift the topmost value right by the value below it, keeping its sign.
+    Shr,
     DefGlobal,
     SetGlobal,
     GetGlobal,
@@ -42,42 +53,130 @@ pub enum Opcode {
     /// Jump to a given index pointer if the topmost value on
     /// the stack is falsy.
     JumpIfFalse,
+    /// Jump to a given index pointer if the topmost value on
+    /// the stack is `StopIteration`, which ends a `for` loop.
+    JumpIfStop,
+    /// Turn the value on top of the stack into an iterator for a `for` loop.
+    Iter,
+    /// Install an error handler at the given offset.
+    Try,
+    /// Remove the innermost error handler.
+    EndTry,
     /// Call the topmost value off the stack.
     Call,
     Call0,
     Call1,
     Call2,
+    /// Call the value below the top of the stack, with the elements of the list
+    /// on top as its arguments.
+    CallList,
     /// Return from the topmost function on the call stack.
     Return,
     /// Pop the topmost value off the stack.
+    ///
+    /// Emitted after each expression statement to discard its unused value, so
+    /// that the stack doesn't grow with every statement run. The value popped
+    /// last is what running a script evaluates to.
     Pop,
     PopN,
+    /// Push a copy of the topmost value on to the stack.
+    Dup,
+    /// Move the value two below the top of the stack to the top.
+    Rot,
     /// Builds a class from the stack.
     Class,
+    /// Make the class below the top of the stack inherit from the class on top.
+    Inherit,
     /// Build a closure from the stack.
     Closure,
     /// Builds a list from the stack.
     List,
+    /// Append the elements of the list on top of the stack to the list below it.
+    ExtendList,
     /// Construct a tuple from the stack.
     Tuple,
     /// Build a map from the stack.
     Map,
     /// Get index into the topmost value on the stack.
     GetIndex,
-    /// Set an value at a given index with the supplied value.
+    /// Store a value in a list element or map entry, leaving the container on
+    /// the stack.
     SetIndex,
-    /// The get opcode.
+    /// Copy a range of a list, string or bytes, with its bounds on top of the stack.
+    Slice,
+    /// Get a field or method of the topmost value on the stack.
     Get,
-    /// Set opcode.
+    /// Set a field of the instance on top of the stack to the value below it.
     Set,
+    /// Bind a method of the class on top of the stack to the receiver below it.
+    GetSuper,
     /// Import (unused).
     Import,
     /// Halt the vm's exectution.
     Halt,
 }
 
-impl From<u8> for Opcode {
-    fn from(opcode: u8) -> Opcode {
-        unsafe { std::mem::transmute(opcode) }
+impl Opcode {
+    /// Every opcode, indexed by its byte value.
+    const ALL: [Opcode; 68] = [
+        Opcode::Const, Opcode::True, Opcode::False, Opcode::Nil, Opcode::Unit, Opcode::String,
+        Opcode::Add, Opcode::Sub, Opcode::Mul, Opcode::Div, Opcode::Mod, Opcode::Pow,
+        Opcode::Negate, Opcode::Equal, Opcode::NotEqual, Opcode::Gte, Opcode::Lte, Opcode::Gt,
+        Opcode::Lt, Opcode::Is, Opcode::IsVariant, Opcode::Not, Opcode::BitAnd, Opcode::BitOr,
+        Opcode::BitXor, Opcode::Shl, Opcode::Shr, Opcode::DefGlobal, Opcode::SetGlobal,
+        Opcode::GetGlobal, Opcode::LoadLocal, Opcode::SaveLocal, Opcode::LoadUpValue,
+        Opcode::SaveUpValue, Opcode::CloseUpValue, Opcode::Loop, Opcode::Jump, Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse, Opcode::JumpIfStop, Opcode::Iter, Opcode::Try, Opcode::EndTry,
+        Opcode::Call, Opcode::Call0, Opcode::Call1, Opcode::Call2, Opcode::CallList, Opcode::Return,
+        Opcode::Pop, Opcode::PopN, Opcode::Dup, Opcode::Rot, Opcode::Class, Opcode::Inherit,
+        Opcode::Closure, Opcode::List, Opcode::ExtendList, Opcode::Tuple, Opcode::Map,
+        Opcode::GetIndex, Opcode::SetIndex, Opcode::Slice, Opcode::Get, Opcode::Set,
+        Opcode::GetSuper, Opcode::Import, Opcode::Halt,
+    ];
+
+    /// The number of operand bytes that follow the opcode in the bytecode stream.
+    pub fn operand_width(&self) -> usize {
+        match self {
+            Opcode::Const
+            | Opcode::String
+            | Opcode::DefGlobal
+            | Opcode::SetGlobal
+            | Opcode::GetGlobal
+            | Opcode::Get
+            | Opcode::Set
+            | Opcode::GetSuper
+            | Opcode::IsVariant
+            | Opcode::LoadLocal
+            | Opcode::SaveLocal
+            | Opcode::LoadUpValue
+            | Opcode::SaveUpValue
+            | Opcode::Call
+            | Opcode::PopN
+            | Opcode::List
+            | Opcode::Tuple
+            | Opcode::Map
+            | Opcode::Closure => 1,
+            Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::JumpIfTrue
+            | Opcode::JumpIfStop
+            | Opcode::Try
+            | Opcode::Loop => 2,
+            // the name, then the number of methods, constructors, statics and fields
+            Opcode::Class => 5,
+            _ => 0,
+        }
+    }
+}
+
+/// Decode an opcode from a byte of bytecode.
+///
+/// Fails with the byte itself if it isn't a valid opcode, as may happen with
+/// corrupt or hand-written bytecode.
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Opcode, u8> {
+        Opcode::ALL.get(byte as usize).copied().ok_or(byte)
     }
 }
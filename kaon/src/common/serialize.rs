@@ -0,0 +1,461 @@
+//! A binary format for compiled bytecode.
+//!
+//! Compiled [Chunk]s can be written to disk with [Chunk::serialize] and loaded
+//! again with [Chunk::deserialize], skipping the compiler entirely.
+//!
+//! # Format
+//!
+//! All integers are little-endian, and lengths and indices are written as
+//! `u64`s. A file starts with the magic bytes `KAON`, followed by a `u16`
+//! version number, a table of the [Source]s referenced by the debug info, and
+//! then the top-level chunk.
+
+use std::rc::Rc;
+
+use crate::common::{
+    Captured, Chunk, DebugInfo, Enum, Function, ImmutableString, Opcode, Origin, Source, Span,
+    Value,
+};
+
+/// The magic bytes at the start of every bytecode file.
+pub const MAGIC: &[u8; 4] = b"KAON";
+
+/// The version of the bytecode format.
+///
+/// This must be bumped whenever the format or the opcode set changes.
+pub const VERSION: u16 = 17;
+
+const TAG_FLOAT: u8 = 0;
+const TAG_INTEGER: u8 = 1;
+const TAG_BOOLEAN: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_FUNCTION: u8 = 4;
+const TAG_UNIT: u8 = 5;
+const TAG_NIL: u8 = 6;
+const TAG_ENUM: u8 = 7;
+
+const CAPTURED_LOCAL: u8 = 0;
+const CAPTURED_NONLOCAL: u8 = 1;
+
+/// Encodes a [Chunk] into bytes.
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    sources: Vec<Rc<Source>>,
+}
+
+impl Encoder {
+    fn encode(mut self, chunk: &Chunk) -> Result<Vec<u8>, String> {
+        let mut body = Encoder::default();
+        body.chunk(chunk)?;
+
+        self.bytes.extend_from_slice(MAGIC);
+        self.bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+        self.sources = body.sources;
+        self.usize(self.sources.len());
+        for source in self.sources.clone() {
+            self.string(&source.path.to_string_lossy());
+            self.string(&source.contents);
+            self.bytes.push(matches!(source.origin, Origin::File) as u8);
+        }
+
+        self.bytes.append(&mut body.bytes);
+        Ok(self.bytes)
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) -> Result<(), String> {
+        self.usize(chunk.opcodes.len());
+        self.bytes.extend_from_slice(&chunk.opcodes);
+
+        self.usize(chunk.constants.len());
+        for constant in &chunk.constants {
+            self.value(constant)?;
+        }
+
+        self.usize(chunk.variables.len());
+        for variable in &chunk.variables {
+            self.string(variable);
+        }
+
+        self.usize(chunk.functions.len());
+        for function in &chunk.functions {
+            self.function(function)?;
+        }
+
+        self.debug_info(&chunk.debug_info);
+
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), String> {
+        self.string(&function.name);
+        self.usize(function.arity);
+
+        self.usize(function.captures.len());
+        for captured in &function.captures {
+            match captured {
+                Captured::Local(index) => {
+                    self.bytes.push(CAPTURED_LOCAL);
+                    self.usize(*index);
+                }
+                Captured::NonLocal(index) => {
+                    self.bytes.push(CAPTURED_NONLOCAL);
+                    self.usize(*index);
+                }
+            }
+        }
+
+        self.chunk(&function.chunk)
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), String> {
+        match value {
+            Value::Float(val) => {
+                self.bytes.push(TAG_FLOAT);
+                self.bytes.extend_from_slice(&val.to_le_bytes());
+            }
+            Value::Integer(val) => {
+                self.bytes.push(TAG_INTEGER);
+                self.bytes.extend_from_slice(&val.to_le_bytes());
+            }
+            Value::Boolean(val) => {
+                self.bytes.push(TAG_BOOLEAN);
+                self.bytes.push(*val as u8);
+            }
+            Value::String(val) => {
+                self.bytes.push(TAG_STRING);
+                self.string(val);
+            }
+            Value::Function(fun) => {
+                self.bytes.push(TAG_FUNCTION);
+                self.function(fun)?;
+            }
+            Value::Enum(enum_) => {
+                self.bytes.push(TAG_ENUM);
+                self.string(&enum_.name);
+
+                self.usize(enum_.variants.len());
+                for (name, arity) in &enum_.variants {
+                    self.string(name);
+                    self.usize(*arity);
+                }
+            }
+            Value::Unit => self.bytes.push(TAG_UNIT),
+            Value::Nil => self.bytes.push(TAG_NIL),
+            val => {
+                return Err(format!(
+                    "cannot serialize constant `{val}` of type `{}`",
+                    val.type_name()
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn debug_info(&mut self, debug_info: &DebugInfo) {
+        self.usize(debug_info.source_map.len());
+        for (offset, span) in &debug_info.source_map {
+            let source = self.source(&span.source);
+
+            self.usize(*offset);
+            self.usize(source);
+            self.usize(span.start);
+            self.usize(span.length);
+        }
+    }
+
+    /// Get the index of a source in the source table, adding it if needed.
+    fn source(&mut self, source: &Rc<Source>) -> usize {
+        match self.sources.iter().position(|s| Rc::ptr_eq(s, source)) {
+            Some(index) => index,
+            None => {
+                self.sources.push(source.clone());
+                self.sources.len() - 1
+            }
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.usize(string.len());
+        self.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    fn usize(&mut self, val: usize) {
+        self.bytes.extend_from_slice(&(val as u64).to_le_bytes());
+    }
+}
+
+/// Decodes a [Chunk] from bytes.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    sources: Vec<Rc<Source>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Decoder {
+            bytes,
+            position: 0,
+            sources: Vec::new(),
+        }
+    }
+
+    fn decode(mut self) -> Result<Chunk, String> {
+        if self.take(MAGIC.len())? != MAGIC {
+            return Err("not a kaon bytecode file".to_string());
+        }
+
+        let version = u16::from_le_bytes(self.array()?);
+        if version != VERSION {
+            return Err(format!(
+                "unsupported bytecode version {version}, expected version {VERSION}"
+            ));
+        }
+
+        for _ in 0..self.usize()? {
+            let path = self.string()?;
+            let contents = self.string()?;
+
+            let source = match self.byte()? {
+                0 => Source::from_string(&path, &contents),
+                _ => Source::new(&contents, &path),
+            };
+            self.sources.push(source);
+        }
+
+        let chunk = self.chunk()?;
+
+        if self.position != self.bytes.len() {
+            return Err("unexpected trailing bytes".to_string());
+        }
+
+        Ok(chunk)
+    }
+
+    fn chunk(&mut self) -> Result<Chunk, String> {
+        let length = self.usize()?;
+        let opcodes = self.take(length)?.to_vec();
+        validate_opcodes(&opcodes)?;
+
+        let mut constants = Vec::new();
+        for _ in 0..self.usize()? {
+            constants.push(Box::new(self.value()?));
+        }
+
+        let mut variables = Vec::new();
+        for _ in 0..self.usize()? {
+            variables.push(self.string()?.into_boxed_str());
+        }
+
+        let mut functions = Vec::new();
+        for _ in 0..self.usize()? {
+            functions.push(self.function()?);
+        }
+
+        let debug_info = self.debug_info()?;
+
+        let chunk = Chunk {
+            opcodes,
+            constants,
+            variables,
+            functions,
+            debug_info,
+        };
+        validate_operands(&chunk)?;
+
+        Ok(chunk)
+    }
+
+    fn function(&mut self) -> Result<Function, String> {
+        let name = self.string()?;
+        let arity = self.usize()?;
+
+        let mut captures = Vec::new();
+        for _ in 0..self.usize()? {
+            let captured = match self.byte()? {
+                CAPTURED_LOCAL => Captured::Local(self.usize()?),
+                CAPTURED_NONLOCAL => Captured::NonLocal(self.usize()?),
+                tag => return Err(format!("invalid capture tag {tag}")),
+            };
+
+            captures.push(captured);
+        }
+
+        let chunk = self.chunk()?;
+
+        Ok(Function::new(name, arity, chunk, captures))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.byte()? {
+            TAG_FLOAT => Ok(Value::Float(f64::from_le_bytes(self.array()?))),
+            TAG_INTEGER => Ok(Value::Integer(i64::from_le_bytes(self.array()?))),
+            TAG_BOOLEAN => Ok(Value::Boolean(self.byte()? != 0)),
+            TAG_STRING => Ok(Value::String(ImmutableString::from(self.string()?))),
+            TAG_FUNCTION => Ok(Value::Function(Rc::new(self.function()?))),
+            TAG_UNIT => Ok(Value::Unit),
+            TAG_NIL => Ok(Value::Nil),
+            TAG_ENUM => {
+                let name = self.string()?;
+
+                let mut variants = Vec::new();
+                for _ in 0..self.usize()? {
+                    variants.push((self.string()?.into_boxed_str(), self.usize()?));
+                }
+
+                Ok(Value::Enum(Rc::new(Enum::new(name, variants))))
+            }
+            tag => Err(format!("invalid constant tag {tag}")),
+        }
+    }
+
+    fn debug_info(&mut self) -> Result<DebugInfo, String> {
+        let mut debug_info = DebugInfo::default();
+
+        for _ in 0..self.usize()? {
+            let offset = self.usize()?;
+            let source = self.usize()?;
+            let start = self.usize()?;
+            let length = self.usize()?;
+
+            let source = self
+                .sources
+                .get(source)
+                .ok_or_else(|| format!("invalid source index {source}"))?;
+
+            debug_info
+                .source_map
+                .push((offset, Span::new(start, length, source)));
+        }
+
+        Ok(debug_info)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.usize()?;
+        let bytes = self.take(length)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string())
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        let val = u64::from_le_bytes(self.array()?);
+        usize::try_from(val).map_err(|_| format!("length {val} is too large for this platform"))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+
+        Ok(array)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = match self.position.checked_add(length) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err("unexpected end of bytecode".to_string()),
+        };
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+}
+
+/// Check that every instruction in `opcodes` starts with a valid opcode, and
+/// isn't cut short before the end of its operands.
+///
+/// Each opcode's operands are skipped so that they aren't mistaken for opcodes.
+/// What the operands refer to is checked by [validate_operands] once the rest
+/// of the chunk has been read.
+fn validate_opcodes(opcodes: &[u8]) -> Result<(), String> {
+    let mut offset = 0;
+    while offset < opcodes.len() {
+        let opcode = Opcode::try_from(opcodes[offset])
+            .map_err(|byte| format!("invalid opcode {byte} at offset {offset}"))?;
+
+        if offset + opcode.operand_width() >= opcodes.len() {
+            return Err(format!("missing operand for `{opcode:?}` at offset {offset}"));
+        }
+
+        offset += 1 + opcode.operand_width();
+    }
+
+    Ok(())
+}
+
+/// Check that the operands of every instruction in `chunk` refer to constants,
+/// names and jump targets that exist.
+///
+/// Operands that depend on the state of the stack, like the slot of a local,
+/// can only be checked by the VM as it runs.
+fn validate_operands(chunk: &Chunk) -> Result<(), String> {
+    let starts = chunk
+        .instructions()
+        .map(|(offset, _, _)| offset)
+        .collect::<Vec<_>>();
+
+    for (offset, opcode, operands) in chunk.instructions() {
+        let operand = |n: usize| operands[n] as usize;
+        let short = || (operand(0) << 8) | operand(1);
+        let next = offset + 1 + operands.len();
+
+        let valid = match opcode {
+            Opcode::Const => operand(0) < chunk.constants.len(),
+            Opcode::Closure => matches!(
+                chunk.constants.get(operand(0)).map(|value| &**value),
+                Some(Value::Function(_))
+            ),
+            Opcode::String
+            | Opcode::DefGlobal
+            | Opcode::SetGlobal
+            | Opcode::GetGlobal
+            | Opcode::Get
+            | Opcode::Set
+            | Opcode::GetSuper
+            | Opcode::IsVariant => operand(0) < chunk.variables.len(),
+            // the name is followed by the names of the fields
+            Opcode::Class => operand(0) + operand(4) < chunk.variables.len(),
+            Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::JumpIfTrue
+            | Opcode::JumpIfStop
+            | Opcode::Try => starts.binary_search(&(next + short())).is_ok(),
+            Opcode::Loop => match next.checked_sub(short()) {
+                Some(target) => starts.binary_search(&target).is_ok(),
+                None => false,
+            },
+            _ => true,
+        };
+
+        if !valid {
+            return Err(format!("invalid operand for `{opcode:?}` at offset {offset}"));
+        }
+    }
+
+    Ok(())
+}
+
+impl Chunk {
+    /// Serialize the chunk into the bytecode file format.
+    ///
+    /// Fails if the chunk has a constant that can't be written, like a list.
+    pub fn serialize(&self) -> Result<Vec<u8>, String> {
+        Encoder::default().encode(self)
+    }
+
+    /// Deserialize a chunk from the bytecode file format.
+    ///
+    /// Fails if the bytes are malformed, or were written by a different version.
+    pub fn deserialize(bytes: &[u8]) -> Result<Chunk, String> {
+        Decoder::new(bytes).decode()
+    }
+}
@@ -2,11 +2,22 @@ use std::fs::read_to_string;
 use std::path::PathBuf;
 use std::rc::Rc;
 
+/// Where the code in a [Source] came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Origin {
+    /// Read from the file at the source's path.
+    File,
+    /// Given as a string, such as a line typed into the REPL. The path is only a name.
+    #[default]
+    Memory,
+}
+
 /// Tracks the source code used within the Kaon language.
 #[derive(Clone, PartialEq, Hash, Default)]
 pub struct Source {
     pub contents: String,
     pub path: PathBuf,
+    pub origin: Origin,
 }
 
 impl Source {
@@ -15,17 +26,37 @@ impl Source {
         Rc::new(Source {
             contents: source.to_string(),
             path: PathBuf::from(path),
+            origin: Origin::File,
         })
     }
 
-    /// Create a new [Source] without specifing the file path.
-    pub fn contents(source: &str) -> Rc<Source> {
+    /// Create a [Source] from a string that wasn't read from a file, naming it `name`.
+    ///
+    /// Errors in the source are reported as being in `<name>`, rather than at a path.
+    pub fn from_string(name: &str, contents: &str) -> Rc<Source> {
         Rc::new(Source {
-            contents: source.to_string(),
-            path: PathBuf::from("./main"),
+            contents: contents.to_string(),
+            path: PathBuf::from(name),
+            origin: Origin::Memory,
         })
     }
 
+    /// Create a new [Source] without specifing the file path.
+    ///
+    /// It's named `<string>` in error messages.
+    pub fn contents(source: &str) -> Rc<Source> {
+        Source::from_string("string", source)
+    }
+
+    /// The name the source is referred to by in error messages: its path if it
+    /// was read from a file, and otherwise its name in angle brackets.
+    pub fn name(&self) -> String {
+        match self.origin {
+            Origin::File => self.path.to_string_lossy().to_string(),
+            Origin::Memory => format!("<{}>", self.path.to_string_lossy()),
+        }
+    }
+
     /// Read a file from the provided path and return it as a [Source]. 
     pub fn from_file(path: &str) -> Result<Rc<Source>, String> {
         match read_to_string(path) {
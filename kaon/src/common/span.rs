@@ -54,7 +54,7 @@ impl Span {
 
 impl fmt::Debug for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("{}", self.source.path.to_str().unwrap()))
+        f.write_str(&self.source.name())
     }
 }
 
@@ -68,6 +68,19 @@ impl Clone for Span {
     }
 }
 
+/// A span is serialized as its start and length, leaving out the source.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Span {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut span = serializer.serialize_struct("Span", 2)?;
+        span.serialize_field("start", &self.start)?;
+        span.serialize_field("length", &self.length)?;
+        span.end()
+    }
+}
+
 pub struct Spanned<T> {
     pub node: T,
     pub source: Span,
@@ -29,18 +29,14 @@ impl State {
         self.values.push(value.to_value());
     }
 
+    /// Get a value by name, returning `None` if it doesn't exist or has the wrong type.
     pub fn get<T: FromValue>(&self, name: &str) -> Option<T> {
-        let value = self.names
+        self.names
             .iter()
             .enumerate()
             .find(|(_, n)| &***n == name)
-            .and_then(|(pos, _)| self.values.get(pos).cloned());
-        
-        if let Some(value) = value {
-            Some(FromValue::from_value(value).unwrap())
-        } else {
-            None
-        }
+            .and_then(|(pos, _)| self.values.get(pos).cloned())
+            .and_then(|value| T::from_value(value).ok())
     }
 
     pub fn register_function<S: Into<Box<str>> + Copy, A, R, F: RegisterFunction<A, R> + Copy>(&mut self, name: S, fun: F) {
@@ -66,6 +62,7 @@ mod test {
         assert_eq!(state.get::<bool>("y").unwrap(), true);
 
         assert_eq!(state.get::<String>("nothing"), None);
+        assert_eq!(state.get::<bool>("x"), None);
 
         println!("{:#?}", state);
     }
@@ -4,18 +4,21 @@ use std::cell::{Ref, RefCell, RefMut};
 use std::cmp::{Ord, Ordering};
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
-use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Sub};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
 use std::rc::Rc;
 
 use smallvec::SmallVec;
 
-use crate::common::Chunk;
+use crate::common::{Chunk, Span};
+use crate::error::RuntimeError;
 use crate::runtime::Vm;
 
-use super::{hash, ImmutableString, Map, ToArgs, Varidic};
+use super::{hash, External, ImmutableString, Map, ToArgs, Varidic};
 
 /// Value type for the Kaon language.
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
+#[derive(Debug, Clone)]
 pub enum Value {
     /// A 64-bit floating pointer number
     Float(f64),
@@ -25,6 +28,8 @@ pub enum Value {
     Boolean(bool),
     /// A string
     String(ImmutableString),
+    /// A sequence of raw bytes
+    Bytes(Rc<Vec<u8>>),
     /// A list of elements of the same type
     List(ValueList),
     /// A tuple
@@ -45,6 +50,12 @@ pub enum Value {
     Constructor(Rc<Constructor>),
     /// A instance method
     Method(Rc<BoundMethod>),
+    /// A Rust object exposed to scripts
+    External(Rc<External>),
+    /// An enum declaration
+    Enum(Rc<Enum>),
+    /// A variant of an enum
+    Variant(Rc<Variant>),
     /// An empty type
     Unit,
     /// A nil value
@@ -55,6 +66,119 @@ impl Value {
     pub const TRUE: Value = Value::Boolean(true);
     pub const FALSE: Value = Value::Boolean(false);
 
+    /// The name of the value's type, as it would be written in a type annotation.
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Float(_) => "f64".to_string(),
+            Value::Integer(_) => "i64".to_string(),
+            Value::Boolean(_) => "bool".to_string(),
+            Value::String(_) => "string".to_string(),
+            Value::Bytes(_) => "Bytes".to_string(),
+            Value::List(_) => "List".to_string(),
+            Value::Tuple(_) => "Tuple".to_string(),
+            Value::Map(_) => "Map".to_string(),
+            Value::NativeFun(_)
+            | Value::Function(_)
+            | Value::Closure(_)
+            | Value::Constructor(_)
+            | Value::Method(_) => "Fun".to_string(),
+            Value::Class(_) => "Class".to_string(),
+            Value::Instance(instance) => instance.class.name.to_string(),
+            Value::External(external) => external.name.to_string(),
+            Value::Enum(_) => "Enum".to_string(),
+            Value::Variant(variant) => variant.parent.name.to_string(),
+            Value::Unit => "void".to_string(),
+            Value::Nil => "nil".to_string(),
+        }
+    }
+
+    /// Whether the value counts as true in a condition.
+    ///
+    /// `nil`, `void`, `false`, numeric zero (`0` or `0.0`), the empty string,
+    /// empty bytes and empty lists, tuples and maps are falsy. Every other value,
+    /// including `NaN`, is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil | Value::Unit => false,
+            Value::Boolean(val) => *val,
+            Value::Float(val) => *val != 0.0,
+            Value::Integer(val) => *val != 0,
+            Value::String(val) => !val.is_empty(),
+            Value::Bytes(val) => !val.is_empty(),
+            Value::List(val) => !val.is_empty(),
+            Value::Tuple(val) => !val.0.is_empty(),
+            Value::Map(val) => !val.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// Whether the value can be called, like a function, method, constructor or class.
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Value::Function(_)
+                | Value::Closure(_)
+                | Value::NativeFun(_)
+                | Value::Constructor(_)
+                | Value::Method(_)
+                | Value::Class(_)
+        )
+    }
+
+    /// Copy the value, recursively copying lists, tuples and maps so that the
+    /// copy shares no mutable state with the original.
+    ///
+    /// [Clone] is cheap and shares lists by reference. Both kinds of copy share
+    /// functions, classes, instances and externals. A container that contains
+    /// itself is copied into one that contains the copy.
+    pub fn deep_clone(&self) -> Value {
+        self.deep_clone_with(&mut HashMap::new())
+    }
+
+    /// Deep clone, reusing the copies in `copies` of containers already seen,
+    /// keyed by their address.
+    fn deep_clone_with(&self, copies: &mut HashMap<*const (), Value>) -> Value {
+        match self {
+            Value::List(list) => {
+                let key = Rc::as_ptr(&list.0) as *const ();
+                if let Some(copy) = copies.get(&key) {
+                    return copy.clone();
+                }
+
+                let copy = ValueList::new();
+                copies.insert(key, Value::List(copy.clone()));
+
+                let items = RefCell::borrow(&list.0).clone();
+                for item in items.iter() {
+                    let item = item.deep_clone_with(copies);
+                    copy.0.borrow_mut().push(item);
+                }
+
+                Value::List(copy)
+            }
+            Value::Tuple(tuple) => Value::Tuple(ValueTuple(Rc::new(
+                tuple.0.iter().map(|item| item.deep_clone_with(copies)).collect(),
+            ))),
+            Value::Map(map) => {
+                let key = map.as_ptr();
+                if let Some(copy) = copies.get(&key) {
+                    return copy.clone();
+                }
+
+                let mut copy = Map::with_capacity(map.len());
+                copies.insert(key, Value::Map(copy.clone()));
+
+                for (key, value) in map.keys().zip(map.values()) {
+                    let value = value.deep_clone_with(copies);
+                    copy.insert(key.clone(), value);
+                }
+
+                Value::Map(copy)
+            }
+            value => value.clone(),
+        }
+    }
+
     pub fn as_closure(&self) -> Option<Rc<Closure>> {
         if let Value::Closure(closure) = self {
             Some(closure.clone())
@@ -72,13 +196,146 @@ impl Value {
     }
 }
 
+/// The smallest magnitude of a float that is out of the range of an `i64`.
+const I64_BOUND: f64 = 9_223_372_036_854_775_808.0;
+
+/// The integer equal to `num`, if it's a whole number in the range of an `i64`.
+fn exact_integer(num: f64) -> Option<i64> {
+    (num.fract() == 0.0 && (-I64_BOUND..I64_BOUND).contains(&num)).then_some(num as i64)
+}
+
+/// Compare a float with an integer by their exact values.
+fn compare_numbers(float: f64, int: i64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float >= I64_BOUND {
+        return Some(Ordering::Greater);
+    }
+    if float < -I64_BOUND {
+        return Some(Ordering::Less);
+    }
+
+    let whole = (float.trunc() as i64).cmp(&int);
+    Some(whole.then(float.fract().partial_cmp(&0.0)?))
+}
+
+/// Compare two sequences of values element by element with [Value::total_cmp],
+/// and then by length.
+fn total_cmp_all(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.total_cmp(b))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+impl Value {
+    /// Format a number with `precision` digits after the decimal point, or as few
+    /// as are needed to represent it exactly if `precision` is `None`.
+    ///
+    /// Whole floats too large to be an `i64` are written in scientific notation,
+    /// rather than with digits that weren't in the original number. Smaller whole
+    /// floats are written exactly like the integers they're equal to.
+    /// Values that aren't numbers are formatted as usual.
+    pub fn format_number(&self, precision: Option<usize>) -> String {
+        match (self, precision) {
+            (Value::Float(num), Some(precision)) => format!("{num:.precision$}"),
+            (Value::Float(num), None) if num.fract() == 0.0 && num.abs() >= I64_BOUND => {
+                format!("{num:e}")
+            }
+            (Value::Float(num), None) => match exact_integer(*num) {
+                Some(int) => format!("{int}"),
+                None => format!("{num}"),
+            },
+            (Value::Integer(num), Some(precision)) if precision > 0 => {
+                format!("{num}.{:0<precision$}", "")
+            }
+            (Value::Integer(num), _) => format!("{num}"),
+            (value, _) => value.to_string(),
+        }
+    }
+
+    /// Compare two values of any kinds, for sorting.
+    ///
+    /// Values are grouped by kind, in the order `nil`, `()`, booleans, numbers,
+    /// strings, bytes, lists, tuples, maps, enum variants, enums, classes,
+    /// instances, functions and methods, and then external values. Within a kind:
+    ///
+    /// - numbers are ordered by value, whether they're floats or integers, with
+    ///   `NaN` after every other number
+    /// - strings and bytes are ordered lexicographically
+    /// - lists and tuples are ordered element by element, then by length
+    ///
+    /// Values of any other kind are equal to each other, so a stable sort keeps
+    /// them in their original order. Unlike `<` and `>`, this never fails or
+    /// treats `NaN` as unordered. [TotalOrd] wraps a value to order it this way
+    /// where [Ord] is needed, breaking ties between values that aren't `==`.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (
+                Value::Float(_) | Value::Integer(_),
+                Value::Float(_) | Value::Integer(_),
+            ) => self
+                .partial_cmp(other)
+                .unwrap_or_else(|| self.is_nan().cmp(&other.is_nan())),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.as_str().cmp(b.as_str()),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => {
+                total_cmp_all(&RefCell::borrow(&a.0), &RefCell::borrow(&b.0))
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => total_cmp_all(&a.0, &b.0),
+            (a, b) => a.kind_order().cmp(&b.kind_order()),
+        }
+    }
+
+    /// The position of the value's kind in the order used by [Value::total_cmp].
+    fn kind_order(&self) -> u8 {
+        match self {
+            Value::Nil => 0,
+            Value::Unit => 1,
+            Value::Boolean(_) => 2,
+            Value::Float(_) | Value::Integer(_) => 3,
+            Value::String(_) => 4,
+            Value::Bytes(_) => 5,
+            Value::List(_) => 6,
+            Value::Tuple(_) => 7,
+            Value::Map(_) => 8,
+            Value::Variant(_) => 9,
+            Value::Enum(_) => 10,
+            Value::Class(_) => 11,
+            Value::Instance(_) => 12,
+            Value::NativeFun(_)
+            | Value::Function(_)
+            | Value::Closure(_)
+            | Value::Constructor(_)
+            | Value::Method(_) => 13,
+            Value::External(_) => 14,
+        }
+    }
+
+    fn is_nan(&self) -> bool {
+        matches!(self, Value::Float(num) if num.is_nan())
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Value::Float(num) => write!(f, "{num}"),
-            Value::Integer(num) => write!(f, "{num}"),
+            Value::Float(_) | Value::Integer(_) => {
+                f.write_str(&self.format_number(f.precision()))
+            }
             Value::Boolean(bool) => write!(f, "{bool}"),
             Value::String(str) => write!(f, "{str}"),
+            Value::Bytes(bytes) => {
+                let escaped = bytes
+                    .iter()
+                    .flat_map(|byte| std::ascii::escape_default(*byte))
+                    .map(char::from)
+                    .collect::<String>();
+                write!(f, "b\"{escaped}\"")
+            }
             Value::Unit => write!(f, "()"),
             Value::Nil => write!(f, "nil"),
             Value::List(list) => {
@@ -101,6 +358,10 @@ impl fmt::Display for Value {
                     }
                     items.push(format!("{}", item))
                 }
+                // a trailing comma sets a one-element tuple apart from a grouping
+                if items.len() == 1 {
+                    return write!(f, "({},)", items[0]);
+                }
                 write!(f, "({})", items.join(", "))
             }
             Value::Map(map) => write!(f, "{}", map.to_string()),
@@ -117,12 +378,255 @@ impl fmt::Display for Value {
             Value::Instance(instance) => write!(f, "{instance}"),
             Value::Method(method) => write!(f, "{method}"),
             Value::Constructor(init) => write!(f, "{init}"),
+            Value::External(external) => write!(f, "{external}"),
+            Value::Enum(enum_) => write!(f, "{enum_}"),
+            Value::Variant(variant) => write!(f, "{variant}"),
+        }
+    }
+}
+
+/// Numbers are equal if they have the same value, whether they're floats or
+/// integers, so `1 == 1.0`. Since `1` and `1.0` are also displayed the same,
+/// they're the same map key. `NaN` is never equal to anything, itself included.
+///
+/// Values of any other type are only equal to values of the same type.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(float), Value::Integer(int)) | (Value::Integer(int), Value::Float(float)) => {
+                compare_numbers(*float, *int) == Some(Ordering::Equal)
+            }
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::NativeFun(a), Value::NativeFun(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => a == b,
+            (Value::Class(a), Value::Class(b)) => a == b,
+            (Value::Instance(a), Value::Instance(b)) => a == b,
+            (Value::Constructor(a), Value::Constructor(b)) => a == b,
+            (Value::Method(a), Value::Method(b)) => a == b,
+            (Value::External(a), Value::External(b)) => a == b,
+            (Value::Enum(a), Value::Enum(b)) => a == b,
+            (Value::Variant(a), Value::Variant(b)) => a == b,
+            (Value::Unit, Value::Unit) | (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Numbers are ordered by value, whether they're floats or integers. Values of
+/// different types are unordered.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => compare_numbers(*a, *b),
+            (Value::Integer(a), Value::Float(b)) => compare_numbers(*b, *a).map(Ordering::reverse),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.partial_cmp(b),
+            (Value::List(a), Value::List(b)) => a.partial_cmp(b),
+            (Value::Tuple(a), Value::Tuple(b)) => a.partial_cmp(b),
+            (Value::Map(a), Value::Map(b)) => a.partial_cmp(b),
+            (Value::NativeFun(a), Value::NativeFun(b)) => a.partial_cmp(b),
+            (Value::Function(a), Value::Function(b)) => a.partial_cmp(b),
+            (Value::Closure(a), Value::Closure(b)) => a.partial_cmp(b),
+            (Value::Class(a), Value::Class(b)) => a.partial_cmp(b),
+            (Value::Instance(a), Value::Instance(b)) => a.partial_cmp(b),
+            (Value::Constructor(a), Value::Constructor(b)) => a.partial_cmp(b),
+            (Value::Method(a), Value::Method(b)) => a.partial_cmp(b),
+            (Value::External(a), Value::External(b)) => a.partial_cmp(b),
+            (Value::Enum(a), Value::Enum(b)) => a.partial_cmp(b),
+            (Value::Variant(a), Value::Variant(b)) => a.partial_cmp(b),
+            (Value::Unit, Value::Unit) | (Value::Nil, Value::Nil) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// A value ordered by [Value::total_cmp], for use where [Ord] is needed, such
+/// as the keys of a `BTreeMap`.
+///
+/// [Value] itself only implements [PartialOrd], since `NaN` isn't equal to
+/// itself and values of different kinds are unordered. Where [Value::total_cmp]
+/// treats two values as equal that aren't `==`, such as two different maps,
+/// they're ordered by their contents or else by their address instead, so only
+/// equal values are equal when wrapped. `TotalOrd(NaN)` is still equal to itself.
+#[derive(Debug, Clone)]
+pub struct TotalOrd(pub Value);
+
+impl PartialEq for TotalOrd {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalOrd {}
+
+impl PartialOrd for TotalOrd {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalOrd {
+    fn cmp(&self, other: &Self) -> Ordering {
+        distinct_cmp(&self.0, &other.0)
+    }
+}
+
+/// Order two values like [Value::total_cmp], but break its ties between values
+/// that aren't `==`.
+///
+/// Lists, tuples, maps and enum variants are compared by their contents, taking
+/// the entries of a map in the order of their keys. Other values that can't be
+/// ordered are told apart by their address, which is arbitrary but doesn't
+/// change while they're alive.
+fn distinct_cmp(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::List(a), Value::List(b)) => {
+            distinct_cmp_all(&RefCell::borrow(&a.0), &RefCell::borrow(&b.0))
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => distinct_cmp_all(&a.0, &b.0),
+        (Value::Map(a), Value::Map(b)) => {
+            let (a, b) = (sorted_entries(a), sorted_entries(b));
+
+            a.iter()
+                .zip(&b)
+                .map(|((key_a, a), (key_b, b))| key_a.cmp(key_b).then_with(|| distinct_cmp(a, b)))
+                .find(|order| order.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        }
+        (Value::Variant(a), Value::Variant(b)) => (&a.parent.name, &a.parent.variants, &a.name)
+            .cmp(&(&b.parent.name, &b.parent.variants, &b.name))
+            .then_with(|| distinct_cmp_all(&a.values, &b.values)),
+        (a, b) => a.total_cmp(b).then_with(|| address(a).cmp(&address(b))),
+    }
+}
+
+/// Compare two sequences of values element by element with [distinct_cmp], and
+/// then by length.
+fn distinct_cmp_all(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| distinct_cmp(a, b))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// The entries of a map, in the order of their keys.
+fn sorted_entries(map: &Map) -> Vec<(&String, &Value)> {
+    let mut entries = map.keys().zip(map.values()).collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+/// The address of a value that's only ever compared by reference, or `0` for
+/// any other value.
+fn address(value: &Value) -> usize {
+    match value {
+        Value::NativeFun(fun) => Rc::as_ptr(fun) as usize,
+        Value::Function(fun) => Rc::as_ptr(fun) as usize,
+        Value::Closure(closure) => Rc::as_ptr(closure) as usize,
+        Value::Class(class) => Rc::as_ptr(class) as usize,
+        Value::Instance(instance) => Rc::as_ptr(instance) as usize,
+        Value::Constructor(constructor) => Rc::as_ptr(constructor) as usize,
+        Value::Method(method) => Rc::as_ptr(method) as usize,
+        Value::External(external) => Rc::as_ptr(external) as usize,
+        Value::Enum(enum_) => Rc::as_ptr(enum_) as usize,
+        _ => 0,
+    }
+}
+
+/// Values hash consistently with `==`, so that equal values have equal hashes.
+///
+/// Numbers, booleans, strings, bytes, `nil`, `()`, and tuples and enum
+/// variants carrying these are hashable. Other values hash by their kind
+/// alone: they never panic, but all lists, maps, functions and so on fall
+/// into the same bucket, so looking them up falls back to comparing with `==`.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // floats and integers with the same value are equal, so they hash the same
+        let number = match self {
+            Value::Float(val) => Some(exact_integer(*val).ok_or(*val)),
+            Value::Integer(val) => Some(Ok(*val)),
+            _ => None,
+        };
+
+        if let Some(number) = number {
+            match number {
+                Ok(int) => int.hash(state),
+                Err(float) => float.to_bits().hash(state),
+            }
+            return;
+        }
+
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Value::Boolean(val) => val.hash(state),
+            Value::String(val) => val.hash(state),
+            Value::Bytes(val) => val.hash(state),
+            Value::Tuple(tuple) => tuple.0.hash(state),
+            Value::Variant(variant) => {
+                variant.name.hash(state);
+                variant.values.hash(state);
+            }
+            _ => {}
         }
     }
 }
 
 /// A generic function type.
-pub type Fun = dyn Fn(&mut Vm, Vec<Value>) -> Value;
+///
+/// Native functions return an error message if they are called with the
+/// wrong number or type of arguments.
+pub type Fun = dyn Fn(&mut Vm, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// Check that a native function was called with the expected number of arguments.
+fn check_arity(expected: usize, args: &[Value]) -> Result<(), String> {
+    if args.len() != expected {
+        return Err(format!(
+            "expected {expected} argument(s), found {}",
+            args.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The return value of a native function.
+///
+/// Native functions may return any [ToValue], or a `Result` to raise a runtime
+/// error, either with the given message or as a [RuntimeError].
+pub trait NativeResult {
+    fn into_result(self) -> Result<Value, RuntimeError>;
+}
+
+impl<T: ToValue> NativeResult for T {
+    fn into_result(self) -> Result<Value, RuntimeError> {
+        Ok(self.to_value())
+    }
+}
+
+impl<T: ToValue> NativeResult for Result<T, String> {
+    fn into_result(self) -> Result<Value, RuntimeError> {
+        self.map(ToValue::to_value).map_err(RuntimeError::from)
+    }
+}
+
+impl<T: ToValue> NativeResult for Result<T, RuntimeError> {
+    fn into_result(self) -> Result<Value, RuntimeError> {
+        self.map(ToValue::to_value)
+    }
+}
 
 /// A trait for defining how a function gets called.
 pub trait Callable<Args> {
@@ -156,10 +660,11 @@ pub trait RegisterFunction<Args, Return> {
     fn to_native_function(self) -> Rc<Fun>;
 }
 
-impl<F: Fn() -> R + 'static, R: ToValue> RegisterFunction<(), R> for F {
+impl<F: Fn() -> R + 'static, R: NativeResult> RegisterFunction<(), R> for F {
     fn to_native_function(self) -> Rc<Fun> {
-        Rc::new(Box::new(move |_vm: &mut Vm, _args: Vec<Value>| {
-            self().to_value()
+        Rc::new(Box::new(move |_vm: &mut Vm, args: Vec<Value>| {
+            check_arity(0, &args)?;
+            self().into_result()
         }))
     }
 
@@ -168,10 +673,11 @@ impl<F: Fn() -> R + 'static, R: ToValue> RegisterFunction<(), R> for F {
     }
 }
 
-impl<F: Fn(Value) -> R + 'static, R: ToValue> RegisterFunction<Value, R> for F {
+impl<F: Fn(&mut V) -> R + 'static, V: FromValue, R: NativeResult> RegisterFunction<&mut V, R> for F {
     fn to_native_function(self) -> Rc<Fun> {
         Rc::new(Box::new(move |_vm: &mut Vm, mut args: Vec<Value>| {
-            self(args.pop().unwrap()).to_value()
+            check_arity(1, &args)?;
+            self(&mut V::from_value(args.pop().unwrap())?).into_result()
         }))
     }
 
@@ -180,22 +686,10 @@ impl<F: Fn(Value) -> R + 'static, R: ToValue> RegisterFunction<Value, R> for F {
     }
 }
 
-impl<F: Fn(&mut V) -> R + 'static, V: FromValue, R: ToValue> RegisterFunction<&mut V, R> for F {
-    fn to_native_function(self) -> Rc<Fun> {
-        Rc::new(Box::new(move |_vm: &mut Vm, mut args: Vec<Value>| {
-            self(&mut V::from_value(args.pop().unwrap()).unwrap()).to_value()
-        }))
-    }
-
-    fn arity(self) -> Box<[TypeId]> {
-        vec![TypeId::of::<Value>()].into_boxed_slice()
-    }
-}
-
-impl<F: Fn(&mut Vm) -> R + 'static, R: ToValue> RegisterFunction<&mut Vm, R> for F {
+impl<F: Fn(&mut Vm) -> R + 'static, R: NativeResult> RegisterFunction<&mut Vm, R> for F {
     fn to_native_function(self) -> Rc<Fun> {
         Rc::new(Box::new(move |vm: &mut Vm, _args: Vec<Value>| {
-            self(vm).to_value()
+            self(vm).into_result()
         }))
     }
 
@@ -204,16 +698,16 @@ impl<F: Fn(&mut Vm) -> R + 'static, R: ToValue> RegisterFunction<&mut Vm, R> for
     }
 
     fn arity(self) -> Box<[TypeId]> {
-        vec![TypeId::of::<Vm>()].into_boxed_slice()
+        vec![].into_boxed_slice()
     }
 }
 
-impl<F: Fn(&mut Vm, Varidic<T>) -> R + 'static, R: ToValue, T: FromValue>
+impl<F: Fn(&mut Vm, Varidic<T>) -> R + 'static, R: NativeResult, T: FromValue>
     RegisterFunction<(&mut Vm, Varidic<T>), R> for F
 {
     fn to_native_function(self) -> Rc<Fun> {
         Rc::new(Box::new(move |vm: &mut Vm, args: Vec<Value>| {
-            self(vm, Varidic::new_from_iter::<Value>(args.iter())).to_value()
+            self(vm, Varidic::new_from_iter::<Value>(args.iter())).into_result()
         }))
     }
 
@@ -222,25 +716,53 @@ impl<F: Fn(&mut Vm, Varidic<T>) -> R + 'static, R: ToValue, T: FromValue>
     }
 
     fn arity(self) -> Box<[TypeId]> {
-        vec![TypeId::of::<Value>()].into_boxed_slice()
+        vec![].into_boxed_slice()
     }
 }
 
+/// Count the number of identifiers passed to the macro.
+macro_rules! count {
+    () => { 0 };
+    ($head:ident $($tail:ident)*) => { 1 + count!($($tail)*) };
+}
+
 macro_rules! register_function {
     () => {};
     ($param1:ident $($param:ident)*)  => {
         register_function!($($param)*);
 
-        impl<FN: Fn(&mut REF, $param1, $($param,)*) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* RET: ToValue, REF: FromValue + 'static> RegisterFunction<(&mut REF, $param1, $($param,)*), RET> for FN {
+        impl<FN: Fn($param1, $($param,)*) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* RET: NativeResult> RegisterFunction<(($param1, $($param,)*),), RET> for FN {
+            #[allow(non_snake_case)]
+            fn to_native_function(self) -> Rc<Fun> {
+                Rc::new(Box::new(move |_vm: &mut Vm, args: Vec<Value>| {
+                    check_arity(count!($param1 $($param)*), &args)?;
+
+                    let mut args = args.into_iter();
+
+                    let $param1 = $param1::from_value(args.next().unwrap())?;
+                    $(let $param = $param::from_value(args.next().unwrap())?;)*
+
+                    self($param1, $($param,)*).into_result()
+                }))
+            }
+
+            fn arity(self) -> Box<[TypeId]> {
+                vec![TypeId::of::<$param1>(), $(TypeId::of::<$param>(),)*].into_boxed_slice()
+            }
+        }
+
+        impl<FN: Fn(&mut REF, $param1, $($param,)*) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* RET: NativeResult, REF: FromValue + 'static> RegisterFunction<(&mut REF, $param1, $($param,)*), RET> for FN {
             #[allow(non_snake_case)]
             fn to_native_function(self) -> Rc<Fun> {
                 Rc::new(Box::new(move |_vm: &mut Vm, mut args: Vec<Value>| {
-                    let $param1 = $param1::from_value(args.pop().unwrap()).unwrap();
-                    $(let $param = $param::from_value(args.pop().unwrap()).unwrap();)*
+                    check_arity(count!($param1 $($param)*) + 1, &args)?;
+
+                    let $param1 = $param1::from_value(args.pop().unwrap())?;
+                    $(let $param = $param::from_value(args.pop().unwrap())?;)*
 
-                    let re = &mut REF::from_value(args.pop().unwrap()).unwrap();
+                    let re = &mut REF::from_value(args.pop().unwrap())?;
 
-                    self(re, $param1, $($param,)*).to_value()
+                    self(re, $param1, $($param,)*).into_result()
                 }))
             }
 
@@ -249,14 +771,18 @@ macro_rules! register_function {
             }
         }
 
-        impl<FN: Fn(&mut Vm, $param1, $($param,)*) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* RET: ToValue> RegisterFunction<(&mut Vm, $param1, $($param,)*), RET> for FN {
+        impl<FN: Fn(&mut Vm, $param1, $($param,)*) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* RET: NativeResult> RegisterFunction<(&mut Vm, $param1, $($param,)*), RET> for FN {
             #[allow(non_snake_case)]
             fn to_native_function(self) -> Rc<Fun> {
-                Rc::new(Box::new(move |vm: &mut Vm, mut args: Vec<Value>| {
-                    let $param1 = $param1::from_value(args.pop().unwrap()).unwrap();
-                    $(let $param = $param::from_value(args.pop().unwrap()).unwrap();)*
+                Rc::new(Box::new(move |vm: &mut Vm, args: Vec<Value>| {
+                    check_arity(count!($param1 $($param)*), &args)?;
 
-                    self(vm, $param1, $($param,)*).to_value()
+                    let mut args = args.into_iter();
+
+                    let $param1 = $param1::from_value(args.next().unwrap())?;
+                    $(let $param = $param::from_value(args.next().unwrap())?;)*
+
+                    self(vm, $param1, $($param,)*).into_result()
                 }))
             }
 
@@ -265,16 +791,20 @@ macro_rules! register_function {
             }
         }
 
-        impl<FN: Fn(&mut Vm, $param1, $($param,)* Varidic<VAL>) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* VAL: FromValue, RET: ToValue> RegisterFunction<(&mut Vm, $param1, $($param,)* Varidic<VAL>), RET> for FN {
+        impl<FN: Fn(&mut Vm, $param1, $($param,)* Varidic<VAL>) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* VAL: FromValue, RET: NativeResult> RegisterFunction<(&mut Vm, $param1, $($param,)* Varidic<VAL>), RET> for FN {
             #[allow(non_snake_case)]
             fn to_native_function(self) -> Rc<Fun> {
                 Rc::new(Box::new(move |vm: &mut Vm, args: Vec<Value>| {
+                    if args.len() < count!($param1 $($param)*) {
+                        check_arity(count!($param1 $($param)*), &args)?;
+                    }
+
                     let mut iter = args.iter();
 
-                    let $param1 = $param1::from_value(iter.next().expect("Oh no. It's broken").clone()).unwrap();
-                    $(let $param = $param::from_value(iter.next().expect("Oh no. It's broken.").clone()).unwrap();)*
+                    let $param1 = $param1::from_value(iter.next().unwrap().clone())?;
+                    $(let $param = $param::from_value(iter.next().unwrap().clone())?;)*
 
-                    self(vm, $param1, $($param,)* Varidic::new_from_iter::<VAL>(iter)).to_value()
+                    self(vm, $param1, $($param,)* Varidic::new_from_iter::<VAL>(iter)).into_result()
                 }))
             }
 
@@ -287,16 +817,20 @@ macro_rules! register_function {
             }
         }
 
-        impl<FN: Fn($param1, $($param,)* Varidic<VAL>) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* VAL: FromValue, RET: ToValue> RegisterFunction<($param1, $($param,)* Varidic<VAL>), RET> for FN {
+        impl<FN: Fn($param1, $($param,)* Varidic<VAL>) -> RET + 'static, $param1: FromValue + 'static, $($param: FromValue + 'static,)* VAL: FromValue, RET: NativeResult> RegisterFunction<($param1, $($param,)* Varidic<VAL>), RET> for FN {
             #[allow(non_snake_case)]
             fn to_native_function(self) -> Rc<Fun> {
                 Rc::new(Box::new(move |_vm: &mut Vm, args: Vec<Value>| {
+                    if args.len() < count!($param1 $($param)*) {
+                        check_arity(count!($param1 $($param)*), &args)?;
+                    }
+
                     let mut iter = args.iter();
 
-                    let $param1 = $param1::from_value(iter.next().expect("Oh no. It's broken").clone()).unwrap();
-                    $(let $param = $param::from_value(iter.next().expect("Oh no. It's broken.").clone()).unwrap();)*
+                    let $param1 = $param1::from_value(iter.next().unwrap().clone())?;
+                    $(let $param = $param::from_value(iter.next().unwrap().clone())?;)*
 
-                    self($param1, $($param,)* Varidic::new_from_iter::<VAL>(iter)).to_value()
+                    self($param1, $($param,)* Varidic::new_from_iter::<VAL>(iter)).into_result()
                 }))
             }
 
@@ -322,6 +856,8 @@ pub struct Class {
     pub methods: RefCell<HashMap<u64, CallableFunction>>,
     /// The class's fields.
     fields: RefCell<Vec<(Box<str>, Value)>>,
+    /// The class this class inherits from, if any.
+    super_class: RefCell<Option<Rc<Class>>>,
 }
 
 impl Class {
@@ -336,9 +872,20 @@ impl Class {
             name: name.into(),
             fields: RefCell::new(Vec::new()),
             methods: RefCell::new(HashMap::new()),
+            super_class: RefCell::new(None),
         }
     }
 
+    /// Make this class inherit the methods and fields of `parent`.
+    pub fn inherit(&self, parent: Rc<Class>) {
+        *self.super_class.borrow_mut() = Some(parent);
+    }
+
+    /// Get the class this class inherits from, if any.
+    pub fn super_class(&self) -> Option<Rc<Class>> {
+        self.super_class.borrow().clone()
+    }
+
     /// Insert a [CallableFunction] into the class's methods table.
     pub fn add_function<S: Into<Box<str>> + Copy>(
         &self,
@@ -424,11 +971,14 @@ impl Class {
         self.methods.borrow_mut().insert(hash, fun);
     }
 
-    /// Get a method.
+    /// Get a method, looking it up along the chain of super classes.
     pub fn get_method(&self, name: &str) -> Option<CallableFunction> {
         let hash = hash::calculate_hash(name, hash::METHOD);
 
-        self.methods.borrow().get(&hash).cloned()
+        match self.methods.borrow().get(&hash) {
+            Some(method) => Some(method.clone()),
+            None => self.super_class()?.get_method(name),
+        }
     }
 
     /// Get a static function.
@@ -449,6 +999,18 @@ impl Class {
     pub fn instance(self: Rc<Self>) -> Rc<Instance> {
         Instance::new(self)
     }
+
+    /// Copy the initial values of this class's fields, and those of its
+    /// super classes, into `fields`.
+    fn init_fields(&self, fields: &mut HashMap<Box<str>, Value>) {
+        if let Some(parent) = self.super_class() {
+            parent.init_fields(fields);
+        }
+
+        for (id, value) in &*self.fields.borrow() {
+            fields.insert(id.clone(), value.clone());
+        }
+    }
 }
 
 impl ToValue for Rc<Class> {
@@ -469,6 +1031,7 @@ impl fmt::Debug for Class {
             .field("name", &self.name)
             .field("fields", &self.fields)
             .field("methods", &self.methods)
+            .field("super_class", &self.super_class)
             .finish()
     }
 }
@@ -497,10 +1060,7 @@ pub struct Instance {
 impl Instance {
     pub fn new(class: Rc<Class>) -> Rc<Self> {
         let mut fields = HashMap::new();
-
-        for (id, value) in &*class.fields.borrow() {
-            fields.insert(id.clone(), value.clone());
-        }
+        class.init_fields(&mut fields);
 
         Rc::new(Self {
             class,
@@ -517,13 +1077,9 @@ impl Instance {
 
     /// Bind a method to an instance.
     pub fn bind<S: Into<Box<str>>>(receiver: Rc<Instance>, name: S) -> BoundMethod {
-        let name = name.into();
-        let hash = hash::calculate_hash(&name, hash::METHOD);
+        let method = receiver.class.get_method(&name.into()).unwrap();
 
-        let methods = &receiver.class.as_ref().methods.borrow();
-        let method = methods.get(&hash).unwrap();
-
-        BoundMethod::new(receiver.clone().to_value(), method.clone())
+        BoundMethod::new(receiver.to_value(), method)
     }
 
     pub fn builtin<S: Into<Box<str>>>(receiver: Value, name: S, class: Rc<Class>) -> BoundMethod {
@@ -535,6 +1091,21 @@ impl Instance {
         BoundMethod::new(receiver, method.clone())
     }
 
+    /// Check if this is an instance of `class`, or of a class inheriting from it.
+    pub fn is_instance_of(&self, class: &Rc<Class>) -> bool {
+        let mut current = Some(self.class.clone());
+
+        while let Some(ancestor) = current {
+            if Rc::ptr_eq(&ancestor, class) {
+                return true;
+            }
+
+            current = ancestor.super_class();
+        }
+
+        false
+    }
+
     /// Get a mutable reference to the instance's fields.
     pub fn fields_mut(&self) -> RefMut<'_, HashMap<Box<str>, Value>> {
         self.fields.borrow_mut()
@@ -650,81 +1221,257 @@ impl Constructor {
     }
 }
 
-/// The runtime representation of a `trait`.
-pub struct TraitObject {
-    
+/// An `enum` declaration.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct Enum {
+    pub name: Box<str>,
+    /// The name of each variant, with the number of values it carries.
+    pub variants: Vec<(Box<str>, usize)>,
 }
 
-impl Add for Value {
-    type Output = Value;
+impl Enum {
+    pub fn new<S: Into<Box<str>>>(name: S, variants: Vec<(Box<str>, usize)>) -> Self {
+        Enum {
+            name: name.into(),
+            variants,
+        }
+    }
 
-    fn add(self, rhs: Value) -> <Self as Add<Value>>::Output {
-        match (self, rhs) {
-            (Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs + rhs),
-            (Value::Tuple(_tuple), Value::Tuple(_other)) => {
-                todo!("This needs to be fixed.")
-            }
-            (Value::String(string), Value::String(other)) => Value::String(string + other),
-            (x, y) => unreachable!("Cannot add non-numbers and non-strings {} {}", x, y),
+    /// The number of values carried by the variant called `name`.
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .find(|(variant, _)| variant.as_ref() == name)
+            .map(|(_, arity)| *arity)
+    }
+
+    /// Look up a variant by name.
+    ///
+    /// A variant without values is returned as is, one with values as a
+    /// native function that constructs it.
+    pub fn variant(self: &Rc<Self>, name: &str) -> Option<Value> {
+        let arity = self.arity(name)?;
+        let name: Box<str> = name.into();
+
+        if arity == 0 {
+            return Some(Value::Variant(Rc::new(Variant::new(
+                self.clone(),
+                name,
+                Vec::new(),
+            ))));
         }
+
+        let parent = self.clone();
+        let constructor = NativeFun::new(
+            name.clone(),
+            vec![TypeId::of::<Value>(); arity].into_boxed_slice(),
+            Rc::new(move |_vm: &mut Vm, args: Vec<Value>| {
+                check_arity(arity, &args)?;
+                let variant = Variant::new(parent.clone(), name.clone(), args);
+                Ok(Value::Variant(Rc::new(variant)))
+            }),
+            false,
+        );
+
+        Some(Value::NativeFun(Rc::new(constructor)))
     }
 }
 
-impl Sub for Value {
-    type Output = Value;
+impl fmt::Display for Enum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
 
-    fn sub(self, rhs: Value) -> <Self as Sub<Value>>::Output {
-        if let (Value::Float(lhs), Value::Float(rhs)) = (self, rhs) {
-            Value::Float(lhs - rhs)
-        } else {
-            unreachable!()
+/// A variant of an [Enum], together with the values it carries.
+///
+/// Two variants are equal if they belong to equal enums, have the same name
+/// and carry equal values.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct Variant {
+    pub parent: Rc<Enum>,
+    pub name: Box<str>,
+    pub values: Vec<Value>,
+}
+
+impl Variant {
+    pub fn new(parent: Rc<Enum>, name: Box<str>, values: Vec<Value>) -> Self {
+        Variant {
+            parent,
+            name,
+            values,
         }
     }
 }
 
-impl Mul for Value {
-    type Output = Value;
-
-    fn mul(self, rhs: Value) -> <Self as Mul<Value>>::Output {
-        if let (Value::Float(lhs), Value::Float(rhs)) = (self, rhs) {
-            Value::Float(lhs * rhs)
-        } else {
-            unreachable!()
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.parent.name, self.name)?;
+
+        if !self.values.is_empty() {
+            let values = self
+                .values
+                .iter()
+                .map(|value| match value {
+                    Value::String(val) => format!("\"{val}\""),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>();
+            write!(f, "({})", values.join(", "))?;
         }
+
+        Ok(())
     }
 }
 
-impl Div for Value {
-    type Output = Value;
+/// The runtime representation of a `trait`.
+pub struct TraitObject {}
 
-    fn div(self, rhs: Value) -> <Self as Div<Value>>::Output {
-        if let (Value::Float(lhs), Value::Float(rhs)) = (self, rhs) {
-            Value::Float(lhs / rhs)
-        } else {
-            unreachable!()
+/// Fallible arithmetic, dispatched by the VM.
+///
+/// Operands of the wrong type produce a [RuntimeError::TypeMismatch] with an
+/// empty span; the VM fills in the span of the failing instruction.
+impl Value {
+    pub fn try_add(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs + rhs)),
+            (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Integer(lhs.wrapping_add(rhs))),
+            (Value::String(lhs), Value::String(rhs)) => Ok(Value::String(lhs + rhs)),
+            (Value::Bytes(lhs), Value::Bytes(rhs)) => {
+                Ok(Value::Bytes(Rc::new([lhs.as_slice(), rhs.as_slice()].concat())))
+            }
+            (Value::Tuple(lhs), Value::Tuple(rhs)) => {
+                let mut tuple = (*lhs.0).clone();
+                tuple.extend(rhs.0.iter().cloned());
+                Ok(Value::Tuple(ValueTuple(Rc::new(tuple))))
+            }
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
         }
     }
-}
 
-impl Rem for Value {
-    type Output = Value;
+    pub fn try_sub(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs - rhs)),
+            (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Integer(lhs.wrapping_sub(rhs))),
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
+        }
+    }
 
-    fn rem(self, rhs: Value) -> <Self as Rem<Value>>::Output {
-        if let (Value::Float(lhs), Value::Float(rhs)) = (self, rhs) {
-            Value::Float(lhs % rhs)
-        } else {
-            unreachable!()
+    pub fn try_mul(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs * rhs)),
+            (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Integer(lhs.wrapping_mul(rhs))),
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
         }
     }
-}
 
-impl Neg for Value {
-    type Output = Value;
-    fn neg(self) -> <Self as Neg>::Output {
-        if let Value::Float(val) = self {
-            Value::Float(-val)
-        } else {
-            unreachable!()
+    pub fn try_div(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs / rhs)),
+            (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Integer(lhs.wrapping_div(rhs))),
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
+        }
+    }
+
+    pub fn try_rem(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs % rhs)),
+            (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Integer(lhs.wrapping_rem(rhs))),
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
+        }
+    }
+
+    /// Raise `self` to the power of `rhs`.
+    ///
+    /// Floats follow [f64::powf], so a negative base with a fractional exponent
+    /// gives `NaN` rather than an error. An integer raised to a negative
+    /// exponent, or to a power that overflows an integer, falls back to
+    /// floating point rather than wrapping around.
+    pub fn try_pow(self, rhs: Value) -> Result<Value, RuntimeError> {
+        match (self, rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => Ok(Value::Float(lhs.powf(rhs))),
+            (Value::Integer(lhs), Value::Integer(rhs)) => {
+                match u32::try_from(rhs).ok().and_then(|exp| lhs.checked_pow(exp)) {
+                    Some(result) => Ok(Value::Integer(result)),
+                    None => Ok(Value::Float((lhs as f64).powf(rhs as f64))),
+                }
+            }
+            (lhs, rhs) => Err(Self::mismatched_operands(&lhs, &rhs)),
+        }
+    }
+
+    /// Shift `self` left by `rhs` bits.
+    ///
+    /// Floats are truncated to integers first. Shifting by a negative amount is
+    /// an error, and shifting by 64 or more bits gives zero.
+    pub fn try_shl(self, rhs: Value) -> Result<Value, RuntimeError> {
+        Self::shift(self, rhs, |lhs, rhs| lhs.checked_shl(rhs).unwrap_or(0))
+    }
+
+    /// Shift `self` right by `rhs` bits, keeping its sign.
+    ///
+    /// Follows the same rules as [Value::try_shl], except that shifting a
+    /// negative number by 64 or more bits gives `-1`.
+    pub fn try_shr(self, rhs: Value) -> Result<Value, RuntimeError> {
+        Self::shift(self, rhs, |lhs, rhs| {
+            lhs.checked_shr(rhs).unwrap_or(if lhs < 0 { -1 } else { 0 })
+        })
+    }
+
+    fn shift(self, rhs: Value, op: fn(i64, u32) -> i64) -> Result<Value, RuntimeError> {
+        let (lhs, count, is_float) = match (&self, &rhs) {
+            (Value::Float(lhs), Value::Float(rhs)) => (*lhs as i64, rhs.trunc(), true),
+            (Value::Integer(lhs), Value::Integer(rhs)) => (*lhs, *rhs as f64, false),
+            (lhs, rhs) => return Err(Self::mismatched_operands(lhs, rhs)),
+        };
+
+        if count < 0.0 {
+            return Err(RuntimeError::Custom(format!(
+                "attempt to shift by a negative amount `{rhs}`"
+            )));
+        }
+
+        let result = op(lhs, count.min(u32::MAX as f64) as u32);
+        match is_float {
+            true => Ok(Value::Float(result as f64)),
+            false => Ok(Value::Integer(result)),
+        }
+    }
+
+    pub fn try_neg(self) -> Result<Value, RuntimeError> {
+        match self {
+            Value::Float(val) => Ok(Value::Float(-val)),
+            Value::Integer(val) => Ok(Value::Integer(val.wrapping_neg())),
+            val => Err(RuntimeError::TypeMismatch {
+                expected: "f64".to_string(),
+                found: val.type_name(),
+                span: Span::empty(),
+            }),
+        }
+    }
+
+    /// The error for a binary operator applied to unsupported operands.
+    ///
+    /// If the left operand could take part in arithmetic, the right one is
+    /// reported as not matching it. Otherwise the left operand is the culprit.
+    fn mismatched_operands(lhs: &Value, rhs: &Value) -> RuntimeError {
+        let (expected, found) = match lhs {
+            Value::Float(_)
+            | Value::Integer(_)
+            | Value::String(_)
+            | Value::Bytes(_)
+            | Value::Tuple(_) => {
+                (lhs.type_name(), rhs.type_name())
+            }
+            _ => ("f64".to_string(), lhs.type_name()),
+        };
+
+        RuntimeError::TypeMismatch {
+            expected,
+            found,
+            span: Span::empty(),
         }
     }
 }
@@ -766,12 +1513,7 @@ impl Not for Value {
     type Output = Value;
 
     fn not(self) -> Self::Output {
-        match self {
-            Self::Boolean(true) => Value::Boolean(false),
-            Self::Boolean(false) => Value::Boolean(true),
-            // this should never be reached
-            _ => Value::Boolean(false),
-        }
+        Value::Boolean(!self.is_truthy())
     }
 }
 
@@ -828,33 +1570,41 @@ impl Ord for Function {
 
 impl Eq for Function {}
 
+/// Where a captured variable currently lives.
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct Upvalue {
-    pub value: Rc<Value>,
-    /// The next upvalue in the list, possibly none.
-    pub next: Option<Rc<Upvalue>>,
-    /// position in the stack?
-    pub position: usize,
-    pub closed: Value,
-}
+pub enum UpvalueLocation {
+    /// The variable is still on the stack, at the given slot.
+    Open(usize),
+    /// The variable's scope has exited, so its value is owned by the upvalue.
+    Closed(Value),
+}
+
+/// A variable captured by a closure.
+///
+/// Upvalues are shared, so every closure that captures the same variable sees
+/// the same value. While the variable is in scope the upvalue points at its
+/// stack slot, and when the scope exits the value is moved into the upvalue.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Upvalue(pub Rc<RefCell<UpvalueLocation>>);
 
 impl Upvalue {
-    pub fn new(value: Rc<Value>, next: Option<Rc<Upvalue>>, position: usize) -> Self {
-        Upvalue {
-            value,
-            next,
-            position,
-            closed: Value::Nil,
-        }
+    /// Create an upvalue pointing at a stack slot.
+    pub fn open(slot: usize) -> Self {
+        Upvalue(Rc::new(RefCell::new(UpvalueLocation::Open(slot))))
     }
 
-    pub fn next(&self) -> Option<Upvalue> {
-        if let Some(val) = &self.next {
-            Some(val.as_ref().clone())
-        } else {
-            None
+    /// The stack slot of an open upvalue.
+    pub fn slot(&self) -> Option<usize> {
+        match &*RefCell::borrow(&self.0) {
+            UpvalueLocation::Open(slot) => Some(*slot),
+            UpvalueLocation::Closed(_) => None,
         }
     }
+
+    /// Move the variable's value into the upvalue.
+    pub fn close(&self, value: Value) {
+        *self.0.borrow_mut() = UpvalueLocation::Closed(value);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -878,10 +1628,6 @@ impl Closure {
         }
     }
 
-    pub fn capture(&mut self, index: usize, value: Value) {
-        self.captures.borrow_mut()[index].value = Rc::new(value);
-    }
-
     /// Helper method for getting the function's name.
     pub fn name(&self) -> &str {
         &self.function.name
@@ -917,7 +1663,7 @@ impl NativeFun {
         self.param_typs.len()
     }
 
-    pub fn call(&self, vm: &mut Vm, args: Vec<Value>) -> Value {
+    pub fn call(&self, vm: &mut Vm, args: Vec<Value>) -> Result<Value, RuntimeError> {
         (self.fun)(vm, args)
     }
 }
@@ -1006,6 +1752,12 @@ impl ToValue for Value {
     }
 }
 
+impl ToValue for Rc<External> {
+    fn to_value(self) -> Value {
+        Value::External(self)
+    }
+}
+
 impl ToValue for Rc<Instance> {
     fn to_value(self) -> Value {
         Value::Instance(self)
@@ -1069,7 +1821,11 @@ macro_rules! impl_from_value {
             fn from_value(value: Value) -> Result<$typ, String> {
                 match value {
                     $T => $e,
-                    value => Err(format!("cannot coerce type from value {}", value)),
+                    value => Err(format!(
+                        "expected a value of type `{}`, found `{}`",
+                        stringify!($typ),
+                        value
+                    )),
                 }
             }
         }
@@ -1086,6 +1842,54 @@ impl_from_value!(bool, (Value::Boolean(v) => Ok(v)));
 impl_from_value!(Rc<Class>, (Value::Class(v) => Ok(v)));
 impl_from_value!(Rc<Instance>, (Value::Instance(v) => Ok(v)));
 impl_from_value!(ImmutableString, (Value::String(str) => Ok(str)));
+impl_from_value!(Rc<External>, (Value::External(v) => Ok(v)));
+impl_from_value!(Vec<Value>, (Value::List(list) => Ok(RefCell::borrow(&list.0).to_vec())));
+
+impl ToValue for Vec<Value> {
+    fn to_value(self) -> Value {
+        Value::List(ValueList::from_vec(&self))
+    }
+}
+
+impl_from_value!(Vec<u8>, (Value::Bytes(bytes) => Ok(bytes.to_vec())));
+
+impl ToValue for Vec<u8> {
+    fn to_value(self) -> Value {
+        Value::Bytes(Rc::new(self))
+    }
+}
+
+/// Implement [TryFrom<Value>] in terms of [FromValue].
+macro_rules! impl_try_from_value {
+    ($($typ:ty),*) => {
+        $(
+            impl TryFrom<Value> for $typ {
+                type Error = String;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    <$typ as FromValue>::from_value(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value!(f64, i64, bool, String, Vec<Value>, Vec<u8>);
+
+/// Implement [From] for [Value] in terms of [ToValue].
+macro_rules! impl_from_for_value {
+    ($($typ:ty),*) => {
+        $(
+            impl From<$typ> for Value {
+                fn from(value: $typ) -> Self {
+                    value.to_value()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_value!(f64, i64, bool, String, &str, Vec<Value>, Vec<u8>);
 
 pub trait Named {
     const NAME: &'static str;
@@ -1095,7 +1899,7 @@ pub trait Named {
 mod test {
     use std::mem;
 
-    use super::{ToValue, Value};
+    use super::{Class, Map, ToValue, TotalOrd, Value, ValueTuple};
 
     #[test]
     fn test_to_value() {
@@ -1104,6 +1908,257 @@ mod test {
         assert_eq!(Value::TRUE, value.to_value());
     }
 
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(f64::try_from(Value::Float(1.5)), Ok(1.5));
+        assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+        assert_eq!(String::try_from(Value::from("kaon")), Ok("kaon".to_string()));
+        assert_eq!(
+            Vec::<Value>::try_from(Value::from(vec![Value::from(1.0)])),
+            Ok(vec![Value::Float(1.0)])
+        );
+
+        assert_eq!(
+            f64::try_from(Value::Boolean(true)),
+            Err("expected a value of type `f64`, found `true`".to_string())
+        );
+        assert!(bool::try_from(Value::Float(0.0)).is_err());
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let falsy = [
+            Value::Nil,
+            Value::Unit,
+            Value::FALSE,
+            Value::Float(0.0),
+            Value::Float(-0.0),
+            Value::Integer(0),
+            Value::from(""),
+            Value::from(Vec::<u8>::new()),
+            Value::from(Vec::<Value>::new()),
+            Value::Tuple(ValueTuple(std::rc::Rc::new(Default::default()))),
+            Value::Map(Map::new()),
+        ];
+        for value in falsy {
+            assert!(!value.is_truthy(), "`{value}` should be falsy");
+            assert_eq!(!value, Value::TRUE);
+        }
+
+        let truthy = [
+            Value::TRUE,
+            Value::Float(0.5),
+            Value::Float(f64::NAN),
+            Value::Integer(-1),
+            Value::from("false"),
+            Value::from(vec![0_u8]),
+            Value::from(vec![Value::Nil]),
+        ];
+        for value in truthy {
+            assert!(value.is_truthy(), "`{value}` should be truthy");
+            assert_eq!(!value, Value::FALSE);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            Value::Float(1.0).try_add(Value::Float(2.0)),
+            Ok(Value::Float(3.0))
+        );
+        assert_eq!(
+            Value::Integer(7).try_rem(Value::Integer(4)),
+            Ok(Value::Integer(3))
+        );
+        assert!(Value::Integer(1).try_div(Value::Integer(0)).is_err());
+        assert!(Value::Float(1.0).try_add(Value::TRUE).is_err());
+        assert!(Value::Nil.try_neg().is_err());
+
+        assert_eq!(
+            Value::Float(2.0).try_pow(Value::Float(10.0)),
+            Ok(Value::Float(1024.0))
+        );
+        assert_eq!(
+            Value::Integer(3).try_pow(Value::Integer(4)),
+            Ok(Value::Integer(81))
+        );
+        assert_eq!(
+            Value::Integer(2).try_pow(Value::Integer(-1)),
+            Ok(Value::Float(0.5))
+        );
+        assert_eq!(
+            Value::Integer(2).try_pow(Value::Integer(64)),
+            Ok(Value::Float(2f64.powi(64)))
+        );
+        assert_eq!(
+            Value::Integer(-3).try_pow(Value::Integer(41)),
+            Ok(Value::Float(-(3f64.powi(41))))
+        );
+        assert!(matches!(
+            Value::Float(-8.0).try_pow(Value::Float(0.5)),
+            Ok(Value::Float(val)) if val.is_nan()
+        ));
+        assert!(Value::from("2").try_pow(Value::Float(2.0)).is_err());
+
+        let tuple = |values: &[f64]| {
+            Value::Tuple(ValueTuple(std::rc::Rc::new(
+                values.iter().map(|v| Value::Float(*v)).collect(),
+            )))
+        };
+        assert_eq!(
+            tuple(&[1.0]).try_add(tuple(&[2.0, 3.0])),
+            Ok(tuple(&[1.0, 2.0, 3.0]))
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        use std::hash::{Hash, Hasher};
+
+        let hash = |value: &Value| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+        let tuple = |values: Vec<Value>| Value::Tuple(ValueTuple(std::rc::Rc::new(values.into())));
+
+        assert_eq!(hash(&Value::Float(1.5)), hash(&Value::Float(3.0 / 2.0)));
+        assert_eq!(hash(&Value::Float(0.0)), hash(&Value::Float(-0.0)));
+        assert_eq!(hash(&Value::from("kaon")), hash(&Value::from("kaon")));
+        assert_eq!(
+            hash(&tuple(vec![Value::Float(1.0), Value::from("a")])),
+            hash(&tuple(vec![Value::Float(1.0), Value::from("a")]))
+        );
+        assert_ne!(hash(&Value::Float(1.0)), hash(&Value::Float(2.0)));
+        assert_eq!(hash(&Value::Float(1.0)), hash(&Value::Integer(1)));
+        assert_eq!(hash(&Value::Float(-0.0)), hash(&Value::Integer(0)));
+        assert_ne!(hash(&Value::Float(1.5)), hash(&Value::Integer(1)));
+
+        // unhashable values hash without panicking, consistently with `==`
+        assert_eq!(
+            hash(&Value::from(vec![Value::Float(1.0)])),
+            hash(&Value::from(vec![Value::Float(1.0)]))
+        );
+        assert_eq!(hash(&Value::Map(Map::new())), hash(&Value::Map(Map::new())));
+    }
+
+    #[test]
+    fn test_numeric_equality() {
+        assert_eq!(Value::Float(1.0), Value::Integer(1));
+        assert_eq!(Value::Integer(-3), Value::Float(-3.0));
+        assert_ne!(Value::Float(1.5), Value::Integer(1));
+        assert_ne!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+        assert_ne!(Value::Float(f64::NAN), Value::Integer(0));
+
+        // 2^63 is one past `i64::MAX`, which is not exactly representable as a float
+        assert_ne!(Value::Float(9_223_372_036_854_775_808.0), Value::Integer(i64::MAX));
+        assert_eq!(Value::Float(-9_223_372_036_854_775_808.0), Value::Integer(i64::MIN));
+
+        assert!(Value::Integer(1) < Value::Float(1.5));
+        assert!(Value::Float(-0.5) < Value::Integer(0));
+        assert_eq!(Value::Float(f64::NAN).partial_cmp(&Value::Integer(1)), None);
+
+        // values that compare equal display the same
+        let big = 1i64 << 60;
+        assert_eq!(Value::Float(big as f64), Value::Integer(big));
+        assert_eq!(Value::Float(big as f64).to_string(), Value::Integer(big).to_string());
+        assert_eq!(Value::Float(2.0).to_string(), Value::Integer(2).to_string());
+    }
+
+    #[test]
+    fn test_total_order() {
+        use std::cmp::Ordering::*;
+
+        let list = |values: Vec<Value>| Value::from(values);
+        let nan = Value::Float(f64::NAN);
+
+        assert_eq!(Value::Nil.total_cmp(&Value::Unit), Less);
+        assert_eq!(Value::Unit.total_cmp(&Value::Boolean(false)), Less);
+        assert_eq!(Value::Boolean(true).total_cmp(&Value::Float(-1.0)), Less);
+        assert_eq!(Value::Float(1e300).total_cmp(&Value::from("")), Less);
+        assert_eq!(Value::from("z").total_cmp(&list(vec![])), Less);
+        assert_eq!(list(vec![]).total_cmp(&Value::Map(Map::new())), Less);
+
+        assert_eq!(Value::Integer(2).total_cmp(&Value::Float(1.5)), Greater);
+        assert_eq!(Value::Float(2.0).total_cmp(&Value::Integer(2)), Equal);
+        assert_eq!(nan.total_cmp(&Value::Float(f64::INFINITY)), Greater);
+        assert_eq!(Value::Integer(i64::MAX).total_cmp(&nan), Less);
+        assert_eq!(nan.total_cmp(&nan), Equal);
+
+        let short = list(vec![Value::Float(1.0)]);
+        let long = list(vec![Value::Float(1.0), Value::Nil]);
+        assert_eq!(short.total_cmp(&long), Less);
+        assert_eq!(long.total_cmp(&list(vec![Value::Float(2.0)])), Less);
+        assert_eq!(list(vec![nan.clone()]).total_cmp(&list(vec![nan.clone()])), Equal);
+
+        // values that can't be ordered are equal, so sorting keeps them in place
+        assert_eq!(Value::Map(Map::new()).total_cmp(&Value::Map(Map::new())), Equal);
+    }
+
+    #[test]
+    fn test_total_ord() {
+        use std::collections::BTreeSet;
+
+        let values = [
+            Value::from("a"),
+            Value::Float(f64::NAN),
+            Value::Integer(2),
+            Value::Nil,
+            Value::Float(2.0),
+            Value::Float(f64::NAN),
+            Value::Boolean(true),
+        ];
+        let set: BTreeSet<TotalOrd> = values.into_iter().map(TotalOrd).collect();
+
+        // `2` and `2.0` are the same element, and so are the two `NaN`s
+        let sorted: Vec<String> = set.iter().map(|value| value.0.to_string()).collect();
+        assert_eq!(sorted, ["nil", "true", "2", "NaN", "a"]);
+
+        assert_eq!(TotalOrd(Value::Float(f64::NAN)), TotalOrd(Value::Float(f64::NAN)));
+        assert!(TotalOrd(Value::Unit) < TotalOrd(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_total_ord_distinct() {
+        use std::collections::BTreeSet;
+
+        let map = |key: &str, value: f64| {
+            let mut map = Map::new();
+            map.insert(key.to_string(), Value::Float(value));
+            Value::Map(map)
+        };
+        let maps = [map("a", 1.0), map("a", 2.0), map("b", 1.0), map("a", 1.0)];
+        let set: BTreeSet<TotalOrd> = maps.into_iter().map(TotalOrd).collect();
+
+        // only the two equal maps are the same element
+        assert_eq!(set.len(), 3);
+
+        let class = |name: &str| TotalOrd(Value::Class(Class::new(name)));
+        let set: BTreeSet<TotalOrd> = [class("A"), class("B")].into_iter().collect();
+        assert_eq!(set.len(), 2);
+
+        let lists = [Value::from(vec![map("a", 1.0)]), Value::from(vec![map("b", 1.0)])];
+        let set: BTreeSet<TotalOrd> = lists.into_iter().map(TotalOrd).collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(Value::Float(3.14159).format_number(Some(2)), "3.14");
+        assert_eq!(Value::Float(2.5).format_number(Some(0)), "2");
+        assert_eq!(Value::Float(1.0).format_number(None), "1");
+        assert_eq!(Value::Integer(7).format_number(Some(3)), "7.000");
+        assert_eq!(Value::Integer(-7).format_number(Some(0)), "-7");
+        assert_eq!(format!("{:.3}", Value::Float(1.0 / 3.0)), "0.333");
+        assert_eq!(format!("{}", Value::Float(0.1)), "0.1");
+
+        assert_eq!(Value::Float(1e300).format_number(None), "1e300");
+        assert_eq!(format!("{}", Value::Float(-1e20)), "-1e20");
+        assert_eq!(format!("{}", Value::Float(9007199254740991.0)), "9007199254740991");
+        assert_eq!(format!("{}", Value::Float(f64::INFINITY)), "inf");
+        assert_eq!(format!("{}", Value::Integer(i64::MIN)), "-9223372036854775808");
+    }
+
     #[test]
     fn test_size_of_value() {
         assert_eq!(16, mem::size_of::<Value>());
@@ -5,6 +5,7 @@ use smallvec::SmallVec;
 use std::fmt::{self, Display};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AST {
     pub nodes: Vec<ASTNode>,
     pub span: Span,
@@ -16,7 +17,22 @@ impl AST {
     }
 }
 
+#[cfg(feature = "serde")]
+impl AST {
+    /// Serialize the AST to JSON, for tools that don't link against Kaon.
+    ///
+    /// Structs become objects keyed by their field names. An enum variant becomes
+    /// an object whose only key is the variant's name, holding its fields in the
+    /// order they are declared, as in `{"Number": [1.0, {"start": 0, "length": 1}]}`.
+    /// Spans are the byte offset and length of a node in the source.
+    pub fn to_json(&self) -> String {
+        // every map in the AST is keyed by strings, so serializing can't fail
+        serde_json::to_string(self).expect("the AST is always valid JSON")
+    }
+}
+
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ASTNode {
     Stmt(Stmt),
     Expr(Expr),
@@ -47,6 +63,7 @@ impl From<&Expr> for ASTNode {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Signature {
     pub name: Ident,
     pub params: (Vec<Ident>, Vec<Option<Expr>>),
@@ -57,6 +74,7 @@ pub struct Signature {
 pub struct StmtBlock(SmallVec<[Stmt; 4]>, Span);
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Trait {
     pub methods: Vec<TraitMethod>,
     pub span: Span,
@@ -65,6 +83,7 @@ pub struct Trait {
 /// A `trait` method, including the function's signature and an
 /// optional default block. 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TraitMethod {
     pub sig: Signature,
     pub default: Option<Stmt>,
@@ -73,13 +92,18 @@ pub struct TraitMethod {
 
 /// A statment
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Stmt {
     /// `if` expr `{` body `}` `else` `{`body `}`
     IfStatement(Expr, Box<(Stmt, Option<Stmt>)>, Span),
-    /// `while` expr `{` body `}`
-    WhileStatement(Expr, Box<Stmt>, Span),
-    /// `loop` `{` body `}`
-    LoopStatement(Box<Stmt>, Span),
+    /// [label `:`] `while` expr `{` body `}`
+    WhileStatement(Expr, Box<Stmt>, Option<Ident>, Span),
+    /// [label `:`] `loop` `{` body `}`
+    LoopStatement(Box<Stmt>, Option<Ident>, Span),
+    /// [label `:`] `for` id `in` expr `{` body `}`
+    ForStatement(Ident, Expr, Box<Stmt>, Option<Ident>, Span),
+    /// `try` `{` body `}` `catch` id `{` body `}`
+    TryStatement(Box<(Stmt, Ident, Stmt)>, Span),
     /// import statement
     ImportStatement(Expr, Span),
     /// `{` body `}`
@@ -95,14 +119,18 @@ pub enum Stmt {
     /// `class` id `{` method | field `}`
     Class(Class, Span),
     Trait(Trait),
+    /// `enum` id `{` variant [`(` ...fields `)`] `}`
+    Enum(Box<Enum>, Span),
+    /// `match` expr `{` pattern `=>` body `}`
+    Match(Box<(Expr, Vec<MatchArm>)>, Span),
     /// `const` name `(` ...args `)` `{` body `}`
     Constructor(Box<Constructor>, Span),
     /// `return` expr
     Return(Option<Expr>, Span),
-    /// `break`
-    Break(Span),
-    /// `continue`
-    Continue(Span),
+    /// `break` [label] [expr]
+    Break(Option<Ident>, Option<Expr>, Span),
+    /// `continue` [label]
+    Continue(Option<Ident>, Span),
     /// [expression][Expr]
     Expr(Expr),
 }
@@ -111,8 +139,10 @@ impl Stmt {
     pub fn span(&self) -> Span {
         match self.clone() {
             Self::IfStatement(_, _, span) => span,
-            Self::WhileStatement(_, _, span) => span,
-            Self::LoopStatement(_, span) => span,
+            Self::WhileStatement(_, _, _, span) => span,
+            Self::ForStatement(_, _, _, _, span) => span,
+            Self::LoopStatement(_, _, span) => span,
+            Self::TryStatement(_, span) => span,
             Self::ImportStatement(_, span) => span,
             Self::Block(_, span) => span,
             Self::VarDeclaration(_, _, _, span) => span,
@@ -121,16 +151,19 @@ impl Stmt {
             Self::Function(_, span) => span,
             Self::Class(_, span) => span,
             Self::Trait(trait_) => trait_.span,
+            Self::Enum(_, span) => span,
+            Self::Match(_, span) => span,
             Self::Constructor(_, span) => span,
             Self::Return(_, span) => span,
-            Self::Break(span) => span,
-            Self::Continue(span) => span,
+            Self::Break(_, _, span) => span,
+            Self::Continue(_, span) => span,
             Self::Expr(expr) => expr.span(),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ScriptFun {
     /// Function name.
     pub name: Ident,
@@ -167,6 +200,7 @@ impl ScriptFun {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FunAccess {
     Public,
     Private,
@@ -174,6 +208,7 @@ pub enum FunAccess {
 
 /// A class declaration.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Class {
     /// The name of the class.
     pub name: Ident,
@@ -185,6 +220,8 @@ pub struct Class {
     pub methods: Vec<Stmt>,
     /// List of constructors.
     pub constructors: Vec<Stmt>,
+    /// List of static functions.
+    pub statics: Vec<Stmt>,
 }
 
 impl Class {
@@ -194,6 +231,7 @@ impl Class {
         fields: Vec<Stmt>,
         methods: Vec<Stmt>,
         constructors: Vec<Stmt>,
+        statics: Vec<Stmt>,
     ) -> Self {
         Self {
             name,
@@ -201,6 +239,7 @@ impl Class {
             fields,
             methods,
             constructors,
+            statics,
         }
     }
 
@@ -210,6 +249,7 @@ impl Class {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Constructor {
     pub name: Ident,
     pub params: Vec<Ident>,
@@ -228,7 +268,59 @@ impl Constructor {
     }
 }
 
+/// An enum declaration.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Enum {
+    /// The name of the enum.
+    pub name: Ident,
+    /// List of variants.
+    pub variants: Vec<Variant>,
+}
+
+/// A variant of an enum.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Variant {
+    /// The name of the variant.
+    pub name: Ident,
+    /// The names of the values carried by the variant, if any.
+    pub fields: Vec<Ident>,
+}
+
+/// An arm of a `match` statement.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    /// The statement run if the pattern matches.
+    pub body: Stmt,
+}
+
+/// The left-hand side of a `match` arm.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Pattern {
+    /// `_`, which matches any value
+    Wildcard(Span),
+    /// A literal, which matches values equal to it
+    Literal(Expr),
+    /// `Enum.Variant` or `Enum.Variant(a, b)`, which binds the values carried by the variant
+    Variant(Expr, Ident, Vec<Ident>, Span),
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Wildcard(span) => span.clone(),
+            Self::Literal(expr) => expr.span(),
+            Self::Variant(_, _, _, span) => span.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BinExpr {
     /// The binary operator.
     pub op: Op,
@@ -245,6 +337,7 @@ impl BinExpr {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Op {
     /// Addition a + b
     Add,
@@ -256,6 +349,8 @@ pub enum Op {
     Divide,
     /// Remainder a % b
     Remainder,
+    /// Exponentiation a ** b
+    Power,
     /// Greater-than check a > b
     GreaterThan,
     /// Greater-than or equal check a >= b
@@ -274,18 +369,37 @@ pub enum Op {
     BitwiseOr,
     /// Bitwise xor a ^ b
     BitwiseXor,
+    /// Left shift a << b
+    Shl,
+    /// Right shift a >> b
+    Shr,
+    /// Class membership check a is B
+    ///
+    /// True if `a` is an instance of the class `B` or one of its subclasses, and
+    /// false for any other value. This isn't an equality check: `==` compares
+    /// two instances structurally, by their class and fields, rather than by
+    /// identity, and `is` is an error if `B` isn't a class.
+    Is,
+    /// Negated class membership check a isnt B
+    Isnt,
     /// Falsy check !a
     Bang,
 }
 
-impl From<&str> for Op {
-    fn from(op: &str) -> Self {
-        match op {
+/// Look up an operator by its symbol, the inverse of its [Display] form.
+///
+/// Fails with the symbol itself if it isn't an operator.
+impl TryFrom<&str> for Op {
+    type Error = String;
+
+    fn try_from(op: &str) -> Result<Self, String> {
+        Ok(match op {
             "+" => Op::Add,
             "-" => Op::Subtract,
             "*" => Op::Multiply,
             "/" => Op::Divide,
             "%" => Op::Remainder,
+            "**" => Op::Power,
             ">" => Op::GreaterThan,
             ">=" => Op::GreaterThanEquals,
             "<" => Op::LessThan,
@@ -295,9 +409,13 @@ impl From<&str> for Op {
             "&" => Op::BitwiseAnd,
             "|" => Op::BitwiseOr,
             "^" => Op::BitwiseXor,
+            "<<" => Op::Shl,
+            ">>" => Op::Shr,
+            "is" => Op::Is,
+            "isnt" => Op::Isnt,
             "!" => Op::Bang,
-            _ => unreachable!(),
-        }
+            _ => return Err(op.to_string()),
+        })
     }
 }
 
@@ -309,6 +427,7 @@ impl Display for Op {
             Op::Multiply => f.write_str("*"),
             Op::Divide => f.write_str("/"),
             Op::Remainder => f.write_str("%"),
+            Op::Power => f.write_str("**"),
             Op::GreaterThan => f.write_str(">"),
             Op::GreaterThanEquals => f.write_str(">="),
             Op::LessThan => f.write_str("<"),
@@ -318,12 +437,17 @@ impl Display for Op {
             Op::BitwiseAnd => f.write_str("&"),
             Op::BitwiseOr => f.write_str("|"),
             Op::BitwiseXor => f.write_str("^"),
+            Op::Shl => f.write_str("<<"),
+            Op::Shr => f.write_str(">>"),
+            Op::Is => f.write_str("is"),
+            Op::Isnt => f.write_str("isnt"),
             Op::Bang => f.write_str("!"),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Ident {
     pub name: String,
     pub span: Span,
@@ -337,6 +461,7 @@ impl Ident {
 
 /// An expression
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr {
     /// f64
     Number(f64, Span),
@@ -350,6 +475,8 @@ pub enum Expr {
     Nil(Span),
     /// self
     SelfExpr(Span),
+    /// super.method
+    SuperExpr(Ident, Span),
     /// id
     Identifier(Ident),
     /// expr `op` expr
@@ -358,6 +485,8 @@ pub enum Expr {
     UnaryExpr(Op, Box<Expr>, Span),
     /// expr `[` expr `]`
     Index(Box<Expr>, Box<Expr>, Span),
+    /// expr[lo..hi], where either bound may be left out
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>, Span),
     /// `(` [Expr] `)`
     ParenExpr(Box<Expr>, Span),
     /// [ expr, ... ]
@@ -370,6 +499,10 @@ pub enum Expr {
     Or(Box<Expr>, Box<Expr>, Span),
     /// expr `and` expr
     And(Box<Expr>, Box<Expr>, Span),
+    /// expr `<` expr `<` expr ..., a chain of two or more comparisons which is
+    /// true if each of them holds, as in `a < b and b < c`, except that each
+    /// operand is evaluated at most once
+    Chain(Box<Expr>, Box<Vec<(Op, Expr)>>, Span),
     /// expr `(` expr, ... `)`
     FunCall(Box<Expr>, Box<Vec<Expr>>, Span),
     /// expr `.` expr
@@ -378,6 +511,14 @@ pub enum Expr {
     AssocExpr(Box<Expr>, Box<Expr>, Span),
     /// type
     Type(TypePath, Span),
+    /// `loop` `{` ... `}`, which evaluates to the value it breaks with
+    Loop(Box<Stmt>, Span),
+    /// `...` expr, expanding a list into the arguments of a call or the
+    /// elements of a list
+    Spread(Box<Expr>, Span),
+    /// expr `=` expr, an assignment in a chain such as `a = b = 0`, which
+    /// evaluates to the value assigned
+    Assign(Box<Expr>, Box<Expr>, Span),
 }
 
 impl Expr {
@@ -389,25 +530,32 @@ impl Expr {
             | Self::Unit(span)
             | Self::Nil(span)
             | Self::SelfExpr(span)
+            | Self::SuperExpr(_, span)
             | Self::BinExpr(_, span)
             | Self::UnaryExpr(_, _, span)
             | Self::ParenExpr(_, span)
             | Self::Index(_, _, span)
+            | Self::Slice(_, _, _, span)
             | Self::List(_, span)
             | Self::Tuple(_, span)
             | Self::Map(_, span)
             | Self::Or(_, _, span)
             | Self::And(_, _, span)
+            | Self::Chain(_, _, span)
             | Self::FunCall(_, _, span)
             | Self::MemberExpr(_, _, span)
             | Self::AssocExpr(_, _, span)
-            | Self::Type(_, span) => span,
+            | Self::Type(_, span)
+            | Self::Loop(_, span)
+            | Self::Spread(_, span)
+            | Self::Assign(_, _, span) => span,
             Self::Identifier(x) => x.span(),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TypePath {
     pub ident: Ident,
     pub arguments: Option<Box<TypePath>>,
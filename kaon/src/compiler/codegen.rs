@@ -1,25 +1,46 @@
-use crate::common::{Captured, Function, Opcode, Span, Value};
+use crate::common::{self, Captured, Function, Opcode, Span, Value};
 use crate::compiler::{
     ASTNode, BinExpr, Class, Constructor, Expr, Ident, Op, Scope, ScriptFun, Stmt, TypePath,
     AST,
 };
 
+use std::collections::HashMap;
 use std::rc::Rc;
 
-use super::ast::Trait;
+use super::ast::{Enum, MatchArm, Pattern, Trait};
+
+/// The furthest distance, in bytes, that a jump can travel.
+///
+/// Jump offsets are encoded as a relative, big-endian `u16` following the
+/// jump opcode, so a single branch or loop body is limited to 65,535 bytes.
+pub const MAX_JUMP: usize = u16::MAX as usize;
 
 /// Track the state of a loop.
 #[derive(Clone)]
 pub struct Loop {
     start_ip: usize,
     jump_placeholders: Vec<usize>,
+    /// The number of enclosing `try` blocks when the loop was entered.
+    try_depth: usize,
+    /// The number of locals when the loop was entered, which are left on the
+    /// stack by `break` and `continue`.
+    locals_count: usize,
+    /// The slot of the local holding the loop's value, for a `loop` that can
+    /// break with one.
+    result: Option<usize>,
+    /// The loop's label, if it has one.
+    label: Option<String>,
 }
 
 impl Loop {
-    pub fn new(start_ip: usize) -> Self {
+    pub fn new(start_ip: usize, try_depth: usize, locals_count: usize) -> Self {
         Loop {
             start_ip,
             jump_placeholders: Vec::default(),
+            try_depth,
+            locals_count,
+            result: None,
+            label: None,
         }
     }
 }
@@ -61,7 +82,7 @@ impl Upvalues {
         self.upvalues_count += 1;
         self.upvalues.push(upvalue);
 
-        index
+        self.upvalues.len() - 1
     }
 }
 
@@ -166,6 +187,14 @@ pub struct Compiler {
     frames: Vec<Frame>,
     /// A stack for tracking loop information.
     loop_stack: Vec<Loop>,
+    /// The number of `try` blocks currently being compiled.
+    try_depth: usize,
+    /// The parents of the classes currently being compiled.
+    parents: Vec<Option<Ident>>,
+    /// The spans of the nodes currently being compiled.
+    spans: Vec<Span>,
+    /// Global constants whose values are known at compile time, keyed by name.
+    constants: HashMap<String, Expr>,
 }
 
 impl Default for Compiler {
@@ -180,6 +209,48 @@ impl Compiler {
             globals: Scope::new(),
             frames: Vec::new(),
             loop_stack: Vec::new(),
+            try_depth: 0,
+            parents: Vec::new(),
+            spans: Vec::new(),
+            constants: HashMap::new(),
+        }
+    }
+
+    /// Evaluate `expr` at compile time, if it only depends on literals and
+    /// other folded constants.
+    ///
+    /// Only arithmetic on numbers is folded, following the same rules as the VM.
+    fn fold_constant(&self, expr: &Expr) -> Option<Expr> {
+        match expr {
+            Expr::Number(..) | Expr::String(..) | Expr::Boolean(..) | Expr::Nil(_) => {
+                Some(expr.clone())
+            }
+            Expr::Identifier(ident) => self.constants.get(&ident.name).cloned(),
+            Expr::ParenExpr(expr, _) => self.fold_constant(expr),
+            Expr::UnaryExpr(op, expr, span) => fold_unary(op, &self.fold_constant(expr)?, span),
+            Expr::BinExpr(bin_expr, span) => {
+                let lhs = match self.fold_constant(&bin_expr.lhs)? {
+                    Expr::Number(val, _) => val,
+                    _ => return None,
+                };
+                let rhs = match self.fold_constant(&bin_expr.rhs)? {
+                    Expr::Number(val, _) => val,
+                    _ => return None,
+                };
+
+                let val = match bin_expr.op {
+                    Op::Add => lhs + rhs,
+                    Op::Subtract => lhs - rhs,
+                    Op::Multiply => lhs * rhs,
+                    Op::Divide => lhs / rhs,
+                    Op::Remainder => lhs % rhs,
+                    Op::Power => lhs.powf(rhs),
+                    _ => return None,
+                };
+
+                Some(Expr::Number(val, span.clone()))
+            }
+            _ => None,
         }
     }
 
@@ -188,6 +259,19 @@ impl Compiler {
         self.current_mut_frame().locals.depth += 1;
     }
 
+    /// Emit pops for the locals above the first `count`, without removing them
+    /// from the scope, for jumping out of the scope early.
+    fn pop_locals(&mut self, count: usize) {
+        let locals = self.current_frame().locals.locals[count..].to_vec();
+        for local in locals.iter().rev() {
+            if local.is_captured {
+                self.emit_opcode(Opcode::CloseUpValue);
+            } else {
+                self.emit_opcode(Opcode::Pop);
+            }
+        }
+    }
+
     /// Leave the scope, and clean up the mess.
     fn exit_scope(&mut self) {
         self.current_mut_frame().locals.depth -= 1;
@@ -276,34 +360,26 @@ impl Compiler {
         None
     }
 
-    /// Find an upvalue, itererating backwards through each frame.
+    /// Find an upvalue for the current frame.
     fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
-        let mut frame_count = self.frames.len() as i32 - 1;
-
-        while frame_count != -1 {
-            let frame = self.frames.get(frame_count as usize)?;
-            let local = self.resolve_local(name, frame);
-
-            let frame = &mut self.frames[frame_count as usize];
-
-            if let Some(local) = local {
-                frame.locals.locals[local].is_captured = true;
-                return self.add_upvalue(local, true);
-            }
+        self.resolve_upvalue_in(self.frame_count() - 1, name)
+    }
 
-            frame_count -= 1;
+    /// Find an upvalue for the frame at `frame`, capturing a local of the
+    /// enclosing frame or, failing that, one of the enclosing frame's upvalues.
+    fn resolve_upvalue_in(&mut self, frame: usize, name: &str) -> Option<usize> {
+        if frame == 0 {
+            return None;
         }
 
-        None
-    }
+        let enclosing = frame - 1;
+        if let Some(local) = self.resolve_local(name, &self.frames[enclosing]) {
+            self.frames[enclosing].locals.locals[local].is_captured = true;
+            return Some(self.frames[frame].upvalues.add_upvalue(local, true));
+        }
 
-    /// Add an upvalue to the current frame.
-    fn add_upvalue(&mut self, index: usize, is_local: bool) -> Option<usize> {
-        Some(
-            self.current_mut_frame()
-                .upvalues
-                .add_upvalue(index, is_local),
-        )
+        let upvalue = self.resolve_upvalue_in(enclosing, name)?;
+        Some(self.frames[frame].upvalues.add_upvalue(upvalue, false))
     }
 
     /// Return a reference to the current frame.
@@ -404,30 +480,45 @@ impl Compiler {
         Ok(())
     }
 
-    /// Emit an identifier.
+    /// Emit an identifier, reusing the slot of an identical one.
     fn emit_indent(&mut self, value: &str) -> usize {
-        self.current_mut_frame().function.chunk.identifier(value)
+        let chunk = &mut self.current_mut_frame().function.chunk;
+        match chunk.variables.iter().position(|ident| &**ident == value) {
+            Some(index) => index,
+            None => chunk.identifier(value),
+        }
     }
 
-    /// Emit a value.
+    /// Emit a value, reusing the slot of an identical number, string or boolean.
     fn emit_constant(&mut self, constant: Value) -> usize {
-        self.current_mut_frame()
-            .function
-            .chunk
-            .add_constant(constant)
+        let chunk = &mut self.current_mut_frame().function.chunk;
+        match chunk
+            .constants
+            .iter()
+            .position(|existing| same_constant(existing, &constant))
+        {
+            Some(index) => index,
+            None => chunk.add_constant(constant),
+        }
     }
 
-    /// Emit a loop.
-    fn emit_loop(&mut self, count: usize) {
+    /// Emit a backwards jump to `count`.
+    fn emit_loop(&mut self, count: usize) -> Result<(), CompileErr> {
         self.emit_opcode(Opcode::Loop);
 
         let offset = self.current_frame().function.chunk.opcodes.len() - count + 2;
+        if offset > MAX_JUMP {
+            return Err(CompileErr("loop body is too large".to_string()));
+        }
 
         self.emit_byte(((offset >> 8) & 0xff) as u8);
         self.emit_byte((offset & 0xff) as u8);
+
+        Ok(())
     }
 
-    /// Emit a jump opcode.
+    /// Emit a forward jump with a placeholder offset, returning the
+    /// position of the offset so it can be backpatched with [`Compiler::patch_jump`].
     fn emit_jump(&mut self, opcode: Opcode) -> usize {
         self.emit_opcode(opcode);
         self.emit_byte(0xff);
@@ -435,10 +526,10 @@ impl Compiler {
         self.current_frame().function.chunk.opcodes.len() - 2
     }
 
-    /// Patch a jump.
+    /// Patch a jump so that it lands on the next opcode to be emitted.
     fn patch_jump(&mut self, offset: usize) -> Result<(), CompileErr> {
         let jump = self.current_frame().function.chunk.opcodes.len() - offset - 2;
-        if jump > u16::MAX.into() {
+        if jump > MAX_JUMP {
             return Err(CompileErr("Too much code to jump".to_string()));
         }
         self.current_mut_frame().function.chunk.opcodes[offset] = (jump as u16 >> 8) as u8;
@@ -474,8 +565,12 @@ impl Compiler {
         self.emit_byte(arg);
     }
 
-    /// Emit an opcode.
+    /// Emit an opcode, recording the span of the node it was compiled from.
     fn emit_opcode(&mut self, opcode: Opcode) {
+        if let Some(span) = self.spans.last().cloned() {
+            self.current_mut_frame().function.chunk.emit_span(span);
+        }
+
         let byte = opcode as u8;
         self.emit_byte(byte);
     }
@@ -513,16 +608,19 @@ impl Compiler {
 impl Compiler {
     /// Compile a statment.
     fn statment(&mut self, stmt: &Stmt) -> Result<(), CompileErr> {
-        self.current_mut_frame()
-            .function
-            .chunk
-            .emit_span(stmt.span());
+        self.spans.push(stmt.span());
 
-        match stmt {
+        let result = match stmt {
             Stmt::Block(stmts, _) => self.block(stmts),
             Stmt::IfStatement(expr, body, _) => self.if_statement(expr, body),
-            Stmt::WhileStatement(expr, body, _) => self.while_statement(expr, body),
-            Stmt::LoopStatement(body, _) => self.loop_statement(body),
+            Stmt::WhileStatement(expr, body, label, _) => {
+                self.while_statement(expr, body, label)
+            }
+            Stmt::LoopStatement(body, label, _) => self.loop_statement(body, label),
+            Stmt::ForStatement(ident, iter, body, label, _) => {
+                self.for_statement(ident, iter, body, label)
+            }
+            Stmt::TryStatement(body, _) => self.try_statement(&body.0, &body.1, &body.2),
             Stmt::ImportStatement(import, _) => self.import_statement(import),
             Stmt::VarDeclaration(ident, expr, _, _) => self.var_decl(ident, expr),
             Stmt::ConDeclaration(ident, expr, _, _) => self.con_decl(ident, expr),
@@ -530,15 +628,24 @@ impl Compiler {
             Stmt::Function(fun, _) => self.fun(fun),
             Stmt::Class(class, _) => self.class(class),
             Stmt::Trait(t) => self.trait_decl(t),
+            Stmt::Enum(enum_, _) => self.enum_decl(enum_),
+            Stmt::Match(match_, _) => self.match_stmt(&match_.0, &match_.1),
             Stmt::Constructor(constructor, _) => self.constructor(constructor),
             Stmt::Return(expr, _) => self.return_stmt(expr),
-            Stmt::Break(_) => self.break_stmt(),
-            Stmt::Continue(_) => self.continue_stmt(),
+            Stmt::Break(label, expr, _) => self.break_stmt(label, expr),
+            Stmt::Continue(label, _) => self.continue_stmt(label),
             Stmt::Expr(expr) => self.emit_expression(expr),
-        }
+        };
+
+        self.spans.pop();
+        result
     }
 
     /// Compile an if-else statement.
+    ///
+    /// `JumpIfFalse` leaves the condition on the stack, so both branches
+    /// start by popping it. The then-branch ends with a jump over the else-branch,
+    /// which is emitted (as an empty branch) even when there is no `else`.
     fn if_statement(
         &mut self,
         condition: &Expr,
@@ -564,40 +671,256 @@ impl Compiler {
     }
 
     /// Compile a while statement.
-    fn while_statement(&mut self, condition: &Expr, block: &Stmt) -> Result<(), CompileErr> {
+    fn while_statement(
+        &mut self,
+        condition: &Expr,
+        block: &Stmt,
+        label: &Option<Ident>,
+    ) -> Result<(), CompileErr> {
         let loop_start = self.current_frame().function.chunk.opcodes.len();
+        let locals_count = self.current_frame().locals.locals_count;
 
-        self.loop_stack.push(Loop::new(loop_start));
+        let mut loop_ = Loop::new(loop_start, self.try_depth, locals_count);
+        loop_.label = label.as_ref().map(|label| label.name.clone());
+        self.loop_stack.push(loop_);
 
         self.expression(condition)?;
         let jump = self.emit_jump(Opcode::JumpIfFalse);
         self.emit_opcode(Opcode::Pop);
 
         self.statment(block)?;
-        self.emit_loop(loop_start);
+        self.emit_loop(loop_start)?;
 
+        self.patch_jump(jump)?;
+        self.emit_opcode(Opcode::Pop);
+
+        // `break` jumps past the condition's pop, as the body already popped it
         self.leave_loop()?;
 
+        Ok(())
+    }
+
+    /// Compile a for loop.
+    ///
+    /// `Iter` turns the value being looped over into an iterator, which is kept
+    /// in a hidden local. Each iteration calls the iterator's `next` method, and
+    /// the loop ends once it returns `StopIteration`. Otherwise, the value
+    /// becomes the loop variable, a local in the scope of the body.
+    fn for_statement(
+        &mut self,
+        ident: &Ident,
+        iter: &Expr,
+        block: &Stmt,
+        label: &Option<Ident>,
+    ) -> Result<(), CompileErr> {
+        self.enter_scope();
+
+        self.expression(iter)?;
+        self.emit_opcode(Opcode::Iter);
+        // not a valid identifier, so it can't be shadowed
+        self.add_local(" iter");
+        let locals_count = self.current_frame().locals.locals_count;
+
+        let loop_start = self.current_frame().function.chunk.opcodes.len();
+
+        let mut loop_ = Loop::new(loop_start, self.try_depth, locals_count);
+        loop_.label = label.as_ref().map(|label| label.name.clone());
+        self.loop_stack.push(loop_);
+
+        self.emit_arg(Opcode::LoadLocal, (locals_count - 1) as u8);
+        let next = self.emit_indent("next");
+        self.emit_arg(Opcode::Get, next as u8);
+        self.emit_opcode(Opcode::Call0);
+        let jump = self.emit_jump(Opcode::JumpIfStop);
+
+        self.enter_scope();
+        self.add_local(&ident.name);
+        self.statment(block)?;
+        self.exit_scope();
+
+        self.emit_loop(loop_start)?;
+
         self.patch_jump(jump)?;
         self.emit_opcode(Opcode::Pop);
 
+        // `break` jumps past the pop of `StopIteration`, as the body already popped its locals
+        self.leave_loop()?;
+        self.exit_scope();
+
+        Ok(())
+    }
+
+    /// Compile a loop statment, discarding the value it breaks with.
+    fn loop_statement(&mut self, block: &Stmt, label: &Option<Ident>) -> Result<(), CompileErr> {
+        self.loop_expr(block, label)?;
+        self.emit_opcode(Opcode::Pop);
+
         Ok(())
     }
 
-    /// Compile a loop statment.
-    fn loop_statement(&mut self, block: &Stmt) -> Result<(), CompileErr> {
+    /// Compile a loop expression.
+    ///
+    /// The loop's value is kept in a hidden local, which starts out as unit and
+    /// is overwritten by `break` with a value. Once the loop ends, the local is
+    /// dropped from the scope but left on the stack as the loop's value.
+    fn loop_expr(&mut self, block: &Stmt, label: &Option<Ident>) -> Result<(), CompileErr> {
+        self.enter_scope();
+
+        self.emit_opcode(Opcode::Unit);
+        // not a valid identifier, so it can't be shadowed
+        self.add_local(" loop");
+        let locals_count = self.current_frame().locals.locals_count;
+
         let start_ip = self.current_frame().function.chunk.opcodes.len();
 
-        self.loop_stack.push(Loop::new(start_ip));
+        let mut loop_ = Loop::new(start_ip, self.try_depth, locals_count);
+        loop_.result = Some(locals_count - 1);
+        loop_.label = label.as_ref().map(|label| label.name.clone());
+        self.loop_stack.push(loop_);
 
         self.statment(block)?;
 
-        self.emit_loop(start_ip);
+        self.emit_loop(start_ip)?;
         self.leave_loop()?;
 
+        let locals = &mut self.current_mut_frame().locals;
+        locals.locals.pop();
+        locals.locals_count -= 1;
+        locals.depth -= 1;
+
         Ok(())
     }
 
+    /// Compile a try-catch statement.
+    ///
+    /// `Try` installs a handler pointing at the catch block, and `EndTry` removes
+    /// it once the body finishes without an error. When an error is caught, the
+    /// VM unwinds the stack and pushes the error message, which becomes the catch
+    /// block's local.
+    fn try_statement(
+        &mut self,
+        body: &Stmt,
+        ident: &Ident,
+        handler: &Stmt,
+    ) -> Result<(), CompileErr> {
+        let handler_jump = self.emit_jump(Opcode::Try);
+
+        self.try_depth += 1;
+        let result = self.statment(body);
+        self.try_depth -= 1;
+        result?;
+
+        self.emit_opcode(Opcode::EndTry);
+        let end_jump = self.emit_jump(Opcode::Jump);
+
+        self.patch_jump(handler_jump)?;
+
+        self.enter_scope();
+        self.add_local(&ident.name);
+        self.statment(handler)?;
+        self.exit_scope();
+
+        self.patch_jump(end_jump)?;
+
+        Ok(())
+    }
+
+    /// Compile a match statement.
+    ///
+    /// The matched value is kept in a hidden local. Each arm compares it with
+    /// its pattern, then binds the values carried by a variant as locals for
+    /// the arm's body. The arms are tried in order, and at most one of them runs.
+    fn match_stmt(&mut self, expr: &Expr, arms: &[MatchArm]) -> Result<(), CompileErr> {
+        self.enter_scope();
+
+        self.expression(expr)?;
+        // not a valid identifier, so it can't be shadowed
+        self.add_local(" match");
+        let slot = self.current_frame().locals.locals_count - 1;
+
+        let mut end_jumps = vec![];
+        for arm in arms {
+            let next_jump = match &arm.pattern {
+                Pattern::Wildcard(_) => None,
+                Pattern::Literal(literal) => {
+                    self.expression(literal)?;
+                    self.emit_arg(Opcode::LoadLocal, slot as u8);
+                    self.emit_opcode(Opcode::Equal);
+
+                    Some(self.emit_jump(Opcode::JumpIfFalse))
+                }
+                Pattern::Variant(enum_, name, _, _) => {
+                    self.emit_arg(Opcode::LoadLocal, slot as u8);
+                    self.expression(enum_)?;
+
+                    let index = self.emit_indent(&name.name);
+                    self.emit_arg(Opcode::IsVariant, index as u8);
+
+                    Some(self.emit_jump(Opcode::JumpIfFalse))
+                }
+            };
+
+            if next_jump.is_some() {
+                self.emit_opcode(Opcode::Pop);
+            }
+
+            self.enter_scope();
+            if let Pattern::Variant(_, _, bindings, _) = &arm.pattern {
+                for (position, binding) in bindings.iter().enumerate() {
+                    self.emit_arg(Opcode::LoadLocal, slot as u8);
+                    self.number(&(position as f64))?;
+                    self.emit_opcode(Opcode::GetIndex);
+
+                    self.add_local(&binding.name);
+                }
+            }
+            self.statment(&arm.body)?;
+            self.exit_scope();
+
+            end_jumps.push(self.emit_jump(Opcode::Jump));
+
+            if let Some(next_jump) = next_jump {
+                self.patch_jump(next_jump)?;
+                self.emit_opcode(Opcode::Pop);
+            }
+        }
+
+        for jump in end_jumps {
+            self.patch_jump(jump)?;
+        }
+
+        self.exit_scope();
+
+        Ok(())
+    }
+
+    /// Find the loop that a `break` or `continue` refers to, which is the
+    /// innermost loop unless a label is given.
+    fn target_loop(&self, label: &Option<Ident>) -> Result<usize, CompileErr> {
+        let position = match label {
+            Some(label) => self
+                .loop_stack
+                .iter()
+                .rposition(|loop_| loop_.label.as_ref() == Some(&label.name)),
+            None => self.loop_stack.len().checked_sub(1),
+        };
+
+        position.ok_or_else(|| match label {
+            Some(label) => CompileErr(format!("unknown loop label `{}`", label.name)),
+            None => CompileErr("cannot use break statement outside of loop".to_string()),
+        })
+    }
+
+    /// Remove the handlers of any `try` blocks, and pop any locals, entered
+    /// since `loop_` began, before jumping out of it.
+    fn exit_to_loop(&mut self, loop_: &Loop) {
+        for _ in loop_.try_depth..self.try_depth {
+            self.emit_opcode(Opcode::EndTry);
+        }
+
+        self.pop_locals(loop_.locals_count);
+    }
+
     /// Compile an import statement.
     fn import_statement(&mut self, import: &Expr) -> Result<(), CompileErr> {
         self.emit_opcode(Opcode::Import);
@@ -619,7 +942,7 @@ impl Compiler {
         Ok(())
     }
 
-    /// Compile a variable declaration.
+    /// Compile a variable declaration. A variable declared without a value is `nil`.
     fn var_decl(&mut self, ident: &Ident, expr: &Option<Expr>) -> Result<(), CompileErr> {
         let expr = if let Some(expr) = expr {
             expr.clone()
@@ -642,6 +965,9 @@ impl Compiler {
     }
 
     /// Compile a constant declaration.
+    ///
+    /// Global constants with a value known at compile time are also inlined
+    /// wherever they're used.
     fn con_decl(&mut self, ident: &Ident, expr: &Expr) -> Result<(), CompileErr> {
         if self.current_frame().locals.depth > 0 {
             self.expression(expr)?;
@@ -649,7 +975,13 @@ impl Compiler {
         } else {
             let global = self.emit_indent(&ident.name);
 
-            self.expression(expr)?;
+            match self.fold_constant(expr) {
+                Some(folded) => {
+                    self.expression(&folded)?;
+                    self.constants.insert(ident.name.clone(), folded);
+                }
+                None => self.expression(expr)?,
+            }
 
             self.declare_global(global);
         }
@@ -660,7 +992,19 @@ impl Compiler {
     /// Compile an assignment statement.
     fn assign_stmt(&mut self, ident: &Expr, expr: &Expr) -> Result<(), CompileErr> {
         self.expression(expr)?;
+        self.assign(ident)
+    }
+
+    /// Compile an assignment in a chain, leaving a copy of the value assigned on
+    /// the stack for the next assignment in the chain.
+    fn assign_expr(&mut self, target: &Expr, value: &Expr) -> Result<(), CompileErr> {
+        self.expression(value)?;
+        self.emit_opcode(Opcode::Dup);
+        self.assign(target)
+    }
 
+    /// Assign the value on top of the stack to `ident`.
+    fn assign(&mut self, ident: &Expr) -> Result<(), CompileErr> {
         if let Expr::Identifier(name) = ident {
             self.save_variable(&name.name);
         }
@@ -671,22 +1015,30 @@ impl Compiler {
 
             self.emit_opcode(Opcode::SetIndex);
 
-            if let Expr::Identifier(name) = &**expr {
-                self.save_variable(&name.name);
+            // maps are copied on write, so the changed map is stored back into
+            // the place it was read from
+            match &**expr {
+                Expr::Identifier(_) | Expr::Index(..) | Expr::MemberExpr(..) => {
+                    self.assign(expr)?
+                }
+                _ => self.emit_opcode(Opcode::Pop),
             }
         }
 
+        if let Expr::Slice(..) = ident {
+            return Err(CompileErr("cannot assign to a slice".to_string()));
+        }
+
         if let Expr::MemberExpr(obj, prop, _) = ident {
             self.expression(obj)?;
-            //self.expression(prop)?;
 
             if let Expr::Identifier(id) = &**prop {
-                self.emit_byte(Opcode::Set as u8);
+                self.emit_opcode(Opcode::Set);
 
                 let offset = self.emit_indent(&id.name);
                 self.emit_byte(offset as u8);
             } else {
-                todo!()
+                return Err(CompileErr("can only assign to a named field".to_string()));
             }
         }
 
@@ -699,6 +1051,8 @@ impl Compiler {
 
         let offset = self.emit_indent(&class.name());
 
+        self.parents.push(class.parent.clone());
+
         for constructor in &class.constructors {
             if let Stmt::Constructor(constructor, _) = constructor {
                 self.compile_function(
@@ -716,10 +1070,22 @@ impl Compiler {
             }
         }
 
+        self.parents.pop();
+
+        for static_fun in &class.statics {
+            if let Stmt::Function(fun, _) = static_fun {
+                self.compile_function(&fun.name, &fun.params, &fun.body, CompileTarget::Function)?;
+            }
+        }
+
+        // a field declared without a value starts out as `nil`
         for field in class.fields.iter() {
-            if let Stmt::VarDeclaration(id, Some(init), _, _) = field {
+            if let Stmt::VarDeclaration(id, init, _, _) = field {
                 self.emit_indent(&id.name);
-                self.expression(init)?;
+                match init {
+                    Some(init) => self.expression(init)?,
+                    None => self.nil()?,
+                }
             }
         }
 
@@ -739,13 +1105,38 @@ impl Compiler {
         self.emit_byte(offset as u8);
         self.emit_byte(class.methods.len() as u8);
         self.emit_byte(class.constructors.len() as u8);
+        self.emit_byte(class.statics.len() as u8);
         self.emit_byte(class.fields.len() as u8);
 
+        if let Some(parent) = &class.parent {
+            self.identifier(parent)?;
+            self.emit_opcode(Opcode::Inherit);
+        }
+
         self.declare_variable(&class.name.name);
 
         Ok(())
     }
 
+    /// Compile an enum declaration.
+    ///
+    /// The enum is known at compile time, so it is loaded as a constant.
+    fn enum_decl(&mut self, enum_: &Enum) -> Result<(), CompileErr> {
+        let variants = enum_
+            .variants
+            .iter()
+            .map(|variant| (variant.name.name.as_str().into(), variant.fields.len()))
+            .collect();
+        let value = common::Enum::new(enum_.name.name.as_str(), variants);
+
+        let index = self.emit_constant(Value::Enum(Rc::new(value)));
+        self.emit_arg(Opcode::Const, index as u8);
+
+        self.declare_variable(&enum_.name.name);
+
+        Ok(())
+    }
+
     /// Compile a class constructor.
     fn constructor(&mut self, _constructor: &Constructor) -> Result<(), CompileErr> {
         Ok(())
@@ -774,57 +1165,74 @@ impl Compiler {
     }
 
     /// Compile a break statement.
-    fn break_stmt(&mut self) -> Result<(), CompileErr> {
+    fn break_stmt(&mut self, label: &Option<Ident>, expr: &Option<Expr>) -> Result<(), CompileErr> {
+        let target = self.target_loop(label)?;
+        let loop_ = self.loop_stack[target].clone();
+
+        if let Some(expr) = expr {
+            let slot = loop_.result.ok_or_else(|| {
+                CompileErr("can only break with a value inside `loop`".to_string())
+            })?;
+
+            self.expression(expr)?;
+            self.emit_opcode(Opcode::SaveLocal);
+            self.emit_byte(slot as u8);
+        }
+
+        self.exit_to_loop(&loop_);
+
         let exit_jump = self.emit_jump(Opcode::Jump);
-        match self.loop_stack.last_mut() {
-            Some(loop_) => loop_.jump_placeholders.push(exit_jump),
-            None => {
-                return Err(CompileErr(
-                    "cannot use break statement outside of loop".to_string(),
-                ))
-            }
-        };
+        self.loop_stack[target].jump_placeholders.push(exit_jump);
 
         Ok(())
     }
 
     /// Compile a continue statement.
-    fn continue_stmt(&mut self) -> Result<(), CompileErr> {
-        let loop_start = self.current_loop()?.start_ip;
+    fn continue_stmt(&mut self, label: &Option<Ident>) -> Result<(), CompileErr> {
+        let loop_ = self.loop_stack[self.target_loop(label)?].clone();
 
-        self.emit_loop(loop_start);
+        self.exit_to_loop(&loop_);
+        self.emit_loop(loop_.start_ip)?;
 
         Ok(())
     }
 
     /// Compile an expression.
     fn expression(&mut self, expr: &Expr) -> Result<(), CompileErr> {
-        self.current_mut_frame()
-            .function
-            .chunk
-            .emit_span(expr.span());
+        self.spans.push(expr.span());
 
-        match expr {
+        let result = match expr {
             Expr::Number(val, _) => self.number(val),
             Expr::String(val, _) => self.string(val),
             Expr::Boolean(val, _) => self.boolean(val),
             Expr::Unit(_) | Expr::Nil(_) => self.nil(),
             Expr::Identifier(ident) => self.identifier(ident),
             Expr::SelfExpr(_) => self.self_expr(),
+            Expr::SuperExpr(method, _) => self.super_expr(method),
             Expr::BinExpr(bin_expr, _) => self.binary_expr(bin_expr),
-            Expr::UnaryExpr(op, unary_expr, _) => self.unary_expr(op, unary_expr),
+            Expr::UnaryExpr(op, unary_expr, span) => self.unary_expr(op, unary_expr, span),
             Expr::ParenExpr(expr, _) => self.expression(&*expr),
             Expr::Index(expr, index, _) => self.index(expr, index),
+            Expr::Slice(expr, lo, hi, _) => self.slice(expr, lo.as_deref(), hi.as_deref()),
             Expr::List(list, _) => self.list((list).to_vec()),
             Expr::Tuple(tuple, _) => self.tuple(tuple),
             Expr::Map(map, _) => self.map(map),
             Expr::Or(lhs, rhs, _) => self.or(lhs, rhs),
             Expr::And(lhs, rhs, _) => self.and(lhs, rhs),
+            Expr::Chain(first, rest, _) => self.chain(first, rest),
             Expr::FunCall(callee, args, _) => self.fun_call(callee, args),
             Expr::MemberExpr(obj, prop, _) => self.member_expr(obj, prop),
             Expr::AssocExpr(obj, prop, _) => self.assoc_expr(obj, prop),
             Expr::Type(typ, _) => self.type_spec(typ),
-        }
+            Expr::Loop(body, _) => self.loop_expr(body, &None),
+            Expr::Spread(..) => Err(CompileErr(
+                "can only spread a list into a call or a list".to_string(),
+            )),
+            Expr::Assign(target, value, _) => self.assign_expr(target, value),
+        };
+
+        self.spans.pop();
+        result
     }
 
     /// Compile type information.
@@ -845,6 +1253,47 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compile a chain of comparisons such as `a < b < c`.
+    ///
+    /// Operands are evaluated left to right. Each operand between two comparisons
+    /// is copied below the result of the first with `Dup` and `Rot`, to be
+    /// compared again if the first holds, and the chain evaluates to `false` as
+    /// soon as one fails.
+    fn chain(&mut self, first: &Expr, rest: &[(Op, Expr)]) -> Result<(), CompileErr> {
+        let ((last_op, last), middle) = rest.split_last().unwrap();
+
+        self.expression(first)?;
+
+        let mut failed = Vec::with_capacity(middle.len());
+        for (op, operand) in middle {
+            self.expression(operand)?;
+            self.emit_opcode(Opcode::Dup);
+            self.emit_opcode(Opcode::Rot);
+            self.emit_opcode(comparison(op));
+
+            failed.push(self.emit_jump(Opcode::JumpIfFalse));
+            self.emit_opcode(Opcode::Pop);
+        }
+
+        // the last operand ends up above the one before it, so it's compared
+        // the other way round
+        self.expression(last)?;
+        self.emit_opcode(comparison(&reversed(last_op)));
+        let end = self.emit_jump(Opcode::Jump);
+
+        // replace the failed comparison and the operand kept below it with `false`
+        for jump in failed {
+            self.patch_jump(jump)?;
+        }
+        self.emit_opcode(Opcode::Pop);
+        self.emit_opcode(Opcode::Pop);
+        self.emit_opcode(Opcode::False);
+
+        self.patch_jump(end)?;
+
+        Ok(())
+    }
+
     /// Compile an `or` expression.
     fn or(&mut self, lhs: &Expr, rhs: &Expr) -> Result<(), CompileErr> {
         self.expression(lhs)?;
@@ -869,6 +1318,7 @@ impl Compiler {
             Op::Multiply => self.emit_opcode(Opcode::Mul),
             Op::Divide => self.emit_opcode(Opcode::Div),
             Op::Remainder => self.emit_opcode(Opcode::Mod),
+            Op::Power => self.emit_opcode(Opcode::Pow),
             Op::EqualTo => self.emit_opcode(Opcode::Equal),
             Op::NotEqual => self.emit_opcode(Opcode::NotEqual),
             Op::GreaterThanEquals => self.emit_opcode(Opcode::Gte),
@@ -878,6 +1328,13 @@ impl Compiler {
             Op::BitwiseAnd => self.emit_opcode(Opcode::BitAnd),
             Op::BitwiseOr => self.emit_opcode(Opcode::BitOr),
             Op::BitwiseXor => self.emit_opcode(Opcode::BitXor),
+            Op::Shl => self.emit_opcode(Opcode::Shl),
+            Op::Shr => self.emit_opcode(Opcode::Shr),
+            Op::Is => self.emit_opcode(Opcode::Is),
+            Op::Isnt => {
+                self.emit_opcode(Opcode::Is);
+                self.emit_opcode(Opcode::Not);
+            }
             _ => {}
         }
 
@@ -885,7 +1342,13 @@ impl Compiler {
     }
 
     /// Compile a unary expression.
-    fn unary_expr(&mut self, op: &Op, expr: &Expr) -> Result<(), CompileErr> {
+    fn unary_expr(&mut self, op: &Op, expr: &Expr, span: &Span) -> Result<(), CompileErr> {
+        // fold operators over literals, so that `-5` is a single constant
+        let folded = fold_literal(expr).and_then(|lit| fold_unary(op, &lit, span));
+        if let Some(folded) = folded {
+            return self.expression(&folded);
+        }
+
         match op {
             Op::Add => self.expression(expr)?,
             Op::Subtract => {
@@ -912,6 +1375,27 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compile a slice, leaving `nil` for a missing bound.
+    fn slice(
+        &mut self,
+        expr: &Expr,
+        lo: Option<&Expr>,
+        hi: Option<&Expr>,
+    ) -> Result<(), CompileErr> {
+        self.expression(expr)?;
+
+        for bound in [lo, hi] {
+            match bound {
+                Some(bound) => self.expression(bound)?,
+                None => self.emit_opcode(Opcode::Nil),
+            }
+        }
+
+        self.emit_opcode(Opcode::Slice);
+
+        Ok(())
+    }
+
     /// Compile a tuple.
     fn tuple(&mut self, tuple: &[Expr]) -> Result<(), CompileErr> {
         for item in tuple.iter().rev() {
@@ -925,6 +1409,10 @@ impl Compiler {
 
     /// Compile a list.
     fn list(&mut self, list: Vec<Expr>) -> Result<(), CompileErr> {
+        if list.iter().any(|item| matches!(item, Expr::Spread(..))) {
+            return self.spread_list(&list);
+        }
+
         for item in list.iter().rev() {
             self.expression(item)?;
         }
@@ -933,13 +1421,49 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compile a list with spread elements.
+    ///
+    /// The list starts out empty, and is extended with each spread list in turn,
+    /// or with a list of the elements between them.
+    fn spread_list(&mut self, list: &[Expr]) -> Result<(), CompileErr> {
+        self.emit_arg(Opcode::List, 0);
+
+        let mut elements = vec![];
+        for item in list {
+            match item {
+                Expr::Spread(expr, _) => {
+                    if !elements.is_empty() {
+                        self.list(std::mem::take(&mut elements))?;
+                        self.emit_opcode(Opcode::ExtendList);
+                    }
+
+                    self.expression(expr)?;
+                    self.emit_opcode(Opcode::ExtendList);
+                }
+                item => elements.push(item.clone()),
+            }
+        }
+
+        if !elements.is_empty() {
+            self.list(elements)?;
+            self.emit_opcode(Opcode::ExtendList);
+        }
+
+        Ok(())
+    }
+
     /// Compile a map.
     fn map(&mut self, map: &[(Expr, Expr)]) -> Result<(), CompileErr> {
         for (key, value) in map.iter().rev() {
             self.expression(value)?;
-            if let Expr::Identifier(ident) = key {
-                let index = self.emit_indent(&ident.name);
-                self.emit_arg(Opcode::Const, index as u8);
+
+            // a bare identifier key is the name of the entry, not a variable
+            match key {
+                Expr::Identifier(ident) => {
+                    let index = self.emit_constant(Value::from(ident.name.as_str()));
+                    self.emit_arg(Opcode::Const, index as u8);
+                }
+                key => self.expression(key)?,
             }
         }
 
@@ -952,6 +1476,14 @@ impl Compiler {
     fn fun_call(&mut self, ident: &Expr, args: &[Expr]) -> Result<(), CompileErr> {
         self.expression(ident)?;
 
+        // with a spread, the arguments are collected into a list at runtime
+        if args.iter().any(|arg| matches!(arg, Expr::Spread(..))) {
+            self.spread_list(args)?;
+            self.emit_opcode(Opcode::CallList);
+
+            return Ok(());
+        }
+
         for arg in args.iter().rev() {
             self.expression(arg)?;
         }
@@ -963,11 +1495,6 @@ impl Compiler {
             _ => self.emit_arg(Opcode::Call, args.len() as u8),
         }
 
-        self.current_mut_frame()
-            .function
-            .chunk
-            .emit_span(ident.span());
-
         Ok(())
     }
 
@@ -997,7 +1524,48 @@ impl Compiler {
 
     /// Compile `self`.
     fn self_expr(&mut self) -> Result<(), CompileErr> {
-        self.emit_arg(Opcode::LoadLocal, 0);
+        self.load_self()
+    }
+
+    /// Load `self`, which is a local of a method, or an upvalue of a function
+    /// nested in one.
+    fn load_self(&mut self) -> Result<(), CompileErr> {
+        match self.resolve_local("self", self.current_frame()) {
+            Some(index) => self.emit_arg(Opcode::LoadLocal, index as u8),
+            None => match self.resolve_upvalue("self") {
+                Some(index) => self.emit_arg(Opcode::LoadUpValue, index as u8),
+                None => {
+                    return Err(CompileErr(
+                        "cannot use `self` outside of a method".to_string(),
+                    ))
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Compile `super.method`, binding the parent's method to `self`.
+    fn super_expr(&mut self, method: &Ident) -> Result<(), CompileErr> {
+        let parent = match self.parents.last() {
+            Some(Some(parent)) => parent.clone(),
+            Some(None) => {
+                return Err(CompileErr(
+                    "cannot use `super` in a class with no parent".to_string(),
+                ))
+            }
+            None => {
+                return Err(CompileErr(
+                    "cannot use `super` outside of a method".to_string(),
+                ))
+            }
+        };
+
+        self.load_self()?;
+        self.identifier(&parent)?;
+
+        let index = self.emit_indent(&method.name);
+        self.emit_arg(Opcode::GetSuper, index as u8);
 
         Ok(())
     }
@@ -1012,10 +1580,13 @@ impl Compiler {
                 Some(index) => {
                     self.emit_arg(Opcode::LoadUpValue, index as u8);
                 }
-                None => {
-                    let index = self.emit_indent(&id.name);
-                    self.emit_arg(Opcode::GetGlobal, index as u8);
-                }
+                None => match self.constants.get(&id.name).cloned() {
+                    Some(constant) => self.expression(&constant)?,
+                    None => {
+                        let index = self.emit_indent(&id.name);
+                        self.emit_arg(Opcode::GetGlobal, index as u8);
+                    }
+                },
             },
         };
 
@@ -1056,3 +1627,62 @@ impl Compiler {
         Ok(())
     }
 }
+
+/// Check if two constants can share a slot in the constants pool.
+///
+/// Numbers are compared by their bits, so `0` and `-0` are kept apart.
+fn same_constant(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The opcode for a comparison operator that can be chained.
+fn comparison(op: &Op) -> Opcode {
+    match op {
+        Op::EqualTo => Opcode::Equal,
+        Op::NotEqual => Opcode::NotEqual,
+        Op::GreaterThanEquals => Opcode::Gte,
+        Op::LessThanEquals => Opcode::Lte,
+        Op::GreaterThan => Opcode::Gt,
+        Op::LessThan => Opcode::Lt,
+        op => unreachable!("`{op}` can't be chained"),
+    }
+}
+
+/// The comparison that holds for `b` and `a` when `op` holds for `a` and `b`.
+fn reversed(op: &Op) -> Op {
+    match op {
+        Op::GreaterThanEquals => Op::LessThanEquals,
+        Op::LessThanEquals => Op::GreaterThanEquals,
+        Op::GreaterThan => Op::LessThan,
+        Op::LessThan => Op::GreaterThan,
+        op => op.clone(),
+    }
+}
+
+/// Fold a literal operand, possibly wrapped in parentheses or unary operators,
+/// to a single literal.
+fn fold_literal(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::Number(..) | Expr::Boolean(..) | Expr::Nil(_) => Some(expr.clone()),
+        Expr::ParenExpr(expr, _) => fold_literal(expr),
+        Expr::UnaryExpr(op, operand, span) => fold_unary(op, &fold_literal(operand)?, span),
+        _ => None,
+    }
+}
+
+/// Apply a unary operator to a literal, following the same rules as the VM.
+fn fold_unary(op: &Op, operand: &Expr, span: &Span) -> Option<Expr> {
+    match (op, operand) {
+        (Op::Add, Expr::Number(val, _)) => Some(Expr::Number(*val, span.clone())),
+        (Op::Subtract, Expr::Number(val, _)) => Some(Expr::Number(-val, span.clone())),
+        (Op::Bang, Expr::Number(val, _)) => Some(Expr::Boolean(*val == 0.0, span.clone())),
+        (Op::Bang, Expr::Boolean(val, _)) => Some(Expr::Boolean(!val, span.clone())),
+        (Op::Bang, Expr::Nil(_)) => Some(Expr::Boolean(true, span.clone())),
+        _ => None,
+    }
+}
@@ -6,13 +6,15 @@ use crate::common::{Span, Spanned};
 use crate::compiler::{token::KEYWORDS, Token, TokenType};
 use crate::error::{Error, Item};
 
-use super::token::Literal;
+use super::token::{Delimiter, Keyword, Literal, Symbol};
 
 /// The lexer for the Kaon language.
 pub struct Lexer {
     source: Rc<Source>,
     previous: usize,
     current: usize,
+    /// The brackets that have been opened but not yet closed.
+    open_delimiters: Vec<Delimiter>,
 }
 
 impl Lexer {
@@ -21,6 +23,7 @@ impl Lexer {
             source,
             previous: 0,
             current: 0,
+            open_delimiters: Vec::new(),
         }
     }
 
@@ -58,10 +61,11 @@ impl Lexer {
     }
 
     fn peek_nth(&mut self, n: usize) -> Option<&str> {
-        if self.source.contents[self.current + n..].is_empty() {
+        // past the end of the input, or in the middle of a character
+        let source = self.source.contents.get(self.current + n..)?;
+        if source.is_empty() {
             None
         } else {
-            let source = &self.source.contents[self.current + n..];
             let mut end = 1;
             while !source.is_char_boundary(end) {
                 end += 1;
@@ -153,97 +157,154 @@ impl Lexer {
         Ok(token)
     }
 
+    /// Scan a string literal, whose opening quote has already been consumed.
+    ///
+    /// The token's value is the contents with any escapes decoded, while its
+    /// span covers the whole literal, quotes included.
     fn string(&mut self) -> Result<Token, Error> {
-        let mut escape = false;
-        let mut string = "".to_string();
-        let mut len = 0;
-
-        let mut source = self.source.contents[self.current..].chars();
-
-        while let Some(c) = source.next() {
-            len += c.len_utf8();
-            if escape {
-                escape = false;
-                string.push(match c {
-                    '"' => '"',
-                    '\\' => '\\',
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    'u' => {
-                        let mut sub_string =
-                            self.source.contents[self.previous + len + 1..].chars();
-
-                        if sub_string.next() != Some('{') {
-                            break;
-                        }
+        let source = self.source.clone();
+        let mut string = String::new();
 
-                        // TODO: replace unwrap with error handling
-                        let mut value: u32 = sub_string.next().unwrap().to_digit(16).unwrap();
-
-                        let mut digits = 1;
-                        loop {
-                            match sub_string.next().unwrap() {
-                                '}' => break,
-                                c => {
-                                    let digit = c.to_digit(16).unwrap();
-                                    digits += 1;
-                                    if digits > 6 {
-                                        continue;
-                                    }
-                                    let digit = digit as u32;
-                                    value = value * 16 + digit;
-                                }
-                            }
-                        }
+        loop {
+            let start = self.current;
+            if self.advance().is_none() {
+                return Err(Error::UnterminatedString(Item::new(
+                    &string,
+                    self.current_span(),
+                )));
+            }
 
-                        for _ in 0..digits + 2 {
-                            len += 1;
-                            source.next();
-                        }
+            match &source.contents[start..self.current] {
+                "\"" => {
+                    return Ok(self.make_token(TokenType::Literal(Literal::StringLiteral(string))))
+                }
+                "\\" if self.peek().is_some() => string.push(self.escape(start)?),
+                c => string.push_str(c),
+            }
+        }
+    }
 
-                        char::from_u32(value).unwrap()
-                    }
-                    o => {
-                        return Err(Error::UnknownEscapeCode(Item::new(
-                            &o.to_string(),
-                            Span::new(
-                                self.previous + len,
-                                self.current - self.previous,
-                                &self.source,
-                            ),
-                        )))
-                    }
-                });
-            } else {
-                match c {
-                    '\\' => escape = true,
-                    '"' => {
-                        for _ in 0..len {
-                            self.advance();
-                        }
+    /// Decode an escape sequence in a string, whose `\` is at `start`.
+    fn escape(&mut self, start: usize) -> Result<char, Error> {
+        let code = self.advance().and_then(|code| code.chars().next());
+
+        match code {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => self.unicode_escape(start),
+            _ => Err(Error::UnknownEscapeCode(self.escape_item(start + 1))),
+        }
+    }
 
-                        return Ok(
-                            self.make_token(TokenType::Literal(Literal::StringLiteral(string)))
-                        );
-                    }
-                    c => string.push(c),
-                }
+    /// Decode a `\u{...}` escape, made up of one to six hex digits naming a
+    /// unicode scalar value.
+    fn unicode_escape(&mut self, start: usize) -> Result<char, Error> {
+        if !self.match_("{") {
+            return Err(Error::InvalidUnicodeEscape(self.escape_item(start)));
+        }
+
+        let digits_start = self.current;
+        while !matches!(self.peek(), None | Some("}" | "\"")) {
+            self.advance();
+        }
+        let digits = &self.source.contents[digits_start..self.current];
+
+        // `from_str_radix` would also accept a sign
+        let value = match digits.len() {
+            1..=6 if digits.chars().all(|c| c.is_ascii_hexdigit()) => {
+                u32::from_str_radix(digits, 16).ok()
             }
+            _ => None,
+        };
+
+        // surrogates and values past `0x10FFFF` aren't scalar values
+        let closed = self.match_("}");
+        match value.and_then(char::from_u32) {
+            Some(c) if closed => Ok(c),
+            _ => Err(Error::InvalidUnicodeEscape(self.escape_item(start))),
         }
+    }
 
-        Err(Error::UnterminatedString(Item::new(
-            &string,
-            Span::new(self.current_span().start, string.len() + 1, &self.source),
-        )))
+    /// The part of an escape sequence from `start` up to the current character.
+    fn escape_item(&self, start: usize) -> Item {
+        Item::new(
+            &self.source.contents[start..self.current],
+            Span::new(start, self.current - start, &self.source),
+        )
     }
 
-    fn single_line_comment(&mut self) -> Token {
+    /// Skip a `//` comment, up to but not including the end of the line.
+    ///
+    /// Like whitespace, comments produce no tokens.
+    fn skip_line_comment(&mut self) {
         while self.peek().is_some() && self.peek() != Some("\n") {
             self.advance();
         }
 
-        self.make_token(TokenType::comment("//"))
+        self.previous = self.current;
+    }
+
+    /// Check if a newline after `last` continues the current line, rather
+    /// than ending a statement.
+    ///
+    /// A line continues inside parentheses or square brackets, and after a
+    /// token that can't end an expression: a binary operator (including `and`,
+    /// `or` and `is`), `=`, `,`, `.` or `..`. Newlines directly inside braces
+    /// are kept, since braces also delimit blocks of statements.
+    fn continues_line(&self, last: Option<&Token>) -> bool {
+        if let Some(Delimiter::OpenParen | Delimiter::OpenBracket) = self.open_delimiters.last() {
+            return true;
+        }
+
+        match last {
+            Some((TokenType::Symbol(symbol), _)) => matches!(
+                symbol,
+                Symbol::Plus
+                    | Symbol::Hypen
+                    | Symbol::Star
+                    | Symbol::StarStar
+                    | Symbol::Slash
+                    | Symbol::Modulo
+                    | Symbol::And
+                    | Symbol::Or
+                    | Symbol::Xor
+                    | Symbol::Equal
+                    | Symbol::EqualsEquals
+                    | Symbol::NotEqual
+                    | Symbol::RightAngleBracket
+                    | Symbol::LeftAngleBracket
+                    | Symbol::GreaterThanEqual
+                    | Symbol::LessThanEqual
+                    | Symbol::LeftShift
+                    | Symbol::RightShift
+                    | Symbol::Comma
+                    | Symbol::Dot
+                    | Symbol::DotDot
+            ),
+            Some((
+                TokenType::Keyword(Keyword::And | Keyword::Or | Keyword::Is | Keyword::Isnt),
+                _,
+            )) => true,
+            _ => false,
+        }
+    }
+
+    /// Keep track of which brackets are open.
+    fn track_delimiter(&mut self, token: &Token) {
+        match &token.0 {
+            TokenType::Delimiter(
+                delimiter @ (Delimiter::OpenParen | Delimiter::OpenBracket | Delimiter::OpenBrace),
+            ) => self.open_delimiters.push(delimiter.clone()),
+            TokenType::Delimiter(
+                Delimiter::CloseParen | Delimiter::CloseBracket | Delimiter::CloseBrace,
+            ) => {
+                self.open_delimiters.pop();
+            }
+            _ => {}
+        }
     }
 
     fn newline(&mut self) -> Token {
@@ -267,17 +328,35 @@ impl Lexer {
     pub fn tokenize(&mut self) -> Result<Spanned<Vec<Token>>, Error> {
         let mut tokens = vec![];
         loop {
+            let continues_line = self.continues_line(tokens.last());
+
             let c = self.advance();
-            tokens.push(match c {
+            let token = match c {
                 Some("+") => self.make_token(TokenType::symbol("+")),
                 Some("-") => self.make_token(TokenType::symbol("-")),
-                Some("*") => self.make_token(TokenType::symbol("*")),
+                Some("*") => {
+                    if self.match_("*") {
+                        self.make_token(TokenType::symbol("**"))
+                    } else {
+                        self.make_token(TokenType::symbol("*"))
+                    }
+                }
                 Some("%") => self.make_token(TokenType::symbol("%")),
                 Some("~") => self.make_token(TokenType::symbol("~")),
                 Some(":") => self.make_token(TokenType::symbol(":")),
                 Some(";") => self.make_token(TokenType::symbol(";")),
                 Some(",") => self.make_token(TokenType::symbol(",")),
-                Some(".") => self.make_token(TokenType::symbol(".")),
+                Some(".") => {
+                    if self.match_(".") {
+                        if self.match_(".") {
+                            self.make_token(TokenType::symbol("..."))
+                        } else {
+                            self.make_token(TokenType::symbol(".."))
+                        }
+                    } else {
+                        self.make_token(TokenType::symbol("."))
+                    }
+                }
                 Some("&") => self.make_token(TokenType::symbol("&")),
                 Some("|") => self.make_token(TokenType::symbol("|")),
                 Some("^") => self.make_token(TokenType::symbol("^")),
@@ -290,6 +369,8 @@ impl Lexer {
                 Some("=") => {
                     if self.match_("=") {
                         self.make_token(TokenType::symbol("=="))
+                    } else if self.match_(">") {
+                        self.make_token(TokenType::symbol("=>"))
                     } else {
                         self.make_token(TokenType::symbol("="))
                     }
@@ -303,25 +384,34 @@ impl Lexer {
                 }
                 Some("/") => {
                     if self.match_("/") {
-                        self.single_line_comment()
+                        self.skip_line_comment();
+                        continue;
                     } else {
                         self.make_token(TokenType::symbol("/"))
                     }
                 }
                 Some(">") => {
-                    if self.match_("=") {
+                    if self.match_(">") {
+                        self.make_token(TokenType::symbol(">>"))
+                    } else if self.match_("=") {
                         self.make_token(TokenType::symbol(">="))
                     } else {
                         self.make_token(TokenType::symbol(">"))
                     }
                 }
                 Some("<") => {
-                    if self.match_("=") {
+                    if self.match_("<") {
+                        self.make_token(TokenType::symbol("<<"))
+                    } else if self.match_("=") {
                         self.make_token(TokenType::symbol("<="))
                     } else {
                         self.make_token(TokenType::symbol("<"))
                     }
                 }
+                Some("\n") if continues_line => {
+                    self.previous = self.current;
+                    continue;
+                }
                 Some("\n") => self.newline(),
                 Some("\"") => self.string()?,
                 None => {
@@ -340,7 +430,10 @@ impl Lexer {
                         self.current_span(),
                     )))
                 }
-            });
+            };
+
+            self.track_delimiter(&token);
+            tokens.push(token);
         }
         Ok(Spanned::new(
             tokens,
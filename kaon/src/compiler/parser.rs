@@ -1,3 +1,36 @@
+//! The parser for the Kaon language.
+//!
+//! # Operator precedence
+//!
+//! Binary operators are parsed by a chain of methods, one per precedence level.
+//! From loosest to tightest binding:
+//!
+//! | Level | Operators                               | Method        |
+//! |-------|-----------------------------------------|---------------|
+//! | 1     | `or`                                    | `disjunction` |
+//! | 2     | `and`                                   | `conjunction` |
+//! | 3     | `\|`                                    | `bitwise_or`  |
+//! | 4     | `^`                                     | `bitwise_xor` |
+//! | 5     | `&`                                     | `bitwise_and` |
+//! | 6     | `==` `!=` `<` `<=` `>` `>=` `is` `isnt` | `comparison`  |
+//! | 7     | `<<` `>>`                               | `shift`       |
+//! | 8     | `+` `-`                                 | `parse_sum`   |
+//! | 9     | `*` `/` `%`                             | `parse_term`  |
+//! | 10    | unary `-` `+` `!`                       | `unary`       |
+//! | 11    | `**`                                    | `parse_power` |
+//! | 12    | calls, indexing, `a.b`, `A:b`           | `member_expr` |
+//!
+//! Every binary level except `**` is left associative, so `a - b - c` is
+//! `(a - b) - c` and `a < b < c` is `(a < b) < c`. Exponentiation is right
+//! associative: `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+//!
+//! As in C, the bitwise operators bind more loosely than comparisons, so
+//! `a == b & c` is `(a == b) & c`, and `1 | 2 & 3` is `1 | (2 & 3)`. Shifts
+//! bind more tightly than comparisons but more loosely than arithmetic, so
+//! `1 << n + 1 == 8` is `(1 << (n + 1)) == 8`. Unary operators bind more
+//! tightly than every binary operator except `**`, as in mathematics: `-a.b` is
+//! `-(a.b)`, `-a * b` is `(-a) * b`, and `-2 ** 2` is `-(2 ** 2)`.
+
 use std::rc::Rc;
 
 use crate::{
@@ -11,7 +44,7 @@ use crate::{
 };
 
 use super::{
-    ast::{Signature, Trait, TraitMethod},
+    ast::{Enum, MatchArm, Pattern, Signature, Trait, TraitMethod, Variant},
     token::{Delimiter, Keyword, Literal, Symbol},
     Lexer,
 };
@@ -21,10 +54,18 @@ use super::{
 ///
 /// # Errors
 /// If the input has any syntactic errors, the parser will return them.
+///
+/// After an error the parser skips ahead to the next statement and carries on,
+/// so that a single run reports every independent error in the input.
 pub struct Parser {
     tokens: Spanned<Vec<Token>>,
     current: Token,
     pos: usize,
+    errors: Vec<Error>,
+    /// The labels of the loops enclosing the current token.
+    labels: Vec<String>,
+    /// Whether an error has been recorded at the end of the file.
+    failed_at_eof: bool,
 }
 
 impl Parser {
@@ -33,14 +74,17 @@ impl Parser {
             tokens,
             current: (TokenType::eof(), Span::empty()),
             pos: 0,
+            errors: vec![],
+            labels: vec![],
+            failed_at_eof: false,
         }
     }
 
-    pub fn parse_source(source: Rc<Source>) -> Result<AST, Error> {
-        let token_stream = Lexer::new(source).tokenize()?;
+    pub fn parse_source(source: Rc<Source>) -> Result<AST, Vec<Error>> {
+        let token_stream = Lexer::new(source).tokenize().map_err(|err| vec![err])?;
         let mut parser = Parser::new(token_stream);
 
-        Ok(parser.parse()?)
+        parser.parse()
     }
 
     /// Consume the next token.
@@ -48,8 +92,8 @@ impl Parser {
         match token_type {
             token_type if token_type == self.current.0 => {
                 self.pos += 1;
-                let old_token =
-                    std::mem::replace(&mut self.current, self.tokens.node[self.pos].clone());
+                let token = self.token_at(self.pos);
+                let old_token = std::mem::replace(&mut self.current, token);
                 Ok(old_token.1)
             }
             _ if self.current.0 == TokenType::Delimiter(Delimiter::Newline) => {
@@ -79,8 +123,8 @@ impl Parser {
         match token_type {
             token_type if token_type == self.current.0 => {
                 self.pos += 1;
-                let old_token =
-                    std::mem::replace(&mut self.current, self.tokens.node[self.pos].clone());
+                let token = self.token_at(self.pos);
+                let old_token = std::mem::replace(&mut self.current, token);
                 Ok(old_token.1)
             }
             TokenType::Delimiter(Delimiter::Eof) => Err(Error::UnexpectedEOF(Item::new(
@@ -98,6 +142,45 @@ impl Parser {
         &self.tokens.node[self.pos - 1]
     }
 
+    /// Record an error and skip to the start of the next statement.
+    ///
+    /// Tokens are skipped up to and including the next newline or `;`, ignoring
+    /// any inside a nested block. When `in_block` is set, skipping also stops
+    /// before the `}` closing the current block, so the block can still end.
+    ///
+    /// Once an error has been recorded at the end of the file, there's nothing
+    /// left to synchronize with, and the errors of every unterminated block
+    /// around it would only repeat it, so they're dropped.
+    fn recover(&mut self, error: Error, in_block: bool) {
+        if self.current.0 == TokenType::Delimiter(Delimiter::Eof) {
+            if self.failed_at_eof {
+                return;
+            }
+            self.failed_at_eof = true;
+        }
+
+        self.errors.push(error);
+
+        let mut depth = 0;
+        loop {
+            match &self.current.0 {
+                TokenType::Delimiter(Delimiter::Eof) => return,
+                TokenType::Delimiter(Delimiter::CloseBrace) if depth == 0 && in_block => return,
+                TokenType::Delimiter(Delimiter::Newline) | TokenType::Symbol(Symbol::SemiColon)
+                    if depth == 0 =>
+                {
+                    self.next();
+                    return;
+                }
+                TokenType::Delimiter(Delimiter::OpenBrace) => depth += 1,
+                TokenType::Delimiter(Delimiter::CloseBrace) => depth = usize::max(depth, 1) - 1,
+                _ => {}
+            }
+
+            self.next();
+        }
+    }
+
     /// Return an unexpected token error.
     fn error(&self) -> Error {
         Error::UnexpectedToken(Item::new(
@@ -109,16 +192,29 @@ impl Parser {
     /// Get the next token without checking it.
     fn next(&mut self) {
         self.pos += 1;
-        self.current = self.tokens.node[self.pos].clone();
+        self.current = self.token_at(self.pos);
+    }
+
+    /// Get the token at `pos`, or the end of the file if `pos` is past it.
+    fn token_at(&self, pos: usize) -> Token {
+        match self.tokens.node.get(pos).or_else(|| self.tokens.node.last()) {
+            Some(token) => token.clone(),
+            None => (TokenType::eof(), self.tokens.source.clone()),
+        }
     }
 
     /// Parse a compound statement.
     fn compound_statement(&mut self) -> Result<Stmt, Error> {
         match &self.current.0 {
             TokenType::Keyword(Keyword::If) => self.if_statement(),
-            TokenType::Keyword(Keyword::Loop) => self.loop_statement(),
-            TokenType::Keyword(Keyword::While) => self.while_statement(),
+            TokenType::Keyword(Keyword::Loop) => self.loop_statement(None),
+            TokenType::Keyword(Keyword::While) => self.while_statement(None),
+            TokenType::Keyword(Keyword::For) => self.for_statement(None),
+            TokenType::Literal(Literal::Id(_)) if self.at_label() => self.labeled_loop(),
+            TokenType::Keyword(Keyword::Try) => self.try_statement(),
+            TokenType::Keyword(Keyword::Match) => self.match_statement(),
             TokenType::Keyword(Keyword::Class) => self.class(),
+            TokenType::Keyword(Keyword::Enum) => self.enum_decl(),
             TokenType::Keyword(Keyword::Trait) => self.parse_trait(),
             TokenType::Keyword(Keyword::Fun) => self.fun(),
             TokenType::Keyword(Keyword::Public) => self.modifier(),
@@ -128,20 +224,22 @@ impl Parser {
     }
 
     /// Parse a statement and consume a delimiter.
+    ///
+    /// An error in the statement itself is reported ahead of a missing delimiter.
     fn statement(&mut self) -> Result<Stmt, Error> {
-        let node = self.simple_statement();
+        let node = self.simple_statement()?;
 
         match &self.current.0 {
             TokenType::Delimiter(Delimiter::Newline) => {
                 self.expect_delimiter(Delimiter::Newline)?;
-                node
+                Ok(node)
             }
             TokenType::Symbol(Symbol::SemiColon) => {
                 self.symbol(Symbol::SemiColon)?;
-                node
+                Ok(node)
             }
             TokenType::Delimiter(Delimiter::Eof) | TokenType::Delimiter(Delimiter::CloseBrace) => {
-                node
+                Ok(node)
             }
             node => Err(Error::ExpectedNewline(Item::new(
                 &node.to_string(),
@@ -154,7 +252,7 @@ impl Parser {
     fn simple_statement(&mut self) -> Result<Stmt, Error> {
         match &self.current.0 {
             TokenType::Keyword(Keyword::Var) => self.var_decl(),
-            TokenType::Keyword(Keyword::Final) => self.const_decl(),
+            TokenType::Keyword(Keyword::Con) => self.const_decl(),
             TokenType::Keyword(Keyword::Break) => self.break_stmt(),
             TokenType::Keyword(Keyword::Continue) => self.continue_stmt(),
             TokenType::Keyword(Keyword::Return) => self.return_stmt(),
@@ -178,50 +276,235 @@ impl Parser {
                     self.expect_delimiter(Delimiter::Newline)?;
                     continue;
                 }
+                // an empty statement
+                TokenType::Symbol(Symbol::SemiColon) => {
+                    self.symbol(Symbol::SemiColon)?;
+                    continue;
+                }
                 TokenType::Delimiter(Delimiter::Eof) => {
                     return Err(Error::UnexpectedEOF(Item::new(
                         "<eof>",
                         self.current.1.clone(),
                     )));
                 }
-                TokenType::Comment(_) => {
-                    self.comment()?;
-                    continue;
-                }
-                _ => {
-                    nodes.push(self.compound_statement()?);
-                }
+                _ => match self.compound_statement() {
+                    Ok(node) => nodes.push(node),
+                    Err(err) => self.recover(err, true),
+                },
             }
         }
 
         Ok(Stmt::Block(Box::new(nodes), self.tokens.source.clone()))
     }
 
-    fn loop_statement(&mut self) -> Result<Stmt, Error> {
+    /// Check for a loop label, an identifier followed by `:` and a loop.
+    fn at_label(&self) -> bool {
+        let lookahead = |offset: usize| {
+            self.tokens
+                .node
+                .get(self.pos + offset)
+                .map(|token| &token.0)
+        };
+
+        lookahead(1) == Some(&TokenType::Symbol(Symbol::Colon))
+            && matches!(
+                lookahead(2),
+                Some(TokenType::Keyword(Keyword::Loop | Keyword::While | Keyword::For))
+            )
+    }
+
+    /// Parse a labeled loop.
+    fn labeled_loop(&mut self) -> Result<Stmt, Error> {
+        let label = self.identifier()?;
+        self.symbol(Symbol::Colon)?;
+
+        match self.current.0 {
+            TokenType::Keyword(Keyword::Loop) => self.loop_statement(Some(label)),
+            TokenType::Keyword(Keyword::For) => self.for_statement(Some(label)),
+            _ => self.while_statement(Some(label)),
+        }
+    }
+
+    /// Parse the body of a loop, within which `break` and `continue` can refer to `label`.
+    fn loop_body(&mut self, label: &Option<Ident>) -> Result<Stmt, Error> {
+        if let Some(label) = label {
+            self.labels.push(label.name.clone());
+        }
+
+        let body = self.block();
+
+        if label.is_some() {
+            self.labels.pop();
+        }
+
+        body
+    }
+
+    fn loop_statement(&mut self, label: Option<Ident>) -> Result<Stmt, Error> {
         self.expect_keyword(Keyword::Loop)?;
         Ok(Stmt::LoopStatement(
-            Box::new(self.block()?),
+            Box::new(self.loop_body(&label)?),
+            label,
             self.current.1.clone(),
         ))
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, Error> {
+    /// Parse a `loop` used as an expression.
+    fn loop_expr(&mut self) -> Result<Expr, Error> {
+        let start = self.expect_keyword(Keyword::Loop)?;
+        Ok(Expr::Loop(Box::new(self.block()?), start))
+    }
+
+    fn while_statement(&mut self, label: Option<Ident>) -> Result<Stmt, Error> {
         self.expect_keyword(Keyword::While)?;
         Ok(Stmt::WhileStatement(
             self.disjunction()?,
-            Box::new(self.block()?),
+            Box::new(self.loop_body(&label)?),
+            label,
+            self.current.1.clone(),
+        ))
+    }
+
+    fn for_statement(&mut self, label: Option<Ident>) -> Result<Stmt, Error> {
+        self.expect_keyword(Keyword::For)?;
+        let ident = self.identifier()?;
+        self.expect_keyword(Keyword::In)?;
+
+        Ok(Stmt::ForStatement(
+            ident,
+            self.disjunction()?,
+            Box::new(self.loop_body(&label)?),
+            label,
             self.current.1.clone(),
         ))
     }
 
+    fn try_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.expect_keyword(Keyword::Try)?;
+        let body = self.block()?;
+
+        self.expect_keyword(Keyword::Catch)?;
+        let ident = self.identifier()?;
+        let handler = self.block()?;
+
+        Ok(Stmt::TryStatement(
+            Box::new((body, ident, handler)),
+            Span::combine(&start, &self.last().1),
+        ))
+    }
+
+    fn match_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.expect_keyword(Keyword::Match)?;
+        let expr = self.disjunction()?;
+
+        self.expect_delimiter(Delimiter::OpenBrace)?;
+
+        let mut arms = vec![];
+        loop {
+            match &self.current.0 {
+                TokenType::Delimiter(Delimiter::CloseBrace) => break,
+                TokenType::Delimiter(Delimiter::Newline) => self.next(),
+                TokenType::Delimiter(Delimiter::Eof) => {
+                    return Err(Error::UnexpectedEOF(Item::new(
+                        "<eof>",
+                        self.current.1.clone(),
+                    )));
+                }
+                _ => {
+                    let pattern = self.pattern()?;
+                    self.symbol(Symbol::Arrow)?;
+                    let body = self.compound_statement()?;
+
+                    arms.push(MatchArm { pattern, body });
+                }
+            }
+        }
+
+        let end = self.expect_delimiter(Delimiter::CloseBrace)?;
+
+        Ok(Stmt::Match(
+            Box::new((expr, arms)),
+            Span::combine(&start, &end),
+        ))
+    }
+
+    /// Parse the pattern of a `match` arm: `_`, a literal, or a variant
+    /// such as `Color.Rgb(r, g, b)`.
+    fn pattern(&mut self) -> Result<Pattern, Error> {
+        match &self.current.0 {
+            TokenType::Literal(Literal::Id(name)) if name == "_" => {
+                let span = self.current.1.clone();
+                self.next();
+                Ok(Pattern::Wildcard(span))
+            }
+            TokenType::Literal(Literal::Id(_)) => {
+                let enum_ = self.identifier()?;
+                self.symbol(Symbol::Dot)?;
+                let name = self.identifier()?;
+
+                let bindings = match self.current.0 {
+                    TokenType::Delimiter(Delimiter::OpenParen) => self.params()?.0,
+                    _ => vec![],
+                };
+
+                let span = Span::combine(&enum_.span, &self.last().1);
+                Ok(Pattern::Variant(
+                    Expr::Identifier(enum_),
+                    name,
+                    bindings,
+                    span,
+                ))
+            }
+            TokenType::Literal(_) | TokenType::Symbol(Symbol::Hypen) => {
+                Ok(Pattern::Literal(self.unary()?))
+            }
+            _ => Err(self.error()),
+        }
+    }
+
     fn break_stmt(&mut self) -> Result<Stmt, Error> {
         let start = self.expect_keyword(Keyword::Break)?;
-        Ok(Stmt::Break(start))
+
+        // an identifier is a label only if it names an enclosing loop,
+        // otherwise it's the value to break with
+        let label = match &self.current.0 {
+            TokenType::Literal(Literal::Id(name)) if self.labels.contains(name) => {
+                Some(self.identifier()?)
+            }
+            _ => None,
+        };
+
+        let expr = match self.current.0 {
+            TokenType::Delimiter(Delimiter::Newline)
+            | TokenType::Delimiter(Delimiter::CloseBrace)
+            | TokenType::Delimiter(Delimiter::Eof)
+            | TokenType::Symbol(Symbol::SemiColon) => None,
+            _ => Some(self.initializer()?),
+        };
+
+        let span = match (&label, &expr) {
+            (_, Some(expr)) => Span::combine(&start, &expr.span()),
+            (Some(label), None) => Span::combine(&start, &label.span()),
+            (None, None) => start,
+        };
+
+        Ok(Stmt::Break(label, expr, span))
     }
 
     fn continue_stmt(&mut self) -> Result<Stmt, Error> {
         let start = self.expect_keyword(Keyword::Continue)?;
-        Ok(Stmt::Continue(start))
+
+        let label = match self.current.0 {
+            TokenType::Literal(Literal::Id(_)) => Some(self.identifier()?),
+            _ => None,
+        };
+
+        let span = match &label {
+            Some(label) => Span::combine(&start, &label.span()),
+            None => start,
+        };
+
+        Ok(Stmt::Continue(label, span))
     }
 
     fn if_statement(&mut self) -> Result<Stmt, Error> {
@@ -271,9 +554,18 @@ impl Parser {
 
         let name = self.identifier()?;
 
+        let parent = match self.current.0 {
+            TokenType::Symbol(Symbol::Colon) => {
+                self.symbol(Symbol::Colon)?;
+                Some(self.identifier()?)
+            }
+            _ => None,
+        };
+
         let mut fields: Vec<Stmt> = vec![];
         let mut methods: Vec<Stmt> = vec![];
         let mut constructors: Vec<Stmt> = vec![];
+        let mut statics: Vec<Stmt> = vec![];
 
         self.consume(TokenType::delimiter("{"))?;
 
@@ -289,6 +581,10 @@ impl Parser {
                     Keyword::Fun => {
                         methods.push(self.fun()?);
                     }
+                    Keyword::Static => {
+                        self.expect_keyword(Keyword::Static)?;
+                        statics.push(self.fun()?);
+                    }
                     Keyword::Var => {
                         fields.push(self.var_decl()?);
                     }
@@ -298,10 +594,6 @@ impl Parser {
                     self.expect_delimiter(Delimiter::Newline)?;
                     continue;
                 }
-                TokenType::Comment(_) => {
-                    self.next();
-                    continue;
-                }
                 TokenType::Delimiter(Delimiter::Eof) => {
                     return Err(Error::UnexpectedEOF(Item::new(
                         "<eof>",
@@ -313,11 +605,58 @@ impl Parser {
         }
 
         let end = self.consume(TokenType::delimiter("}"))?;
-        let class = Class::new(name, None, fields, methods, constructors);
+        let class = Class::new(name, parent, fields, methods, constructors, statics);
 
         Ok(Stmt::Class(class, Span::combine(&start, &end)))
     }
 
+    fn enum_decl(&mut self) -> Result<Stmt, Error> {
+        let start = self.expect_keyword(Keyword::Enum)?;
+        let name = self.identifier()?;
+
+        self.expect_delimiter(Delimiter::OpenBrace)?;
+
+        let mut variants = vec![];
+        loop {
+            match &self.current.0 {
+                TokenType::Delimiter(Delimiter::CloseBrace) => break,
+                TokenType::Delimiter(Delimiter::Newline) | TokenType::Symbol(Symbol::Comma) => {
+                    self.next()
+                }
+                TokenType::Delimiter(Delimiter::Eof) => {
+                    return Err(Error::UnexpectedEOF(Item::new(
+                        "<eof>",
+                        self.current.1.clone(),
+                    )));
+                }
+                _ => {
+                    let name = self.identifier()?;
+                    let fields = match self.current.0 {
+                        TokenType::Delimiter(Delimiter::OpenParen) => self.params()?.0,
+                        _ => vec![],
+                    };
+
+                    variants.push(Variant { name, fields });
+
+                    // variants are separated by commas or newlines
+                    match self.current.0 {
+                        TokenType::Symbol(Symbol::Comma)
+                        | TokenType::Delimiter(Delimiter::Newline)
+                        | TokenType::Delimiter(Delimiter::CloseBrace) => {}
+                        _ => return Err(self.error()),
+                    }
+                }
+            }
+        }
+
+        let end = self.expect_delimiter(Delimiter::CloseBrace)?;
+
+        Ok(Stmt::Enum(
+            Box::new(Enum { name, variants }),
+            Span::combine(&start, &end),
+        ))
+    }
+
     fn parse_trait(&mut self) -> Result<Stmt, Error> {
         self.expect_keyword(Keyword::Trait)?;
 
@@ -453,6 +792,12 @@ impl Parser {
             match &self.current.0 {
                 TokenType::Symbol(Symbol::Comma) => {
                     self.consume(TokenType::symbol(","))?;
+
+                    // allow a trailing comma
+                    if let TokenType::Delimiter(Delimiter::CloseParen) = self.current.0 {
+                        break;
+                    }
+
                     params.push(self.identifier()?);
                     typs.push(self.type_spec()?);
                 }
@@ -473,7 +818,7 @@ impl Parser {
         let expr = if let TokenType::Delimiter(_) = self.current.0 {
             None
         } else {
-            Some(self.disjunction()?)
+            Some(self.initializer()?)
         };
 
         let end = if let Some(expr) = &expr {
@@ -492,7 +837,7 @@ impl Parser {
         let start = &self.consume(TokenType::keyword("import"))?;
 
         // import name ...
-        let import_name = self.dot_expr()?;
+        let import_name = self.member_expr()?;
 
         let end = &import_name.span();
 
@@ -514,7 +859,7 @@ impl Parser {
 
         let init = if let TokenType::Symbol(Symbol::Equal) = self.current.0 {
             self.consume(TokenType::symbol("="))?;
-            Some(self.disjunction()?)
+            Some(self.initializer()?)
         } else {
             None
         };
@@ -535,6 +880,10 @@ impl Parser {
 
         let typ = self.type_spec()?;
 
+        // unlike a variable, a constant can't be given its value later
+        if self.current.0 != TokenType::symbol("=") {
+            return Err(Error::MissingInitializer(Item::new(&id.name, id.span())));
+        }
         self.consume(TokenType::symbol("="))?;
         let init = self.disjunction()?;
 
@@ -568,7 +917,7 @@ impl Parser {
             TokenType::Symbol(Symbol::LeftAngleBracket) => {
                 self.consume(TokenType::symbol("<"))?;
                 let argument = Some(Box::new(self.type_path()?));
-                self._expect(TokenType::symbol(">"))?;
+                self.close_type_arguments()?;
                 argument
             }
             _ => None,
@@ -577,13 +926,31 @@ impl Parser {
         Ok(TypePath { ident, arguments })
     }
 
+    /// Expect the `>` closing a list of type arguments.
+    ///
+    /// Nested arguments like `List<List<f64>>` end in a `>>` token, which is
+    /// split so the outer list can consume the second `>`.
+    fn close_type_arguments(&mut self) -> Result<(), Error> {
+        if let TokenType::Symbol(Symbol::RightShift) = &self.current.0 {
+            let span = &self.current.1;
+            self.current = (
+                TokenType::symbol(">"),
+                Span::new(span.start + 1, 1, &span.source),
+            );
+            return Ok(());
+        }
+
+        self._expect(TokenType::symbol(">"))?;
+        Ok(())
+    }
+
     fn args(&mut self) -> Result<Vec<Expr>, Error> {
         self.expect_delimiter(Delimiter::OpenParen)?;
 
         let mut args = vec![];
 
         if self.current.0 != TokenType::delimiter(")") {
-            args.push(self.disjunction()?);
+            args.push(self.element()?);
         }
 
         loop {
@@ -593,7 +960,13 @@ impl Parser {
                 }
                 TokenType::Symbol(Symbol::Comma) => {
                     self.consume(TokenType::symbol(","))?;
-                    args.push(self.disjunction()?);
+
+                    // allow a trailing comma
+                    if let TokenType::Delimiter(Delimiter::CloseParen) = self.current.0 {
+                        break;
+                    }
+
+                    args.push(self.element()?);
                 }
                 _ => break,
             }
@@ -620,8 +993,9 @@ impl Parser {
                     ))
                 }
             };
+            Parser::assignment_target(&id)?;
 
-            let val = self.disjunction()?;
+            let val = self.assignment_value()?;
             let end = &val.span();
 
             let node = Stmt::AssignStatement(id, val, Span::combine(start, end));
@@ -631,20 +1005,67 @@ impl Parser {
         Ok(node)
     }
 
+    /// Parse the value of an assignment, which may itself be an assignment.
+    ///
+    /// Assignment is right-associative, so `a = b = 0` assigns `0` to `b` and
+    /// then to `a`.
+    fn assignment_value(&mut self) -> Result<Expr, Error> {
+        let value = self.initializer()?;
+
+        if let TokenType::Symbol(Symbol::Equal) = &self.current.0 {
+            self.consume(TokenType::symbol("="))?;
+            Parser::assignment_target(&value)?;
+
+            let rhs = self.assignment_value()?;
+            let span = Span::combine(&value.span(), &rhs.span());
+            return Ok(Expr::Assign(Box::new(value), Box::new(rhs), span));
+        }
+
+        Ok(value)
+    }
+
+    /// Check that an expression can be assigned to: a variable, an element or a field.
+    fn assignment_target(target: &Expr) -> Result<(), Error> {
+        let assignable = match target {
+            // `a[lo:hi]` is subscripted by an associated item, not an element index
+            Expr::Index(_, index, _) => !matches!(**index, Expr::AssocExpr(..)),
+            Expr::Identifier(_) | Expr::MemberExpr(..) => true,
+            _ => false,
+        };
+
+        if !assignable {
+            return Err(Error::InvalidAssignment(Item::new(
+                "assignment target",
+                target.span(),
+            )));
+        }
+
+        Ok(())
+    }
+
     fn expression(&mut self) -> Result<Stmt, Error> {
         Ok(Stmt::Expr(self.disjunction()?))
     }
 
+    /// Parse the value of a `var`, assignment, `return` or `break`.
+    ///
+    /// A `loop` is only an expression here, where nothing else is on the stack
+    /// when it runs, so that the locals declared in its body stay in their slots.
+    fn initializer(&mut self) -> Result<Expr, Error> {
+        match self.current.0 {
+            TokenType::Keyword(Keyword::Loop) => self.loop_expr(),
+            _ => self.disjunction(),
+        }
+    }
+
     fn disjunction(&mut self) -> Result<Expr, Error> {
         let mut node = self.conjunction()?;
         let start = &node.span();
         while let TokenType::Keyword(Keyword::Or) = &self.current.0 {
             self.consume(TokenType::keyword("or"))?;
-            node = Expr::Or(
-                Box::new(node),
-                Box::new(self.conjunction()?),
-                Span::combine(start, &self.current.1),
-            );
+            let rhs = self.conjunction()?;
+            let span = Span::combine(start, &rhs.span());
+            node = Expr::Or(Box::new(node), Box::new(rhs), span);
         }
 
         Ok(node)
@@ -656,11 +1077,9 @@ impl Parser {
         loop {
             if let TokenType::Keyword(Keyword::And) = &self.current.0 {
                 self.consume(TokenType::keyword("and"))?;
-                node = Expr::And(
-                    Box::new(node),
-                    Box::new(self.bitwise_or()?),
-                    Span::combine(start, &self.current.1),
-                );
+                let rhs = self.bitwise_or()?;
+                let span = Span::combine(start, &rhs.span());
+                node = Expr::And(Box::new(node), Box::new(rhs), span);
             } else {
                 break;
             }
@@ -674,12 +1093,7 @@ impl Parser {
         loop {
             if let TokenType::Symbol(Symbol::Or) = &self.current.0 {
                 self.next();
-                let start = &node.span();
-
-                node = Expr::BinExpr(
-                    Box::new(BinExpr::new(Op::BitwiseOr, node, self.bitwise_xor()?)),
-                    Span::combine(start, &self.current.1),
-                );
+                node = binary(Op::BitwiseOr, node, self.bitwise_xor()?);
             } else {
                 break;
             }
@@ -694,12 +1108,7 @@ impl Parser {
         loop {
             if let TokenType::Symbol(Symbol::Xor) = &self.current.0 {
                 self.next();
-                let start = &node.span();
-
-                node = Expr::BinExpr(
-                    Box::new(BinExpr::new(Op::BitwiseXor, node, self.bitwise_and()?)),
-                    Span::combine(start, &self.current.1),
-                );
+                node = binary(Op::BitwiseXor, node, self.bitwise_and()?);
             } else {
                 break;
             }
@@ -714,12 +1123,7 @@ impl Parser {
         loop {
             if let TokenType::Symbol(Symbol::And) = &self.current.0 {
                 self.next();
-                let start = &node.span();
-
-                node = Expr::BinExpr(
-                    Box::new(BinExpr::new(Op::BitwiseAnd, node, self.comparison()?)),
-                    Span::combine(start, &self.current.1),
-                );
+                node = binary(Op::BitwiseAnd, node, self.comparison()?);
             } else {
                 break;
             }
@@ -728,85 +1132,85 @@ impl Parser {
         Ok(node)
     }
 
+    /// Parse comparisons, where a run of `<`, `<=`, `>`, `>=`, `==` and `!=`
+    /// forms a chain such as `1 < x < 10`, which holds if each of its
+    /// comparisons does.
     fn comparison(&mut self) -> Result<Expr, Error> {
-        let mut node = self.parse_sum()?;
-        let start = &node.span();
-
+        let mut node = self.shift()?;
         loop {
             match &self.current.0 {
-                TokenType::Symbol(Symbol::EqualsEquals) => {
-                    self.consume(TokenType::symbol("=="))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::EqualTo, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                TokenType::Keyword(Keyword::Is) => {
+                    self.expect_keyword(Keyword::Is)?;
+                    node = binary(Op::Is, node, self.shift()?);
                 }
-                TokenType::Symbol(Symbol::NotEqual) => {
-                    self.consume(TokenType::symbol("!="))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::NotEqual, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                TokenType::Keyword(Keyword::Isnt) => {
+                    self.expect_keyword(Keyword::Isnt)?;
+                    node = binary(Op::Isnt, node, self.shift()?);
                 }
-                TokenType::Symbol(Symbol::GreaterThanEqual) => {
-                    self.consume(TokenType::symbol(">="))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::GreaterThanEquals, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
-                }
-                TokenType::Symbol(Symbol::LessThanEqual) => {
-                    self.consume(TokenType::symbol("<="))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::LessThanEquals, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
-                }
-                TokenType::Symbol(Symbol::RightAngleBracket) => {
-                    self.consume(TokenType::symbol(">"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::GreaterThan, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
-                }
-                TokenType::Symbol(Symbol::LeftAngleBracket) => {
-                    self.consume(TokenType::symbol("<"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::LessThan, node, self.parse_sum()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                _ => {
+                    let mut chain = Vec::new();
+                    while let Some(op) = self.chained_op() {
+                        self.next();
+                        chain.push((op, self.shift()?));
+                    }
+
+                    node = match chain.len() {
+                        0 => break,
+                        1 => {
+                            let (op, rhs) = chain.remove(0);
+                            binary(op, node, rhs)
+                        }
+                        _ => {
+                            let last = &chain[chain.len() - 1].1;
+                            let span = Span::combine(&node.span(), &last.span());
+                            Expr::Chain(Box::new(node), Box::new(chain), span)
+                        }
+                    };
                 }
-                _ => break,
             }
         }
         Ok(node)
     }
 
-    /// Parse bitwise `and`, `or` operators.
-    fn _bitwise_and_or(&mut self) -> Result<Expr, Error> {
-        let node = self.parse_sum()?;
+    /// The comparison operator at the current token, if it's one that chains.
+    fn chained_op(&self) -> Option<Op> {
+        match &self.current.0 {
+            TokenType::Symbol(Symbol::EqualsEquals) => Some(Op::EqualTo),
+            TokenType::Symbol(Symbol::NotEqual) => Some(Op::NotEqual),
+            TokenType::Symbol(Symbol::GreaterThanEqual) => Some(Op::GreaterThanEquals),
+            TokenType::Symbol(Symbol::LessThanEqual) => Some(Op::LessThanEquals),
+            TokenType::Symbol(Symbol::RightAngleBracket) => Some(Op::GreaterThan),
+            TokenType::Symbol(Symbol::LeftAngleBracket) => Some(Op::LessThan),
+            _ => None,
+        }
+    }
+
+    fn shift(&mut self) -> Result<Expr, Error> {
+        let mut node = self.parse_sum()?;
+        loop {
+            let op = match &self.current.0 {
+                TokenType::Symbol(Symbol::LeftShift) => Op::Shl,
+                TokenType::Symbol(Symbol::RightShift) => Op::Shr,
+                _ => break,
+            };
 
+            self.next();
+            node = binary(op, node, self.parse_sum()?);
+        }
         Ok(node)
     }
 
     fn parse_sum(&mut self) -> Result<Expr, Error> {
         let mut node = self.parse_term()?;
-        let start = &node.span();
         loop {
             match &self.current.0 {
                 TokenType::Symbol(Symbol::Plus) => {
                     self.consume(TokenType::symbol("+"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::Add, node, self.parse_term()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                    node = binary(Op::Add, node, self.parse_term()?);
                 }
                 TokenType::Symbol(Symbol::Hypen) => {
                     self.consume(TokenType::symbol("-"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::Subtract, node, self.parse_term()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                    node = binary(Op::Subtract, node, self.parse_term()?);
                 }
                 _ => {
                     break;
@@ -817,30 +1221,20 @@ impl Parser {
     }
 
     fn parse_term(&mut self) -> Result<Expr, Error> {
-        let mut node = self.member_expr()?;
-        let start = &node.span();
+        let mut node = self.unary()?;
         loop {
             match &self.current.0 {
                 TokenType::Symbol(Symbol::Star) => {
                     self.consume(TokenType::symbol("*"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::Multiply, node, self.member_expr()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                    node = binary(Op::Multiply, node, self.unary()?);
                 }
                 TokenType::Symbol(Symbol::Slash) => {
                     self.consume(TokenType::symbol("/"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::Divide, node, self.member_expr()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                    node = binary(Op::Divide, node, self.unary()?);
                 }
                 TokenType::Symbol(Symbol::Modulo) => {
                     self.consume(TokenType::symbol("%"))?;
-                    node = Expr::BinExpr(
-                        Box::new(BinExpr::new(Op::Remainder, node, self.member_expr()?)),
-                        Span::combine(start, &self.current.1),
-                    );
+                    node = binary(Op::Remainder, node, self.unary()?);
                 }
                 _ => {
                     break;
@@ -850,66 +1244,79 @@ impl Parser {
         Ok(node)
     }
 
+    /// Parse a prefix `-`, `+` or `!`, which applies to everything up to the
+    /// next binary operator other than `**`.
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let op = match &self.current.0 {
+            TokenType::Symbol(Symbol::Plus) => Op::Add,
+            TokenType::Symbol(Symbol::Hypen) => Op::Subtract,
+            TokenType::Symbol(Symbol::Bang) => Op::Bang,
+            _ => return self.parse_power(),
+        };
+
+        let start = self.current.1.clone();
+        self.next();
+
+        let expr = self.unary()?;
+        let span = Span::combine(&start, &expr.span());
+        Ok(Expr::UnaryExpr(op, Box::new(expr), span))
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, Error> {
+        let node = self.member_expr()?;
+
+        if let TokenType::Symbol(Symbol::StarStar) = &self.current.0 {
+            self.consume(TokenType::symbol("**"))?;
+            let start = &node.span();
+
+            // recurse rather than loop, making `**` right associative; the
+            // exponent may itself be negated, as in `2 ** -1`
+            let rhs = self.unary()?;
+            let end = &rhs.span();
+
+            return Ok(Expr::BinExpr(
+                Box::new(BinExpr::new(Op::Power, node, rhs)),
+                Span::combine(start, end),
+            ));
+        }
+
+        Ok(node)
+    }
+
+    /// Parse a chain of calls, indexes and member accesses.
+    ///
+    /// The chain is left-associative, so `a.b(1).c` is `((a.b)(1)).c` and each
+    /// link applies to the value produced by the one before it.
     fn member_expr(&mut self) -> Result<Expr, Error> {
-        let mut node = self.dot_expr()?;
+        let mut node = self.paren_expr()?;
         let start = node.span();
 
         loop {
             match self.current.0.clone() {
                 TokenType::Delimiter(Delimiter::OpenBracket) => {
                     self.expect_delimiter(Delimiter::OpenBracket)?;
-                    node = Expr::Index(
-                        Box::new(node),
-                        Box::new(self.disjunction()?),
-                        Span::combine(&start, &self.current.1.clone()),
-                    );
+                    node = self.index_or_slice(node, &start)?;
                     self.expect_delimiter(Delimiter::CloseBracket)?;
                 }
                 TokenType::Delimiter(Delimiter::OpenParen) => {
-                    node = Expr::FunCall(
-                        Box::new(node),
-                        Box::new(self.args()?),
-                        Span::combine(&start, &self.current.1.clone()),
-                    );
-                }
-                TokenType::Comment(typ) => {
-                    self.consume(TokenType::Comment(typ))?;
-                    continue;
+                    let args = self.args()?;
+                    // the span ends at the closing paren
+                    let span = Span::combine(&start, &self.last().1);
+                    node = Expr::FunCall(Box::new(node), Box::new(args), span);
                 }
-                _ => break,
-            }
-        }
-
-        Ok(node)
-    }
-
-    fn dot_expr(&mut self) -> Result<Expr, Error> {
-        let mut node = self.paren_expr()?;
-        let start = node.span();
-
-        loop {
-            match &self.current.0 {
                 TokenType::Symbol(Symbol::Dot) => {
                     self.consume(TokenType::symbol("."))?;
 
-                    node = Expr::MemberExpr(
-                        Box::new(node),
-                        Box::new(self.paren_expr()?),
-                        Span::combine(&start, &self.current.1.clone()),
-                    );
+                    let prop = self.paren_expr()?;
+                    let span = Span::combine(&start, &prop.span());
+                    node = Expr::MemberExpr(Box::new(node), Box::new(prop), span);
                 }
                 TokenType::Symbol(Symbol::Colon) => {
                     self.consume(TokenType::symbol(":"))?;
 
-                    node = Expr::AssocExpr(
-                        Box::new(node),
-                        Box::new(self.paren_expr()?),
-                        Span::combine(&start, &self.current.1),
-                    );
-                }
-                TokenType::Comment(_) => {
-                    self.comment()?;
-                    continue;
+                    let prop = self.paren_expr()?;
+                    let span = Span::combine(&start, &prop.span());
+                    node = Expr::AssocExpr(Box::new(node), Box::new(prop), span);
                 }
                 _ => break,
             }
@@ -920,15 +1327,7 @@ impl Parser {
 
     fn paren_expr(&mut self) -> Result<Expr, Error> {
         if let TokenType::Delimiter(Delimiter::OpenParen) = self.current.0 {
-            let start = &self.expect_delimiter(Delimiter::OpenParen)?;
-            let node = self.disjunction()?;
-
-            if let TokenType::Symbol(Symbol::Comma) = self.current.0 {
-                todo!()
-            }
-
-            let end = &self.expect_delimiter(Delimiter::CloseParen)?;
-            return Ok(Expr::ParenExpr(Box::new(node), Span::combine(start, end)));
+            return self.tuple();
         }
 
         self.factor()
@@ -965,38 +1364,12 @@ impl Parser {
                     node = Expr::Identifier(self.identifier()?);
                 }
             },
-            TokenType::Symbol(sym) => match sym {
-                Symbol::Plus => {
-                    self.consume(TokenType::symbol("+"))?;
-                    node = Expr::UnaryExpr(
-                        Op::Add,
-                        Box::new(self.paren_expr()?),
-                        self.current.1.clone(),
-                    );
-                }
-                Symbol::Hypen => {
-                    self.consume(TokenType::symbol("-"))?;
-                    node = Expr::UnaryExpr(
-                        Op::Subtract,
-                        Box::new(self.paren_expr()?),
-                        self.current.1.clone(),
-                    );
-                }
-                Symbol::Bang => {
-                    self.consume(TokenType::symbol("!"))?;
-                    node = Expr::UnaryExpr(
-                        Op::Bang,
-                        Box::new(self.paren_expr()?),
-                        self.current.1.clone(),
-                    );
-                }
-                sym => {
-                    return Err(Error::UnexpectedToken(Item::new(
-                        &sym.to_string(),
-                        self.current.1.clone(),
-                    )))
-                }
-            },
+            TokenType::Symbol(sym) => {
+                return Err(Error::UnexpectedToken(Item::new(
+                    &sym.to_string(),
+                    self.current.1.clone(),
+                )))
+            }
             TokenType::Delimiter(delimiter) => match delimiter {
                 Delimiter::OpenParen => node = self.tuple()?,
                 Delimiter::OpenBracket => node = self.list()?,
@@ -1016,6 +1389,14 @@ impl Parser {
                 node = Expr::SelfExpr(self.current.1.clone());
                 self.next();
             }
+            TokenType::Keyword(Keyword::Super) => {
+                let start = self.expect_keyword(Keyword::Super)?;
+                self.symbol(Symbol::Dot)?;
+                let method = self.identifier()?;
+
+                let span = Span::combine(&start, &method.span());
+                node = Expr::SuperExpr(method, span);
+            }
             _ => {
                 return Err(Error::UnexpectedToken(Item::new(
                     &self.current.0.to_string(),
@@ -1026,6 +1407,29 @@ impl Parser {
         Ok(node)
     }
 
+    /// Parse the inside of `expr[...]`, either an index or a slice `lo..hi`.
+    fn index_or_slice(&mut self, expr: Expr, start: &Span) -> Result<Expr, Error> {
+        let lo = match self.current.0 {
+            TokenType::Symbol(Symbol::DotDot) => None,
+            _ => Some(Box::new(self.disjunction()?)),
+        };
+
+        if self.current.0 != TokenType::Symbol(Symbol::DotDot) {
+            let span = Span::combine(start, &self.current.1);
+            return Ok(Expr::Index(Box::new(expr), lo.unwrap(), span));
+        }
+
+        self.symbol(Symbol::DotDot)?;
+
+        let hi = match self.current.0 {
+            TokenType::Delimiter(Delimiter::CloseBracket) => None,
+            _ => Some(Box::new(self.disjunction()?)),
+        };
+
+        let span = Span::combine(start, &self.current.1);
+        Ok(Expr::Slice(Box::new(expr), lo, hi, span))
+    }
+
     fn list(&mut self) -> Result<Expr, Error> {
         self.expect_delimiter(Delimiter::OpenBracket)?;
 
@@ -1039,7 +1443,7 @@ impl Parser {
                 TokenType::Delimiter(Delimiter::CloseBracket) => {
                     break;
                 }
-                _ => nodes.push(self.disjunction()?),
+                _ => nodes.push(self.element()?),
             }
         }
 
@@ -1048,21 +1452,43 @@ impl Parser {
         Ok(Expr::List(Box::new(nodes), self.current.1.clone()))
     }
 
+    /// Parse an argument of a call or an element of a list, which may be
+    /// spread with `...`.
+    fn element(&mut self) -> Result<Expr, Error> {
+        match self.current.0 {
+            TokenType::Symbol(Symbol::DotDotDot) => {
+                let start = self.symbol(Symbol::DotDotDot)?;
+                let expr = self.disjunction()?;
+                let span = Span::combine(&start, &expr.span());
+
+                Ok(Expr::Spread(Box::new(expr), span))
+            }
+            _ => self.disjunction(),
+        }
+    }
+
+    /// Parse a tuple `(a, b)`, or a parenthesized expression `(a)`.
+    ///
+    /// A single element followed by a comma, as in `(a,)`, is a one-element tuple.
     fn tuple(&mut self) -> Result<Expr, Error> {
         let start = self.expect_delimiter(Delimiter::OpenParen)?;
-
-        let mut tuple = Vec::new();
         let node = self.disjunction()?;
 
         if let TokenType::Delimiter(Delimiter::CloseParen) = self.current.0 {
-            self.expect_delimiter(Delimiter::CloseParen)?;
-            return Ok(node);
+            let end = &self.expect_delimiter(Delimiter::CloseParen)?;
+            return Ok(Expr::ParenExpr(Box::new(node), Span::combine(&start, end)));
         }
 
-        tuple.push(node);
+        let mut tuple = vec![node];
 
         while let TokenType::Symbol(Symbol::Comma) = &self.current.0 {
             self.consume(TokenType::symbol(","))?;
+
+            // allow a trailing comma
+            if let TokenType::Delimiter(Delimiter::CloseParen) = self.current.0 {
+                break;
+            }
+
             tuple.push(self.disjunction()?);
         }
 
@@ -1092,7 +1518,11 @@ impl Parser {
                 TokenType::Delimiter(Delimiter::CloseBrace) => break,
                 _ => {
                     self.consume(TokenType::symbol(","))?;
-                    continue;
+
+                    // allow a trailing comma
+                    if let TokenType::Delimiter(Delimiter::CloseBrace) = self.current.0 {
+                        break;
+                    }
                 }
             }
         }
@@ -1110,12 +1540,6 @@ impl Parser {
         Ok(Ident { name, span })
     }
 
-    fn comment(&mut self) -> Result<(), Error> {
-        self.next();
-
-        Ok(())
-    }
-
     pub fn parse_file(&mut self) -> Result<AST, Error> {
         let mut nodes = vec![];
 
@@ -1126,22 +1550,37 @@ impl Parser {
                     self.expect_delimiter(Delimiter::Newline)?;
                     continue;
                 }
-                TokenType::Comment(_) => {
-                    self.comment()?;
+                TokenType::Symbol(Symbol::SemiColon) => {
+                    self.symbol(Symbol::SemiColon)?;
                     continue;
                 }
-                _ => nodes.push(ASTNode::from(self.compound_statement()?)),
+                _ => match self.compound_statement() {
+                    Ok(node) => nodes.push(ASTNode::from(node)),
+                    Err(err) => self.recover(err, false),
+                },
             }
         }
 
         Ok(AST::new(nodes, self.tokens.source.clone()))
     }
 
-    pub fn parse(&mut self) -> Result<AST, Error> {
-        self.current = self.tokens.node[self.pos].clone();
-
-        let ast = self.parse_file()?;
+    /// Parse the token stream, returning every syntax error found.
+    pub fn parse(&mut self) -> Result<AST, Vec<Error>> {
+        self.current = self.token_at(self.pos);
 
-        Ok(ast)
+        match self.parse_file() {
+            Ok(ast) if self.errors.is_empty() => Ok(ast),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(err) => {
+                self.errors.push(err);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
     }
 }
+
+/// Build a binary expression spanning from the start of `lhs` to the end of `rhs`.
+fn binary(op: Op, lhs: Expr, rhs: Expr) -> Expr {
+    let span = Span::combine(&lhs.span(), &rhs.span());
+    Expr::BinExpr(Box::new(BinExpr::new(op, lhs, rhs)), span)
+}
@@ -1,6 +1,7 @@
+use crate::common::Span;
 use crate::compiler::{ASTNode, BinExpr, Expr, Ident, Op, ScriptFun, Stmt, Class, TypePath, ast::Constructor};
 
-use super::ast::Trait;
+use super::ast::{Enum, MatchArm, Trait};
 
 /// A trait used for each pass the compiler makes.
 ///
@@ -18,8 +19,12 @@ pub trait Pass<T: Default, E> {
         match stmt {
             Stmt::Block(stmts, _) => self.block(stmts),
             Stmt::IfStatement(expr, body, _) => self.if_statement(expr, body),
-            Stmt::WhileStatement(expr, body, _) => self.while_statement(expr, body),
-            Stmt::LoopStatement(body, _) => self.loop_statement(body),
+            Stmt::WhileStatement(expr, body, label, _) => self.while_statement(expr, body, label),
+            Stmt::LoopStatement(body, label, _) => self.loop_statement(body, label),
+            Stmt::ForStatement(ident, iter, body, label, _) => {
+                self.for_statement(ident, iter, body, label)
+            }
+            Stmt::TryStatement(body, _) => self.try_statement(&body.0, &body.1, &body.2),
             Stmt::ImportStatement(import, _) => self.import_statement(import),
             Stmt::VarDeclaration(ident, expr, _, _) => self.var_decl(ident, expr),
             Stmt::ConDeclaration(ident, expr, _, _) => self.con_decl(ident, expr),
@@ -27,19 +32,31 @@ pub trait Pass<T: Default, E> {
             Stmt::Function(fun, _) => self.fun(fun),
             Stmt::Class(class, _) => self.class(class),
             Stmt::Constructor(constructor, _) => self.constructor(constructor),
-            Stmt::Return(expr, _) => self.return_stmt(expr),
-            Stmt::Break(_) => self.break_stmt(),
-            Stmt::Continue(_) => self.continue_stmt(),
+            Stmt::Return(expr, span) => self.return_stmt(expr, span),
+            Stmt::Break(label, expr, span) => self.break_stmt(label, expr, span),
+            Stmt::Continue(label, span) => self.continue_stmt(label, span),
             Stmt::Expr(expr) => self.expression(expr),
             Stmt::Trait(trait_) => self.trait_decl(trait_),
+            Stmt::Enum(enum_, _) => self.enum_decl(enum_),
+            Stmt::Match(match_, _) => self.match_stmt(&match_.0, &match_.1),
         }
     }
 
     fn if_statement(&mut self, expr: &Expr, body: &(Stmt, Option<Stmt>)) -> Result<T, E>;
 
-    fn while_statement(&mut self, expr: &Expr, body: &Stmt) -> Result<T, E>;
+    fn while_statement(&mut self, expr: &Expr, body: &Stmt, label: &Option<Ident>) -> Result<T, E>;
 
-    fn loop_statement(&mut self, body: &Stmt) -> Result<T, E>;
+    fn loop_statement(&mut self, body: &Stmt, label: &Option<Ident>) -> Result<T, E>;
+
+    fn for_statement(
+        &mut self,
+        ident: &Ident,
+        iter: &Expr,
+        body: &Stmt,
+        label: &Option<Ident>,
+    ) -> Result<T, E>;
+
+    fn try_statement(&mut self, body: &Stmt, ident: &Ident, handler: &Stmt) -> Result<T, E>;
 
     fn import_statement(&mut self, import: &Expr) -> Result<T, E>;
 
@@ -57,15 +74,24 @@ pub trait Pass<T: Default, E> {
         Ok(T::default())
     }
 
+    fn enum_decl(&mut self, _enum: &Enum) -> Result<T, E>;
+
+    fn match_stmt(&mut self, expr: &Expr, arms: &[MatchArm]) -> Result<T, E>;
+
     fn constructor(&mut self, _constructor: &Constructor) -> Result<T, E>;
 
     fn fun(&mut self, _fun: &ScriptFun) -> Result<T, E>;
 
-    fn return_stmt(&mut self, expr: &Option<Expr>) -> Result<T, E>;
+    fn return_stmt(&mut self, expr: &Option<Expr>, span: &Span) -> Result<T, E>;
 
-    fn break_stmt(&mut self) -> Result<T, E>;
+    fn break_stmt(
+        &mut self,
+        label: &Option<Ident>,
+        expr: &Option<Expr>,
+        span: &Span,
+    ) -> Result<T, E>;
 
-    fn continue_stmt(&mut self) -> Result<T, E>;
+    fn continue_stmt(&mut self, label: &Option<Ident>, span: &Span) -> Result<T, E>;
 
     fn expression(&mut self, expr: &Expr) -> Result<T, E> {
         match expr {
@@ -75,34 +101,48 @@ pub trait Pass<T: Default, E> {
             Expr::Unit(_) | Expr::Nil(_) => self.nil(),
             Expr::Identifier(ident) => self.identifier(ident),
             Expr::SelfExpr(_) => self.self_expr(),
+            Expr::SuperExpr(method, _) => self.super_expr(method),
             Expr::BinExpr(bin_expr, _) => self.binary_expr(bin_expr),
             Expr::UnaryExpr(op, unary_expr, _) => self.unary_expr(op, unary_expr),
             Expr::ParenExpr(expr, _) => self.expression(&*expr),
             Expr::Index(expr, index, _) => self.index(expr, index),
+            Expr::Slice(expr, lo, hi, _) => self.slice(expr, lo.as_deref(), hi.as_deref()),
             Expr::List(list, _) => self.list((list).to_vec()),
             Expr::Tuple(tuple, _) => self.tuple(tuple),
             Expr::Map(map, _) => self.map(map),
             Expr::Or(lhs, rhs, _) => self.or(lhs, rhs),
             Expr::And(lhs, rhs, _) => self.and(lhs, rhs),
+            Expr::Chain(first, rest, _) => self.chain(first, rest),
             Expr::FunCall(callee, args, _) => self.fun_call(callee, args),
             Expr::MemberExpr(obj, prop, _) => self.member_expr(obj, prop),
             Expr::AssocExpr(obj, prop, _) => self.assoc_expr(obj, prop),
             Expr::Type(typ, _) => self.type_spec(typ),
+            Expr::Loop(body, _) => self.loop_expr(body),
+            Expr::Spread(expr, _) => self.spread(expr),
+            Expr::Assign(target, value, _) => self.assign_stmt(target, value),
         }
     }
 
+    fn spread(&mut self, expr: &Expr) -> Result<T, E>;
+
+    fn loop_expr(&mut self, body: &Stmt) -> Result<T, E>;
+
     fn type_spec(&mut self, typ: &TypePath) -> Result<T, E>;
 
     fn and(&mut self, lhs: &Expr, rhs: &Expr) -> Result<T, E>;
 
     fn or(&mut self, lhs: &Expr, rhs: &Expr) -> Result<T, E>;
 
+    fn chain(&mut self, first: &Expr, rest: &[(Op, Expr)]) -> Result<T, E>;
+
     fn binary_expr(&mut self, bin_expr: &BinExpr) -> Result<T, E>;
 
     fn unary_expr(&mut self, _op: &Op, expr: &Expr) -> Result<T, E>;
 
     fn index(&mut self, expr: &Expr, index: &Expr) -> Result<T, E>;
 
+    fn slice(&mut self, expr: &Expr, lo: Option<&Expr>, hi: Option<&Expr>) -> Result<T, E>;
+
     fn tuple(&mut self, tuple: &[Expr]) -> Result<T, E>;
 
     fn list(&mut self, list: Vec<Expr>) -> Result<T, E>;
@@ -117,6 +157,8 @@ pub trait Pass<T: Default, E> {
 
     fn self_expr(&mut self) -> Result<T, E>;
 
+    fn super_expr(&mut self, method: &Ident) -> Result<T, E>;
+
     fn identifier(&mut self, _ident: &Ident) -> Result<T, E>;
 
     fn number(&mut self, _val: &f64) -> Result<T, E>;
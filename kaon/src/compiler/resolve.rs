@@ -1,10 +1,14 @@
 use crate::{
-    common::{Span, state::State},
+    common::{Span, state::State, Value},
     compiler::{ASTNode, BinExpr, Class, Expr, Ident, Op, Pass, ScriptFun, Stmt, AST, TypePath},
-    error::{Error, Item},
+    error::{Error, Item, Warning},
     core::{self},
 };
 
+use super::ast::{Enum, MatchArm, Pattern};
+
+use std::collections::HashMap;
+
 pub enum SymbolTy {
     Ty(String),
     Variable(String),
@@ -13,6 +17,30 @@ pub enum SymbolTy {
 #[derive(Clone, Debug)]
 pub struct Scope {
     symbols: Vec<Symbol>,
+    /// The symbols declared with `con`, which can't be reassigned.
+    constants: Vec<Symbol>,
+    /// The arities of the native functions bound in this scope.
+    natives: HashMap<String, NativeArity>,
+}
+
+/// The number of arguments a native function takes.
+#[derive(Clone, Copy, Debug)]
+struct NativeArity {
+    params: usize,
+    is_varidic: bool,
+}
+
+impl NativeArity {
+    /// The arity of `value` if it is a native function.
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::NativeFun(fun) => Some(NativeArity {
+                params: fun.arity(),
+                is_varidic: fun.is_varidic,
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl Default for Scope {
@@ -25,10 +53,13 @@ impl Scope {
     pub fn new() -> Self {
         Scope {
             symbols: Vec::new(),
+            constants: Vec::new(),
+            natives: HashMap::new(),
         }
     }
 
     pub fn insert(&mut self, symbol: Symbol) {
+        self.natives.remove(&symbol.0);
         self.symbols.push(symbol);
     }
 
@@ -39,13 +70,31 @@ impl Scope {
     pub fn find(&mut self, name: &str) -> Option<&Symbol> {
         self.symbols.iter().filter(|sym| sym.0 == name).last()
     }
+
+    /// Mark a symbol in this scope as a constant.
+    pub fn insert_constant(&mut self, symbol: Symbol) {
+        self.constants.push(symbol);
+    }
 }
 
+/// Bind the names in `state`, remembering the arities of its native functions
+/// so that calls to them can be checked before the script runs.
 impl From<State> for Scope {
     fn from(state: State) -> Self {
         let symbols = state.names.iter().map(|n| Symbol(n.to_string(), Span::empty())).collect::<Vec<Symbol>>();
 
-        Scope { symbols }
+        let natives = state
+            .names
+            .iter()
+            .zip(&state.values)
+            .filter_map(|(name, value)| Some((name.to_string(), NativeArity::of(value)?)))
+            .collect();
+
+        Scope {
+            symbols,
+            constants: Vec::new(),
+            natives,
+        }
     }
 }
 
@@ -87,9 +136,10 @@ impl ScopedMap {
         }
     }
 
+    /// Create a map whose global scope starts with the symbols in `scope`.
     pub fn with_global_scope(scope: Scope) -> Self {
         Self {
-            scopes: vec![scope, Scope::from(core::prelude())]
+            scopes: vec![Scope::from(core::prelude()), scope],
         }
     }
 
@@ -112,6 +162,37 @@ impl ScopedMap {
         None
     }
 
+    /// Find a symbol declared in any scope enclosing the current one, innermost first.
+    pub fn find_enclosing(&mut self, symbol: &str) -> Option<&Symbol> {
+        let len = self.scopes.len();
+        self.scopes[..len - 1]
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.find(symbol))
+    }
+
+    /// Find the declaration of `symbol` if the innermost binding of it is a constant.
+    pub fn find_constant(&self, symbol: &str) -> Option<&Symbol> {
+        let scope = self
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.symbols.iter().any(|sym| sym.0 == symbol))?;
+
+        scope.constants.iter().find(|sym| sym.0 == symbol)
+    }
+
+    /// Find the arity of `symbol` if its innermost binding is a native function.
+    fn find_native(&self, symbol: &str) -> Option<NativeArity> {
+        let scope = self
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.symbols.iter().any(|sym| sym.0 == symbol))?;
+
+        scope.natives.get(symbol).copied()
+    }
+
     pub fn enter_scope(&mut self) {
         self.scopes.push(Scope::new());
     }
@@ -121,11 +202,48 @@ impl ScopedMap {
     }
 }
 
+/// A local variable, and whether it has been read.
+struct Local {
+    symbol: Symbol,
+    depth: usize,
+    used: bool,
+}
+
+/// A scope entered by the resolver.
+#[derive(Clone, Copy)]
+struct ScopeId {
+    id: usize,
+    /// Whether this is the outermost scope of a function body.
+    is_function: bool,
+}
+
+/// A reference to a name that has not been declared yet.
+struct Unresolved {
+    symbol: Symbol,
+    /// The scopes enclosing the reference, outermost first.
+    scopes: Vec<ScopeId>,
+}
+
 #[derive(Default)]
 pub struct Resolver {
     symbols: ScopedMap,
-    unresolved_symbols: Vec<Symbol>,
+    unresolved_symbols: Vec<Unresolved>,
+    /// Calls to native functions with the wrong number of arguments, which are
+    /// reported unless the script goes on to declare a symbol with the same name.
+    native_calls: Vec<(String, Error)>,
+    /// Locals declared with `var` or `con` that are currently in scope.
+    locals: Vec<Local>,
+    /// The scopes enclosing the current node, where an empty stack is the global scope.
+    scopes: Vec<ScopeId>,
+    scope_count: usize,
+    /// The labels of the loops enclosing the current node, within the current function.
+    loops: Vec<Option<String>>,
+    /// The names and field counts of the variants of each enum declared so far.
+    enums: HashMap<String, Vec<(String, usize)>>,
+    /// Warn when a `var` shadows a binding in an enclosing scope.
+    pub warn_shadowing: bool,
     pub errors: Vec<Error>,
+    pub warnings: Vec<Warning>,
 }
 
 impl Resolver {
@@ -136,6 +254,23 @@ impl Resolver {
         }
     }
 
+    /// Check calls to the native functions among the host's `globals`, like those
+    /// registered with [Vm::register_function](crate::runtime::Vm::register_function).
+    ///
+    /// Only natives whose names are in the global scope are checked, since the
+    /// others can't be called from the script.
+    pub fn register_natives<'a>(
+        &mut self,
+        globals: impl IntoIterator<Item = (&'a String, &'a Value)>,
+    ) {
+        let scope = self.symbols.current_scope();
+        for (name, value) in globals {
+            if let Some(arity) = NativeArity::of(value) {
+                scope.natives.insert(name.clone(), arity);
+            }
+        }
+    }
+
     pub fn resolve_ast(&mut self, ast: &AST) {
         for node in &ast.nodes {
             let result = match node {
@@ -148,33 +283,336 @@ impl Resolver {
             }
         }
 
-        for unresolved_symbol in &self.unresolved_symbols {
+        for (_, error) in self.native_calls.drain(..) {
+            self.errors.push(error);
+        }
+
+        for unresolved in &self.unresolved_symbols {
             self.errors.push(Error::UnresolvedIdentifier(Item::new(
-                &unresolved_symbol.0,
-                unresolved_symbol.1.clone(),
+                &unresolved.symbol.0,
+                unresolved.symbol.1.clone(),
             )))
         }
     }
 
     fn declare_symbol(&mut self, symbol: Symbol) {
         let name = &symbol.0;
-        self.unresolved_symbols.retain(|s| &s.0 != name);
+        self.unresolved_symbols.retain(|s| &s.symbol.0 != name);
+        self.native_calls.retain(|(native, _)| native != name);
         self.symbols.insert(symbol);
     }
 
+    /// Declare a variable, tracking it if it is local so that it can be
+    /// reported if it is never read.
+    fn declare_variable(&mut self, ident: &Ident) -> Result<(), Error> {
+        let forward_reference = self.forward_reference(ident);
+
+        if self.warn_shadowing {
+            if let Some(original) = self.symbols.find_enclosing(&ident.name) {
+                self.warnings.push(Warning::ShadowedVariable(
+                    Item::new(&ident.name, ident.span()),
+                    Item::new(&original.0, original.1.clone()),
+                ));
+            }
+        }
+
+        if !self.scopes.is_empty() {
+            self.locals.push(Local {
+                symbol: Symbol(ident.name.clone(), ident.span()),
+                depth: self.scopes.len(),
+                used: false,
+            });
+        }
+
+        self.declare_symbol(Symbol(ident.name.clone(), ident.span()));
+
+        match forward_reference {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Find a reference to a variable made earlier in the scope it's declared in.
+    ///
+    /// References from within a nested function are allowed, since the function
+    /// may not be called until after the variable is declared.
+    fn forward_reference(&self, ident: &Ident) -> Option<Error> {
+        let current = self.scopes.last().map(|scope| scope.id);
+
+        for unresolved in &self.unresolved_symbols {
+            if unresolved.symbol.0 != ident.name {
+                continue;
+            }
+
+            let enclosed = match current {
+                Some(id) => unresolved.scopes.iter().position(|scope| scope.id == id),
+                None => Some(0),
+            };
+
+            if let Some(position) = enclosed {
+                let inner = match current {
+                    Some(_) => &unresolved.scopes[position + 1..],
+                    None => &unresolved.scopes[..],
+                };
+
+                if !inner.iter().any(|scope| scope.is_function) {
+                    return Some(Error::UseBeforeDeclaration(
+                        Item::new(&unresolved.symbol.0, unresolved.symbol.1.clone()),
+                        Item::new(&ident.name, ident.span()),
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn enter_scope(&mut self, is_function: bool) {
+        self.symbols.enter_scope();
+
+        self.scope_count += 1;
+        self.scopes.push(ScopeId {
+            id: self.scope_count,
+            is_function,
+        });
+    }
+
+    /// Leave the current scope, warning about any locals that were never read.
+    fn exit_scope(&mut self) {
+        self.symbols.exit_scope();
+        self.scopes.pop();
+
+        let depth = self.scopes.len();
+        let position = self
+            .locals
+            .iter()
+            .position(|local| local.depth > depth)
+            .unwrap_or(self.locals.len());
+
+        for local in self.locals.drain(position..) {
+            if !local.used && !local.symbol.0.starts_with('_') {
+                self.warnings.push(Warning::UnusedVariable(Item::new(
+                    &local.symbol.0,
+                    local.symbol.1,
+                )));
+            }
+        }
+    }
+
+    /// Warn about any statements following a `return`, `break` or `continue`.
+    fn check_reachable(&mut self, stmts: &[Stmt]) {
+        for (pos, stmt) in stmts.iter().enumerate() {
+            let keyword = match stmt {
+                Stmt::Return(_, _) => "return",
+                Stmt::Break(..) => "break",
+                Stmt::Continue(..) => "continue",
+                _ => continue,
+            };
+
+            if let Some(unreachable) = stmts.get(pos + 1) {
+                self.warnings
+                    .push(Warning::UnreachableCode(Item::new(keyword, unreachable.span())));
+            }
+
+            return;
+        }
+    }
+
+    /// Resolve the body of a loop, where `break` and `continue` can be used.
+    fn loop_body(&mut self, body: &Stmt, label: &Option<Ident>) -> Result<(), Error> {
+        self.loops.push(label.as_ref().map(|label| label.name.clone()));
+        let result = self.statment(body);
+        self.loops.pop();
+
+        result
+    }
+
+    /// Check that a `break` or `continue` is inside a loop, and that its label
+    /// names one of the enclosing loops.
+    fn check_loop_exit(
+        &self,
+        keyword: &str,
+        label: &Option<Ident>,
+        span: &Span,
+    ) -> Result<(), Error> {
+        if self.loops.is_empty() {
+            return Err(Error::OutsideLoop(Item::new(keyword, span.clone())));
+        }
+
+        match label {
+            Some(label) if !self.loops.contains(&Some(label.name.clone())) => {
+                Err(Error::UnknownLabel(Item::new(&label.name, label.span())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that every arm of a `match` can be reached, and warn if the arms
+    /// may not cover every value of the matched expression.
+    fn check_arms(&mut self, expr: &Expr, arms: &[MatchArm]) -> Result<(), Error> {
+        for (i, arm) in arms.iter().enumerate() {
+            let earlier = arms[..i]
+                .iter()
+                .find(|earlier| covers(&earlier.pattern, &arm.pattern));
+
+            if let Some(earlier) = earlier {
+                return Err(Error::UnreachableArm(
+                    Item::new("arm", arm.pattern.span()),
+                    Item::new("arm", earlier.pattern.span()),
+                ));
+            }
+        }
+
+        if arms.iter().any(|arm| matches!(arm.pattern, Pattern::Wildcard(_))) {
+            return Ok(());
+        }
+
+        let missing = match self.missing_cases(arms) {
+            Some(missing) if missing.is_empty() => return Ok(()),
+            Some(missing) => missing,
+            // numbers and strings can never all be listed
+            None => vec![],
+        };
+
+        self.warnings.push(Warning::NonExhaustiveMatch(
+            Item::new("match", expr.span()),
+            missing,
+        ));
+
+        Ok(())
+    }
+
+    /// The cases not matched by any arm, if the arms all match values from a
+    /// closed set: booleans, or the variants of a single enum.
+    fn missing_cases(&self, arms: &[MatchArm]) -> Option<Vec<String>> {
+        let booleans = arms
+            .iter()
+            .map(|arm| match &arm.pattern {
+                Pattern::Literal(Expr::Boolean(val, _)) => Some(*val),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+
+        if let Some(booleans) = booleans {
+            let missing = [true, false].into_iter().filter(|val| !booleans.contains(val));
+            return Some(missing.map(|val| val.to_string()).collect());
+        }
+
+        let variants = arms
+            .iter()
+            .map(|arm| match &arm.pattern {
+                Pattern::Variant(Expr::Identifier(enum_), variant, _, _) => {
+                    Some((enum_.name.as_str(), variant.name.as_str()))
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let enum_ = variants.first()?.0;
+        if variants.iter().any(|(name, _)| *name != enum_) {
+            return None;
+        }
+
+        let missing = self.enums.get(enum_)?.iter().filter(|(variant, _)| {
+            !variants.iter().any(|(_, matched)| matched == variant)
+        });
+        Some(missing.map(|(variant, _)| format!("{enum_}.{variant}")).collect())
+    }
+
+    /// Check that a variant pattern binds every field of the variant, or none
+    /// of them to only match the variant.
+    fn check_bindings(
+        &self,
+        enum_: &Expr,
+        variant: &Ident,
+        found: usize,
+        span: &Span,
+    ) -> Result<(), Error> {
+        let enum_ = match enum_ {
+            Expr::Identifier(enum_) => &enum_.name,
+            _ => return Ok(()),
+        };
+
+        let fields = self.enums.get(enum_).and_then(|variants| {
+            variants
+                .iter()
+                .find(|(name, _)| *name == variant.name)
+                .map(|(_, fields)| *fields)
+        });
+
+        match fields {
+            Some(expected) if found != 0 && found != expected => Err(Error::PatternArity(
+                Item::new(&format!("{enum_}.{}", variant.name), span.clone()),
+                expected,
+                found,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The global scope, including any globals declared by the script.
     pub fn global_scope(&mut self) -> Scope {
-        self.symbols.scopes.first().unwrap().clone()
+        self.symbols.scopes.last().unwrap().clone()
+    }
+}
+
+/// Check if every value matched by the `later` pattern is already matched by `earlier`.
+fn covers(earlier: &Pattern, later: &Pattern) -> bool {
+    match (earlier, later) {
+        (Pattern::Wildcard(_), _) => true,
+        (Pattern::Literal(earlier), Pattern::Literal(later)) => {
+            literal_value(earlier).is_some() && literal_value(earlier) == literal_value(later)
+        }
+        (
+            Pattern::Variant(Expr::Identifier(earlier_enum), earlier, _, _),
+            Pattern::Variant(Expr::Identifier(later_enum), later, _, _),
+        ) => earlier_enum.name == later_enum.name && earlier.name == later.name,
+        _ => false,
     }
 }
 
+/// The value of a literal pattern, as a string tagged with its type.
+fn literal_value(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Number(val, _) => Some(format!("number {val}")),
+        Expr::String(val, _) => Some(format!("string {val}")),
+        Expr::Boolean(val, _) => Some(format!("boolean {val}")),
+        Expr::Nil(_) => Some("nil".to_string()),
+        Expr::Unit(_) => Some("()".to_string()),
+        Expr::UnaryExpr(Op::Subtract, expr, _) => match &**expr {
+            Expr::Number(val, _) => Some(format!("number {}", -val)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Find the first name that repeats an earlier one, such as a parameter or field
+/// declared twice, pointing at the second occurrence.
+fn duplicate_name(names: &[Ident]) -> Option<Error> {
+    names.iter().enumerate().find_map(|(i, name)| {
+        names[..i]
+            .iter()
+            .find(|original| original.name == name.name)
+            .map(|original| {
+                Error::DuplicateIdentifier(
+                    Item::new(&original.name, original.span()),
+                    Item::new(&name.name, name.span()),
+                )
+            })
+    })
+}
+
 impl Pass<(), Error> for Resolver {
     fn block(&mut self, stmts: &[Stmt]) -> Result<(), Error> {
-        self.symbols.enter_scope();
+        self.check_reachable(stmts);
+
+        self.enter_scope(false);
         for node in stmts {
             self.statment(node)?;
         }
 
-        self.symbols.exit_scope();
+        self.exit_scope();
         Ok(())
     }
 
@@ -188,13 +626,57 @@ impl Pass<(), Error> for Resolver {
         Ok(())
     }
 
-    fn while_statement(&mut self, expr: &Expr, body: &Stmt) -> Result<(), Error> {
+    fn while_statement(
+        &mut self,
+        expr: &Expr,
+        body: &Stmt,
+        label: &Option<Ident>,
+    ) -> Result<(), Error> {
         self.expression(expr)?;
-        self.statment(body)
+        self.loop_body(body, label)
+    }
+
+    fn loop_statement(&mut self, body: &Stmt, label: &Option<Ident>) -> Result<(), Error> {
+        self.loop_body(body, label)
+    }
+
+    fn for_statement(
+        &mut self,
+        ident: &Ident,
+        iter: &Expr,
+        body: &Stmt,
+        label: &Option<Ident>,
+    ) -> Result<(), Error> {
+        self.expression(iter)?;
+
+        self.enter_scope(false);
+        let mut result = self.declare_variable(ident);
+        if result.is_ok() {
+            result = self.loop_body(body, label);
+        }
+        self.exit_scope();
+
+        result
+    }
+
+    fn loop_expr(&mut self, body: &Stmt) -> Result<(), Error> {
+        self.loop_body(body, &None)
+    }
+
+    fn spread(&mut self, expr: &Expr) -> Result<(), Error> {
+        self.expression(expr)
     }
 
-    fn loop_statement(&mut self, body: &Stmt) -> Result<(), Error> {
-        self.statment(body)
+    fn try_statement(&mut self, body: &Stmt, ident: &Ident, handler: &Stmt) -> Result<(), Error> {
+        self.statment(body)?;
+
+        self.enter_scope(false);
+        self.symbols
+            .insert(Symbol(ident.name.clone(), ident.span()));
+        let result = self.statment(handler);
+        self.exit_scope();
+
+        result
     }
 
     fn import_statement(&mut self, import: &Expr) -> Result<(), Error> {
@@ -227,6 +709,87 @@ impl Pass<(), Error> for Resolver {
             self.declare_symbol(symbol);
         }
 
+        if let Some(parent) = &class.parent {
+            self.identifier(parent)?;
+        }
+
+        let fields = class
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                Stmt::VarDeclaration(ident, _, _, _) => Some(ident.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(error) = duplicate_name(&fields) {
+            return Err(error);
+        }
+
+        let functions = class.methods.iter().chain(&class.statics);
+        for stmt in class.constructors.iter().chain(functions) {
+            let params = match stmt {
+                Stmt::Constructor(constructor, _) => &constructor.params,
+                Stmt::Function(fun, _) => &fun.params,
+                _ => continue,
+            };
+
+            if let Some(error) = duplicate_name(params) {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enum_decl(&mut self, enum_: &Enum) -> Result<(), Error> {
+        if let Some(duplicate) = self.symbols.current_scope().find(&enum_.name.name) {
+            return Err(Error::DuplicateIdentifier(
+                Item::new(&enum_.name.name, enum_.name.span()),
+                Item::new(&duplicate.0.clone(), duplicate.1.clone()),
+            ));
+        }
+
+        self.declare_symbol(Symbol(enum_.name.name.clone(), enum_.name.span()));
+
+        let variants = enum_
+            .variants
+            .iter()
+            .map(|variant| (variant.name.name.clone(), variant.fields.len()));
+        self.enums
+            .insert(enum_.name.name.clone(), variants.collect());
+
+        Ok(())
+    }
+
+    fn match_stmt(&mut self, expr: &Expr, arms: &[MatchArm]) -> Result<(), Error> {
+        self.expression(expr)?;
+        self.check_arms(expr, arms)?;
+
+        for arm in arms {
+            match &arm.pattern {
+                Pattern::Wildcard(_) => {}
+                Pattern::Literal(literal) => self.expression(literal)?,
+                Pattern::Variant(enum_, _, _, _) => self.expression(enum_)?,
+            }
+
+            self.enter_scope(false);
+
+            let mut result = Ok(());
+            if let Pattern::Variant(enum_, variant, bindings, span) = &arm.pattern {
+                result = self.check_bindings(enum_, variant, bindings.len(), span);
+
+                for binding in bindings.iter().filter(|binding| binding.name != "_") {
+                    result = result.and(self.declare_variable(binding));
+                }
+            }
+
+            let result = result.and(self.statment(&arm.body));
+            self.exit_scope();
+
+            result?;
+        }
+
         Ok(())
     }
 
@@ -247,34 +810,35 @@ impl Pass<(), Error> for Resolver {
             self.declare_symbol(symbol);
         }
 
-        self.symbols.enter_scope();
+        self.enter_scope(true);
 
-        for param in &fun.params {
-            if self.symbols.current_scope().has_symbol(&param.name) {
-                self.symbols.exit_scope();
-
-                let original = self.symbols.current_scope().find(&param.name).unwrap();
+        if let Some(error) = duplicate_name(&fun.params) {
+            self.exit_scope();
+            return Err(error);
+        }
 
-                return Err(Error::DuplicateIdentifier(
-                    Item::new(&original.0, original.1.clone()),
-                    Item::new(&param.name, param.span()),
-                ));
-            } else {
-                self.symbols
-                    .insert(Symbol(param.name.clone(), param.span()));
-            }
+        for param in &fun.params {
+            self.symbols
+                .insert(Symbol(param.name.clone(), param.span()));
         }
 
+        // loops outside of the function can't be broken out of from inside it
+        let loops = std::mem::take(&mut self.loops);
+
         if let Stmt::Block(stmts, _) = &fun.body {
+            self.check_reachable(stmts);
+
             for stmt in (*stmts).iter() {
                 if let Err(err) = self.statment(stmt) {
-                    self.symbols.exit_scope();
+                    self.loops = loops;
+                    self.exit_scope();
                     return Err(err);
                 }
             }
         }
 
-        self.symbols.exit_scope();
+        self.loops = loops;
+        self.exit_scope();
 
         Ok(())
     }
@@ -294,10 +858,7 @@ impl Pass<(), Error> for Resolver {
             self.expression(expr)?;
         }
         // insert new symbol.
-        let symbol = Symbol(ident.name.clone(), ident.span());
-        self.declare_symbol(symbol);
-
-        Ok(())
+        self.declare_variable(ident)
     }
 
     fn con_decl(&mut self, ident: &Ident, init: &Expr) -> Result<(), Error> {
@@ -312,13 +873,15 @@ impl Pass<(), Error> for Resolver {
 
         self.expression(init)?;
 
-        let symbol = Symbol(ident.name.clone(), ident.span());
-        self.declare_symbol(symbol);
+        let result = self.declare_variable(ident);
+        self.symbols
+            .current_scope()
+            .insert_constant(Symbol(ident.name.clone(), ident.span()));
 
-        Ok(())
+        result
     }
 
-    fn assign_stmt(&mut self, _ident: &Expr, expr: &Expr) -> Result<(), Error> {
+    fn assign_stmt(&mut self, ident: &Expr, expr: &Expr) -> Result<(), Error> {
         /*if self.symbols.find(&ident.name).is_none() {
             return Err(Error::UnresolvedIdentifier(Item::new(
                 &ident.name,
@@ -326,22 +889,45 @@ impl Pass<(), Error> for Resolver {
             )));
         }*/
 
+        if let Expr::Identifier(ident) = ident {
+            if let Some(constant) = self.symbols.find_constant(&ident.name) {
+                return Err(Error::AssignToConstant(
+                    Item::new(&ident.name, ident.span()),
+                    Item::new(&constant.0, constant.1.clone()),
+                ));
+            }
+        }
+
         self.expression(expr)
     }
 
-    fn return_stmt(&mut self, expr: &Option<Expr>) -> Result<(), Error> {
+    fn return_stmt(&mut self, expr: &Option<Expr>, span: &Span) -> Result<(), Error> {
+        if !self.scopes.iter().any(|scope| scope.is_function) {
+            return Err(Error::OutsideFunction(Item::new("return", span.clone())));
+        }
+
         match expr {
             Some(expr) => self.expression(expr),
             None => Ok(())
         }
     }
 
-    fn break_stmt(&mut self) -> Result<(), Error> {
-        Ok(())
+    fn break_stmt(
+        &mut self,
+        label: &Option<Ident>,
+        expr: &Option<Expr>,
+        span: &Span,
+    ) -> Result<(), Error> {
+        self.check_loop_exit("break", label, span)?;
+
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => Ok(()),
+        }
     }
 
-    fn continue_stmt(&mut self) -> Result<(), Error> {
-        Ok(())
+    fn continue_stmt(&mut self, label: &Option<Ident>, span: &Span) -> Result<(), Error> {
+        self.check_loop_exit("continue", label, span)
     }
 
     fn type_spec(&mut self, _typ: &TypePath) -> Result<(), Error> {
@@ -353,6 +939,15 @@ impl Pass<(), Error> for Resolver {
         self.expression(rhs)
     }
 
+    fn chain(&mut self, first: &Expr, rest: &[(Op, Expr)]) -> Result<(), Error> {
+        self.expression(first)?;
+        for (_, operand) in rest {
+            self.expression(operand)?;
+        }
+
+        Ok(())
+    }
+
     fn or(&mut self, lhs: &Expr, rhs: &Expr) -> Result<(), Error> {
         self.expression(lhs)?;
         self.expression(rhs)
@@ -374,6 +969,16 @@ impl Pass<(), Error> for Resolver {
         self.expression(index)
     }
 
+    fn slice(&mut self, expr: &Expr, lo: Option<&Expr>, hi: Option<&Expr>) -> Result<(), Error> {
+        self.expression(expr)?;
+
+        for bound in [lo, hi].into_iter().flatten() {
+            self.expression(bound)?;
+        }
+
+        Ok(())
+    }
+
     fn list(&mut self, list: Vec<Expr>) -> Result<(), Error> {
         for item in list {
             self.expression(&item)?;
@@ -389,7 +994,14 @@ impl Pass<(), Error> for Resolver {
         Ok(())
     }
 
-    fn map(&mut self, _map: &[(Expr, Expr)]) -> Result<(), Error> {
+    fn map(&mut self, map: &[(Expr, Expr)]) -> Result<(), Error> {
+        for (key, value) in map {
+            if !matches!(key, Expr::Identifier(_)) {
+                self.expression(key)?;
+            }
+            self.expression(value)?;
+        }
+
         Ok(())
     }
 
@@ -399,6 +1011,32 @@ impl Pass<(), Error> for Resolver {
             self.expression(arg)?;
         }
 
+        // the number of arguments spread from a list is only known at runtime
+        if args.iter().any(|arg| matches!(arg, Expr::Spread(..))) {
+            return Ok(());
+        }
+
+        if let Expr::Identifier(ident) = callee {
+            if let Some(arity) = self.symbols.find_native(&ident.name) {
+                let found = args.len();
+                if found < arity.params || (found > arity.params && !arity.is_varidic) {
+                    let expected = match arity.is_varidic {
+                        true => format!("at least {}", arity.params),
+                        false => arity.params.to_string(),
+                    };
+
+                    let error = Error::MismatchArgCount(
+                        Item::new(&expected, Span::empty()),
+                        Item::new(&found.to_string(), callee.span()),
+                        args.iter()
+                            .map(|arg| Item::new("", arg.span()))
+                            .collect::<Vec<Item>>(),
+                    );
+                    self.native_calls.push((ident.name.clone(), error));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -415,10 +1053,25 @@ impl Pass<(), Error> for Resolver {
         Ok(())
     }
 
+    fn super_expr(&mut self, _method: &Ident) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn identifier(&mut self, ident: &Ident) -> Result<(), Error> {
+        if let Some(local) = self
+            .locals
+            .iter_mut()
+            .rev()
+            .find(|local| local.symbol.0 == ident.name)
+        {
+            local.used = true;
+        }
+
         if self.symbols.find(&ident.name).is_none() {
-            self.unresolved_symbols
-                .push(Symbol(ident.name.clone(), ident.span()));
+            self.unresolved_symbols.push(Unresolved {
+                symbol: Symbol(ident.name.clone(), ident.span()),
+                scopes: self.scopes.clone(),
+            });
         }
 
         Ok(())
@@ -4,7 +4,8 @@ use std::fmt::{self, Display};
 
 pub const KEYWORDS: &[&str] = &[
     "and", "or", "if", "else", "var", "con", "loop", "while", "for", "in", "break", "continue",
-    "fun", "return", "class", "create", "const", "self", "import", "from", "public", "trait", "impl"
+    "fun", "return", "class", "create", "const", "self", "super", "import", "from", "public", "trait",
+    "impl", "try", "catch", "is", "isnt", "static", "enum", "match"
 ];
 
 /// Represents a symbol.
@@ -16,6 +17,8 @@ pub enum Symbol {
     Hypen,
     /// *
     Star,
+    /// **
+    StarStar,
     /// /
     Slash,
     /// %
@@ -30,6 +33,8 @@ pub enum Symbol {
     Comma,
     /// .
     Dot,
+    /// ..
+    DotDot,
     /// ...
     DotDotDot,
     /// &
@@ -70,6 +75,7 @@ impl Display for Symbol {
             Self::Plus => f.write_str("+"),
             Self::Hypen => f.write_str("-"),
             Self::Star => f.write_str("*"),
+            Self::StarStar => f.write_str("**"),
             Self::Slash => f.write_str("\\"),
             Self::Modulo => f.write_str("%"),
             Self::Tilde => f.write_str("~"),
@@ -77,6 +83,7 @@ impl Display for Symbol {
             Self::SemiColon => f.write_str(";"),
             Self::Comma => f.write_str(","),
             Self::Dot => f.write_str("."),
+            Self::DotDot => f.write_str(".."),
             Self::And => f.write_str("&"),
             Self::Or => f.write_str("|"),
             Self::Equal => f.write_str("="),
@@ -133,37 +140,13 @@ impl Display for Delimiter {
     }
 }
 
-/// A comment token.
-#[derive(Debug, Clone, PartialEq)]
-pub enum Comment {
-    /// //
-    LineComment,
-    /// /*
-    BlockComment,
-    /// ///
-    LineDocComment,
-    /// /**
-    BlockDocComment,
-}
-
-impl Display for Comment {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Comment::LineComment => f.write_str("//"),
-            Comment::BlockComment => f.write_str("/*"),
-            Comment::LineDocComment => f.write_str("///"),
-            Comment::BlockDocComment => f.write_str("/**"),
-        }
-    }
-}
-
 /// A keyword.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
     /// var
     Var,
-    /// final
-    Final,
+    /// con
+    Con,
     /// and
     And,
     /// or
@@ -192,10 +175,14 @@ pub enum Keyword {
     Class,
     /// create
     Create,
+    /// static
+    Static,
     /// const
     Const,
     /// self
     Self_,
+    /// super
+    Super,
     /// import
     Import,
     /// from
@@ -206,13 +193,25 @@ pub enum Keyword {
     Trait,
     /// impl
     Impl,
+    /// try
+    Try,
+    /// catch
+    Catch,
+    /// is
+    Is,
+    /// isnt
+    Isnt,
+    /// enum
+    Enum,
+    /// match
+    Match,
 }
 
 impl Display for Keyword {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Keyword::Var => f.write_str("var"),
-            Keyword::Final => f.write_str("final"),
+            Keyword::Con => f.write_str("con"),
             Keyword::And => f.write_str("and"),
             Keyword::Or => f.write_str("or"),
             Keyword::If => f.write_str("if"),
@@ -227,13 +226,21 @@ impl Display for Keyword {
             Keyword::Return => f.write_str("return"),
             Keyword::Class => f.write_str("class"),
             Keyword::Create => f.write_str("create"),
+            Keyword::Static => f.write_str("static"),
             Keyword::Const => f.write_str("const"),
             Keyword::Self_ => f.write_str("self"),
+            Keyword::Super => f.write_str("super"),
             Keyword::Import => f.write_str("import"),
             Keyword::From => f.write_str("from"),
             Keyword::Public => f.write_str("public"),
             Keyword::Trait => f.write_str("trait"),
             Keyword::Impl => f.write_str("impl"),
+            Keyword::Try => f.write_str("try"),
+            Keyword::Catch => f.write_str("catch"),
+            Keyword::Is => f.write_str("is"),
+            Keyword::Isnt => f.write_str("isnt"),
+            Keyword::Enum => f.write_str("enum"),
+            Keyword::Match => f.write_str("match"),
         }
     }
 }
@@ -277,8 +284,6 @@ pub enum TokenType {
     Keyword(Keyword),
     /// a symbol
     Symbol(Symbol),
-    /// a comment
-    Comment(Comment),
     /// An opening or closing delimiter.
     Delimiter(Delimiter),
     /// A literal token.
@@ -291,6 +296,7 @@ impl TokenType {
             "+" => Symbol::Plus,
             "-" => Symbol::Hypen,
             "*" => Symbol::Star,
+            "**" => Symbol::StarStar,
             "/" => Symbol::Slash,
             "%" => Symbol::Modulo,
             "~" => Symbol::Tilde,
@@ -298,6 +304,7 @@ impl TokenType {
             ";" => Symbol::SemiColon,
             "," => Symbol::Comma,
             "." => Symbol::Dot,
+            ".." => Symbol::DotDot,
             "&" => Symbol::And,
             "|" => Symbol::Or,
             "^" => Symbol::Xor,
@@ -324,6 +331,9 @@ impl TokenType {
         match keyword {
             "or" => TokenType::Keyword(Keyword::Or),
             "in" => TokenType::Keyword(Keyword::In),
+            "is" => TokenType::Keyword(Keyword::Is),
+            "isnt" => TokenType::Keyword(Keyword::Isnt),
+            "try" => TokenType::Keyword(Keyword::Try),
             "if" => TokenType::Keyword(Keyword::If),
             "var" => TokenType::Keyword(Keyword::Var),
             "and" => TokenType::Keyword(Keyword::And),
@@ -333,13 +343,18 @@ impl TokenType {
             "loop" => TokenType::Keyword(Keyword::Loop),
             "self" => TokenType::Keyword(Keyword::Self_),
             "from" => TokenType::Keyword(Keyword::From),
+            "super" => TokenType::Keyword(Keyword::Super),
             "break" => TokenType::Keyword(Keyword::Break),
-            "final" => TokenType::Keyword(Keyword::Final),
+            "catch" => TokenType::Keyword(Keyword::Catch),
+            "con" => TokenType::Keyword(Keyword::Con),
             "while" => TokenType::Keyword(Keyword::While),
             "class" => TokenType::Keyword(Keyword::Class),
             "create" => TokenType::Keyword(Keyword::Create),
+            "static" => TokenType::Keyword(Keyword::Static),
             "const" => TokenType::Keyword(Keyword::Const),
             "trait" => TokenType::Keyword(Keyword::Trait),
+            "enum" => TokenType::Keyword(Keyword::Enum),
+            "match" => TokenType::Keyword(Keyword::Match),
             "impl" => TokenType::Keyword(Keyword::Impl),
             "return" => TokenType::Keyword(Keyword::Return),
             "import" => TokenType::Keyword(Keyword::Import),
@@ -349,16 +364,6 @@ impl TokenType {
         }
     }
 
-    pub fn comment(typ: &str) -> TokenType {
-        match typ {
-            "//" => TokenType::Comment(Comment::LineComment),
-            "/*" => TokenType::Comment(Comment::BlockComment),
-            "///" => TokenType::Comment(Comment::LineDocComment),
-            "/**" => TokenType::Comment(Comment::BlockDocComment),
-            _ => unimplemented!(),
-        }
-    }
-
     pub fn delimiter(typ: &str) -> TokenType {
         match typ {
             "{" => TokenType::Delimiter(Delimiter::OpenBrace),
@@ -383,7 +388,6 @@ impl Display for TokenType {
         match &*self {
             TokenType::Keyword(keyword) => write!(f, "{keyword}"),
             TokenType::Symbol(symbol) => write!(f, "{symbol}"),
-            TokenType::Comment(typ) => write!(f, "{typ}"),
             TokenType::Delimiter(delimiter) => write!(f, "{delimiter}"),
             TokenType::Literal(literal) => write!(f, "{literal}"),
         }
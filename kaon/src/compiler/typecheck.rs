@@ -9,7 +9,7 @@ use crate::{
 };
 use std::{collections::HashMap, fmt, fmt::Debug, fmt::Display};
 
-use super::ast::Trait;
+use super::ast::{Enum, MatchArm, Pattern, Trait};
 
 /// enum containing all possible data types for the Kaon langauge
 #[derive(Debug, Clone, PartialEq)]
@@ -173,8 +173,10 @@ impl TypeChecker {
     pub fn check_stmt(&mut self, stmt: &Stmt) -> Result<Type, Error> {
         match stmt {
             Stmt::IfStatement(expr, body, _) => self.if_statement(expr, body),
-            Stmt::WhileStatement(expr, body, _) => self.while_statement(expr, body),
-            Stmt::LoopStatement(body, _) => self.loop_statement(body),
+            Stmt::WhileStatement(expr, body, _, _) => self.while_statement(expr, body),
+            Stmt::LoopStatement(body, _, _) => self.loop_statement(body),
+            Stmt::ForStatement(ident, iter, body, _, _) => self.for_statement(ident, iter, body),
+            Stmt::TryStatement(body, _) => self.try_statement(&body.0, &body.1, &body.2),
             Stmt::ImportStatement(import, _) => self.import_statement(import),
             Stmt::Block(stmts, _) => self.block(stmts),
             Stmt::VarDeclaration(ident, expr, typ, _) => self.var_decl(ident, expr, typ),
@@ -183,10 +185,12 @@ impl TypeChecker {
             Stmt::Function(fun, _) => self.fun(fun),
             Stmt::Class(class, _) => self.class(class),
             Stmt::Trait(t) => self.trait_decl(t),
+            Stmt::Enum(enum_, _) => self.enum_decl(enum_),
+            Stmt::Match(match_, _) => self.match_stmt(&match_.0, &match_.1),
             Stmt::Constructor(constructor, _) => self.constructor(constructor),
             Stmt::Return(expr, span) => self.return_stmt(expr, span),
-            Stmt::Break(_) => self.break_stmt(),
-            Stmt::Continue(_) => self.continue_stmt(),
+            Stmt::Break(_, expr, _) => self.break_stmt(expr),
+            Stmt::Continue(_, _) => self.continue_stmt(),
             Stmt::Expr(expr) => self.check_expr(expr),
         }
     }
@@ -219,6 +223,32 @@ impl TypeChecker {
         self.check_stmt(body)
     }
 
+    fn for_statement(&mut self, ident: &Ident, iter: &Expr, body: &Stmt) -> Result<Type, Error> {
+        self.check_expr(iter)?;
+
+        // the values an iterator yields aren't typed
+        self.enter_scope();
+        self.current_env()
+            .insert(Symbol::new(ident.name.to_owned()), Type::Any);
+        let result = self.check_stmt(body);
+        self.exit_scope();
+
+        result
+    }
+
+    fn try_statement(&mut self, body: &Stmt, ident: &Ident, handler: &Stmt) -> Result<Type, Error> {
+        self.check_stmt(body)?;
+
+        self.enter_scope();
+        self.current_env()
+            .insert(Symbol::new(ident.name.to_owned()), Type::String);
+        let result = self.check_stmt(handler);
+        self.exit_scope();
+
+        result?;
+        Ok(Type::Void)
+    }
+
     fn import_statement(&mut self, _import: &Expr) -> Result<Type, Error> {
         unimplemented!()
     }
@@ -239,6 +269,43 @@ impl TypeChecker {
         Ok(Type::Any)
     }
 
+    fn enum_decl(&mut self, enum_: &Enum) -> Result<Type, Error> {
+        self.current_env()
+            .insert(Symbol::new(enum_.name.name.clone()), Type::Any);
+
+        Ok(Type::Any)
+    }
+
+    fn match_stmt(&mut self, expr: &Expr, arms: &[MatchArm]) -> Result<Type, Error> {
+        self.check_expr(expr)?;
+
+        for arm in arms {
+            match &arm.pattern {
+                Pattern::Wildcard(_) => {}
+                Pattern::Literal(literal) => {
+                    self.check_expr(literal)?;
+                }
+                Pattern::Variant(enum_, _, _, _) => {
+                    self.check_expr(enum_)?;
+                }
+            }
+
+            self.enter_scope();
+            if let Pattern::Variant(_, _, bindings, _) = &arm.pattern {
+                for binding in bindings {
+                    self.current_env()
+                        .insert(Symbol::new(binding.name.to_owned()), Type::Any);
+                }
+            }
+            let result = self.check_stmt(&arm.body);
+            self.exit_scope();
+
+            result?;
+        }
+
+        Ok(Type::Void)
+    }
+
     fn constructor(&mut self, _constructor: &Constructor) -> Result<Type, Error> {
         todo!()
     }
@@ -374,7 +441,11 @@ impl TypeChecker {
         Ok(typ.clone())
     }
 
-    fn break_stmt(&mut self) -> Result<Type, Error> {
+    fn break_stmt(&mut self, expr: &Option<Expr>) -> Result<Type, Error> {
+        if let Some(expr) = expr {
+            self.check_expr(expr)?;
+        }
+
         Ok(Type::Void)
     }
 
@@ -390,22 +461,42 @@ impl TypeChecker {
             Expr::Unit(_) | Expr::Nil(_) => self.nil(),
             Expr::Identifier(ident) => self.identifier(ident),
             Expr::SelfExpr(_) => self.self_expr(),
+            Expr::SuperExpr(method, _) => self.super_expr(method),
             Expr::BinExpr(bin_expr, _) => self.binary_expr(bin_expr),
             Expr::UnaryExpr(op, unary_expr, _) => self.unary_expr(op, unary_expr),
             Expr::ParenExpr(expr, _) => self.check_expr(&*expr),
             Expr::Index(expr, index, _) => self.index(expr, index),
+            Expr::Slice(expr, lo, hi, _) => self.slice(expr, lo.as_deref(), hi.as_deref()),
             Expr::List(list, _) => self.list((list).to_vec()),
             Expr::Tuple(tuple, _) => self.tuple(tuple),
             Expr::Map(map, _) => self.map(map),
             Expr::Or(lhs, rhs, _) => self.or(lhs, rhs),
             Expr::And(lhs, rhs, _) => self.and(lhs, rhs),
+            Expr::Chain(first, rest, _) => self.chain(first, rest),
             Expr::FunCall(callee, args, _) => self.fun_call(callee, args),
             Expr::MemberExpr(obj, prop, _) => self.member_expr(obj, prop),
             Expr::AssocExpr(obj, prop, _) => self.assoc_expr(obj, prop),
             Expr::Type(typ_name, _) => self.type_spec(typ_name),
+            Expr::Loop(body, _) => self.loop_expr(body),
+            Expr::Spread(expr, _) => self.spread(expr),
+            Expr::Assign(target, value, _) => self.assign_stmt(target, value),
         }
     }
 
+    fn spread(&mut self, expr: &Expr) -> Result<Type, Error> {
+        self.check_expr(expr)?;
+
+        // the elements of a list aren't typed
+        Ok(Type::Any)
+    }
+
+    fn loop_expr(&mut self, body: &Stmt) -> Result<Type, Error> {
+        self.check_stmt(body)?;
+
+        // a loop can break with values of different types
+        Ok(Type::Any)
+    }
+
     fn type_spec(&mut self, type_name: &TypePath) -> Result<Type, Error> {
         let name = type_name.ident.clone();
 
@@ -431,6 +522,34 @@ impl TypeChecker {
         self.check_expr(rhs)
     }
 
+    /// Check a chain of comparisons, where each operand must have the same type
+    /// as the one before it.
+    fn chain(&mut self, first: &Expr, rest: &[(Op, Expr)]) -> Result<Type, Error> {
+        let mut lhs = first;
+        let mut lhs_typ = self.check_expr(first)?;
+
+        for (op, rhs) in rest {
+            let rhs_typ = self.check_expr(rhs)?;
+
+            match (&lhs_typ, &rhs_typ) {
+                (lhs, rhs) if lhs == rhs => {}
+                (Type::Any, _) | (_, Type::Any) => {}
+                _ => {
+                    return Err(Error::MismatchBinOp(
+                        Item::new(&op.to_string(), rhs.span()),
+                        Item::new(&lhs_typ.to_string()[..], lhs.span()),
+                        Item::new(&rhs_typ.to_string()[..], rhs.span()),
+                    ))
+                }
+            }
+
+            lhs = rhs;
+            lhs_typ = rhs_typ;
+        }
+
+        Ok(Type::Bool)
+    }
+
     fn or(&mut self, lhs: &Expr, rhs: &Expr) -> Result<Type, Error> {
         self.check_expr(lhs)?;
         self.check_expr(rhs)
@@ -439,6 +558,11 @@ impl TypeChecker {
     fn binary_expr(&mut self, bin_expr: &BinExpr) -> Result<Type, Error> {
         let lhs_typ = self.check_expr(&bin_expr.lhs)?;
         let rhs_typ = self.check_expr(&bin_expr.rhs)?;
+
+        if bin_expr.op == Op::Is || bin_expr.op == Op::Isnt {
+            return Ok(Type::Bool);
+        }
+
         match (&lhs_typ, &rhs_typ) {
             (lhs, rhs) if lhs == rhs => {}
             (Type::Any, _) | (_, Type::Any) => {}
@@ -457,15 +581,20 @@ impl TypeChecker {
             | Op::Multiply
             | Op::Divide
             | Op::Remainder
+            | Op::Power
             | Op::BitwiseAnd
             | Op::BitwiseOr
-            | Op::BitwiseXor => Ok(lhs_typ),
+            | Op::BitwiseXor
+            | Op::Shl
+            | Op::Shr => Ok(lhs_typ),
             Op::GreaterThan
             | Op::GreaterThanEquals
             | Op::LessThan
             | Op::LessThanEquals
             | Op::EqualTo
             | Op::NotEqual
+            | Op::Is
+            | Op::Isnt
             | Op::Bang => Ok(Type::Bool),
         }
     }
@@ -474,6 +603,14 @@ impl TypeChecker {
         self.check_expr(expr)
     }
 
+    fn slice(&mut self, expr: &Expr, lo: Option<&Expr>, hi: Option<&Expr>) -> Result<Type, Error> {
+        for bound in [lo, hi].into_iter().flatten() {
+            self.check_expr(bound)?;
+        }
+
+        self.check_expr(expr)
+    }
+
     fn index(&mut self, expr: &Expr, index: &Expr) -> Result<Type, Error> {
         let typ = self.check_expr(expr)?;
         let _ = self.check_expr(index)?;
@@ -521,6 +658,22 @@ impl TypeChecker {
     fn fun_call(&mut self, callee: &Expr, args: &[Expr]) -> Result<Type, Error> {
         let typ = self.check_expr(callee)?;
 
+        // the number of arguments spread from a list is only known at runtime
+        if args.iter().any(|arg| matches!(arg, Expr::Spread(..))) {
+            for arg in args {
+                self.check_expr(arg)?;
+            }
+
+            return match typ {
+                Type::Fun(_, res) => Ok(*res),
+                Type::Any => Ok(Type::Any),
+                typ => Err(Error::ExpectedFunction(Item::new(
+                    &typ.to_string(),
+                    callee.span(),
+                ))),
+            };
+        }
+
         if let Type::Fun(params, res) = typ {
             if params.len() != args.len() {
                 return Err(Error::MismatchArgCount(
@@ -575,6 +728,10 @@ impl TypeChecker {
         Ok(Type::Any)
     }
 
+    fn super_expr(&mut self, _method: &Ident) -> Result<Type, Error> {
+        Ok(Type::Any)
+    }
+
     fn identifier(&mut self, ident: &Ident) -> Result<Type, Error> {
         match self.lookup_symbol(&ident.name[..]) {
             Some((_, typ)) => Ok(typ.clone()),
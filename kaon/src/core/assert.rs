@@ -0,0 +1,26 @@
+use crate::common::Varidic;
+use crate::error::RuntimeError;
+use crate::Value;
+
+/// Raise an error if `cond` is falsy, with an optional message.
+pub fn assert(cond: Value, message: Varidic<Value>) -> Result<Value, RuntimeError> {
+    if cond.is_truthy() {
+        return Ok(Value::Unit);
+    }
+
+    let message = match message.iter().next() {
+        Some(message) => message.to_string(),
+        None => format!("`{cond}` is falsy"),
+    };
+
+    Err(RuntimeError::AssertionFailed(message))
+}
+
+/// Raise an error showing both values if `lhs` and `rhs` are not equal.
+pub fn assert_eq(lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+    if lhs == rhs {
+        return Ok(Value::Unit);
+    }
+
+    Err(RuntimeError::AssertionFailed(format!("`{lhs}` != `{rhs}`")))
+}
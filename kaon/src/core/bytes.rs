@@ -0,0 +1,42 @@
+// Methods receive their `Bytes` receiver as the `Vec<u8>` it converts from.
+#![allow(clippy::ptr_arg)]
+
+use std::rc::Rc;
+
+use crate::common::{Class, ImmutableString};
+
+fn len(bytes: &mut Vec<u8>) -> usize {
+    bytes.len()
+}
+
+fn is_empty(bytes: &mut Vec<u8>) -> bool {
+    bytes.is_empty()
+}
+
+fn slice(bytes: &mut Vec<u8>, start: f64, end: f64) -> Result<Vec<u8>, String> {
+    if start < 0.0 || end < start || end as usize > bytes.len() {
+        return Err(format!(
+            "range {start}..{end} is out of bounds for bytes of length {}",
+            bytes.len()
+        ));
+    }
+
+    Ok(bytes[start as usize..end as usize].to_vec())
+}
+
+fn to_string(bytes: &mut Vec<u8>) -> Result<ImmutableString, String> {
+    String::from_utf8(bytes.clone())
+        .map(ImmutableString::from)
+        .map_err(|err| err.to_string())
+}
+
+pub fn make_class() -> Rc<Class> {
+    let class = Class::new("Bytes");
+
+    class.register_method("len", len);
+    class.register_method("is_empty", is_empty);
+    class.register_method("slice", slice);
+    class.register_method("to_string", to_string);
+
+    class
+}
@@ -0,0 +1,91 @@
+use crate::common::ImmutableString;
+use crate::error::RuntimeError;
+use crate::runtime::Vm;
+use crate::Value;
+
+/// Convert any value to its string representation.
+pub fn to_string(vm: &mut Vm, value: Value) -> Result<ImmutableString, RuntimeError> {
+    vm.display_value(&value).map(ImmutableString::from)
+}
+
+/// A copy of `value` that shares no lists or maps with it, however deeply nested.
+pub fn copy(value: Value) -> Value {
+    value.deep_clone()
+}
+
+/// Parse a number, using the same syntax as number literals.
+///
+/// Surrounding whitespace and a leading sign are allowed, so `" -1.5e3\n"`
+/// parses, but forms the lexer rejects, such as `.5` or `inf`, do not.
+pub fn parse_number(string: ImmutableString) -> Result<f64, String> {
+    let trimmed = string.as_str().trim();
+    let digits = trimmed.strip_prefix(['-', '+']).unwrap_or(trimmed);
+
+    if !is_number_literal(digits) {
+        return Err(format!("cannot parse `{string}` as a number"));
+    }
+
+    trimmed
+        .parse::<f64>()
+        .map_err(|_| format!("cannot parse `{string}` as a number"))
+}
+
+/// Parse `true` or `false`, ignoring surrounding whitespace.
+pub fn parse_bool(string: ImmutableString) -> Result<bool, String> {
+    match string.as_str().trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("cannot parse `{string}` as a boolean")),
+    }
+}
+
+/// Check if `string` is a number literal: digits, then an optional fraction,
+/// then an optional exponent.
+fn is_number_literal(string: &str) -> bool {
+    fn digits(string: &str) -> (&str, &str) {
+        let end = string
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(string.len());
+        string.split_at(end)
+    }
+
+    let (integer, mut rest) = digits(string);
+    if integer.is_empty() {
+        return false;
+    }
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let (fraction, after) = digits(fraction);
+        if fraction.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+
+    if let Some(exponent) = rest.strip_prefix('e') {
+        let exponent = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        let (exponent, after) = digits(exponent);
+        if exponent.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_number_literal;
+
+    #[test]
+    fn number_literals() {
+        for valid in ["0", "42", "1.5", "1e3", "1.5e3", "2e-4", "2e+4"] {
+            assert!(is_number_literal(valid), "{valid}");
+        }
+
+        for invalid in ["", ".5", "5.", "1e", "1.5.2", "inf", "NaN", "0x10", "1 2"] {
+            assert!(!is_number_literal(invalid), "{invalid}");
+        }
+    }
+}
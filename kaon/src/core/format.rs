@@ -0,0 +1,82 @@
+use crate::common::{ImmutableString, Varidic};
+use crate::error::RuntimeError;
+use crate::runtime::Vm;
+use crate::Value;
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Replace each `{}` in `fmt` with the next argument, converted to a string.
+///
+/// `{:.N}` formats a number with `N` digits after the decimal point. `{{` and
+/// `}}` produce literal braces. It is an error for the number of placeholders
+/// and arguments to differ.
+pub fn format(
+    vm: &mut Vm,
+    fmt: ImmutableString,
+    args: Varidic<Value>,
+) -> Result<ImmutableString, RuntimeError> {
+    let mut output = String::with_capacity(fmt.as_str().len());
+    let found = args.len();
+    let mut args = args.iter();
+    let mut placeholders = 0;
+
+    let mut chars = fmt.as_str().chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                output.push(c);
+                chars.next();
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+
+                if let Some(arg) = args.next() {
+                    output.push_str(&vm.display_value(arg)?);
+                }
+            }
+            ('{', Some(':')) => {
+                chars.next();
+                let precision = precision(&mut chars)?;
+                placeholders += 1;
+
+                if let Some(arg) = args.next() {
+                    output.push_str(&arg.format_number(Some(precision)));
+                }
+            }
+            ('{', _) => return Err("unmatched `{` in format string".into()),
+            ('}', _) => return Err("unmatched `}` in format string".into()),
+            (c, _) => output.push(c),
+        }
+    }
+
+    if found != placeholders {
+        return Err(format!(
+            "format string has {placeholders} placeholder(s), found {found} argument(s)"
+        )
+        .into());
+    }
+
+    Ok(ImmutableString::from(output))
+}
+
+/// Read the `.N}` that ends a placeholder with a precision.
+fn precision(chars: &mut Peekable<Chars>) -> Result<usize, String> {
+    let invalid = || "expected a precision such as `{:.2}` in format string".to_string();
+
+    if chars.next() != Some('.') {
+        return Err(invalid());
+    }
+
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+
+    if chars.next() != Some('}') {
+        return Err(invalid());
+    }
+
+    digits.parse().map_err(|_| invalid())
+}
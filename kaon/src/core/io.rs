@@ -1,38 +1,80 @@
 use std::rc::Rc;
 
-use crate::common::{Value, ImmutableString, Class, Varidic};
-use crate::runtime::Vm;
-
-pub fn print(vm: &mut Vm, args: Varidic<Value>) {
-    let stdout = &vm.context
-        .as_ref()
-        .borrow()
-        .settings
-        .stdout;
-    
-    for (pos, value) in args.iter().enumerate() {
-        stdout.write(value.to_string().as_bytes()).unwrap();
-
-        // don't print a trailing space
-        if pos != args.len() - 1 {
-            stdout.write(" ".as_bytes()).unwrap();
-        }
+use crate::common::{Value, ImmutableString, Class, KaonRead, KaonWrite, Varidic};
+use crate::error::RuntimeError;
+use crate::runtime::{KaonFsFile, Vm};
+
+/// Write the values to stdout separated by spaces, without a trailing newline.
+pub fn print(vm: &mut Vm, args: Varidic<Value>) -> Result<(), RuntimeError> {
+    let line = display_values(vm, &args)?;
+    let stdout = &vm.context.as_ref().borrow().settings.stdout;
+
+    stdout.write(line.as_bytes()).unwrap();
+    stdout.flush().unwrap();
+    Ok(())
+}
+
+/// Write the values to stdout separated by spaces, followed by a newline.
+pub fn println(vm: &mut Vm, args: Varidic<Value>) -> Result<(), RuntimeError> {
+    let line = display_values(vm, &args)?;
+    let stdout = &vm.context.as_ref().borrow().settings.stdout;
+
+    stdout.writeln(&line).unwrap();
+    Ok(())
+}
+
+/// Display the values separated by spaces.
+fn display_values(vm: &mut Vm, args: &Varidic<Value>) -> Result<String, RuntimeError> {
+    let mut values = Vec::with_capacity(args.len());
+    for value in args.iter() {
+        values.push(vm.display_value(value)?);
     }
 
-    stdout.writeln("").unwrap();
+    Ok(values.join(" "))
 }
 
-fn readline(vm: &mut Vm) -> ImmutableString {
+/// Read a line from stdin, without its trailing newline.
+///
+/// Returns `nil` once the end of the input has been reached.
+pub fn read_line(vm: &mut Vm) -> Result<Value, String> {
     let stdin = &vm.context.as_ref().borrow().settings.stdin;
 
-    ImmutableString::from(stdin.read_line().unwrap().unwrap())
+    let line = match stdin.read_line()? {
+        Some(line) => line,
+        None => return Ok(Value::Nil),
+    };
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    Ok(Value::String(ImmutableString::from(line)))
+}
+
+/// Print `prompt` without a newline, then read a line from stdin.
+pub fn input(vm: &mut Vm, prompt: Value) -> Result<Value, String> {
+    {
+        let stdout = &vm.context.as_ref().borrow().settings.stdout;
+        stdout.write(prompt.to_string().as_bytes())?;
+        stdout.flush()?;
+    }
+
+    read_line(vm)
+}
+
+pub fn read_bytes(path: String) -> Result<Vec<u8>, String> {
+    KaonFsFile::open(path).read_bytes()
+}
+
+pub fn write_bytes(path: String, bytes: Vec<u8>) -> Result<(), String> {
+    KaonFsFile::create(path)?.write(&bytes)
 }
 
 pub(crate) fn make_class() -> Rc<Class> {
     let system = Class::new("System");
 
     system.register_static("print", print);
-    system.register_static("readline", readline);
+    system.register_static("println", println);
+    system.register_static("readline", read_line);
 
     system
 }
\ No newline at end of file
@@ -0,0 +1,21 @@
+use std::rc::Rc;
+
+use crate::common::Class;
+use crate::Value;
+
+thread_local! {
+    static STOP_ITERATION: Rc<Class> = Class::new("StopIteration");
+}
+
+/// The value an iterator's `next` method returns once it has no more elements.
+///
+/// It is a class of its own rather than `nil`, so that iterators can yield any
+/// value, including `nil`.
+pub fn stop_iteration() -> Rc<Class> {
+    STOP_ITERATION.with(Rc::clone)
+}
+
+/// Whether `value` is [stop_iteration], ending a `for` loop.
+pub fn is_stop_iteration(value: &Value) -> bool {
+    matches!(value, Value::Class(class) if Rc::ptr_eq(class, &stop_iteration()))
+}
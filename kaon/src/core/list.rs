@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::common::Varidic;
+use crate::error::RuntimeError;
+use crate::runtime::Vm;
+use crate::Value;
+
+/// The distinct elements of `list`, in the order they first appear.
+///
+/// Elements are bucketed by hash and then compared with `==`, so unhashable
+/// elements such as nested lists are still deduplicated, only more slowly.
+pub fn unique(list: Vec<Value>) -> Vec<Value> {
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut distinct: Vec<Value> = Vec::new();
+
+    for value in list {
+        let mut hasher = ahash::AHasher::default();
+        value.hash(&mut hasher);
+
+        let bucket = seen.entry(hasher.finish()).or_default();
+        if bucket.iter().any(|index| distinct[*index] == value) {
+            continue;
+        }
+
+        bucket.push(distinct.len());
+        distinct.push(value);
+    }
+
+    distinct
+}
+
+/// The sum of the elements of `list`, added together with `+`, or `0` if it's empty.
+pub fn sum(list: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut elements = list.into_iter();
+    let first = match elements.next() {
+        Some(first) => first,
+        None => return Ok(Value::Float(0.0)),
+    };
+
+    elements.try_fold(first, Value::try_add)
+}
+
+/// A string or list repeated `times` times, end to end.
+pub fn repeat(value: Value, times: Value) -> Result<Value, RuntimeError> {
+    let times = match times {
+        Value::Float(times) if times >= 0.0 && times.fract() == 0.0 => times as usize,
+        Value::Integer(times) if times >= 0 => times as usize,
+        times => {
+            return Err(format!("expected a count of zero or more, found `{times}`").into());
+        }
+    };
+
+    // the result's size is checked up front, so a huge count is an error rather
+    // than a crash
+    let too_long = || RuntimeError::from(format!("cannot repeat a value {times} times"));
+    match value {
+        Value::String(string) => {
+            let length = string.len().checked_mul(times).ok_or_else(too_long)?;
+
+            let mut repeated = String::new();
+            repeated.try_reserve_exact(length).map_err(|_| too_long())?;
+            while repeated.len() < length {
+                repeated.push_str(string.as_str());
+            }
+            Ok(Value::from(repeated))
+        }
+        Value::List(list) => {
+            let elements = list.0.borrow();
+            let length = elements.len().checked_mul(times).ok_or_else(too_long)?;
+
+            // the elements themselves are shared rather than copied
+            let mut repeated = Vec::new();
+            repeated.try_reserve_exact(length).map_err(|_| too_long())?;
+            while repeated.len() < length {
+                repeated.extend_from_slice(&elements);
+            }
+            Ok(Value::from(repeated))
+        }
+        value => Err(format!("can only repeat a string or a list, found `{value}`").into()),
+    }
+}
+
+/// The strings in `list` joined together, with `separator` between each one.
+///
+/// Every element must already be a string, rather than being converted to one.
+pub fn join(list: Vec<Value>, separator: String) -> Result<String, RuntimeError> {
+    let mut strings = Vec::with_capacity(list.len());
+    for (index, element) in list.iter().enumerate() {
+        match element {
+            Value::String(string) => strings.push(string.as_str()),
+            value => {
+                return Err(format!("can only join strings, found `{value}` at index {index}")
+                    .into())
+            }
+        }
+    }
+
+    Ok(strings.join(&separator))
+}
+
+/// The smallest element of `list`, which must hold numbers, strings or
+/// booleans, but not a mix of them.
+pub fn min(list: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme("min", list, Ordering::Less)
+}
+
+/// The largest element of `list`, which must hold numbers, strings or
+/// booleans, but not a mix of them.
+pub fn max(list: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme("max", list, Ordering::Greater)
+}
+
+/// The first element of `list` that is ordered `wanted` relative to every other.
+fn extreme(name: &str, list: Vec<Value>, wanted: Ordering) -> Result<Value, RuntimeError> {
+    let mut elements = list.into_iter();
+    let mut best = elements
+        .next()
+        .ok_or_else(|| format!("cannot take the {name} of an empty list"))?;
+
+    for element in elements {
+        if compare(&element, &best)? == wanted {
+            best = element;
+        }
+    }
+
+    Ok(best)
+}
+
+/// Compare two elements of a list, which must both be numbers, strings or booleans.
+fn compare(a: &Value, b: &Value) -> Result<Ordering, RuntimeError> {
+    match (a, b) {
+        (Value::Float(_) | Value::Integer(_), Value::Float(_) | Value::Integer(_))
+        | (Value::String(_), Value::String(_))
+        | (Value::Boolean(_), Value::Boolean(_)) => Ok(a.total_cmp(b)),
+        _ => Err(format!("cannot compare `{a}` with `{b}`").into()),
+    }
+}
+
+/// A sorted copy of `list`, in ascending order.
+///
+/// Elements of different kinds can be sorted together: they're grouped by kind,
+/// such as numbers before strings, as described by [Value::total_cmp].
+///
+/// An optional comparator can be passed to sort in another order. It's called
+/// with two elements, and returns a negative number if the first comes before
+/// the second, a positive number if it comes after, or `0` if either order will
+/// do. The sort is stable, so equal elements keep their relative order.
+pub fn sorted(
+    vm: &mut Vm,
+    mut list: Vec<Value>,
+    comparator: Varidic<Value>,
+) -> Result<Vec<Value>, RuntimeError> {
+    match comparator.iter().next() {
+        Some(comparator) => merge_sort(list, &mut |a, b| {
+            let args = vec![a.clone(), b.clone()];
+            match vm.call_function(comparator.clone(), args) {
+                Ok(Value::Float(order)) => Ok(order.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+                Ok(Value::Integer(order)) => Ok(order.cmp(&0)),
+                Ok(value) => {
+                    Err(format!("the comparator must return a number, found `{value}`").into())
+                }
+                Err(trace) => Err(trace.error),
+            }
+        }),
+        None => {
+            list.sort_by(Value::total_cmp);
+            Ok(list)
+        }
+    }
+}
+
+/// Sort `list` with a comparison that may fail.
+///
+/// Unlike `slice::sort_by`, a comparison that isn't a total order can't cause a
+/// panic, only an unspecified order.
+fn merge_sort<F>(mut list: Vec<Value>, compare: &mut F) -> Result<Vec<Value>, RuntimeError>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering, RuntimeError>,
+{
+    if list.len() <= 1 {
+        return Ok(list);
+    }
+
+    let right = merge_sort(list.split_off(list.len() / 2), compare)?;
+    let left = merge_sort(list, compare)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // take from the left on ties, to keep the sort stable
+        if compare(a, b)? == Ordering::Greater {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+
+    merged.extend(left);
+    merged.extend(right);
+
+    Ok(merged)
+}
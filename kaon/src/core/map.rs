@@ -22,6 +22,27 @@ fn is_empty(map: &mut Map) -> bool {
     map.is_empty()
 }
 
+/// The keys of `map`, in insertion order.
+pub fn keys(map: Map) -> Vec<Value> {
+    map.keys().map(|key| Value::from(key.as_str())).collect()
+}
+
+/// The values of `map`, in insertion order.
+pub fn values(map: Map) -> Vec<Value> {
+    map.values().cloned().collect()
+}
+
+/// Whether `map` has an entry for `key`.
+pub fn has(map: Map, key: ImmutableString) -> bool {
+    map.contains_key(key.as_str())
+}
+
+/// A copy of `map` without the entry for `key`.
+pub fn remove_key(mut map: Map, key: ImmutableString) -> Map {
+    map.remove(key.as_str());
+    map
+}
+
 pub fn make_class() -> Rc<Class> {
     let class = Class::new(Map::NAME);
 
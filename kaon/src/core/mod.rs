@@ -1,19 +1,23 @@
 //! The core library for the Kaon language
 
+mod assert;
+mod bytes;
+mod convert;
 mod float;
+mod format;
 mod io;
+pub mod iter;
+mod list;
 mod map;
 mod os;
+mod random;
 mod string;
+mod time;
 mod tuple;
 
-use crate::{common::{state::State, Class, ImmutableString}, Value};
+use crate::common::{state::State, Class};
 use std::rc::Rc;
 
-fn str(v: Value) -> ImmutableString {
-    ImmutableString::from(v.to_string())
-}
-
 pub fn prelude() -> State {
     let mut prelude = State::new();
 
@@ -22,9 +26,39 @@ pub fn prelude() -> State {
     prelude.add::<Rc<Class>>("System", io::make_class());
     prelude.add::<Rc<Class>>("Os", os::make_class());
     prelude.add::<Rc<Class>>("Map", map::make_class());
+    prelude.add::<Rc<Class>>("Bytes", bytes::make_class());
+    prelude.add::<Rc<Class>>("StopIteration", iter::stop_iteration());
 
     prelude.register_function("print", io::print);
-    prelude.register_function("str", str);
+    prelude.register_function("println", io::println);
+    prelude.register_function("format", format::format);
+    prelude.register_function("read_line", io::read_line);
+    prelude.register_function("input", io::input);
+    prelude.register_function("str", convert::to_string);
+    prelude.register_function("to_string", convert::to_string);
+    prelude.register_function("copy", convert::copy);
+    prelude.register_function("parse_number", convert::parse_number);
+    prelude.register_function("parse_bool", convert::parse_bool);
+    prelude.register_function("assert", assert::assert);
+    prelude.register_function("assert_eq", assert::assert_eq);
+    prelude.register_function("clock", time::clock);
+    prelude.register_function("now", time::now);
+    prelude.register_function("random", random::random);
+    prelude.register_function("random_int", random::random_int);
+    prelude.register_function("seed", random::seed);
+    prelude.register_function("read_bytes", io::read_bytes);
+    prelude.register_function("write_bytes", io::write_bytes);
+    prelude.register_function("keys", map::keys);
+    prelude.register_function("values", map::values);
+    prelude.register_function("has", map::has);
+    prelude.register_function("remove", map::remove_key);
+    prelude.register_function("unique", list::unique);
+    prelude.register_function("sum", list::sum);
+    prelude.register_function("min", list::min);
+    prelude.register_function("max", list::max);
+    prelude.register_function("sorted", list::sorted);
+    prelude.register_function("repeat", list::repeat);
+    prelude.register_function("join", list::join);
 
     prelude
 }
@@ -0,0 +1,30 @@
+use crate::runtime::Vm;
+
+/// A random float in the range `[0, 1)`.
+pub fn random(vm: &mut Vm) -> f64 {
+    vm.rng.next_f64()
+}
+
+/// A random integer in the inclusive range `[lo, hi]`.
+pub fn random_int(vm: &mut Vm, lo: f64, hi: f64) -> Result<f64, String> {
+    for bound in [lo, hi] {
+        if bound.fract() != 0.0 || !bound.is_finite() {
+            return Err(format!("expected an integer bound, found `{bound}`"));
+        }
+    }
+
+    if lo > hi {
+        return Err(format!("empty range: `{lo}` is greater than `{hi}`"));
+    }
+
+    Ok(vm.rng.next_in_range(lo as i64, hi as i64) as f64)
+}
+
+/// Seed the random number generator, making the numbers it produces
+/// reproducible.
+///
+/// The sequence for a seed is stable: after `seed(42)`, the first two calls to
+/// `random()` return `0.1941059175341826` and `0.5626318272656207`.
+pub fn seed(vm: &mut Vm, seed: f64) {
+    vm.seed_random(seed as i64 as u64);
+}
@@ -19,6 +19,10 @@ fn contains(str: &mut ImmutableString, other: String) -> bool {
     s.contains(&other)
 }
 
+fn bytes(str: &mut ImmutableString) -> Vec<u8> {
+    str.as_bytes().to_vec()
+}
+
 pub fn make_class() -> Rc<Class> {
     let class = Class::new("String");
 
@@ -27,6 +31,7 @@ pub fn make_class() -> Rc<Class> {
     class.register_method("len", len);
     class.register_method("is_empty", is_empty);
     class.register_method("contains", contains);
+    class.register_method("bytes", bytes);
 
     class
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+use crate::runtime::Vm;
+
+/// Seconds elapsed since an arbitrary, fixed point in time.
+pub fn clock(vm: &mut Vm) -> f64 {
+    vm.context.borrow().settings.clock.monotonic()
+}
+
+/// Seconds elapsed since the Unix epoch.
+pub fn now(vm: &mut Vm) -> f64 {
+    vm.context.borrow().settings.clock.unix()
+}
@@ -2,13 +2,15 @@
 
 pub mod diagnostic;
 pub mod renderer;
+pub mod runtime;
 pub mod syntax;
 
 use crate::error::renderer::Renderer;
 
 pub use crate::error::diagnostic::Diagnostic;
 pub use crate::error::diagnostic::{Label, LabelStyle, Severity};
-pub use crate::error::syntax::{Error, Item, Errors};
+pub use crate::error::runtime::RuntimeError;
+pub use crate::error::syntax::{Error, Item, Errors, Warning};
 use termcolor::{ColorChoice, StandardStream};
 
 /// A trait for emitting a [Diagnostic].
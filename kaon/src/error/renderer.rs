@@ -45,9 +45,13 @@ impl Styles {
     }
 }
 
+/// The number of columns a tab is expanded to by default.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
 pub struct Renderer<'writer> {
     pub writer: &'writer mut dyn WriteColor,
     styles: Styles,
+    tab_width: usize,
 }
 
 impl<'writer> Renderer<'writer> {
@@ -55,7 +59,35 @@ impl<'writer> Renderer<'writer> {
         Renderer {
             writer,
             styles: Styles::new(),
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    /// Set the width of a tab stop used when drawing source lines.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    /// Expand the tabs in `line` to the next tab stop.
+    pub fn expand_tabs(&self, line: &str) -> String {
+        let mut expanded = String::with_capacity(line.len());
+        for char in line.chars() {
+            if char == '\t' {
+                let width = self.tab_width - expanded.chars().count() % self.tab_width;
+                expanded.push_str(&" ".repeat(width));
+            } else {
+                expanded.push(char);
+            }
         }
+
+        expanded
+    }
+
+    /// The display column of the first `chars` characters of `line`, once tabs are expanded.
+    fn display_column(&self, line: &str, chars: usize) -> usize {
+        let prefix = line.chars().take(chars).collect::<String>();
+        self.expand_tabs(&prefix).chars().count()
     }
 
     pub fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
@@ -136,6 +168,7 @@ impl<'writer> Renderer<'writer> {
     pub fn render_span(
         &mut self,
         offset: usize,
+        width: usize,
         label: Label,
         caret: &str,
         severity: &Severity,
@@ -157,7 +190,7 @@ impl<'writer> Renderer<'writer> {
             self.writer,
             "{}{} {}",
             " ".repeat(offset),
-            caret.repeat(label.span.length),
+            caret.repeat(width),
             label.message
         )?;
 
@@ -250,7 +283,7 @@ impl<'writer> Renderer<'writer> {
                 self.render_line_number(*line + 1)?;
                 self.inner_gutter()?;
 
-                writeln!(self.writer, "{}", &lines[*line])?;
+                writeln!(self.writer, "{}", self.expand_tabs(&lines[*line]))?;
                 previous_line = current_line;
                 current_line = *line as isize;
             }
@@ -266,7 +299,20 @@ impl<'writer> Renderer<'writer> {
             self.render_padding(padding)?;
             self.inner_gutter()?;
 
-            self.render_span(label.1 .1, (*label_span).clone(), caret, severity)?;
+            // carets are placed by display column, so they still line up after tabs
+            // are expanded, and don't run past the end of the line
+            let text = &lines[*line];
+            let (_, col) = label.1;
+            let spanned = source[label_span.span.start..label_span.span.end()]
+                .split('\n')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .count();
+            let offset = self.display_column(text, col);
+            let width = self.display_column(text, col + spanned) - offset;
+
+            self.render_span(offset, width, (*label_span).clone(), caret, severity)?;
 
             writeln!(self.writer)?;
         }
@@ -287,7 +333,7 @@ impl<'writer> Renderer<'writer> {
 
         let location = format!(
             "{}:{}:{}",
-            &diagnostic.labels[0].span.source.path.to_string_lossy(),
+            &diagnostic.labels[0].span.source.name(),
             readable_start_line,
             readable_start_col,
         );
@@ -0,0 +1,175 @@
+//! Errors raised by the VM while running a script.
+
+use std::fmt::{self, Display};
+
+use crate::common::{Opcode, Span};
+
+/// The cause of a runtime error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// A value of the wrong type was used.
+    TypeMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    /// A global was read before it was defined.
+    UndefinedVariable { name: String, span: Span },
+    /// A function was called with the wrong number of arguments.
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A list was indexed outside of its bounds.
+    IndexOutOfBounds { index: f64, length: usize },
+    /// An integer was divided by zero.
+    DivisionByZero,
+    /// A script assertion did not hold.
+    AssertionFailed(String),
+    /// A debug hook paused the script.
+    Paused,
+    /// An instruction tried to pop from an empty stack, which only happens when
+    /// running malformed bytecode.
+    StackUnderflow { opcode: Opcode, offset: usize },
+    /// A byte that isn't a valid opcode was found where an instruction was
+    /// expected, which only happens when running malformed bytecode.
+    InvalidOpcode { byte: u8, offset: usize },
+    /// An operand was missing or referred to something that doesn't exist,
+    /// which only happens when running malformed bytecode.
+    InvalidOperand { opcode: Opcode, offset: usize },
+    /// Execution ran past the last instruction, which only happens when
+    /// running malformed bytecode.
+    UnexpectedEnd { offset: usize },
+    /// Any other error, described by a message.
+    Custom(String),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeMismatch {
+                expected, found, ..
+            } => write!(
+                f,
+                "mismatched types: expected `{expected}`, found `{found}`"
+            ),
+            Self::UndefinedVariable { name, .. } => write!(f, "cannot find `{name}`"),
+            Self::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(f, "`{name}` expected {expected} argument(s), found {found}"),
+            Self::IndexOutOfBounds { index, length } => write!(
+                f,
+                "index out of bounds: the length is {length} but the index is {index}"
+            ),
+            Self::DivisionByZero => write!(f, "attempt to divide by zero"),
+            Self::AssertionFailed(message) => write!(f, "assertion failed: {message}"),
+            Self::Paused => write!(f, "execution paused"),
+            Self::StackUnderflow { opcode, offset } => {
+                write!(f, "stack underflow in `{opcode:?}` at offset {offset}")
+            }
+            Self::InvalidOpcode { byte, offset } => {
+                write!(f, "invalid opcode {byte} at offset {offset}")
+            }
+            Self::InvalidOperand { opcode, offset } => {
+                write!(f, "invalid operand for `{opcode:?}` at offset {offset}")
+            }
+            Self::UnexpectedEnd { offset } => {
+                write!(f, "unexpected end of bytecode at offset {offset}")
+            }
+            Self::Custom(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// Attribute an error raised by a native function to the function `name`.
+    ///
+    /// Only plain messages are prefixed; structured errors are left as they are.
+    pub(crate) fn in_call_to(self, name: &str) -> Self {
+        match self {
+            Self::Custom(message) => Self::Custom(format!("in call to `{name}`: {message}")),
+            error => error,
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError::Custom(message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> Self {
+        RuntimeError::Custom(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RuntimeError;
+    use crate::common::{Opcode, Span};
+
+    #[test]
+    fn display() {
+        let errors = [
+            (
+                RuntimeError::TypeMismatch {
+                    expected: "f64".to_string(),
+                    found: "bool".to_string(),
+                    span: Span::empty(),
+                },
+                "mismatched types: expected `f64`, found `bool`",
+            ),
+            (
+                RuntimeError::UndefinedVariable {
+                    name: "x".to_string(),
+                    span: Span::empty(),
+                },
+                "cannot find `x`",
+            ),
+            (
+                RuntimeError::ArityMismatch {
+                    name: "add".to_string(),
+                    expected: 2,
+                    found: 3,
+                },
+                "`add` expected 2 argument(s), found 3",
+            ),
+            (
+                RuntimeError::IndexOutOfBounds {
+                    index: 4.0,
+                    length: 2,
+                },
+                "index out of bounds: the length is 2 but the index is 4",
+            ),
+            (RuntimeError::DivisionByZero, "attempt to divide by zero"),
+            (
+                RuntimeError::AssertionFailed("`1` != `2`".to_string()),
+                "assertion failed: `1` != `2`",
+            ),
+            (RuntimeError::Paused, "execution paused"),
+            (
+                RuntimeError::InvalidOperand {
+                    opcode: Opcode::Const,
+                    offset: 3,
+                },
+                "invalid operand for `Const` at offset 3",
+            ),
+            (
+                RuntimeError::UnexpectedEnd { offset: 5 },
+                "unexpected end of bytecode at offset 5",
+            ),
+            (RuntimeError::from("oops"), "oops"),
+        ];
+
+        for (error, message) in errors {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+}
@@ -30,6 +30,7 @@ pub enum Error {
     ExpectedToken(Item, Item),
     UnexpectedEOF(Item),
     ExpectedNewline(Item),
+    MissingInitializer(Item),
     // typechecker errors
     MismatchType(Item, Item),
     NotInScope(Item),
@@ -40,6 +41,14 @@ pub enum Error {
     UnresolvedIdentifier(Item),
     ExpectedFunction(Item),
     MismatchArgCount(Item, Item, Vec<Item>),
+    UseBeforeDeclaration(Item, Item),
+    AssignToConstant(Item, Item),
+    OutsideLoop(Item),
+    OutsideFunction(Item),
+    UnknownLabel(Item),
+    InvalidAssignment(Item),
+    UnreachableArm(Item, Item),
+    PatternArity(Item, usize, usize),
 }
 
 impl Error {
@@ -163,13 +172,13 @@ impl Error {
                 .with_help(vec![
                     "valid escape characters are \\\", \\\\, \\n, \\r, \\t and \\u{}".into(),
                 ]),
-            Error::InvalidUnicodeEscape(escape_char) => Diagnostic::error()
+            Error::InvalidUnicodeEscape(escape) => Diagnostic::error()
                 .with_code("E0010")
-                .with_message(&format!(
-                    "unknown escape character: `{}`",
-                    escape_char.content
-                ))
-                .with_labels(vec![Label::primary(escape_char.span.clone())]),
+                .with_message(&format!("invalid unicode escape: `{}`", escape.content))
+                .with_labels(vec![Label::primary(escape.span.clone())])
+                .with_help(vec![
+                    "a unicode escape is written as \\u{} around 1 to 6 hex digits".into(),
+                ]),
             Error::UnterminatedString(string) => Diagnostic::error()
                 .with_code("E0011")
                 .with_message("unterminated string")
@@ -200,6 +209,79 @@ impl Error {
                     ]
                     .concat(),
                 ),
+            Error::UseBeforeDeclaration(reference, declaration) => Diagnostic::error()
+                .with_code("E0015")
+                .with_message(&format!(
+                    "cannot use variable `{}` before it is declared",
+                    reference.content
+                ))
+                .with_labels(vec![
+                    Label::primary(reference.span.clone()).with_message("used here"),
+                    Label::secondary(declaration.span.clone())
+                        .with_message(&format!("`{}` declared here", declaration.content)),
+                ]),
+            Error::AssignToConstant(assignment, declaration) => Diagnostic::error()
+                .with_code("E0016")
+                .with_message(&format!(
+                    "cannot assign twice to constant `{}`",
+                    assignment.content
+                ))
+                .with_labels(vec![
+                    Label::primary(assignment.span.clone())
+                        .with_message("cannot assign to a constant"),
+                    Label::secondary(declaration.span.clone())
+                        .with_message(&format!("`{}` declared with `con` here", declaration.content)),
+                ]),
+            Error::OutsideLoop(stmt) => Diagnostic::error()
+                .with_code("E0017")
+                .with_message(&format!("`{}` outside of a loop", stmt.content))
+                .with_labels(vec![Label::primary(stmt.span.clone())
+                    .with_message(&format!("cannot `{}` outside of a loop", stmt.content))]),
+            Error::UnknownLabel(label) => Diagnostic::error()
+                .with_code("E0018")
+                .with_message(&format!("unknown loop label `{}`", label.content))
+                .with_labels(vec![Label::primary(label.span.clone())
+                    .with_message("not the label of an enclosing loop")]),
+            Error::InvalidAssignment(target) => Diagnostic::error()
+                .with_code("E0019")
+                .with_message("invalid assignment target")
+                .with_labels(vec![Label::primary(target.span.clone())
+                    .with_message("cannot assign to this expression")])
+                .with_help(vec![
+                    "only variables, elements and fields can be assigned to".to_string()
+                ]),
+            Error::UnreachableArm(arm, earlier) => Diagnostic::error()
+                .with_code("E0020")
+                .with_message("unreachable match arm")
+                .with_labels(vec![
+                    Label::primary(arm.span.clone()).with_message("this arm is never matched"),
+                    Label::secondary(earlier.span.clone())
+                        .with_message("its values are already matched here"),
+                ]),
+            Error::MissingInitializer(name) => Diagnostic::error()
+                .with_code("E0021")
+                .with_message(&format!("constant `{}` has no value", name.content))
+                .with_labels(vec![Label::primary(name.span.clone())
+                    .with_message("a constant must be given a value where it's declared")])
+                .with_help(vec![
+                    "use `var` for a variable that is given its value later".to_string()
+                ]),
+            Error::OutsideFunction(stmt) => Diagnostic::error()
+                .with_code("E0022")
+                .with_message(&format!("`{}` outside of a function", stmt.content))
+                .with_labels(vec![Label::primary(stmt.span.clone())
+                    .with_message(&format!("cannot `{}` outside of a function", stmt.content))]),
+            Error::PatternArity(pattern, expected, found) => Diagnostic::error()
+                .with_code("E0023")
+                .with_message(&format!(
+                    "this pattern binds {found} field(s), but `{}` has {expected}",
+                    pattern.content
+                ))
+                .with_labels(vec![Label::primary(pattern.span.clone())
+                    .with_message(&format!("expected {expected} binding(s)"))])
+                .with_help(vec![
+                    "leave out the parentheses to match the variant without its fields".to_string()
+                ]),
         }
     }
 }
@@ -213,6 +295,78 @@ impl Display for Error {
     }
 }
 
+/// A non-fatal diagnostic.
+#[derive(Debug, Clone)]
+pub enum Warning {
+    UnusedVariable(Item),
+    UnreachableCode(Item),
+    ShadowedVariable(Item, Item),
+    /// A `match` with no arm for some values, listing the missing cases if they're known.
+    NonExhaustiveMatch(Item, Vec<String>),
+}
+
+impl Warning {
+    pub fn report(&self) -> Diagnostic {
+        match self {
+            Warning::UnusedVariable(ident) => Diagnostic::warning()
+                .with_code("W0001")
+                .with_message(&format!("unused variable `{}`", ident.content))
+                .with_labels(vec![Label::primary(ident.span.clone()).with_message(&format!(
+                    "help: if this is intentional, prefix it with an underscore: `_{}`",
+                    ident.content
+                ))]),
+            Warning::UnreachableCode(stmt) => Diagnostic::warning()
+                .with_code("W0002")
+                .with_message("unreachable statement")
+                .with_labels(vec![Label::primary(stmt.span.clone())
+                    .with_message("unreachable statement")])
+                .with_help(vec![format!(
+                    "any code following `{}` will never be run",
+                    stmt.content
+                )]),
+            Warning::ShadowedVariable(shadow, original) => Diagnostic::warning()
+                .with_code("W0003")
+                .with_message(&format!("variable `{}` shadows an outer binding", shadow.content))
+                .with_labels(vec![
+                    Label::primary(shadow.span.clone()).with_message("declared here"),
+                    Label::secondary(original.span.clone())
+                        .with_message(&format!("`{}` originally declared here", original.content)),
+                ]),
+            Warning::NonExhaustiveMatch(expr, missing) if missing.is_empty() => {
+                Diagnostic::warning()
+                    .with_code("W0004")
+                    .with_message("non-exhaustive match")
+                    .with_labels(vec![Label::primary(expr.span.clone())
+                        .with_message("values not matched by any arm are ignored")])
+                    .with_help(vec!["add a `_` arm to match any other value".to_string()])
+            }
+            Warning::NonExhaustiveMatch(expr, missing) => {
+                let missing = missing
+                    .iter()
+                    .map(|case| format!("`{case}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                Diagnostic::warning()
+                    .with_code("W0004")
+                    .with_message("non-exhaustive match")
+                    .with_labels(vec![Label::primary(expr.span.clone())
+                        .with_message(&format!("{missing} not matched"))])
+                    .with_help(vec!["add an arm for each missing case, or a `_` arm".to_string()])
+            }
+        }
+    }
+}
+
+impl Emitter for Warning {}
+
+impl Display for Warning {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.emit(&[self.report()]);
+        Ok(())
+    }
+}
+
 /// A wrapper around multiple errors.
 pub struct Errors(pub Vec<Diagnostic>);
 
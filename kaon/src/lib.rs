@@ -14,8 +14,8 @@ extern crate fnv;
 
 use common::{Function, KaonFile, Spanned, state::State};
 use compiler::{Resolver, Token, TypeChecker, AST};
-use error::{Error, Errors};
-use runtime::{Vm, VmSettings};
+use error::{Error, Errors, Warning};
+use runtime::{Clock, Trace, Vm, VmSettings};
 
 use std::{fmt, fmt::Debug, fmt::Display, path::PathBuf, rc::Rc};
 
@@ -26,7 +26,7 @@ pub type Result<T> = std::result::Result<T, KaonError>;
 pub enum KaonError {
     ParserError(Error),
     CompilerError(String),
-    RuntimeError(String),
+    RuntimeError(Trace),
     InvalidScriptPath(String),
     MultipleErrors(Errors),
 }
@@ -63,6 +63,7 @@ pub struct KaonSettings {
     pub stdin: Rc<dyn KaonFile>,
     pub stdout: Rc<dyn KaonFile>,
     pub stderr: Rc<dyn KaonFile>,
+    pub clock: Rc<dyn Clock>,
 }
 
 impl Default for KaonSettings {
@@ -72,6 +73,7 @@ impl Default for KaonSettings {
             stdin: settings.stdin,
             stdout: settings.stdout,
             stderr: settings.stderr,
+            clock: settings.clock,
         }
     }
 }
@@ -98,6 +100,7 @@ pub struct Kaon {
     pub vm: Vm,
     pub state: State,
     chunk: Function,
+    warnings: Vec<Warning>,
 }
 
 impl Default for Kaon {
@@ -117,9 +120,11 @@ impl Kaon {
                 stdin: settings.stdin,
                 stdout: settings.stdout,
                 stderr: settings.stderr,
+                clock: settings.clock,
             }),
             state: State::new(),
             chunk: Function::script(),
+            warnings: Vec::new(),
         }
     }
 
@@ -158,8 +163,11 @@ impl Kaon {
         let ast = self.parse(tokens)?;
 
         let mut resolver = Resolver::with_scope(scope);
+        resolver.register_natives(&self.vm.context.borrow().globals);
         resolver.resolve_ast(&ast);
 
+        self.warnings = std::mem::take(&mut resolver.warnings);
+
         let globals = resolver.global_scope();
 
         if !resolver.errors.is_empty() {
@@ -202,8 +210,11 @@ impl Kaon {
         scope: &mut Scope,
     ) -> Result<(Function, Scope)> {
         let mut resolver = Resolver::with_scope(scope);
+        resolver.register_natives(&self.vm.context.borrow().globals);
         resolver.resolve_ast(&ast);
 
+        self.warnings = std::mem::take(&mut resolver.warnings);
+
         let globals = resolver.global_scope();
 
         if !resolver.errors.is_empty() {
@@ -265,12 +276,15 @@ impl Kaon {
     }
 
     /// Parse a stream of [Token]s into an [AST].
+    ///
+    /// A single syntax error is returned as [KaonError::ParserError], and several
+    /// as [KaonError::MultipleErrors].
     pub fn parse(&self, tokens: Spanned<Vec<Token>>) -> Result<AST> {
         let mut parser = compiler::Parser::new(tokens);
-        let ast = parser.parse();
-        match ast {
+        match parser.parse() {
             Ok(ast) => Ok(ast),
-            Err(err) => Err(KaonError::ParserError(err)),
+            Err(mut errors) if errors.len() == 1 => Err(KaonError::ParserError(errors.remove(0))),
+            Err(errors) => Err(KaonError::MultipleErrors(Errors::from(errors))),
         }
     }
 
@@ -289,6 +303,8 @@ impl Kaon {
         let mut resolver = Resolver::default();
         resolver.resolve_ast(ast);
 
+        self.warnings = std::mem::take(&mut resolver.warnings);
+
         if !resolver.errors.is_empty() {
             return Err(KaonError::MultipleErrors(Errors::from(resolver.errors)));
         }
@@ -303,6 +319,11 @@ impl Kaon {
         Ok(resolver.global_scope())
     }
 
+    /// The warnings generated while compiling the last script.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     /// Read a file from provided path.
     pub fn read_file(&self, path: PathBuf) -> Result<Rc<Source>> {
         Source::from_file(path.to_str().unwrap())
@@ -0,0 +1,45 @@
+//! Time sources for the `clock` and `now` builtins.
+//!
+//! The [Vm] reads the time through a [Clock] trait object in its settings, so
+//! embedders and tests can swap in a fake clock.
+//!
+//! [Vm]: crate::runtime::Vm
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A source of time.
+pub trait Clock {
+    /// Seconds elapsed since an arbitrary, fixed point in time.
+    ///
+    /// This never goes backwards, so it is suitable for measuring durations.
+    fn monotonic(&self) -> f64;
+
+    /// Seconds elapsed since the Unix epoch.
+    fn unix(&self) -> f64;
+}
+
+/// The system clock.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn monotonic(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn unix(&self) -> f64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs_f64(),
+            Err(err) => -err.duration().as_secs_f64(),
+        }
+    }
+}
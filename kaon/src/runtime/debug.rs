@@ -0,0 +1,54 @@
+//! Hooks for stepping through a script instruction by instruction.
+//!
+//! A hook registered with [Vm::set_debug_hook](crate::runtime::Vm::set_debug_hook)
+//! is called before each instruction with a [DebugState], and decides how
+//! execution carries on by returning a [DebugAction].
+
+use crate::common::{Opcode, Span, Value};
+use crate::runtime::Frame;
+
+/// A callback run before each instruction.
+pub type DebugHook = Box<dyn FnMut(&DebugState) -> DebugAction>;
+
+/// What the VM should do after a debug hook returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Run the instruction, then call the hook again before the next one.
+    Step,
+    /// Run the rest of the script without calling the hook.
+    Continue,
+    /// Stop before running the instruction.
+    ///
+    /// The VM returns a [RuntimeError::Paused](crate::error::RuntimeError::Paused)
+    /// error, and execution can be picked up again with [Vm::resume](crate::runtime::Vm::resume).
+    Pause,
+}
+
+/// A snapshot of the VM, passed to a debug hook.
+pub struct DebugState<'a> {
+    /// The instruction about to be run.
+    pub opcode: Opcode,
+    /// The offset of the instruction in the current function's chunk.
+    pub offset: usize,
+    /// The operand stack, with the top of the stack last.
+    pub stack: &'a [Value],
+    /// The call stack, with the current frame last.
+    pub frames: &'a [Frame],
+}
+
+impl DebugState<'_> {
+    /// The frame of the function being run.
+    pub fn frame(&self) -> &Frame {
+        self.frames.last().expect("a script is running")
+    }
+
+    /// The span of the instruction about to be run, if it has debug info.
+    pub fn span(&self) -> Option<Span> {
+        self.frame()
+            .closure
+            .function
+            .chunk
+            .debug_info
+            .span_at(self.offset)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::common::{KaonFile, KaonRead, KaonWrite};
+use std::{
+    fmt,
+    fmt::Debug,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+/// A file on disk.
+pub struct KaonFsFile {
+    path: PathBuf,
+}
+
+impl KaonFsFile {
+    /// Open an existing file.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Self {
+        KaonFsFile { path: path.into() }
+    }
+
+    /// Create a file, truncating it if it already exists.
+    pub fn create<P: Into<PathBuf>>(path: P) -> Result<Self, String> {
+        let file = KaonFsFile::open(path);
+        fs::write(&file.path, []).map_err(|err| file.error(err))?;
+
+        Ok(file)
+    }
+
+    fn error(&self, err: std::io::Error) -> String {
+        format!("{}: {err}", self.path.display())
+    }
+}
+
+impl KaonFile for KaonFsFile {
+    fn path(&self) -> Result<String, String> {
+        Ok(self.path.to_string_lossy().to_string())
+    }
+}
+
+impl KaonRead for KaonFsFile {
+    fn read_to_string(&self) -> Result<String, String> {
+        fs::read_to_string(&self.path).map_err(|err| self.error(err))
+    }
+
+    fn read_bytes(&self) -> Result<Vec<u8>, String> {
+        fs::read(&self.path).map_err(|err| self.error(err))
+    }
+}
+
+impl KaonWrite for KaonFsFile {
+    fn write(&self, bytes: &[u8]) -> Result<(), String> {
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(bytes))
+            .map_err(|err| self.error(err))
+    }
+
+    fn writeln(&self, line: &str) -> Result<(), String> {
+        self.write(format!("{line}\n").as_bytes())
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Debug for KaonFsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KaonFsFile")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Display for KaonFsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
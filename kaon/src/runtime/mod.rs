@@ -3,11 +3,18 @@
 //! The [Vm] (_virtual machine_) is stack-based; values are pushed and 
 //! popped from a stack. It runs the bytecode generated by the compiler.
 
+pub mod clock;
+pub mod debug;
+pub mod fs;
+pub mod random;
 pub mod stack;
 pub mod stdio;
 pub mod trace;
 pub mod vm;
 
+pub use clock::{Clock, SystemClock};
+pub use debug::{DebugAction, DebugHook, DebugState};
+pub use fs::KaonFsFile;
 pub use stack::{Frame, Stack};
 pub use stdio::{KaonStderr, KaonStdin, KaonStdout};
 pub use trace::Trace;
@@ -0,0 +1,64 @@
+//! The pseudo-random number generator behind the `random` builtins.
+//!
+//! This is a xorshift64* generator. It is small and fast, but it is not
+//! cryptographically secure.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A xorshift64* pseudo-random number generator.
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    /// Create a generator, seeded so that the same seed always produces the
+    /// same sequence.
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a zero state, so scramble the seed with a
+        // step of splitmix64 first; this maps every seed to a non-zero state.
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+
+        Xorshift {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// The next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A float in the range `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An integer in the inclusive range `[lo, hi]`.
+    pub fn next_in_range(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = hi.wrapping_sub(lo) as u64;
+
+        match span.checked_add(1) {
+            Some(span) => lo.wrapping_add((self.next_u64() % span) as i64),
+            None => self.next_u64() as i64,
+        }
+    }
+}
+
+impl Default for Xorshift {
+    /// A generator seeded from the system time.
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Xorshift::new(seed)
+    }
+}
@@ -2,9 +2,12 @@ use std::rc::Rc;
 
 use crate::common::{Closure, Value};
 
+/// A call frame.
 #[derive(Clone, Debug)]
 pub struct Frame {
     pub closure: Rc<Closure>,
+    /// The offset of the next instruction. Stack traces use this to find the
+    /// span of the instruction the frame is executing.
     pub ip: usize,
     pub base_ip: usize,
 }
@@ -29,10 +32,10 @@ impl Stack {
         Stack { stack: vec![] }
     }
 
-    /// Pop a [Value] from the stack.
+    /// Pop a [Value] from the stack, or `None` if the stack is empty.
     #[inline]
-    pub fn pop(&mut self) -> Value {
-        self.stack.pop().expect("Stack should not be empty")
+    pub fn pop(&mut self) -> Option<Value> {
+        self.stack.pop()
     }
 
     /// Push a [Value] from the stack.
@@ -41,10 +44,10 @@ impl Stack {
         self.stack.push(value);
     }
 
-    /// Lookup a [Value] on the stack.
+    /// Look at the [Value] on top of the stack, without cloning it.
     #[inline]
-    pub fn peek(&self) -> Value {
-        self.stack[self.stack.len() - 1].clone()
+    pub fn peek(&self) -> &Value {
+        &self.stack[self.stack.len() - 1]
     }
 
     #[inline]
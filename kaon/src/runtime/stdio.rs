@@ -59,8 +59,11 @@ impl KaonWrite for KaonStdin {}
 impl KaonRead for KaonStdin {
     fn read_line(&self) -> Result<Option<String>, String> {
         let mut result = String::new();
-        io::stdin().read_line(&mut result).unwrap();
-        Ok(Some(result))
+        match io::stdin().read_line(&mut result) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(result)),
+            Err(err) => Err(err.to_string()),
+        }
     }
 
     fn read_to_string(&self) -> Result<String, String> {
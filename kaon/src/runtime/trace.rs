@@ -1,57 +1,93 @@
 use std::fmt::{self, Display};
 
-use crate::{common::Span, runtime::Frame};
+use crate::{common::Span, error::RuntimeError, runtime::Frame};
 
 /// The stack trace.
 ///
 /// Used for post-mortem debugging.
+#[derive(Debug)]
 pub struct Trace {
-    /// the error message
-    pub error: String,
+    /// the cause of the error
+    pub error: RuntimeError,
     /// the call stack
     pub frames: Vec<Frame>,
 }
 
 impl Trace {
-    pub fn new(error: &str, frames: Vec<Frame>) -> Self {
+    pub fn new<E: Into<RuntimeError>>(error: E, frames: Vec<Frame>) -> Self {
         Trace {
-            error: error.to_string(),
+            error: error.into(),
             frames,
         }
     }
 }
 
+impl Trace {
+    /// Lookup the [Span] of the instruction a frame was executing.
+    fn frame_span(frame: &Frame) -> Option<Span> {
+        frame
+            .closure
+            .function
+            .chunk
+            .debug_info
+            .span_at(frame.ip.saturating_sub(1))
+    }
+
+    /// Render the offending line of source, with a caret underline.
+    fn snippet(f: &mut fmt::Formatter<'_>, span: &Span) -> fmt::Result {
+        let source = &span.source.as_ref().contents;
+
+        let (line, col) = Span::line_index(source, span.start);
+        let text = Span::lines(source)[line].clone();
+
+        let line_number = (line + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let width = text.chars().count().saturating_sub(col).min(span.length).max(1);
+
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter,
+            span.source.name(),
+            line_number,
+            col + 1
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_number, text)?;
+        writeln!(f, "{} | {}{}", gutter, " ".repeat(col), "^".repeat(width))
+    }
+}
+
 impl Display for Trace {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "{}", self.error)?;
 
-        for (_, frame) in &mut self.frames.iter().rev().enumerate() {
-            let span = frame
-                .closure
-                .function
-                .chunk
-                .debug_info
-                .source_map
-                .last()
-                .unwrap()
-                .1
-                .clone();
-
-            let source = &span.source.as_ref().contents;
-
-            let (start_line, start_col) = Span::line_index(source, span.start);
-            let readable_start_line = (start_line + 1).to_string();
-            let readable_start_col = (start_col + 1).to_string();
-
-            writeln!(
-                f,
-                "{} in {} ({}:{}:{})",
-                " ".repeat(3),
-                frame.closure.function.name,
-                span.source.path.to_string_lossy(),
-                readable_start_line,
-                readable_start_col
-            )?;
+        if let Some(span) = self.frames.last().and_then(Trace::frame_span) {
+            Trace::snippet(f, &span)?;
+        }
+
+        for frame in self.frames.iter().rev() {
+            match Trace::frame_span(frame) {
+                Some(span) => {
+                    let source = &span.source.as_ref().contents;
+
+                    let (start_line, start_col) = Span::line_index(source, span.start);
+                    let readable_start_line = (start_line + 1).to_string();
+                    let readable_start_col = (start_col + 1).to_string();
+
+                    writeln!(
+                        f,
+                        "{} in {} ({}:{}:{})",
+                        " ".repeat(3),
+                        frame.closure.function.name,
+                        span.source.name(),
+                        readable_start_line,
+                        readable_start_col
+                    )?;
+                }
+                None => writeln!(f, "{} in {}", " ".repeat(3), frame.closure.function.name)?,
+            }
         }
 
         Ok(())
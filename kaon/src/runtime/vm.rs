@@ -1,24 +1,31 @@
-use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::ops::Range;
 use std::rc::Rc;
-use std::u8;
+use std::time::{Duration, Instant};
 
 use fnv::FnvHashMap;
 
 use crate::common::state::State;
-use crate::common::value::{CallableFunction, ToValue, ValueList, ValueTuple};
+use crate::common::value::{
+    CallableFunction, RegisterFunction, ToValue, ValueList, ValueTuple,
+};
 use crate::common::{
-    BoundMethod, Captured, Class, Closure, Constructor, Function, ImmutableString, Instance,
-    KaonFile, Map, Named, NativeFun, Opcode, Upvalue, Value,
+    BoundMethod, Captured, Chunk, Class, Closure, Constructor, External, Function, ImmutableString,
+    Instance, KaonFile, Map, Named, NativeFun, Opcode, Span, Upvalue, UpvalueLocation, Value,
 };
 use crate::core::{self};
-use crate::runtime::{Frame, KaonStderr, KaonStdin, KaonStdout, Stack, Trace};
+use crate::error::RuntimeError;
+use crate::runtime::random::Xorshift;
+use crate::runtime::{
+    Clock, DebugAction, DebugHook, DebugState, Frame, KaonStderr, KaonStdin, KaonStdout, Stack,
+    SystemClock, Trace,
+};
 
 pub struct VmSettings {
     pub stdout: Rc<dyn KaonFile>,
     pub stdin: Rc<dyn KaonFile>,
     pub stderr: Rc<dyn KaonFile>,
+    pub clock: Rc<dyn Clock>,
 }
 
 impl Default for VmSettings {
@@ -27,6 +34,7 @@ impl Default for VmSettings {
             stdout: Rc::new(KaonStdout::default()),
             stdin: Rc::new(KaonStdin::default()),
             stderr: Rc::new(KaonStderr::default()),
+            clock: Rc::new(SystemClock::default()),
         }
     }
 }
@@ -55,6 +63,61 @@ impl Default for VmContext {
     }
 }
 
+/// How many instructions are executed between checks of the wall-clock timeout.
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// Limits on how much work a script may do before it's stopped.
+#[derive(Default)]
+struct Limits {
+    instruction_limit: Option<u64>,
+    timeout: Option<Duration>,
+    /// the number of instructions executed since the script was started
+    executed: u64,
+    deadline: Option<Instant>,
+    /// whether a limit has been hit, so the error can't be caught by the script
+    exceeded: bool,
+}
+
+impl Limits {
+    /// Reset the counters at the start of a run.
+    fn start(&mut self) {
+        self.executed = 0;
+        self.exceeded = false;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Account for a single instruction, failing if a limit has been reached.
+    fn tick(&mut self) -> Result<(), &'static str> {
+        self.executed += 1;
+
+        if let Some(limit) = self.instruction_limit {
+            if self.executed > limit {
+                self.exceeded = true;
+                return Err("instruction limit exceeded");
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.executed.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && Instant::now() >= deadline {
+                self.exceeded = true;
+                return Err("time limit exceeded");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An error handler installed by a `try` block.
+struct Handler {
+    /// the number of frames on the call stack when the handler was installed
+    frame_count: usize,
+    /// the height of the stack when the handler was installed
+    stack_len: usize,
+    /// the offset of the catch block
+    ip: usize,
+}
+
 /// The Kaon VM.
 pub struct Vm {
     /// the operand stack
@@ -65,7 +128,23 @@ pub struct Vm {
     pub context: Rc<RefCell<VmContext>>,
     /// the number of frames on the call stack
     frame_count: usize,
-    open_upvalues: Option<Upvalue>,
+    /// upvalues pointing at variables that are still on the stack
+    open_upvalues: Vec<Upvalue>,
+    limits: Limits,
+    /// whether any limits are set, so the main loop only pays for a bool check
+    limited: bool,
+    /// the error handlers of the `try` blocks being executed
+    handlers: Vec<Handler>,
+    /// the generator behind the `random` builtins
+    pub(crate) rng: Xorshift,
+    debug_hook: Option<DebugHook>,
+    /// whether the debug hook should be called before the next instruction
+    debugging: bool,
+    /// whether execution is picking up from a pause, so the paused
+    /// instruction runs without calling the hook again
+    resuming: bool,
+    /// the instruction being executed and its offset, for reporting malformed bytecode
+    instruction: (Opcode, usize),
 }
 
 impl Default for Vm {
@@ -81,7 +160,15 @@ impl Vm {
             stack: Stack::new(),
             context: Rc::new(RefCell::new(VmContext::default())),
             frame_count: 0,
-            open_upvalues: None,
+            open_upvalues: Vec::new(),
+            limits: Limits::default(),
+            limited: false,
+            handlers: Vec::new(),
+            rng: Xorshift::default(),
+            debug_hook: None,
+            debugging: false,
+            resuming: false,
+            instruction: (Opcode::Halt, 0),
         }
     }
 
@@ -93,38 +180,372 @@ impl Vm {
     }
 
     /// Clear the VM's state.
+    ///
+    /// This drops the stack, call frames, open upvalues and error handlers
+    /// left behind by a run, but keeps any globals.
     pub fn clear(&mut self) {
         self.frames.clear();
         self.stack.clear();
         self.frame_count = 0;
-        self.open_upvalues = None;
+        self.open_upvalues.clear();
+        self.handlers.clear();
+    }
+
+    /// Reset the VM to the state it was created in.
+    ///
+    /// As well as everything removed by [Vm::clear], this forgets every global,
+    /// including native functions added with [Vm::register_function]. The
+    /// settings, limits and random number generator are kept.
+    pub fn reset(&mut self) {
+        self.clear();
+        self.context.borrow_mut().globals.clear();
+    }
+
+    /// Limit the number of instructions a script may execute.
+    ///
+    /// Once the limit is reached the script is stopped with an
+    /// "instruction limit exceeded" runtime error.
+    pub fn set_instruction_limit(&mut self, limit: u64) {
+        self.limits.instruction_limit = Some(limit);
+        self.limited = true;
+    }
+
+    /// Limit the wall-clock time a script may run for.
+    ///
+    /// The clock is only checked periodically, so a script may slightly
+    /// overrun the timeout before it's stopped with a "time limit exceeded" error.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.limits.timeout = Some(timeout);
+        self.limited = true;
+    }
+
+    /// Seed the random number generator, making the numbers it produces
+    /// reproducible.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Xorshift::new(seed);
+    }
+
+    /// Remove any instruction or time limits.
+    pub fn clear_limits(&mut self) {
+        self.limits = Limits::default();
+        self.limited = false;
+    }
+
+    /// Call `hook` before each instruction.
+    ///
+    /// The hook decides whether to step to the next instruction, continue
+    /// without the hook, or pause. When no hook is set the VM doesn't pay for
+    /// any of this beyond a single check per instruction.
+    pub fn set_debug_hook(&mut self, hook: DebugHook) {
+        self.debug_hook = Some(hook);
+    }
+
+    /// Remove the debug hook.
+    pub fn clear_debug_hook(&mut self) {
+        self.debug_hook = None;
+        self.debugging = false;
+    }
+
+    /// Pick up a script paused by a debug hook where it left off.
+    ///
+    /// The paused instruction is run without calling the hook again.
+    pub fn resume(&mut self) -> Result<Value, Trace> {
+        self.debugging = self.debug_hook.is_some();
+        self.resuming = true;
+
+        self.run()
+    }
+
+    /// Register a native function as a global.
+    ///
+    /// Arguments are converted with [FromValue](crate::common::value::FromValue),
+    /// and the call fails with a runtime error if the number or types of
+    /// arguments don't match the function signature.
+    ///
+    /// ```
+    /// # use kaon::runtime::Vm;
+    /// let mut vm = Vm::new();
+    /// vm.register_function("add", |a: f64, b: f64| a + b);
+    /// ```
+    pub fn register_function<A, R, F: RegisterFunction<A, R> + Copy>(
+        &mut self,
+        name: &str,
+        fun: F,
+    ) {
+        let fun = NativeFun::new(name, fun.arity(), fun.to_native_function(), fun.is_varidic());
+
+        self.context
+            .borrow_mut()
+            .globals
+            .insert(name.to_string(), Value::NativeFun(Rc::new(fun)));
+    }
+
+    /// Set a global to a value provided by the host, replacing any existing global
+    /// with the same name.
+    ///
+    /// Scripts read the value like any other global. The compiler only knows about
+    /// the global if its name is in the [Scope](crate::Scope) the script is compiled
+    /// with, and a script that declares a global with that name again is rejected
+    /// with a duplicate identifier error. A script compiled without the name in
+    /// scope may still declare it, in which case the declaration overwrites the
+    /// injected value when it runs.
+    ///
+    /// ```
+    /// # use kaon::{common::Value, runtime::Vm};
+    /// let mut vm = Vm::new();
+    /// vm.set_global("answer", Value::Float(42.0));
+    /// ```
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.context
+            .borrow_mut()
+            .globals
+            .insert(name.to_string(), value);
+    }
+
+    /// Get the value of a global, whether it was defined by a script or set by
+    /// the host, or `None` if there's no global with that name.
+    ///
+    /// ```
+    /// # use kaon::{common::Value, runtime::Vm};
+    /// let mut vm = Vm::new();
+    /// vm.set_global("answer", Value::Float(42.0));
+    /// assert_eq!(vm.get_global("answer"), Some(Value::Float(42.0)));
+    /// assert_eq!(vm.get_global("question"), None);
+    /// ```
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.context.borrow().globals.get(name).cloned()
+    }
+
+    /// Call `visit` with the name and value of each global the VM holds, sorted
+    /// by name.
+    ///
+    /// Builtins from the prelude aren't included. The globals are borrowed while
+    /// `visit` runs, so it must not run code on the VM.
+    ///
+    /// ```
+    /// # use kaon::{common::Value, runtime::Vm};
+    /// let mut vm = Vm::new();
+    /// vm.set_global("answer", Value::Float(42.0));
+    ///
+    /// let mut names = Vec::new();
+    /// vm.visit_globals(|name, _| names.push(name.to_string()));
+    /// assert_eq!(names, ["answer"]);
+    /// ```
+    pub fn visit_globals(&self, mut visit: impl FnMut(&str, &Value)) {
+        let context = self.context.borrow();
+
+        let mut globals: Vec<(&String, &Value)> = context.globals.iter().collect();
+        globals.sort_by_key(|(name, _)| *name);
+
+        for (name, value) in globals {
+            visit(name, value);
+        }
+    }
+
+    /// Convert a value to the string printed for it.
+    ///
+    /// This is the value's [Display](std::fmt::Display) form, except that an
+    /// [External] with a `to_string` method is rendered by calling that method.
+    /// Values nested inside lists, tuples and maps are displayed as usual.
+    pub fn display_value(&mut self, value: &Value) -> Result<String, RuntimeError> {
+        let method = match value {
+            Value::External(external) => external.method("to_string"),
+            _ => None,
+        };
+
+        match method {
+            Some(method) => match method.call(self, vec![value.clone()])? {
+                Value::String(string) => Ok(string.to_string()),
+                found => Err(format!("`to_string` must return a string, found `{found}`").into()),
+            },
+            None => Ok(value.to_string()),
+        }
     }
 
     /// Run a chunk of bytecode.
-    pub fn execute(&mut self, fun: Rc<Function>) -> Result<Value, String> {
+    ///
+    /// A VM can execute any number of chunks. Globals defined by one chunk
+    /// are visible to the chunks executed after it, until the VM is [reset](Vm::reset).
+    /// The stack may be left in an arbitrary state if a chunk fails, so
+    /// [Vm::clear] should be called between runs.
+    pub fn execute(&mut self, fun: Rc<Function>) -> Result<Value, Trace> {
+        self.limits.start();
+        self.debugging = self.debug_hook.is_some();
+        self.resuming = false;
+
         self.frames
             .push(Frame::new(Rc::new(Closure::wrap(fun.clone())), 0, 1));
         self.frame_count += 1;
 
         self.stack.push(Value::Function(fun));
 
-        match self.run() {
-            Ok(result) => Ok(result),
-            Err(traceback) => Err(traceback.to_string()),
+        self.run()
+    }
+
+    /// Run a chunk of bytecode as a script, returning its result.
+    ///
+    /// The result is the value left on top of the stack by the chunk, or
+    /// otherwise the value of its last expression statement, or `()` if it has
+    /// none. Any state left behind by a previous run is [cleared](Vm::clear) first.
+    pub fn eval(&mut self, chunk: Chunk) -> Result<Value, Trace> {
+        self.clear();
+
+        let script = Function::new("script".to_string(), 0, chunk, vec![]);
+        let result = self.execute(Rc::new(script))?;
+
+        // the script itself occupies the bottom slot of the stack
+        if self.stack.len() > 1 {
+            return Ok(self.stack.peek().clone());
         }
+
+        Ok(result)
     }
 
     /// Build a number from the bytecode stream.
-    fn next_number(&self) -> usize {
-        self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .opcodes[self.frames[self.frame_count - 1].ip] as usize
+    fn next_number(&self) -> Result<usize, Trace> {
+        self.chunk()
+            .opcodes
+            .get(self.frames[self.frame_count - 1].ip)
+            .map(|byte| *byte as usize)
+            .ok_or_else(|| self.invalid_operand())
+    }
+
+    /// The chunk of the function running in the current frame.
+    #[inline]
+    fn chunk(&self) -> &Chunk {
+        &self.frames[self.frame_count - 1].closure.function.chunk
+    }
+
+    /// Call a global function by name from Rust.
+    ///
+    /// The function is run until it returns, and its return value is handed back.
+    /// Fails if the global doesn't exist, isn't callable, or is called with the
+    /// wrong number of arguments.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Trace> {
+        let fun = {
+            let context = self.context.as_ref().borrow();
+            match context.globals.get(name) {
+                Some(value) => value.clone(),
+                None => context.prelude.get::<Value>(name).ok_or_else(|| {
+                    Trace::new(
+                        RuntimeError::UndefinedVariable {
+                            name: name.to_string(),
+                            span: Span::empty(),
+                        },
+                        vec![],
+                    )
+                })?,
+            }
+        };
+
+        self.call_named(name, fun, args)
+    }
+
+    /// Call a function value from Rust, such as a closure passed to a native
+    /// function.
+    ///
+    /// Behaves like [Vm::call], but is given the function itself rather than
+    /// the name of a global.
+    pub fn call_function(&mut self, fun: Value, args: Vec<Value>) -> Result<Value, Trace> {
+        let name = match &fun {
+            Value::Closure(closure) => closure.function.name.to_string(),
+            Value::Function(fun) => fun.name.to_string(),
+            Value::NativeFun(fun) => fun.name.to_string(),
+            value => value.to_string(),
+        };
+
+        self.call_named(&name, fun, args)
+    }
+
+    /// Call `fun`, naming it `name` in any error.
+    fn call_named(&mut self, name: &str, fun: Value, args: Vec<Value>) -> Result<Value, Trace> {
+        if !fun.is_callable() {
+            return Err(Trace::new(format!("`{name}` is not a function"), vec![]));
+        }
+
+        let arity = args.len();
+        let depth = self.frame_count;
+        let stack_len = self.stack.len();
+
+        if depth == 0 {
+            self.limits.start();
+        }
+
+        // arguments are pushed in reverse, so the first one ends up on top
+        self.stack.push(fun.clone());
+        for arg in args.into_iter().rev() {
+            self.stack.push(arg);
+        }
+
+        // natives leave their result on the stack, while functions push a frame
+        // that has to run before their result is there
+        let result = match self.call_value(fun, arity) {
+            Ok(()) if self.frame_count > depth => self.run_until(depth),
+            Ok(()) => self.pop(),
+            Err(trace) => Err(trace),
+        };
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.frame_count = depth;
+            self.stack.truncate(stack_len);
+        }
+
+        result
     }
 
     /// The main VM loop.
     pub fn run(&mut self) -> Result<Value, Trace> {
+        self.run_until(0)
+    }
+
+    /// Run until the call stack unwinds to `depth` frames, or the script halts.
+    ///
+    /// Errors are handed to the innermost `try` block entered since `depth`, and
+    /// propagate to the caller if there isn't one.
+    fn run_until(&mut self, depth: usize) -> Result<Value, Trace> {
+        loop {
+            match self.dispatch(depth) {
+                Err(trace) if self.catch(&trace, depth) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Unwind to the innermost handler installed since `depth`, passing it the
+    /// error's message. Returns `false` if there is no such handler.
+    ///
+    /// Running out of instructions or time, or being paused, can't be caught.
+    fn catch(&mut self, trace: &Trace, depth: usize) -> bool {
+        // keep the handlers around, since a paused script can be resumed
+        if trace.error == RuntimeError::Paused {
+            return false;
+        }
+
+        let handler = match self.handlers.last() {
+            Some(handler) if handler.frame_count > depth && !self.limits.exceeded => {
+                self.handlers.pop().unwrap()
+            }
+            _ => {
+                self.handlers.retain(|handler| handler.frame_count <= depth);
+                return false;
+            }
+        };
+
+        self.close_upvalues(handler.stack_len);
+        self.frames.truncate(handler.frame_count);
+        self.frame_count = handler.frame_count;
+        self.stack.truncate(handler.stack_len);
+
+        self.stack
+            .push(Value::String(trace.error.to_string().into()));
+        self.frames[self.frame_count - 1].ip = handler.ip;
+
+        true
+    }
+
+    /// Execute instructions until the script halts, the call stack unwinds to
+    /// `depth` frames, or an error occurs.
+    fn dispatch(&mut self, depth: usize) -> Result<Value, Trace> {
         let mut result = Value::Unit;
 
         loop {
@@ -133,137 +554,143 @@ impl Vm {
                 //self.debug_stack();
             }
 
-            match self.decode_opcode() {
+            if self.limited {
+                if let Err(error) = self.limits.tick() {
+                    return Err(Trace::new(error, self.frames.clone()));
+                }
+            }
+
+            if self.debugging {
+                match self.call_debug_hook() {
+                    DebugAction::Step => {}
+                    DebugAction::Continue => self.debugging = false,
+                    DebugAction::Pause => {
+                        return Err(Trace::new(RuntimeError::Paused, self.frames.clone()));
+                    }
+                }
+            }
+
+            match self.decode_opcode()? {
                 Opcode::Const => {
-                    let index = self.next_number();
+                    let index = self.next_number()?;
                     self.next();
-                    self.stack.push(
-                        *self.frames[self.frame_count - 1]
-                            .closure
-                            .function
-                            .chunk
-                            .constants[index]
-                            .clone(),
-                    );
+                    // clone the value itself, not the box it's stored in
+                    let value = match self.chunk().constants.get(index) {
+                        Some(value) => Value::clone(value),
+                        None => return Err(self.invalid_operand()),
+                    };
+                    self.stack.push(value);
                 }
                 Opcode::True => self.stack.push(Value::Boolean(true)),
                 Opcode::False => self.stack.push(Value::Boolean(false)),
                 Opcode::Nil => self.stack.push(Value::Nil),
                 Opcode::Unit => self.stack.push(Value::Unit),
                 Opcode::String => self.load_string()?,
-                Opcode::Add => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
-                    self.stack.push(lhs + rhs)
-                }
-                Opcode::Sub => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
-                    self.stack.push(lhs - rhs)
-                }
-                Opcode::Mul => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
-                    self.stack.push(lhs * rhs)
-                }
-                Opcode::Div => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
-                    self.stack.push(lhs / rhs)
-                }
-                Opcode::Mod => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
-                    self.stack.push(lhs % rhs)
-                }
+                Opcode::Add => self.binary_op(Value::try_add)?,
+                Opcode::Sub => self.binary_op(Value::try_sub)?,
+                Opcode::Mul => self.binary_op(Value::try_mul)?,
+                Opcode::Div => self.binary_op(Value::try_div)?,
+                Opcode::Mod => self.binary_op(Value::try_rem)?,
+                Opcode::Pow => self.binary_op(Value::try_pow)?,
                 Opcode::Negate => {
-                    let val = self.stack.pop();
-                    self.stack.push(-val)
+                    let val = self.pop()?;
+                    let result = val.try_neg().map_err(|err| self.arithmetic_error(err))?;
+                    self.stack.push(result)
                 }
                 Opcode::Equal => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs == rhs))
                 }
                 Opcode::NotEqual => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs != rhs))
                 }
                 Opcode::Gte => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs >= rhs))
                 }
                 Opcode::Lte => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs <= rhs))
                 }
                 Opcode::Gt => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs > rhs))
                 }
                 Opcode::Lt => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(Value::Boolean(lhs < rhs))
                 }
+                Opcode::Is => self.is_instance()?,
+                Opcode::IsVariant => self.is_variant()?,
                 Opcode::Not => {
-                    let val = self.stack.pop();
+                    let val = self.pop()?;
                     self.stack.push(!val)
                 }
                 Opcode::BitAnd => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(lhs & rhs);
                 }
                 Opcode::BitOr => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(lhs | rhs);
                 }
                 Opcode::BitXor => {
-                    let lhs = self.stack.pop();
-                    let rhs = self.stack.pop();
+                    let lhs = self.pop()?;
+                    let rhs = self.pop()?;
                     self.stack.push(lhs ^ rhs);
                 }
+                Opcode::Shl => self.binary_op(Value::try_shl)?,
+                Opcode::Shr => self.binary_op(Value::try_shr)?,
                 Opcode::DefGlobal => {
-                    let name = self.get_constant();
+                    let value = self.pop()?;
+                    let name = self.get_constant()?;
                     self.context
                         .as_ref()
                         .borrow_mut()
                         .globals
-                        .insert(name.to_string(), self.stack.pop());
+                        .insert(name.to_string(), value);
 
                     self.next();
                 }
                 Opcode::SetGlobal => {
-                    let name = self.get_constant();
-                    match self
-                        .context
-                        .as_ref()
-                        .borrow_mut()
-                        .globals
-                        .entry(name.to_string())
-                    {
-                        Occupied(mut val) => val.insert(self.stack.pop()),
-                        Vacant(_) => panic!("Cannot assign to undefined variable"),
-                    };
+                    let value = self.pop()?;
+                    let name = self.get_constant()?;
+                    if let Some(global) = self.context.as_ref().borrow_mut().globals.get_mut(name) {
+                        *global = value;
+                    } else {
+                        return Err(Trace::new(
+                            RuntimeError::UndefinedVariable {
+                                name: name.to_string(),
+                                span: self.current_span(),
+                            },
+                            self.frames.clone(),
+                        ));
+                    }
 
                     self.next();
                 }
                 Opcode::GetGlobal => {
-                    let name = self.get_constant();
-                    let context = &self.context.as_ref().borrow_mut();
+                    let name = self.get_constant()?;
+                    let context = &self.context.as_ref().borrow();
                     let result = match context.globals.get(name) {
                         Some(val) => self.stack.push(val.clone()),
                         None => match context.prelude.get::<Value>(name) {
                             Some(val) => self.stack.push(val.clone()),
                             None => {
                                 return Err(Trace::new(
-                                    &format!("Cannot find {name}"),
+                                    RuntimeError::UndefinedVariable {
+                                        name: name.to_string(),
+                                        span: self.current_span(),
+                                    },
                                     self.frames.clone(),
                                 ))
                             }
@@ -273,90 +700,143 @@ impl Vm {
                     result
                 }
                 Opcode::SaveLocal => {
-                    let data = self.stack.pop();
+                    let data = self.pop()?;
 
-                    let index = self.next_number();
-                    self.stack
-                        .save_local(index + self.frames[self.frame_count - 1].base_ip, data);
+                    let index = self.next_number()? + self.frames[self.frame_count - 1].base_ip;
+                    match self.stack.stack.get_mut(index) {
+                        Some(slot) => *slot = data,
+                        None => return Err(self.invalid_operand()),
+                    }
 
                     self.next();
                 }
                 Opcode::LoadLocal => {
-                    let index = self.next_number();
-                    let slot = self
-                        .stack
-                        .get(index + self.frames[self.frame_count - 1].base_ip);
+                    let index = self.next_number()? + self.frames[self.frame_count - 1].base_ip;
+                    let slot = match self.stack.stack.get(index) {
+                        Some(slot) => slot.clone(),
+                        None => return Err(self.invalid_operand()),
+                    };
 
                     self.next();
                     self.stack.push(slot);
                 }
                 Opcode::SaveUpValue => {
-                    let index = self.next_number();
-                    let value = self.stack.pop();
-                    self.frames[self.frame_count - 1]
-                        .closure
-                        .captures
-                        .borrow_mut()[index]
-                        .value = Rc::new(value);
+                    let index = self.next_number()?;
+                    let value = self.pop()?;
+                    let upvalue = self.upvalue(index)?;
+                    self.write_upvalue(&upvalue, value);
 
                     self.next();
                 }
                 Opcode::LoadUpValue => {
-                    let index = self.next_number();
-                    let data = self.frames[self.frame_count - 1].closure.captures.borrow()[index]
-                        .borrow()
-                        .to_owned();
+                    let index = self.next_number()?;
+                    let upvalue = self.upvalue(index)?;
 
                     self.next();
-                    self.stack.push(data.value.as_ref().borrow().clone());
+                    self.stack.push(self.read_upvalue(&upvalue));
                 }
                 Opcode::CloseUpValue => {
-                    self.close_upvalues(self.stack.len() - 1);
-                    self.stack.pop();
+                    // an empty stack is reported by `pop` rather than underflowing here
+                    if !self.stack.is_empty() {
+                        self.close_upvalues(self.stack.len() - 1);
+                    }
+                    self.pop()?;
                 }
                 Opcode::Loop => {
-                    self.frames[self.frame_count - 1].ip -= self.read_short();
+                    let offset = self.read_short()?;
+                    let ip = self.frames[self.frame_count - 1]
+                        .ip
+                        .checked_sub(offset)
+                        .ok_or_else(|| self.invalid_operand())?;
+                    self.frames[self.frame_count - 1].ip = ip;
                 }
                 Opcode::Jump => {
-                    self.frames[self.frame_count - 1].ip += self.read_short();
+                    self.frames[self.frame_count - 1].ip += self.read_short()?;
                 }
                 Opcode::JumpIfFalse => {
-                    let base_ip = self.read_short();
-                    if self.is_falsy() {
+                    let base_ip = self.read_short()?;
+                    if self.is_falsy()? {
                         self.frames[self.frame_count - 1].ip += base_ip;
                     }
                 }
+                Opcode::JumpIfStop => {
+                    let base_ip = self.read_short()?;
+                    if core::iter::is_stop_iteration(self.peek(1)?) {
+                        self.frames[self.frame_count - 1].ip += base_ip;
+                    }
+                }
+                Opcode::Iter => self.iter()?,
+                Opcode::Try => {
+                    let offset = self.read_short()?;
+                    self.handlers.push(Handler {
+                        frame_count: self.frame_count,
+                        stack_len: self.stack.len(),
+                        ip: self.frames[self.frame_count - 1].ip + offset,
+                    });
+                }
+                Opcode::EndTry => {
+                    self.handlers.pop();
+                }
                 Opcode::JumpIfTrue => {
-                    let base_ip = self.read_short();
-                    if !self.is_falsy() {
+                    let base_ip = self.read_short()?;
+                    if !self.is_falsy()? {
                         self.frames[self.frame_count - 1].ip += base_ip;
                     }
                 }
                 Opcode::Import => self.import()?,
                 Opcode::Class => self.class()?,
+                Opcode::Inherit => self.inherit()?,
                 Opcode::Call => {
-                    let arity = self.next_number();
+                    let arity = self.next_number()?;
                     self.next();
 
-                    self.call(arity)?;
+                    self.call_value(self.callee(arity)?, arity)?;
                 }
-                Opcode::Call0 => self.call(0)?,
-                Opcode::Call1 => self.call(1)?,
-                Opcode::Call2 => self.call(2)?,
+                Opcode::Call0 => self.call_value(self.callee(0)?, 0)?,
+                Opcode::Call1 => self.call_value(self.callee(1)?, 1)?,
+                Opcode::Call2 => self.call_value(self.callee(2)?, 2)?,
+                Opcode::CallList => self.call_list()?,
                 Opcode::Closure => self.closure()?,
-                Opcode::Return => self.return_(),
+                Opcode::Return => {
+                    self.return_()?;
+
+                    while matches!(self.handlers.last(), Some(handler) if handler.frame_count > self.frame_count)
+                    {
+                        self.handlers.pop();
+                    }
+
+                    if self.frame_count == depth {
+                        return self.pop();
+                    }
+                }
                 Opcode::List => self.list()?,
+                Opcode::ExtendList => self.extend_list()?,
                 Opcode::Tuple => self.tuple()?,
                 Opcode::Map => self.map()?,
                 Opcode::GetIndex => self.get_index()?,
                 Opcode::SetIndex => self.set_index()?,
+                Opcode::Slice => self.slice()?,
                 Opcode::Get => self.get()?,
                 Opcode::Set => self.set()?,
+                Opcode::GetSuper => self.get_super()?,
                 Opcode::Pop => {
-                    result = self.stack.pop();
+                    result = self.pop()?;
+                }
+                Opcode::Dup => {
+                    let value = self.pop()?;
+                    self.stack.push(value.clone());
+                    self.stack.push(value);
+                }
+                Opcode::Rot => {
+                    let top = self.pop()?;
+                    let middle = self.pop()?;
+                    let bottom = self.pop()?;
+                    self.stack.push(middle);
+                    self.stack.push(top);
+                    self.stack.push(bottom);
                 }
                 Opcode::PopN => {
-                    let num = self.next_number();
+                    let num = self.next_number()?;
                     self.stack.truncate(num);
 
                     self.next();
@@ -374,24 +854,23 @@ impl Vm {
 
     /// Construct a closure from a function.
     fn closure(&mut self) -> Result<(), Trace> {
-        let fun = match &*self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .constants[self.next_number()]
-        {
-            Value::Function(fun) => fun,
-            _ => panic!("expected a function"),
+        let index = self.next_number()?;
+        let fun = match self.chunk().constants.get(index).map(|value| &**value) {
+            Some(Value::Function(fun)) => fun.clone(),
+            _ => return Err(self.invalid_operand()),
         };
 
-        let closure = Closure::wrap(fun.clone());
+        let closure = Closure::wrap(fun);
 
         for captured in closure.function.captures.iter() {
             let reference = match captured {
-                Captured::Local(index) => self.capture_upvalue(*index),
-                Captured::NonLocal(index) => {
-                    self.frames[self.frame_count - 1].closure.captures.borrow()[*index].clone()
+                Captured::Local(index) => {
+                    if self.frames[self.frame_count - 1].base_ip + index >= self.stack.len() {
+                        return Err(self.invalid_operand());
+                    }
+                    self.capture_upvalue(*index)
                 }
+                Captured::NonLocal(index) => self.upvalue(*index)?,
             };
 
             closure.captures.borrow_mut().push(reference);
@@ -402,21 +881,40 @@ impl Vm {
         Ok(())
     }
 
-    /// Call the value off the top of the stack.
-    fn call(&mut self, arity: usize) -> Result<(), Trace> {
-        match self.stack.get(self.stack.len() - 1 - arity) {
-            Value::NativeFun(fun) => self.native_call(fun, arity),
-            Value::Closure(closure) => self.fun_call(closure, arity),
-            Value::Constructor(constructor) => self.constructor_call(constructor),
-            Value::Method(method) => self.method_call(method, arity),
+    /// The value being called with the top `arity` values of the stack.
+    fn callee(&self, arity: usize) -> Result<Value, Trace> {
+        self.peek(arity + 1).cloned()
+    }
+
+    /// Call `callee` with `arity` arguments. The callee and then its arguments,
+    /// the first on top, must already be on the stack.
+    ///
+    /// This is the only place calls are dispatched, whether from bytecode or from
+    /// Rust. Natives run straight away and replace the callee and arguments with
+    /// their result, while functions push a frame for the VM loop to run.
+    fn call_value(&mut self, callee: Value, arity: usize) -> Result<(), Trace> {
+        match callee {
+            Value::NativeFun(fun) => self.native_call(fun, arity)?,
+            Value::Closure(closure) => {
+                let function = &closure.function;
+                self.check_arity(&function.name, function.arity, arity, false)?;
+                self.fun_call(closure, arity)
+            }
+            Value::Function(fun) => {
+                self.check_arity(&fun.name, fun.arity, arity, false)?;
+                self.fun_call(Rc::new(Closure::wrap(fun)), arity)
+            }
+            Value::Constructor(constructor) => self.constructor_call(constructor, arity)?,
+            Value::Method(method) => self.method_call(method, arity)?,
             Value::Class(class) => {
-                self.stack.pop();
+                // the default constructor takes no arguments
+                self.check_arity(&class.name, 0, arity, false)?;
+                self.pop()?;
                 let instance = class.instance();
 
                 self.stack.push(Value::Instance(instance));
             }
-            v => {
-                println!("{v}");
+            _ => {
                 return Err(Trace::new("can only call functions", self.frames.clone()));
             }
         }
@@ -424,39 +922,96 @@ impl Vm {
         Ok(())
     }
 
+    /// Call a value with the elements of the list on top of the stack as its
+    /// arguments, so the number of arguments is only known at runtime.
+    fn call_list(&mut self) -> Result<(), Trace> {
+        let args = self.pop_spread()?;
+        let arity = args.len();
+
+        // the first argument goes on top
+        for arg in args.into_iter().rev() {
+            self.stack.push(arg);
+        }
+
+        self.call_value(self.callee(arity)?, arity)
+    }
+
+    /// Check that a function was called with the number of arguments it expects.
+    ///
+    /// Variadic functions may be passed any number of extra arguments.
+    fn check_arity(
+        &self,
+        name: &str,
+        expected: usize,
+        found: usize,
+        is_varidic: bool,
+    ) -> Result<(), Trace> {
+        if found < expected || (found > expected && !is_varidic) {
+            return Err(Trace::new(
+                RuntimeError::ArityMismatch {
+                    name: name.to_string(),
+                    expected,
+                    found,
+                },
+                self.frames.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Call a foreign function.
-    fn native_call(&mut self, fun: Rc<NativeFun>, arity: usize) {
+    fn native_call(&mut self, fun: Rc<NativeFun>, arity: usize) -> Result<(), Trace> {
+        self.check_arity(&fun.name, fun.arity(), arity, fun.is_varidic)?;
+
         let mut args = vec![];
         for _ in 0..arity {
-            args.push(self.stack.pop());
+            args.push(self.pop()?);
         }
 
-        let result = fun.call(self, args);
-        self.stack.pop();
+        let result = self.call_native(&fun, args)?;
+        self.pop()?;
         self.stack.push(result);
+
+        Ok(())
+    }
+
+    /// Invoke a native function, turning a failed call into a runtime error.
+    fn call_native(&mut self, fun: &NativeFun, args: Vec<Value>) -> Result<Value, Trace> {
+        fun.call(self, args)
+            .map_err(|err| Trace::new(err.in_call_to(&fun.name), self.frames.clone()))
     }
 
     /// Call a constructor.
-    fn constructor_call(&mut self, init: Rc<Constructor>) {
+    fn constructor_call(&mut self, init: Rc<Constructor>, arity: usize) -> Result<(), Trace> {
         let function = match &init.function {
             CallableFunction::Native(fun) => {
+                self.check_arity(&fun.name, fun.arity(), arity, fun.is_varidic)?;
+
                 let args = self
                     .stack
                     .stack
-                    .drain(fun.arity()..)
+                    .drain(self.stack.len() - arity..)
                     .rev()
                     .collect::<Vec<Value>>();
-                let result = fun.call(self, args);
+                let result = self.call_native(fun, args)?;
 
-                self.stack.pop();
+                self.pop()?;
 
                 self.stack.push(result);
 
-                return;
+                return Ok(());
             }
             CallableFunction::Function(f) => f,
         };
 
+        self.check_arity(
+            &function.function.name,
+            function.function.arity,
+            arity,
+            false,
+        )?;
+
         let instance = init.class.clone().instance();
 
         let frame = Frame::new(
@@ -474,12 +1029,18 @@ impl Vm {
             self.stack.len() - function.function.arity,
             instance.to_value(),
         );
+
+        Ok(())
     }
 
     /// Call a method
-    fn method_call(&mut self, bound: Rc<BoundMethod>, arity: usize) {
+    fn method_call(&mut self, bound: Rc<BoundMethod>, arity: usize) -> Result<(), Trace> {
         let function = match &bound.function {
             CallableFunction::Native(fun) => {
+                // the receiver is counted as the first parameter
+                let params = fun.arity().saturating_sub(1);
+                self.check_arity(&fun.name, params, arity, fun.is_varidic)?;
+
                 let arity = if fun.is_varidic {
                     arity + 1
                 } else {
@@ -496,16 +1057,23 @@ impl Vm {
                 let mut arg_list = vec![bound.receiver.clone()];
                 arg_list.append(&mut args);
 
-                let result = fun.call(self, arg_list);
+                let result = self.call_native(fun, arg_list)?;
 
-                self.stack.pop();
+                self.pop()?;
                 self.stack.push(result);
 
-                return;
+                return Ok(());
             }
             CallableFunction::Function(f) => f,
         };
 
+        self.check_arity(
+            &function.function.name,
+            function.function.arity,
+            arity,
+            false,
+        )?;
+
         let frame = Frame::new(
             function.clone(),
             0,
@@ -519,6 +1087,8 @@ impl Vm {
             self.stack.len() - function.function.arity,
             bound.receiver.clone(),
         );
+
+        Ok(())
     }
 
     /// Call a function.
@@ -530,12 +1100,10 @@ impl Vm {
     }
 
     /// Return from a function.
-    fn return_(&mut self) {
-        let return_val = self.stack.pop();
+    fn return_(&mut self) -> Result<(), Trace> {
+        let return_val = self.pop()?;
 
-        for i in self.frames[self.frame_count - 1].base_ip..self.stack.stack.len() {
-            self.close_upvalues(i);
-        }
+        self.close_upvalues(self.frames[self.frame_count - 1].base_ip);
 
         self.next();
         let frame = self.frames.pop().unwrap();
@@ -544,95 +1112,109 @@ impl Vm {
 
         self.frame_count -= 1;
 
-        self.stack.pop();
+        self.pop()?;
         self.stack.push(return_val);
+
+        Ok(())
     }
 
-    /// Capture an upvalue from the stack.
+    /// Capture a local of the current frame, reusing an open upvalue if the
+    /// local has already been captured.
     fn capture_upvalue(&mut self, index: usize) -> Upvalue {
-        /*let mut prev_upvalue = None;
-        let mut upvalue = self.open_upvalues.clone();
+        let slot = self.frames[self.frame_count - 1].base_ip + index;
 
-        while upvalue.is_some() && upvalue.as_ref().unwrap().position > index {
-            prev_upvalue = upvalue.clone();
-            upvalue = upvalue.unwrap().next();
+        if let Some(upvalue) = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| upvalue.slot() == Some(slot))
+        {
+            return upvalue.clone();
         }
 
-        if let Some(upvalue) = upvalue.clone() {
-            if upvalue.position == index {
-                return upvalue;
-            }
-        }*/
-
-        let new_upvalue = Upvalue::new(
-            Rc::new(
-                self.stack
-                    .get(self.frames[self.frame_count - 1].base_ip + index),
-            ),
-            None, //upvalue.map(Rc::new),
-            index,
-        );
+        let upvalue = Upvalue::open(slot);
+        self.open_upvalues.push(upvalue.clone());
 
-        new_upvalue
+        upvalue
     }
 
-    /// Close over an upvalue.
+    /// Close every open upvalue pointing at or above the stack slot `last`.
     fn close_upvalues(&mut self, last: usize) {
-        while self.open_upvalues.is_some() && self.open_upvalues.as_ref().unwrap().position >= last
-        {
-            let mut upvalue = self.open_upvalues.as_ref().unwrap().clone();
+        let stack = &self.stack.stack;
 
-            upvalue.closed = upvalue.value.as_ref().borrow().clone();
-            upvalue.value = Rc::new(upvalue.closed);
+        self.open_upvalues.retain(|upvalue| match upvalue.slot() {
+            Some(slot) if slot >= last => {
+                upvalue.close(stack[slot].clone());
+                false
+            }
+            _ => true,
+        });
+    }
 
-            self.open_upvalues = upvalue.next.as_ref().map(|v| v.as_ref().borrow().clone());
+    /// Read the value of a captured variable.
+    fn read_upvalue(&self, upvalue: &Upvalue) -> Value {
+        match &*RefCell::borrow(&upvalue.0) {
+            UpvalueLocation::Open(slot) => self.stack.stack[*slot].clone(),
+            UpvalueLocation::Closed(value) => value.clone(),
+        }
+    }
+
+    /// Assign to a captured variable.
+    fn write_upvalue(&mut self, upvalue: &Upvalue, value: Value) {
+        match &mut *upvalue.0.borrow_mut() {
+            UpvalueLocation::Open(slot) => self.stack.stack[*slot] = value,
+            UpvalueLocation::Closed(closed) => *closed = value,
         }
     }
 
     /// Create a new class.
     fn class(&mut self) -> Result<(), Trace> {
-        let index = self.next_number();
-        let name = &*self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .variables[index];
+        let index = self.next_number()?;
+        let class = Class::new(self.variable(index)?);
+        self.next();
 
-        let class = Class::new(name);
+        let num_methods = self.next_number()?;
         self.next();
 
-        let num_methods = self.next_number();
+        let num_constructors = self.next_number()?;
         self.next();
 
-        let num_constructors = self.next_number();
+        let num_statics = self.next_number()?;
         self.next();
 
-        let num_fields = self.next_number();
+        let num_fields = self.next_number()?;
         self.next();
 
         for i in (0..num_fields).rev() {
-            let name =
-                &*self.frames.last().unwrap().closure.function.chunk.variables[index + i + 1];
-            let value = self.stack.pop();
+            let value = self.pop()?;
+            let name = self.variable(index + i + 1)?;
 
             class.add_field(name, value);
         }
 
+        for _ in 0..num_statics {
+            let static_fun = self.pop_closure()?;
+
+            class.add_static(
+                static_fun.function.name.as_str(),
+                CallableFunction::Function(static_fun.clone()),
+            );
+        }
+
         for _ in 0..num_methods {
-            let method = self.stack.pop();
+            let method = self.pop_closure()?;
 
             class.add_method(
-                method.as_closure().unwrap().function.name.as_str(),
-                CallableFunction::Function(method.as_closure().unwrap()),
+                method.function.name.as_str(),
+                CallableFunction::Function(method.clone()),
             );
         }
 
         for _ in 0..num_constructors {
-            let constructor = self.stack.pop();
+            let constructor = self.pop_closure()?;
 
             class.add_init(
-                constructor.as_closure().unwrap().function.name.as_str(),
-                CallableFunction::Function(constructor.as_closure().unwrap()),
+                constructor.function.name.as_str(),
+                CallableFunction::Function(constructor.clone()),
             );
         }
 
@@ -641,6 +1223,81 @@ impl Vm {
         Ok(())
     }
 
+    /// Check if a value is an instance of a class or one of its subclasses.
+    ///
+    /// Values other than instances are never an instance of a class.
+    fn is_instance(&mut self) -> Result<(), Trace> {
+        let value = self.pop()?;
+        let class = match self.pop()? {
+            Value::Class(class) => class,
+            value => {
+                return Err(Trace::new(
+                    format!("expected a class after `is`, found `{value}`"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        let is_instance = match value {
+            Value::Instance(instance) => instance.is_instance_of(&class),
+            _ => false,
+        };
+        self.stack.push(Value::Boolean(is_instance));
+
+        Ok(())
+    }
+
+    /// Check if the value below the enum on top of the stack is the variant
+    /// named by the operand.
+    fn is_variant(&mut self) -> Result<(), Trace> {
+        let name = self.get_constant()?.to_string();
+        self.next();
+
+        let enum_ = match self.pop()? {
+            Value::Enum(enum_) => enum_,
+            value => {
+                return Err(Trace::new(
+                    format!("expected an enum, found `{value}`"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        if enum_.arity(&name).is_none() {
+            return Err(Trace::new(
+                format!("no variant `{name}` found for enum '{}'", enum_.name),
+                self.frames.clone(),
+            ));
+        }
+
+        let is_variant = match self.pop()? {
+            Value::Variant(variant) => variant.parent == enum_ && *variant.name == *name,
+            _ => false,
+        };
+        self.stack.push(Value::Boolean(is_variant));
+
+        Ok(())
+    }
+
+    /// Make the class below the top of the stack inherit from the class on top.
+    fn inherit(&mut self) -> Result<(), Trace> {
+        let parent = match self.pop()? {
+            Value::Class(parent) => parent,
+            value => {
+                return Err(Trace::new(
+                    format!("cannot inherit from `{value}`, it is not a class"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        if let Value::Class(class) = self.peek(1)? {
+            class.inherit(parent);
+        }
+
+        Ok(())
+    }
+
     /// Handle imports.
     fn import(&mut self) -> Result<(), Trace> {
         todo!()
@@ -649,36 +1306,117 @@ impl Vm {
     /// Create a list.
     fn list(&mut self) -> Result<(), Trace> {
         let mut list: Vec<Value> = vec![];
-        let length = self.get_opcode(self.frames[self.frame_count - 1].ip) as usize;
+        let length = self.next_number()?;
         for _ in 0..length {
-            list.push(self.stack.pop());
+            list.push(self.pop()?);
         }
         self.next();
         self.stack.push(Value::List(ValueList::from_vec(&list)));
         Ok(())
     }
 
+    /// Append the elements of the list on top of the stack to the list being
+    /// built below it.
+    fn extend_list(&mut self) -> Result<(), Trace> {
+        let elements = self.pop_spread()?;
+
+        match self.peek(1)? {
+            Value::List(list) => list.0.borrow_mut().extend(elements),
+            value => {
+                return Err(Trace::new(
+                    format!("cannot extend `{value}`, expected a list"),
+                    self.frames.clone(),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Turn the value on top of the stack into an iterator for a `for` loop.
+    ///
+    /// An instance is iterated over by calling its `iter` method, which must
+    /// return a value with a `next` method. Built-in collections are iterated
+    /// over by a snapshot of their elements: the items of a list or tuple, the
+    /// characters of a string, the bytes of a byte string, or the keys of a map.
+    fn iter(&mut self) -> Result<(), Trace> {
+        let elements = match self.pop()? {
+            Value::List(list) => list.0.borrow().to_vec(),
+            Value::Tuple(tuple) => tuple.0.to_vec(),
+            Value::String(string) => string
+                .chars()
+                .map(|char| Value::String(char.to_string().into()))
+                .collect(),
+            Value::Bytes(bytes) => bytes.iter().map(|byte| Value::Float(*byte as f64)).collect(),
+            Value::Map(map) => map.keys().map(|key| Value::from(key.as_str())).collect(),
+            Value::Instance(instance) => match instance.class.get_method("iter") {
+                Some(method) => {
+                    let method = BoundMethod::new(Value::Instance(instance), method);
+                    let method = Value::Method(Rc::new(method));
+                    self.stack.push(method.clone());
+
+                    return self.call_value(method, 0);
+                }
+                None => return Err(self.not_iterable(Value::Instance(instance))),
+            },
+            value => return Err(self.not_iterable(value)),
+        };
+
+        self.stack.push(Value::External(elements_iter(elements)));
+
+        Ok(())
+    }
+
+    fn not_iterable(&self, value: Value) -> Trace {
+        Trace::new(
+            RuntimeError::TypeMismatch {
+                expected: "iterable".to_string(),
+                found: value.type_name(),
+                span: self.current_span(),
+            },
+            self.frames.clone(),
+        )
+    }
+
+    /// Pop a list being spread with `...`, returning its elements.
+    fn pop_spread(&mut self) -> Result<Vec<Value>, Trace> {
+        match self.pop()? {
+            Value::List(list) => Ok(list.0.borrow().to_vec()),
+            value => Err(Trace::new(
+                RuntimeError::TypeMismatch {
+                    expected: "List".to_string(),
+                    found: value.type_name(),
+                    span: self.current_span(),
+                },
+                self.frames.clone(),
+            )),
+        }
+    }
+
     /// Create a tuple.
     fn tuple(&mut self) -> Result<(), Trace> {
         let mut tuple = vec![];
-        let length = self.get_opcode(self.frames[self.frame_count - 1].ip) as usize;
+        let length = self.next_number()?;
         for _ in 0..length {
-            tuple.push(self.stack.pop());
+            tuple.push(self.pop()?);
         }
 
         self.next();
-        self.stack.push(Value::Tuple(ValueTuple::new()));
+        self.stack.push(Value::Tuple(ValueTuple(Rc::new(tuple.into()))));
         Ok(())
     }
 
-    /// Build the map.
+    /// Build a map from the key-value pairs on the stack.
+    ///
+    /// The first entry is on top of the stack, so when a key appears more than
+    /// once the last value wins, in the position of the first.
     fn map(&mut self) -> Result<(), Trace> {
-        let length = self.get_opcode(self.frames[self.frame_count - 1].ip) as usize;
+        let length = self.next_number()?;
         let mut map = Map::with_capacity(length);
         for _ in 0..length {
-            let key = self.stack.pop();
-            let value = self.stack.pop();
-            map.insert(key.to_string(), value);
+            let key = self.pop()?;
+            let value = self.pop()?;
+            map.insert(self.map_key(key)?, value);
         }
 
         self.next();
@@ -686,92 +1424,239 @@ impl Vm {
         Ok(())
     }
 
-    /// Index into a list on the stack.
-    fn get_index(&mut self) -> Result<(), Trace> {
-        let index = self.stack.pop();
-        let expr = self.stack.pop();
-
-        match index {
-            Value::Float(index) => {
-                match expr {
-                    Value::List(list) => {
-                        let length = list.len();
-                        self.bounds_check(length, index)?;
-
-                        // if index is negative, index backwards into list
-                        if index.is_sign_negative() {
-                            self.stack.push(
-                                list.0.as_ref().borrow()[length - index.abs() as usize].clone(),
-                            );
-                            Ok(())
-                        } else {
-                            self.stack
-                                .push(list.0.as_ref().borrow()[index as usize].clone());
-                            Ok(())
-                        }
-                    }
-                    val => Err(Trace::new(
-                        &format!("cannot index into {val}"),
-                        self.frames.clone(),
-                    )),
-                }
+    /// The key a value is stored under in a map, which is how it's displayed, so
+    /// that a float and an integer with the same value are the same key.
+    ///
+    /// `NaN` isn't equal to itself, so it can't be a key.
+    fn map_key(&self, key: Value) -> Result<String, Trace> {
+        match key {
+            Value::Float(num) if num.is_nan() => {
+                Err(Trace::new("cannot use `NaN` as a map key", self.frames.clone()))
             }
-            _ => Err(Trace::new("can only index into lists", self.frames.clone())),
+            key => Ok(key.to_string()),
         }
     }
 
-    /// Update the list at the giving index.
+    /// Index into a list, bytes or map on the stack.
+    ///
+    /// Reading a map key that has no entry is an error.
+    fn get_index(&mut self) -> Result<(), Trace> {
+        let index = self.pop()?;
+        let expr = self.pop()?;
+
+        let value = match (expr, index) {
+            (Value::List(list), Value::Float(index)) => {
+                let index = self.resolve_index(list.len(), index)?;
+                list.0.as_ref().borrow()[index].clone()
+            }
+            (Value::Bytes(bytes), Value::Float(index)) => {
+                let index = self.resolve_index(bytes.len(), index)?;
+                Value::Float(bytes[index] as f64)
+            }
+            (Value::Map(map), key) => map
+                .get(&self.map_key(key.clone())?)
+                .cloned()
+                .ok_or_else(|| {
+                    Trace::new(format!("no entry found for key `{key}`"), self.frames.clone())
+                })?,
+            (Value::Variant(variant), Value::Float(index)) => {
+                let index = self.resolve_index(variant.values.len(), index)?;
+                variant.values[index].clone()
+            }
+            (Value::List(_) | Value::Bytes(_) | Value::Variant(_), index) => {
+                return Err(self.index_type_error(index))
+            }
+            (val, _) => {
+                return Err(Trace::new(
+                    format!("cannot index into {val}"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Store a value in a list element or map entry, and push the container
+    /// back so that it can be stored where it came from.
+    ///
+    /// A list index must already be in bounds, since assignment never grows a
+    /// list. Assigning to a map key with no entry inserts it.
     fn set_index(&mut self) -> Result<(), Trace> {
-        let index = self.stack.pop();
-        let expr = self.stack.pop();
-        let value = self.stack.pop();
+        let index = self.pop()?;
+        let expr = self.pop()?;
+        let value = self.pop()?;
+
+        let container = match (expr, index) {
+            (Value::List(list), Value::Float(index)) => {
+                let index = self.resolve_index(list.len(), index)?;
+                list.0.borrow_mut()[index] = value;
+                Value::List(list)
+            }
+            (Value::Map(mut map), key) => {
+                map.insert(self.map_key(key)?, value);
+                Value::Map(map)
+            }
+            (Value::List(_), index) => return Err(self.index_type_error(index)),
+            (val, _) => {
+                return Err(Trace::new(
+                    format!("cannot assign to an index of {val}"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        self.stack.push(container);
 
-        match expr {
+        Ok(())
+    }
+
+    /// Copy the elements of a list, string or bytes between two bounds.
+    ///
+    /// Like in Python, out of range bounds are clamped rather than being an
+    /// error, so `[1, 2, 3][1..10]` is `[2, 3]`, and a range that ends before
+    /// it starts is empty. Strings are sliced by characters, not bytes.
+    fn slice(&mut self) -> Result<(), Trace> {
+        let hi = self.pop()?;
+        let lo = self.pop()?;
+        let expr = self.pop()?;
+
+        let value = match expr {
             Value::List(list) => {
-                match index {
-                    Value::Float(index) => {
-                        let length = list.0.as_ref().borrow().len();
-                        self.bounds_check(length, index)?;
-
-                        // if index is negative, index backwards into list
-                        if index.is_sign_negative() {
-                            list.0.borrow_mut()[length - index.abs() as usize] = value;
-                            self.stack.push(Value::List(list))
-                        } else {
-                            list.0.borrow_mut()[index as usize] = value;
-                            self.stack.push(Value::List(list))
-                        }
-                    }
-                    _ => {
-                        return Err(Trace::new(
-                            "lists can only be indexed by a number",
-                            self.frames.clone(),
-                        ))
-                    }
-                };
+                let range = self.slice_range(list.len(), lo, hi)?;
+                Value::List(ValueList::from_vec(&list.0.borrow()[range]))
+            }
+            Value::String(string) => {
+                let range = self.slice_range(string.chars().count(), lo, hi)?;
+                let slice = string
+                    .chars()
+                    .skip(range.start)
+                    .take(range.len())
+                    .collect::<String>();
+
+                Value::String(ImmutableString::from(slice))
+            }
+            Value::Bytes(bytes) => {
+                let range = self.slice_range(bytes.len(), lo, hi)?;
+                Value::Bytes(Rc::new(bytes[range].to_vec()))
+            }
+            val => {
+                return Err(Trace::new(
+                    format!("cannot slice {val}"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Resolve the bounds of a slice into a range, clamped to `0..length`.
+    ///
+    /// A `nil` bound is open, and negative bounds count back from the end.
+    fn slice_range(&self, length: usize, lo: Value, hi: Value) -> Result<Range<usize>, Trace> {
+        let bound = |value: Value, default: usize| match value {
+            Value::Nil => Ok(default),
+            Value::Float(index) if index < 0.0 => {
+                Ok(length.saturating_sub(index.abs() as usize))
             }
-            _ => return Err(Trace::new("can only index into lists", self.frames.clone())),
+            Value::Float(index) => Ok((index as usize).min(length)),
+            value => Err(self.index_type_error(value)),
+        };
+
+        let lo = bound(lo, 0)?;
+        let hi = bound(hi, length)?;
+
+        Ok(lo..hi.max(lo))
+    }
+
+    /// Bounds check an index, counting negative indices back from the end.
+    fn resolve_index(&self, length: usize, index: f64) -> Result<usize, Trace> {
+        self.bounds_check(length, index)?;
+
+        if index < 0.0 {
+            Ok(length - index.abs() as usize)
+        } else {
+            Ok(index as usize)
         }
+    }
+
+    /// The error for indexing a sequence with something other than a number.
+    fn index_type_error(&self, index: Value) -> Trace {
+        Trace::new(
+            RuntimeError::TypeMismatch {
+                expected: "f64".to_string(),
+                found: index.type_name(),
+                span: self.current_span(),
+            },
+            self.frames.clone(),
+        )
+    }
+
+    /// Apply a binary arithmetic operator to the top two values on the stack.
+    fn binary_op(
+        &mut self,
+        op: fn(Value, Value) -> Result<Value, RuntimeError>,
+    ) -> Result<(), Trace> {
+        let lhs = self.pop()?;
+        let rhs = self.pop()?;
+        let result = op(lhs, rhs).map_err(|err| self.arithmetic_error(err))?;
+        self.stack.push(result);
 
         Ok(())
     }
 
+    /// Attach the current span to an error raised by an arithmetic operator.
+    fn arithmetic_error(&self, error: RuntimeError) -> Trace {
+        let error = match error {
+            RuntimeError::TypeMismatch {
+                expected, found, ..
+            } => RuntimeError::TypeMismatch {
+                expected,
+                found,
+                span: self.current_span(),
+            },
+            error => error,
+        };
+
+        Trace::new(error, self.frames.clone())
+    }
+
+    /// The [Span] of the instruction currently being executed.
+    fn current_span(&self) -> Span {
+        self.frames
+            .last()
+            .and_then(|frame| {
+                frame
+                    .closure
+                    .function
+                    .chunk
+                    .debug_info
+                    .span_at(frame.ip.saturating_sub(1))
+            })
+            .unwrap_or_else(Span::empty)
+    }
+
     /// Bounds check a list.
+    ///
+    /// An index that isn't a whole number, including NaN, is always out of
+    /// bounds. Negative zero is treated the same as zero.
     fn bounds_check<T: Into<f64>>(&self, length: usize, index: T) -> Result<(), Trace> {
         let index = index.into();
 
-        // upper bounds check
-        if index.is_sign_positive() && length as f64 <= index {
-            return Err(Trace::new(
-                &format!("index out of bounds: the length is {length} but the index is {index}"),
-                self.frames.clone(),
-            ));
-        }
+        let in_bounds = if index < 0.0 {
+            index.abs() <= length as f64
+        } else {
+            index < length as f64
+        };
 
-        // lower bounds check
-        if index.is_sign_negative() && length as f64 <= index.abs() - 1. {
+        if index.fract() != 0.0 || !in_bounds {
             return Err(Trace::new(
-                &format!("index out of bounds: the length is {length} but the index is {index}"),
+                RuntimeError::IndexOutOfBounds { index, length },
                 self.frames.clone(),
             ));
         }
@@ -781,20 +1666,34 @@ impl Vm {
 
     /// Handle the get opcode.
     fn get(&mut self) -> Result<(), Trace> {
-        match self.stack.pop() {
-            value @ Value::Map(_) => {
-                let name = self.get_constant();
+        match self.pop()? {
+            Value::Map(map) => {
+                let name = self.get_constant()?;
 
-                let class = RefCell::borrow(self.context.as_ref())
+                let class: Rc<Class> = RefCell::borrow(self.context.as_ref())
                     .prelude
                     .get(Map::NAME)
                     .unwrap();
 
-                let method = Instance::builtin(value, name, class);
-                self.stack.push(Value::Method(Rc::new(method)));
+                // the members of a module come before the builtin methods, so
+                // they may shadow them, but the entries of other maps come after
+                match map.get(name).cloned() {
+                    Some(member) if map.is_module() => self.stack.push(member),
+                    _ if class.get_method(name).is_some() => {
+                        let method = Instance::builtin(Value::Map(map), name, class);
+                        self.stack.push(Value::Method(Rc::new(method)));
+                    }
+                    Some(member) => self.stack.push(member),
+                    None => {
+                        return Err(Trace::new(
+                            format!("no method or entry `{name}` found for map"),
+                            self.frames.clone(),
+                        ))
+                    }
+                }
             }
             value @ Value::String(_) => {
-                let name = self.get_constant();
+                let name = self.get_constant()?;
 
                 let class = RefCell::borrow(self.context.as_ref())
                     .prelude
@@ -804,8 +1703,19 @@ impl Vm {
                 let method = Instance::builtin(value, name, class);
                 self.stack.push(Value::Method(Rc::new(method)));
             }
+            value @ Value::Bytes(_) => {
+                let name = self.get_constant()?;
+
+                let class = RefCell::borrow(self.context.as_ref())
+                    .prelude
+                    .get("Bytes")
+                    .unwrap();
+
+                let method = Instance::builtin(value, name, class);
+                self.stack.push(Value::Method(Rc::new(method)));
+            }
             value @ Value::Float(_) => {
-                let name = self.get_constant();
+                let name = self.get_constant()?;
 
                 let class = RefCell::borrow(self.context.as_ref())
                     .prelude
@@ -829,8 +1739,8 @@ impl Vm {
                     .unwrap()
                 {
                     self.stack.push(
-                        map.get(&self.get_constant().to_string()[..])
-                            .unwrap()
+                        map.get(self.get_constant()?)
+                            .ok_or_else(|| self.invalid_operand())?
                             .clone(),
                     );
                 } else {
@@ -838,7 +1748,7 @@ impl Vm {
                 }
             }
             Value::Class(class) => {
-                let name = self.get_constant();
+                let name = self.get_constant()?;
 
                 if let Some(init) = class.get_init(name) {
                     self.stack
@@ -850,17 +1760,26 @@ impl Vm {
                     }
                 } else {
                     return Err(Trace::new(
-                        &format!("no method `{name}` found for class '{}' ", class.name)[..],
+                        format!("no method `{name}` found for class '{}' ", class.name),
                         self.frames.clone(),
                     ));
                 }
             }
+            Value::Enum(enum_) => {
+                let name = self.get_constant()?;
+
+                match enum_.variant(name) {
+                    Some(variant) => self.stack.push(variant),
+                    None => {
+                        return Err(Trace::new(
+                            format!("no variant `{name}` found for enum '{}'", enum_.name),
+                            self.frames.clone(),
+                        ))
+                    }
+                }
+            }
             Value::Instance(instance) => {
-                let name = &*self.frames[self.frame_count - 1]
-                    .closure
-                    .function
-                    .chunk
-                    .variables[self.next_number()];
+                let name = self.get_constant()?;
 
                 if instance.fields().get(name).is_some() {
                     self.stack
@@ -875,6 +1794,23 @@ impl Vm {
 
                 self.stack.push(Value::Method(Rc::new(method)));
             }
+            Value::External(external) => {
+                let name = self.get_constant()?;
+
+                match external.method(name) {
+                    Some(fun) => {
+                        let method =
+                            BoundMethod::new(external.clone(), CallableFunction::Native(fun));
+                        self.stack.push(Value::Method(Rc::new(method)));
+                    }
+                    None => {
+                        return Err(Trace::new(
+                            format!("no method `{name}` found for '{external}'"),
+                            self.frames.clone(),
+                        ))
+                    }
+                }
+            }
             _ => return Err(Trace::new("can only index into a map", self.frames.clone())),
         };
 
@@ -882,21 +1818,24 @@ impl Vm {
         Ok(())
     }
 
+    /// Handle the set opcode, assigning to a field of an instance.
+    ///
+    /// Instances are shared, so the new value is seen through every alias.
     fn set(&mut self) -> Result<(), Trace> {
-        let name = &*self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .variables[self.next_number()];
+        let object = self.pop()?;
+        let name = Box::<str>::from(self.get_constant()?);
 
-        match self.stack.pop() {
+        match object {
             Value::Instance(instance) => {
-                instance
-                    .fields
-                    .borrow_mut()
-                    .insert(name.into(), self.stack.pop());
+                let value = self.pop()?;
+                instance.fields.borrow_mut().insert(name, value);
+            }
+            value => {
+                return Err(Trace::new(
+                    format!("cannot assign to field `{name}` of `{value}`"),
+                    self.frames.clone(),
+                ))
             }
-            _ => todo!(),
         }
 
         self.next();
@@ -904,41 +1843,61 @@ impl Vm {
         Ok(())
     }
 
-    fn load_string(&mut self) -> Result<(), Trace> {
-        let index = self.next_number();
+    /// Handle the get super opcode, binding a method of a super class to `self`.
+    fn get_super(&mut self) -> Result<(), Trace> {
+        let name = self.get_constant()?.to_string();
+
+        let class = match self.pop()? {
+            Value::Class(class) => class,
+            value => {
+                return Err(Trace::new(
+                    format!("expected a super class, found `{value}`"),
+                    self.frames.clone(),
+                ))
+            }
+        };
+        let receiver = self.pop()?;
+
+        match class.get_method(&name) {
+            Some(method) => {
+                let method = BoundMethod::new(receiver, method);
+                self.stack.push(Value::Method(Rc::new(method)));
+            }
+            None => {
+                return Err(Trace::new(
+                    format!("no method `{name}` found for class '{}'", class.name),
+                    self.frames.clone(),
+                ))
+            }
+        }
+
         self.next();
 
-        let s = &*self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .variables[index];
+        Ok(())
+    }
 
-        self.stack.push(Value::String(ImmutableString::from(s)));
+    fn load_string(&mut self) -> Result<(), Trace> {
+        let index = self.next_number()?;
+        self.next();
+
+        let string = ImmutableString::from(self.variable(index)?);
+        self.stack.push(Value::String(string));
 
         Ok(())
     }
 
     /// Read a u16 from the stream of bytecode.
-    fn read_short(&mut self) -> usize {
-        self.frames[self.frame_count - 1].ip += 2;
-        let base_ip = ((self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .opcodes[self.frames[self.frame_count - 1].ip - 2] as u16)
-            << 8)
-            | self.frames[self.frame_count - 1]
-                .closure
-                .function
-                .chunk
-                .opcodes[self.frames[self.frame_count - 1].ip - 1] as u16;
-        base_ip as usize
+    fn read_short(&mut self) -> Result<usize, Trace> {
+        let high = self.next_number()?;
+        self.next();
+        let low = self.next_number()?;
+        self.next();
+        Ok((high << 8) | low)
     }
 
     #[inline]
-    fn is_falsy(&self) -> bool {
-        matches!(self.stack.peek(), Value::Boolean(false))
+    fn is_falsy(&self) -> Result<bool, Trace> {
+        Ok(!self.peek(1)?.is_truthy())
     }
 
     #[inline]
@@ -947,32 +1906,132 @@ impl Vm {
     }
 
     #[inline]
-    fn get_opcode(&mut self, index: usize) -> u8 {
-        self.frames[self.frame_count - 1]
-            .closure
-            .function
-            .chunk
-            .opcodes[index]
+    fn get_constant(&self) -> Result<&str, Trace> {
+        self.variable(self.next_number()?)
     }
 
+    /// Get the name at `index` in the current chunk's table of names.
     #[inline]
-    fn get_constant(&self) -> &str {
-        &*self.frames[self.frame_count - 1]
+    fn variable(&self, index: usize) -> Result<&str, Trace> {
+        self.chunk()
+            .variables
+            .get(index)
+            .map(|name| &**name)
+            .ok_or_else(|| self.invalid_operand())
+    }
+
+    /// Get the variable at `index` captured by the running closure.
+    fn upvalue(&self, index: usize) -> Result<Upvalue, Trace> {
+        self.frames[self.frame_count - 1]
             .closure
-            .function
-            .chunk
-            .variables[self.next_number()]
+            .captures
+            .borrow()
+            .get(index)
+            .cloned()
+            .ok_or_else(|| self.invalid_operand())
+    }
+
+    /// Show the debug hook the instruction about to be run.
+    fn call_debug_hook(&mut self) -> DebugAction {
+        if self.resuming {
+            self.resuming = false;
+            return DebugAction::Step;
+        }
+
+        let mut hook = match self.debug_hook.take() {
+            Some(hook) => hook,
+            None => return DebugAction::Continue,
+        };
+
+        let frame = &self.frames[self.frame_count - 1];
+        let byte = frame.closure.function.chunk.opcodes.get(frame.ip).copied();
+        let opcode = match byte.map(Opcode::try_from) {
+            Some(Ok(opcode)) => opcode,
+            // leave it to the VM to report the invalid opcode
+            _ => {
+                self.debug_hook = Some(hook);
+                return DebugAction::Step;
+            }
+        };
+        let state = DebugState {
+            opcode,
+            offset: frame.ip,
+            stack: &self.stack.stack,
+            frames: &self.frames[..self.frame_count],
+        };
+        let action = hook(&state);
+
+        self.debug_hook = Some(hook);
+        action
     }
 
     #[inline]
-    fn decode_opcode(&mut self) -> Opcode {
+    fn decode_opcode(&mut self) -> Result<Opcode, Trace> {
+        let offset = self.frames[self.frame_count - 1].ip;
         self.next();
-        Opcode::from(
-            self.frames[self.frame_count - 1]
-                .closure
-                .function
-                .chunk
-                .opcodes[self.frames[self.frame_count - 1].ip - 1],
+        let byte = match self.chunk().opcodes.get(offset) {
+            Some(byte) => *byte,
+            None => {
+                return Err(Trace::new(
+                    RuntimeError::UnexpectedEnd { offset },
+                    self.frames.clone(),
+                ))
+            }
+        };
+
+        let opcode = Opcode::try_from(byte).map_err(|byte| {
+            Trace::new(
+                RuntimeError::InvalidOpcode { byte, offset },
+                self.frames.clone(),
+            )
+        })?;
+
+        self.instruction = (opcode, offset);
+        Ok(opcode)
+    }
+
+    /// Pop a value from the stack.
+    ///
+    /// Compiled code never pops from an empty stack, but malformed bytecode can,
+    /// so this raises an error instead of panicking.
+    fn pop(&mut self) -> Result<Value, Trace> {
+        self.stack.pop().ok_or_else(|| self.stack_underflow())
+    }
+
+    /// Look at the value `depth` slots from the top of the stack, where the top
+    /// is at a depth of 1.
+    ///
+    /// Like [Vm::pop], this raises an error instead of panicking when malformed
+    /// bytecode reaches below the bottom of the stack.
+    fn peek(&self, depth: usize) -> Result<&Value, Trace> {
+        if depth == 0 || depth > self.stack.len() {
+            return Err(self.stack_underflow());
+        }
+
+        Ok(self.stack.peek_backwards(depth))
+    }
+
+    /// Pop a closure, such as a method being added to a class, from the stack.
+    fn pop_closure(&mut self) -> Result<Rc<Closure>, Trace> {
+        let value = self.pop()?;
+        value.as_closure().ok_or_else(|| self.invalid_operand())
+    }
+
+    fn stack_underflow(&self) -> Trace {
+        let (opcode, offset) = self.instruction;
+        Trace::new(
+            RuntimeError::StackUnderflow { opcode, offset },
+            self.frames.clone(),
+        )
+    }
+
+    /// Report an operand of the current instruction that is missing or refers
+    /// to something that doesn't exist.
+    fn invalid_operand(&self) -> Trace {
+        let (opcode, offset) = self.instruction;
+        Trace::new(
+            RuntimeError::InvalidOperand { opcode, offset },
+            self.frames.clone(),
         )
     }
 
@@ -1006,3 +2065,24 @@ impl Vm {
         todo!()
     }
 }
+
+/// An iterator over a snapshot of the elements of a built-in collection.
+struct ElementsIter {
+    elements: std::vec::IntoIter<Value>,
+}
+
+/// Create an iterator whose `next` method returns each of `elements` in turn,
+/// then `StopIteration`.
+fn elements_iter(elements: Vec<Value>) -> Rc<External> {
+    let iter = ElementsIter {
+        elements: elements.into_iter(),
+    };
+
+    External::builder(iter)
+        .method("next", |iter: &mut ElementsIter, _args| {
+            iter.elements
+                .next()
+                .unwrap_or_else(|| Value::Class(core::iter::stop_iteration()))
+        })
+        .build()
+}
@@ -1,6 +1,7 @@
 use kaon::{
-    common::{Opcode, Value},
-    Kaon,
+    common::{Disassembler, Opcode, Span, Value},
+    compiler::codegen::MAX_JUMP,
+    Kaon, Scope, Source,
 };
 
 #[test]
@@ -53,19 +54,289 @@ fn compile_binary() {
     );
 }
 
+#[test]
+fn dedup_constants() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("1 + 1").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::Const as u8,
+            0,
+            Opcode::Const as u8,
+            0,
+            Opcode::Add as u8,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+    assert_eq!(function.chunk.constants, vec![Box::new(Value::Float(1.0))]);
+
+    // `0` and `-0` are different constants
+    let function = kaon.compile("0 + -0 + 0").unwrap();
+    assert_eq!(function.chunk.constants.len(), 2);
+
+    let function = kaon.compile("var a = \"key\"\na = \"key\"").unwrap();
+    assert_eq!(function.chunk.variables.len(), 2);
+}
+
 #[test]
 fn compile_unary() {
     let mut kaon = Kaon::new();
-    let function = kaon.compile("-7").unwrap();
+    let function = kaon.compile("-(1 + 2)").unwrap();
     assert_eq!(
         function.chunk.opcodes,
         vec![
             Opcode::Const as u8,
             0,
+            Opcode::Const as u8,
+            1,
+            Opcode::Add as u8,
             Opcode::Negate as u8,
             Opcode::Pop as u8,
             Opcode::Halt as u8,
         ]
     );
-    assert_eq!(function.chunk.constants, vec![Box::new(Value::Float(7.0))]);
+}
+
+#[test]
+fn fold_unary() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("-7").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::Const as u8,
+            0,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+    assert_eq!(function.chunk.constants, vec![Box::new(Value::Float(-7.0))]);
+
+    let function = kaon.compile("-4 - -(-8)").unwrap();
+    assert_eq!(
+        function.chunk.constants,
+        vec![Box::new(Value::Float(8.0)), Box::new(Value::Float(-4.0))]
+    );
+    assert!(!function.chunk.opcodes.contains(&(Opcode::Negate as u8)));
+
+    let function = kaon.compile("!true").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![Opcode::False as u8, Opcode::Pop as u8, Opcode::Halt as u8]
+    );
+}
+
+#[test]
+fn compile_tuple() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("(1, 2)").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::Const as u8,
+            0,
+            Opcode::Const as u8,
+            1,
+            Opcode::Tuple as u8,
+            2,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+    assert_eq!(
+        function.chunk.constants,
+        vec![Box::new(Value::Float(2.0)), Box::new(Value::Float(1.0))]
+    );
+
+    let function = kaon.compile("(1,)").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::Const as u8,
+            0,
+            Opcode::Tuple as u8,
+            1,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+
+    // without a comma the parentheses only group
+    let function = kaon.compile("(1)").unwrap();
+    assert!(!function.chunk.opcodes.contains(&(Opcode::Tuple as u8)));
+}
+
+#[test]
+fn compile_map() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("({\"a\": 1, b: 2})").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::Const as u8,
+            0,
+            Opcode::Const as u8,
+            1,
+            Opcode::Const as u8,
+            2,
+            Opcode::String as u8,
+            0,
+            Opcode::Map as u8,
+            2,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+    assert_eq!(
+        function.chunk.constants,
+        vec![
+            Box::new(Value::Float(2.0)),
+            Box::new(Value::from("b")),
+            Box::new(Value::Float(1.0)),
+        ]
+    );
+    assert_eq!(function.chunk.variables, vec!["a".into()]);
+}
+
+#[test]
+fn compile_if_else() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("if true { 1 } else { 2 }").unwrap();
+    assert_eq!(
+        function.chunk.opcodes,
+        vec![
+            Opcode::True as u8,
+            Opcode::JumpIfFalse as u8,
+            0,
+            7,
+            Opcode::Pop as u8,
+            Opcode::Const as u8,
+            0,
+            Opcode::Pop as u8,
+            Opcode::Jump as u8,
+            0,
+            4,
+            Opcode::Pop as u8,
+            Opcode::Const as u8,
+            1,
+            Opcode::Pop as u8,
+            Opcode::Halt as u8,
+        ]
+    );
+}
+
+#[test]
+fn compile_long_jump() {
+    let mut kaon = Kaon::new();
+    let body = "1\n".repeat(MAX_JUMP / 3 + 1);
+    let tokens = kaon
+        .tokenize(Source::contents(&format!("if true {{\n{body}}}")))
+        .unwrap();
+    let ast = kaon.parse(tokens).unwrap();
+
+    assert!(kaon.compile_ast(ast, &mut Scope::new()).is_err());
+}
+
+#[test]
+fn disassemble() {
+    let mut kaon = Kaon::new();
+    let function = kaon
+        .compile("var x = \"hi\"\nfun f(a: f64): f64 {\n    return a\n}\nif true { 1 }")
+        .unwrap();
+
+    assert_eq!(
+        Disassembler::new("script", &function.chunk).disassemble_to_string(),
+        r#"== script ==
+0000 String            1 ; "hi"
+0002 DefGlobal         0 ; "x"
+0004 Closure           0 ; <fun f>
+    == f ==
+    0000 LoadLocal         0
+    0002 Return
+    0003 Pop
+    0004 Unit
+    0005 Return
+0006 DefGlobal         2 ; "f"
+0008 True
+0009 JumpIfFalse       7 ; -> 0019
+0012 Pop
+0013 Const             1 ; 1
+0015 Pop
+0016 Jump              1 ; -> 0020
+0019 Pop
+0020 Halt
+"#
+    );
+}
+
+#[test]
+fn fold_constants() {
+    let mut kaon = Kaon::new();
+    let function = kaon
+        .compile("con WIDTH = 4\ncon AREA = WIDTH * (WIDTH + 1)\nAREA")
+        .unwrap();
+
+    assert_eq!(
+        Disassembler::new("script", &function.chunk).disassemble_to_string(),
+        r#"== script ==
+0000 Const             0 ; 4
+0002 DefGlobal         0 ; "WIDTH"
+0004 Const             1 ; 20
+0006 DefGlobal         1 ; "AREA"
+0008 Const             1 ; 20
+0010 Pop
+0011 Halt
+"#
+    );
+}
+
+#[test]
+fn decode_instructions() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("var x = 1\nif x { x }").unwrap();
+
+    let instructions = function
+        .chunk
+        .instructions()
+        .map(|(offset, opcode, operands)| (offset, format!("{opcode:?}"), operands.to_vec()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        instructions,
+        vec![
+            (0, "Const".to_string(), vec![0]),
+            (2, "DefGlobal".to_string(), vec![0]),
+            (4, "GetGlobal".to_string(), vec![0]),
+            (6, "JumpIfFalse".to_string(), vec![0, 7]),
+            (9, "Pop".to_string(), vec![]),
+            (10, "GetGlobal".to_string(), vec![0]),
+            (12, "Pop".to_string(), vec![]),
+            (13, "Jump".to_string(), vec![0, 1]),
+            (16, "Pop".to_string(), vec![]),
+            (17, "Halt".to_string(), vec![]),
+        ]
+    );
+}
+
+#[test]
+fn source_map() {
+    let mut kaon = Kaon::new();
+    let source = "var x = 1\nprint(x * 2)";
+    let function = kaon.compile(source).unwrap();
+    let chunk = &function.chunk;
+
+    let (offset, _, _) = chunk
+        .instructions()
+        .find(|(_, opcode, _)| *opcode == Opcode::Mul)
+        .unwrap();
+    let span = chunk.debug_info.span_at(offset).unwrap();
+
+    assert_eq!(&source[span.start..span.end()], "x * 2");
+    assert_eq!(Span::line_index(source, span.start), (1, 6));
+
+    // consecutive opcodes compiled from the same span share an entry
+    assert!(chunk.debug_info.source_map.len() < chunk.instructions().count());
+    assert!(chunk.debug_info.span_at(chunk.opcodes.len() - 1).is_some());
 }
@@ -1,5 +1,7 @@
 use kaon::common::{Source, Span};
+use kaon::error::renderer::Renderer;
 use kaon::error::{Diagnostic, Emitter, Label};
+use termcolor::Buffer;
 
 struct MockError;
 
@@ -68,3 +70,38 @@ fn test_warning() {
 
     //MockError.emit(&[warning]);
 }
+
+/// Render `diagnostic` without color, using the given tab width.
+fn render(diagnostic: Diagnostic, tab_width: usize) -> String {
+    let mut buffer = Buffer::no_color();
+    Renderer::new(&mut buffer)
+        .with_tab_width(tab_width)
+        .render(diagnostic)
+        .unwrap();
+
+    String::from_utf8(buffer.into_inner()).unwrap()
+}
+
+#[test]
+fn test_tab_alignment() {
+    let source = Source::new("fun main() {\n\t\tvar x = y\n}", "tabs.kaon");
+    let start = source.contents.find('y').unwrap();
+
+    for tab_width in [2, 4, 8] {
+        let diagnostic = Diagnostic::error()
+            .with_message("unknown identifier `y`")
+            .with_labels(vec![
+                Label::primary(Span::new(start, 1, &source)).with_message("not found")
+            ]);
+
+        let output = render(diagnostic, tab_width);
+        let lines = output.lines().collect::<Vec<_>>();
+        let code = lines.iter().find(|line| line.contains("var x")).unwrap();
+        let caret = lines.iter().find(|line| line.contains('^')).unwrap();
+
+        assert!(!code.contains('\t'));
+        assert!(code.contains(&format!("{}var x = y", " ".repeat(2 * tab_width))));
+        assert_eq!(caret.find('^'), code.find('y'));
+        assert_eq!(caret.matches('^').count(), 1);
+    }
+}
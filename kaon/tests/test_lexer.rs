@@ -1,6 +1,7 @@
 use kaon::common::{Source, Span};
 use kaon::compiler::token::Literal;
 use kaon::compiler::{Lexer, TokenType};
+use kaon::error::Error;
 
 use std::rc::Rc;
 
@@ -73,6 +74,24 @@ fn tokenize_eof() {
     );
 }
 
+#[test]
+fn tokenize_blank_input() {
+    for input in ["", "// comment", "\n\n\n"] {
+        let (mut lexer, _) = new_lexer(input);
+        let tokens = lexer.tokenize().unwrap().node;
+
+        // newlines may be kept, but nothing else
+        assert_eq!(tokens.last().map(|(token, _)| token), Some(&TokenType::eof()));
+        assert!(tokens[..tokens.len() - 1]
+            .iter()
+            .all(|(token, _)| *token == TokenType::delimiter("\\n")));
+    }
+
+    // a trailing `.` doesn't look past the end of the input
+    let (mut lexer, _) = new_lexer("1.");
+    assert!(lexer.tokenize().is_ok());
+}
+
 #[test]
 fn tokenize_bin_op() {
     let (mut lexer, source) = new_lexer("1 + 2");
@@ -97,3 +116,132 @@ fn tokenize_bin_op() {
     );
     assert_eq!(tokens[3], (TokenType::eof(), Span::new(5, 0, &source)));
 }
+
+#[test]
+fn tokenize_multibyte_string() {
+    let (mut lexer, source) = new_lexer("\"héllo\" 1");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(
+        tokens[0],
+        (
+            TokenType::Literal(Literal::StringLiteral("héllo".to_string())),
+            Span::new(0, 8, &source)
+        )
+    );
+    assert_eq!(
+        tokens[1],
+        (
+            TokenType::Literal(Literal::NumberLiteral("1".to_string())),
+            Span::new(9, 1, &source)
+        )
+    );
+}
+
+#[test]
+fn tokenize_strings() {
+    let string = |value: &str| TokenType::Literal(Literal::StringLiteral(value.to_string()));
+
+    let (mut lexer, source) = new_lexer("\"\"");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(tokens[0], (string(""), Span::new(0, 2, &source)));
+    assert_eq!(tokens[1], (TokenType::eof(), Span::new(2, 0, &source)));
+
+    // the value excludes the quotes, while the span covers them
+    let (mut lexer, source) = new_lexer("\"ab\".len");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(tokens[0], (string("ab"), Span::new(0, 4, &source)));
+    assert_eq!(tokens[1], (TokenType::symbol("."), Span::new(4, 1, &source)));
+
+    let (mut lexer, source) = new_lexer("(\"日本\")");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(tokens[1], (string("日本"), Span::new(1, 8, &source)));
+    assert_eq!(
+        tokens[2],
+        (TokenType::delimiter(")"), Span::new(9, 1, &source))
+    );
+
+    // the span covers escapes as written, not as decoded
+    let (mut lexer, source) = new_lexer("\"é\\t\\\"\\u{1F600}\" 1");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(tokens[0], (string("é\t\"😀"), Span::new(0, 17, &source)));
+    assert_eq!(
+        tokens[1],
+        (
+            TokenType::Literal(Literal::NumberLiteral("1".to_string())),
+            Span::new(18, 1, &source)
+        )
+    );
+}
+
+#[test]
+fn invalid_strings() {
+    let (mut lexer, source) = new_lexer("\"é\\q\"");
+    match lexer.tokenize() {
+        Err(Error::UnknownEscapeCode(item)) => {
+            assert_eq!(item.content, "q");
+            assert_eq!(item.span, Span::new(4, 1, &source));
+        }
+        Err(error) => panic!("expected an unknown escape, found {error:?}"),
+        Ok(_) => panic!("expected an unknown escape"),
+    }
+
+    let (mut lexer, source) = new_lexer("\"a\\u{D800}\"");
+    match lexer.tokenize() {
+        Err(Error::InvalidUnicodeEscape(item)) => {
+            assert_eq!(item.content, "\\u{D800}");
+            assert_eq!(item.span, Span::new(2, 8, &source));
+        }
+        Err(error) => panic!("expected an invalid unicode escape, found {error:?}"),
+        Ok(_) => panic!("expected an invalid unicode escape"),
+    }
+
+    let escapes = ["\\u", "\\u{}", "\\u{zz}", "\\u{+41}", "\\u{1234567}", "\\u{110000}", "\\u{41"];
+    for escape in escapes {
+        let (mut lexer, _) = new_lexer(&format!("\"{escape}\""));
+        assert!(
+            matches!(lexer.tokenize(), Err(Error::InvalidUnicodeEscape(_))),
+            "expected `{escape}` to be invalid"
+        );
+    }
+
+    let (mut lexer, source) = new_lexer("\"ab\\\"");
+    match lexer.tokenize() {
+        Err(Error::UnterminatedString(item)) => assert_eq!(item.span, Span::new(0, 5, &source)),
+        Err(error) => panic!("expected an unterminated string, found {error:?}"),
+        Ok(_) => panic!("expected an unterminated string"),
+    }
+}
+
+#[test]
+fn tokenize_range() {
+    let (mut lexer, source) = new_lexer("1..3");
+    let tokens = lexer.tokenize().unwrap().node;
+    assert_eq!(
+        tokens[1],
+        (TokenType::symbol(".."), Span::new(1, 2, &source))
+    );
+}
+
+#[test]
+fn tokenize_shifts() {
+    let (mut lexer, _) = new_lexer("<< >> <= < >= >");
+    let tokens = lexer
+        .tokenize()
+        .unwrap()
+        .node
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        tokens[..6],
+        [
+            TokenType::symbol("<<"),
+            TokenType::symbol(">>"),
+            TokenType::symbol("<="),
+            TokenType::symbol("<"),
+            TokenType::symbol(">="),
+            TokenType::symbol(">"),
+        ]
+    );
+}
@@ -1,3 +1,4 @@
+use kaon::compiler::ast::Pattern;
 use kaon::compiler::{ASTNode, BinExpr, Expr, Stmt, Op, AST, Lexer, Parser};
 use kaon::common::{Span, Source};
 use kaon::error::Error;
@@ -47,3 +48,542 @@ fn test_parser() {
     let res = new_parser(input);
     assert_eq!(res.is_ok(), true);
 }
+
+/// Render an expression as a fully parenthesized string, ignoring spans.
+fn grouping(expr: &Expr) -> String {
+    match expr {
+        Expr::BinExpr(bin, _) => {
+            format!("({} {:?} {})", grouping(&bin.lhs), bin.op, grouping(&bin.rhs))
+        }
+        Expr::And(lhs, rhs, _) => format!("({} and {})", grouping(lhs), grouping(rhs)),
+        Expr::Or(lhs, rhs, _) => format!("({} or {})", grouping(lhs), grouping(rhs)),
+        Expr::ParenExpr(expr, _) => grouping(expr),
+        Expr::UnaryExpr(op, expr, _) => format!("({:?} {})", op, grouping(expr)),
+        Expr::Number(val, _) => val.to_string(),
+        Expr::Identifier(ident) => ident.name.clone(),
+        expr => panic!("unexpected expression {expr:?}"),
+    }
+}
+
+fn parse_expr(input: &str) -> String {
+    let (ast, _) = new_parser(input).unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Expr(expr)) => grouping(expr),
+        node => panic!("expected an expression, found {node:?}"),
+    }
+}
+
+#[test]
+fn bitwise_precedence() {
+    assert_eq!(parse_expr("1 | 2 & 3"), "(1 BitwiseOr (2 BitwiseAnd 3))");
+    assert_eq!(parse_expr("1 & 2 | 3"), "((1 BitwiseAnd 2) BitwiseOr 3)");
+    assert_eq!(parse_expr("1 ^ 2 & 3"), "(1 BitwiseXor (2 BitwiseAnd 3))");
+    assert_eq!(parse_expr("1 | 2 ^ 3"), "(1 BitwiseOr (2 BitwiseXor 3))");
+    assert_eq!(parse_expr("a == b & c"), "((a EqualTo b) BitwiseAnd c)");
+    assert_eq!(parse_expr("a & b + c"), "(a BitwiseAnd (b Add c))");
+    assert_eq!(parse_expr("a and b | c or d"), "((a and (b BitwiseOr c)) or d)");
+}
+
+#[test]
+fn shift_precedence() {
+    assert_eq!(parse_expr("1 << 4 == 16"), "((1 Shl 4) EqualTo 16)");
+    assert_eq!(parse_expr("1 << n + 1"), "(1 Shl (n Add 1))");
+    assert_eq!(parse_expr("a >> 1 & b"), "((a Shr 1) BitwiseAnd b)");
+    assert_eq!(parse_expr("1 << 2 >> 1"), "((1 Shl 2) Shr 1)");
+
+    let (ast, _) = new_parser("var x: List<List<f64>> = []").unwrap();
+    assert!(matches!(ast.nodes[0], ASTNode::Stmt(Stmt::VarDeclaration(..))));
+}
+
+#[test]
+fn unary_precedence() {
+    assert_eq!(parse_expr("-2 ** 2"), "(Subtract (2 Power 2))");
+    assert_eq!(parse_expr("2 ** -1"), "(2 Power (Subtract 1))");
+    assert_eq!(parse_expr("-a * b"), "((Subtract a) Multiply b)");
+    assert_eq!(parse_expr("--x"), "(Subtract (Subtract x))");
+    assert_eq!(parse_expr("!a == b"), "((Bang a) EqualTo b)");
+    assert_eq!(parse_expr("-4 - -8"), "((Subtract 4) Subtract (Subtract 8))");
+}
+
+/// The first expression in `input`.
+fn first_expr(input: &str) -> Expr {
+    let (ast, _) = new_parser(input).unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Expr(expr)) => expr.clone(),
+        node => panic!("expected an expression, found {node:?}"),
+    }
+}
+
+#[test]
+fn op_from_symbol() {
+    let ops = [
+        Op::Add, Op::Subtract, Op::Multiply, Op::Divide, Op::Remainder, Op::Power,
+        Op::GreaterThan, Op::GreaterThanEquals, Op::LessThan, Op::LessThanEquals,
+        Op::EqualTo, Op::NotEqual, Op::BitwiseAnd, Op::BitwiseOr, Op::BitwiseXor,
+        Op::Shl, Op::Shr, Op::Is, Op::Isnt, Op::Bang,
+    ];
+    for op in ops {
+        assert_eq!(Op::try_from(&op.to_string()[..]), Ok(op));
+    }
+
+    assert_eq!(Op::try_from("+="), Err("+=".to_string()));
+    assert_eq!(Op::try_from(""), Err(String::new()));
+}
+
+#[test]
+fn chained_comparison() {
+    let number = |expr: &Expr| match expr {
+        Expr::Number(val, _) => *val,
+        expr => panic!("expected a number, found {expr:?}"),
+    };
+
+    match first_expr("1 < x <= 10") {
+        Expr::Chain(first, rest, _) => {
+            assert_eq!(number(&first), 1.0);
+            assert_eq!(rest.len(), 2);
+            assert_eq!(rest[0].0, Op::LessThan);
+            assert!(matches!(&rest[0].1, Expr::Identifier(ident) if ident.name == "x"));
+            assert_eq!(rest[1].0, Op::LessThanEquals);
+            assert_eq!(number(&rest[1].1), 10.0);
+        }
+        expr => panic!("expected a chain, found {expr:?}"),
+    }
+
+    // every operand of a chain is one of its comparisons' operands
+    match first_expr("a == b != c > d >= e") {
+        Expr::Chain(_, rest, _) => {
+            let ops: Vec<Op> = rest.iter().map(|(op, _)| op.clone()).collect();
+            assert_eq!(
+                ops,
+                [Op::EqualTo, Op::NotEqual, Op::GreaterThan, Op::GreaterThanEquals]
+            );
+        }
+        expr => panic!("expected a chain, found {expr:?}"),
+    }
+
+    // a single comparison is still a binary expression, and `is` doesn't chain
+    assert!(matches!(first_expr("a < b"), Expr::BinExpr(..)));
+    match first_expr("a < b < c is D") {
+        Expr::BinExpr(bin_expr, _) => {
+            assert_eq!(bin_expr.op, Op::Is);
+            assert!(matches!(bin_expr.lhs, Expr::Chain(..)));
+        }
+        expr => panic!("expected `is`, found {expr:?}"),
+    }
+}
+
+#[test]
+fn class_membership() {
+    let op = |input: &str| match first_expr(input) {
+        Expr::BinExpr(bin_expr, _) => bin_expr.op,
+        expr => panic!("expected a binary expression, found {expr:?}"),
+    };
+
+    assert_eq!(op("a is b"), Op::Is);
+    assert_eq!(op("a isnt b"), Op::Isnt);
+
+    // `isnt` binds like the other comparisons
+    match first_expr("a isnt B or c") {
+        Expr::Or(lhs, _, _) => {
+            assert!(matches!(*lhs, Expr::BinExpr(bin_expr, _) if bin_expr.op == Op::Isnt))
+        }
+        expr => panic!("expected `or`, found {expr:?}"),
+    }
+}
+
+#[test]
+fn trailing_commas() {
+    assert!(matches!(first_expr("[1, 2,]"), Expr::List(list, _) if list.len() == 2));
+    assert!(matches!(first_expr("(1, 2,)"), Expr::Tuple(tuple, _) if tuple.len() == 2));
+    assert!(matches!(first_expr("(1,)"), Expr::Tuple(tuple, _) if tuple.len() == 1));
+    assert!(matches!(first_expr("(1)"), Expr::ParenExpr(..)));
+    assert!(matches!(first_expr("f(\n    1,\n    2,\n)"), Expr::FunCall(_, args, _) if args.len() == 2));
+
+    let (ast, _) = new_parser("var m = {\"a\": 1, \"b\": 2,}").unwrap();
+    assert!(matches!(
+        &ast.nodes[0],
+        ASTNode::Stmt(Stmt::VarDeclaration(_, Some(Expr::Map(map, _)), ..)) if map.len() == 2
+    ));
+
+    let (ast, _) = new_parser("fun add(a, b,) {\n    return a + b\n}").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Function(fun, _)) => assert_eq!(fun.params.len(), 2),
+        node => panic!("expected a function, found {node:?}"),
+    }
+}
+
+#[test]
+fn line_continuation() {
+    assert_eq!(parse_expr("(1\n + 2\n)"), "(1 Add 2)");
+    assert_eq!(parse_expr("1 +\n 2 *\n 3"), "(1 Add (2 Multiply 3))");
+    assert_eq!(parse_expr("a and\n b or\n c"), "((a and b) or c)");
+
+    let (ast, _) = new_parser("1\n+ 2").unwrap();
+    assert_eq!(ast.nodes.len(), 2);
+}
+
+#[test]
+fn power_precedence() {
+    assert_eq!(parse_expr("2 ** 3 ** 2"), "(2 Power (3 Power 2))");
+    assert_eq!(parse_expr("2 * 3 ** 2"), "(2 Multiply (3 Power 2))");
+    assert_eq!(parse_expr("2 ** 3 * 2"), "((2 Power 3) Multiply 2)");
+    assert_eq!(parse_expr("a * b"), "(a Multiply b)");
+}
+
+#[test]
+fn report_multiple_errors() {
+    let input = "var = 1\nvar x = 2\nfun f() {\n    x = * 3\n    return x\n}\nprint(x)";
+    let source = Source::new(input, "./main");
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let errors = Parser::new(tokens).parse().unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(&errors[0], Error::UnexpectedToken(item) if item.content == "="));
+    assert!(matches!(&errors[1], Error::UnexpectedToken(item) if item.content == "*"));
+
+    // the blocks left open at the end of the file are only reported once
+    let input = "fun f() {\n    if x {\n        x = * 3\n    while true {\n";
+    let source = Source::new(input, "./main");
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let errors = Parser::new(tokens).parse().unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(&errors[0], Error::UnexpectedToken(item) if item.content == "*"));
+    assert!(matches!(&errors[1], Error::UnexpectedEOF(_)));
+}
+
+#[test]
+fn interleaved_comments() {
+    let input = "// leading comment\n\
+        var x = 1 // trailing comment\n\
+        // between statements\n\
+        fun f(a) {\n\
+            // inside a block\n\
+            return a // after a return\n\
+            // before the closing brace\n\
+        }\n\
+        class Point {\n\
+            // inside a class\n\
+            var x = 0\n\
+        }\n\
+        print(x) // a call\n\
+        // trailing comment at the end of the file";
+    let (ast, _) = new_parser(input).unwrap();
+
+    assert_eq!(ast.nodes.len(), 4);
+    assert!(matches!(ast.nodes[0], ASTNode::Stmt(Stmt::VarDeclaration(..))));
+    assert!(matches!(ast.nodes[1], ASTNode::Stmt(Stmt::Function(..))));
+    assert!(matches!(ast.nodes[2], ASTNode::Stmt(Stmt::Class(..))));
+    assert!(matches!(ast.nodes[3], ASTNode::Stmt(Stmt::Expr(..))));
+}
+
+#[test]
+fn enum_declaration() {
+    let (ast, _) = new_parser("enum Color {\n    Red,\n    Green\n    Rgb(r, g, b),\n}").unwrap();
+    let enum_ = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Enum(enum_, _)) => enum_,
+        node => panic!("expected an enum, found {node:?}"),
+    };
+
+    assert_eq!(enum_.name.name, "Color");
+
+    let variants = enum_
+        .variants
+        .iter()
+        .map(|variant| (variant.name.name.as_str(), variant.fields.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(variants, [("Red", 0), ("Green", 0), ("Rgb", 3)]);
+
+    let (ast, _) = new_parser("enum Unit { One }").unwrap();
+    assert!(matches!(&ast.nodes[0], ASTNode::Stmt(Stmt::Enum(enum_, _)) if enum_.variants.len() == 1));
+}
+
+#[test]
+fn match_arms() {
+    let input = "match color {\n    Color.Rgb(r, _, b) => print(r)\n    1 => {}\n    _ => {}\n}";
+    let (ast, _) = new_parser(input).unwrap();
+    let arms = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Match(match_, _)) => &match_.1,
+        node => panic!("expected a match, found {node:?}"),
+    };
+
+    assert_eq!(arms.len(), 3);
+    match &arms[0].pattern {
+        Pattern::Variant(Expr::Identifier(enum_), name, bindings, _) => {
+            assert_eq!(enum_.name, "Color");
+            assert_eq!(name.name, "Rgb");
+            assert_eq!(bindings.len(), 3);
+        }
+        pattern => panic!("expected a variant pattern, found {pattern:?}"),
+    }
+    assert!(matches!(&arms[1].pattern, Pattern::Literal(Expr::Number(..))));
+    assert!(matches!(&arms[2].pattern, Pattern::Wildcard(_)));
+}
+
+#[test]
+fn loop_expression() {
+    let (ast, _) = new_parser("var x = loop {\n    break 42\n}").unwrap();
+    let body = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::VarDeclaration(_, Some(Expr::Loop(body, _)), _, _)) => body,
+        node => panic!("expected a loop expression, found {node:?}"),
+    };
+
+    match &**body {
+        Stmt::Block(stmts, _) => {
+            assert!(matches!(&stmts[0], Stmt::Break(None, Some(Expr::Number(val, _)), _) if *val == 42.0))
+        }
+        stmt => panic!("expected a block, found {stmt:?}"),
+    }
+
+    let (ast, _) = new_parser("loop {\n    break\n}").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::LoopStatement(body, None, _)) => {
+            assert!(matches!(&**body, Stmt::Block(stmts, _) if matches!(stmts[0], Stmt::Break(None, None, _))))
+        }
+        node => panic!("expected a loop statement, found {node:?}"),
+    }
+}
+
+#[test]
+fn loop_labels() {
+    let input = "outer: while true {\n    inner: loop {\n        continue outer\n        break inner\n    }\n    break outer 1\n}";
+    let (ast, _) = new_parser(input).unwrap();
+
+    let body = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::WhileStatement(_, body, Some(label), _)) => {
+            assert_eq!(label.name, "outer");
+            body
+        }
+        node => panic!("expected a labeled while loop, found {node:?}"),
+    };
+
+    let stmts = match &**body {
+        Stmt::Block(stmts, _) => stmts,
+        stmt => panic!("expected a block, found {stmt:?}"),
+    };
+
+    let inner = match &stmts[0] {
+        Stmt::LoopStatement(body, Some(label), _) => {
+            assert_eq!(label.name, "inner");
+            body
+        }
+        stmt => panic!("expected a labeled loop, found {stmt:?}"),
+    };
+    match &**inner {
+        Stmt::Block(stmts, _) => {
+            assert!(matches!(&stmts[0], Stmt::Continue(Some(label), _) if label.name == "outer"));
+            assert!(matches!(&stmts[1], Stmt::Break(Some(label), None, _) if label.name == "inner"));
+        }
+        stmt => panic!("expected a block, found {stmt:?}"),
+    }
+
+    assert!(matches!(
+        &stmts[1],
+        Stmt::Break(Some(label), Some(Expr::Number(..)), _) if label.name == "outer"
+    ));
+
+    // outside of a loop labeled `x`, `x` is the value to break with
+    let (ast, _) = new_parser("loop {\n    break x\n}").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::LoopStatement(body, None, _)) => assert!(matches!(
+            &**body,
+            Stmt::Block(stmts, _) if matches!(&stmts[0], Stmt::Break(None, Some(Expr::Identifier(_)), _))
+        )),
+        node => panic!("expected a loop, found {node:?}"),
+    }
+}
+
+/// The source text covered by the span of the first expression in `input`.
+fn expr_source(input: &str) -> Vec<String> {
+    let (ast, _) = new_parser(input).unwrap();
+    let expr = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Expr(expr)) => expr,
+        node => panic!("expected an expression, found {node:?}"),
+    };
+
+    let text = |span: Span| input[span.start..span.end()].to_string();
+    match expr {
+        Expr::BinExpr(bin, span) => vec![
+            text(span.clone()),
+            text(bin.lhs.span()),
+            text(bin.rhs.span()),
+        ],
+        Expr::FunCall(callee, _, span) => vec![text(span.clone()), text(callee.span())],
+        expr => vec![text(expr.span())],
+    }
+}
+
+#[test]
+fn compound_spans() {
+    assert_eq!(expr_source("a * b + c\nd"), ["a * b + c", "a * b", "c"]);
+    assert_eq!(expr_source("1 + 2 * 3 // three"), ["1 + 2 * 3", "1", "2 * 3"]);
+    assert_eq!(expr_source("x == f(1)\n"), ["x == f(1)", "x", "f(1)"]);
+    assert_eq!(expr_source("add(1, 2) // sum"), ["add(1, 2)", "add"]);
+    assert_eq!(expr_source("list[0](a)[1]\n"), ["list[0](a)[1]"]);
+    assert_eq!(expr_source("-x + 1"), ["-x + 1", "-x", "1"]);
+    assert_eq!(expr_source("a and b or c\n"), ["a and b or c"]);
+    assert_eq!(expr_source("p.x\n"), ["p.x"]);
+    assert_eq!(expr_source("b.add(1).add(2)\n"), ["b.add(1).add(2)", "b.add(1).add"]);
+}
+
+#[test]
+fn spread() {
+    let (ast, _) = new_parser("f(1, ...args)").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Expr(Expr::FunCall(_, args, _))) => {
+            assert!(matches!(args[0], Expr::Number(..)));
+            assert!(matches!(&args[1], Expr::Spread(expr, _) if matches!(**expr, Expr::Identifier(..))));
+        }
+        node => panic!("expected a call, found {node:?}"),
+    }
+
+    let (ast, _) = new_parser("[...a, ...b]").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::Expr(Expr::List(items, _))) => {
+            assert!(items.iter().all(|item| matches!(item, Expr::Spread(..))))
+        }
+        node => panic!("expected a list, found {node:?}"),
+    }
+}
+
+#[test]
+fn semicolons() {
+    let (ast, _) = new_parser("a = 1; b = 2").unwrap();
+    assert_eq!(ast.nodes.len(), 2);
+    assert!(ast
+        .nodes
+        .iter()
+        .all(|node| matches!(node, ASTNode::Stmt(Stmt::AssignStatement(..)))));
+
+    // empty statements are skipped
+    let (ast, _) = new_parser(";;\nvar a = 1;;\n{ ; a = 2; }").unwrap();
+    assert_eq!(ast.nodes.len(), 2);
+    match &ast.nodes[1] {
+        ASTNode::Stmt(Stmt::Block(stmts, _)) => assert_eq!(stmts.len(), 1),
+        node => panic!("expected a block, found {node:?}"),
+    }
+}
+
+#[test]
+fn for_loop() {
+    let (ast, _) = new_parser("for x in xs {\n    x\n}").unwrap();
+    match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::ForStatement(ident, Expr::Identifier(iter), _, None, _)) => {
+            assert_eq!(ident.name, "x");
+            assert_eq!(iter.name, "xs");
+        }
+        node => panic!("expected a for loop, found {node:?}"),
+    }
+
+    let (ast, _) = new_parser("outer: for x in xs {\n    break outer\n}").unwrap();
+    assert!(matches!(
+        &ast.nodes[0],
+        ASTNode::Stmt(Stmt::ForStatement(_, _, _, Some(label), _)) if label.name == "outer"
+    ));
+}
+
+#[test]
+fn else_if_chain() {
+    let (ast, _) = new_parser("if a {\n    1\n} else if b {\n    2\n} else {\n    3\n}").unwrap();
+    assert_eq!(ast.nodes.len(), 1);
+
+    let alternate = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::IfStatement(Expr::Identifier(a), body, _)) => {
+            assert_eq!(a.name, "a");
+            assert!(matches!(body.0, Stmt::Block(..)));
+            body.1.as_ref().expect("expected an else branch")
+        }
+        node => panic!("expected an if statement, found {node:?}"),
+    };
+
+    // the `else if` is an if statement in the else branch, not a block around one
+    match alternate {
+        Stmt::IfStatement(Expr::Identifier(b), body, _) => {
+            assert_eq!(b.name, "b");
+            assert!(matches!(body.1, Some(Stmt::Block(..))));
+        }
+        stmt => panic!("expected an if statement, found {stmt:?}"),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ast_to_json() {
+    let (ast, _) = new_parser("var x = 1 + 2").unwrap();
+    let json = ast.to_json();
+
+    let expected = concat!(
+        r#"{"nodes":[{"Stmt":{"VarDeclaration":[{"name":"x","span":{"start":4,"length":1}},"#,
+        r#"{"BinExpr":[{"op":"Add","lhs":{"Number":[1.0,{"start":8,"length":1}]},"#,
+        r#""rhs":{"Number":[2.0,{"start":12,"length":1}]}}"#,
+    );
+    assert!(json.starts_with(expected), "unexpected json: {json}");
+    assert!(json.contains(r#""span":{"start":0,"length":13}"#), "unexpected json: {json}");
+}
+
+#[test]
+fn blank_input() {
+    for input in ["", "// comment", "\n\n\n"] {
+        let (ast, _) = new_parser(input).unwrap();
+        assert!(ast.nodes.is_empty(), "expected no nodes for {input:?}");
+    }
+}
+
+#[test]
+fn chained_assignment() {
+    let (ast, _) = new_parser("a = b = c = 0").unwrap();
+
+    let (target, value) = match &ast.nodes[0] {
+        ASTNode::Stmt(Stmt::AssignStatement(target, value, _)) => (target, value),
+        node => panic!("expected an assignment, found {node:?}"),
+    };
+    assert!(matches!(target, Expr::Identifier(ident) if ident.name == "a"));
+
+    // `=` is right-associative, so `b` is assigned before `a`
+    match value {
+        Expr::Assign(target, value, _) => {
+            assert!(matches!(&**target, Expr::Identifier(ident) if ident.name == "b"));
+            assert!(matches!(
+                &**value,
+                Expr::Assign(target, value, _)
+                    if matches!(&**target, Expr::Identifier(ident) if ident.name == "c")
+                        && matches!(**value, Expr::Number(..))
+            ));
+        }
+        expr => panic!("expected a chained assignment, found {expr:?}"),
+    }
+
+    for input in ["1 = x", "a = b + 1 = 2", "a[0:1] = [1]"] {
+        let source = Source::new(input, "./main");
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+
+        assert!(matches!(&errors[0], Error::InvalidAssignment(_)), "{input}");
+    }
+}
+
+#[test]
+fn uninitialized_var() {
+    let (ast, _) = new_parser("var x\nvar y: f64\nx = 1").unwrap();
+    assert!(matches!(
+        &ast.nodes[0],
+        ASTNode::Stmt(Stmt::VarDeclaration(ident, None, None, _)) if ident.name == "x"
+    ));
+    assert!(matches!(
+        &ast.nodes[1],
+        ASTNode::Stmt(Stmt::VarDeclaration(ident, None, Some(Expr::Type(..)), _))
+            if ident.name == "y"
+    ));
+
+    // a constant still needs a value
+    for input in ["con x", "con x: f64\nvar y = 1"] {
+        let source = Source::new(input, "./main");
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+
+        assert!(
+            matches!(&errors[0], Error::MissingInitializer(name) if name.content == "x"),
+            "{input}"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use kaon::compiler::{Lexer, Parser, Resolver};
+use kaon::common::Source;
+use kaon::error::{Error, Warning};
+
+fn resolve(input: &str) -> Resolver {
+    let source = Source::new(input, "./main");
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let ast = Parser::new(tokens).parse().unwrap();
+
+    let mut resolver = Resolver::default();
+    resolver.resolve_ast(&ast);
+    resolver
+}
+
+#[test]
+fn unused_local() {
+    let resolver = resolve(
+        "fun main() {
+            var used = 1
+            var unused = 2
+            var _ignored = 3
+            print(used)
+        }",
+    );
+
+    assert_eq!(resolver.warnings.len(), 1);
+    match &resolver.warnings[0] {
+        Warning::UnusedVariable(item) => assert_eq!(item.content, "unused"),
+        warning => panic!("unexpected warning: {warning:?}"),
+    }
+}
+
+#[test]
+fn unreachable_after_return() {
+    let script = "fun main() {
+            return 1
+            print(2)
+        }";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.warnings.len(), 1);
+    match &resolver.warnings[0] {
+        Warning::UnreachableCode(item) => {
+            assert_eq!(item.content, "return");
+            assert_eq!(item.span.start, script.find("print(2)").unwrap());
+        }
+        warning => panic!("unexpected warning: {warning:?}"),
+    }
+}
+
+#[test]
+fn globals_are_not_reported() {
+    let resolver = resolve("var x = 1");
+
+    assert!(resolver.warnings.is_empty());
+}
+
+#[test]
+fn use_before_declaration() {
+    let script = "print(x)\nvar x = 1";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::UseBeforeDeclaration(reference, declaration) => {
+            assert_eq!(reference.content, "x");
+            assert_eq!(reference.span.start, 6);
+            assert_eq!(declaration.span.start, script.rfind('x').unwrap());
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn forward_reference_from_function() {
+    let resolver = resolve(
+        "fun main() {
+            print(x)
+        }
+        var x = 1
+        main()",
+    );
+
+    assert!(resolver.errors.is_empty());
+}
+
+#[test]
+fn shadowed_variable() {
+    let source = Source::new(
+        "fun main() {
+            var x = 1
+            {
+                var x = 2
+                print(x)
+            }
+            print(x)
+        }",
+        "./main",
+    );
+    let tokens = Lexer::new(source).tokenize().unwrap();
+    let ast = Parser::new(tokens).parse().unwrap();
+
+    let mut resolver = Resolver::default();
+    resolver.warn_shadowing = true;
+    resolver.resolve_ast(&ast);
+
+    assert_eq!(resolver.warnings.len(), 1);
+    match &resolver.warnings[0] {
+        Warning::ShadowedVariable(shadow, original) => {
+            assert_eq!(shadow.content, "x");
+            assert!(original.span.start < shadow.span.start);
+        }
+        warning => panic!("unexpected warning: {warning:?}"),
+    }
+}
+
+#[test]
+fn assign_to_constant() {
+    let script = "con x = 1\nx = 2";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::AssignToConstant(assignment, declaration) => {
+            assert_eq!(assignment.content, "x");
+            assert_eq!(assignment.span.start, script.rfind('x').unwrap());
+            assert_eq!(declaration.span.start, 4);
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn assign_to_variable() {
+    let resolver = resolve(
+        "var x = 1
+        x = 2
+        con y = 1
+        fun f() {
+            var y = 2
+            y = 3
+            return y
+        }",
+    );
+
+    assert!(resolver.errors.is_empty());
+}
+
+#[test]
+fn continue_outside_loop() {
+    let script = "while true {\n    fun f() {\n        continue\n    }\n    break\n}\ncontinue";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 2);
+    match &resolver.errors[0] {
+        Error::OutsideLoop(stmt) => {
+            assert_eq!(stmt.content, "continue");
+            assert_eq!(stmt.span.start, script.find("continue").unwrap());
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+    match &resolver.errors[1] {
+        Error::OutsideLoop(stmt) => {
+            assert_eq!(stmt.span.start, script.rfind("continue").unwrap())
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+
+    let resolver = resolve("loop {\n    if true {\n        continue\n    }\n    break\n}");
+    assert!(resolver.errors.is_empty());
+}
+
+#[test]
+fn return_outside_function() {
+    for script in ["return 1", "if true {\n    return\n}", "loop {\n    return 1\n}"] {
+        let resolver = resolve(script);
+
+        assert_eq!(resolver.errors.len(), 1, "{script}");
+        match &resolver.errors[0] {
+            Error::OutsideFunction(stmt) => {
+                assert_eq!(stmt.content, "return");
+                assert_eq!(stmt.span.start, script.find("return").unwrap());
+            }
+            error => panic!("unexpected error: {error:?}"),
+        }
+    }
+
+    let resolver = resolve("fun f() {\n    loop {\n        return 1\n    }\n}");
+    assert!(resolver.errors.is_empty());
+}
+
+#[test]
+fn break_outside_loop() {
+    let script = "if true {\n    break\n}";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::OutsideLoop(stmt) => {
+            assert_eq!(stmt.content, "break");
+            assert_eq!(stmt.span.start, script.find("break").unwrap());
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn unknown_loop_label() {
+    let script = "outer: loop {\n    loop {\n        continue inner\n    }\n}";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::UnknownLabel(label) => {
+            assert_eq!(label.content, "inner");
+            assert_eq!(label.span.start, script.find("inner").unwrap());
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn duplicate_parameter() {
+    for script in [
+        "fun f(x, y, x) {\n    return y\n}",
+        "class A {\n    create new(x, x) {\n    }\n}",
+        "class A {\n    fun f(y, x, x) {\n    }\n}",
+    ] {
+        let resolver = resolve(script);
+
+        assert_eq!(resolver.errors.len(), 1, "{script}");
+        match &resolver.errors[0] {
+            Error::DuplicateIdentifier(original, duplicate) => {
+                assert_eq!(duplicate.content, "x");
+                assert_eq!(original.span.start, script.find('x').unwrap());
+                assert_eq!(duplicate.span.start, script.rfind('x').unwrap());
+            }
+            error => panic!("unexpected error: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn duplicate_field() {
+    let script = "class Point {\n    var x = 0\n    var y = 0\n    var x = 1\n}";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::DuplicateIdentifier(original, duplicate) => {
+            assert_eq!(duplicate.content, "x");
+            assert_eq!(original.span.start, script.find('x').unwrap());
+            assert_eq!(duplicate.span.start, script.rfind('x').unwrap());
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn non_exhaustive_match() {
+    let script = "match true {\n    true => print(1)\n}";
+    let resolver = resolve(script);
+
+    assert!(resolver.errors.is_empty());
+    match &resolver.warnings[..] {
+        [Warning::NonExhaustiveMatch(expr, missing)] => {
+            assert_eq!(expr.span.start, script.find("true").unwrap());
+            assert_eq!(missing, &["false".to_string()]);
+        }
+        warnings => panic!("unexpected warnings: {warnings:?}"),
+    }
+
+    let shape = "enum Shape { Circle(r), Rect(w, h), Empty }\n";
+    let resolver = resolve(&format!(
+        "{shape}match Shape.Empty {{\n    Shape.Circle(r) => print(r)\n    Shape.Empty => print(0)\n}}"
+    ));
+    assert!(matches!(
+        &resolver.warnings[..],
+        [Warning::NonExhaustiveMatch(_, missing)] if missing == &["Shape.Rect".to_string()]
+    ));
+
+    // numbers can't all be listed, so only a wildcard covers them
+    let resolver = resolve("match 1 {\n    1 => print(1)\n}");
+    assert!(matches!(
+        &resolver.warnings[..],
+        [Warning::NonExhaustiveMatch(_, missing)] if missing.is_empty()
+    ));
+
+    for script in [
+        "match true {\n    true => print(1)\n    false => print(0)\n}",
+        "match 1 {\n    1 => print(1)\n    _ => print(0)\n}",
+    ] {
+        let resolver = resolve(script);
+        assert!(resolver.warnings.is_empty(), "{script}");
+    }
+}
+
+#[test]
+fn unreachable_match_arm() {
+    for (script, earlier, arm) in [
+        ("match 1 {\n    _ => print(0)\n    1 => print(1)\n}", "_", "1 =>"),
+        (
+            "match 1 {\n    -1 => print(1)\n    2 => print(2)\n    -1 => print(3)\n}",
+            "-1",
+            "-1 => print(3)",
+        ),
+        (
+            "match \"a\" {\n    \"a\" => print(1)\n    \"a\" => print(2)\n    _ => print(0)\n}",
+            "\"a\" => print(1)",
+            "\"a\" => print(2)",
+        ),
+    ] {
+        let resolver = resolve(script);
+
+        assert_eq!(resolver.errors.len(), 1, "{script}");
+        match &resolver.errors[0] {
+            Error::UnreachableArm(unreachable, original) => {
+                assert_eq!(original.span.start, script.find(earlier).unwrap());
+                assert_eq!(unreachable.span.start, script.find(arm).unwrap());
+            }
+            error => panic!("unexpected error: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn variant_pattern_arity() {
+    let script = |pattern: &str| {
+        format!(
+            "enum Color {{\n    Red\n    Pair(a, b)\n}}\n\
+            match Color.Red {{\n    {pattern} => print(1)\n    _ => print(0)\n}}"
+        )
+    };
+
+    for (pattern, found) in [("Color.Pair(p)", 1), ("Color.Pair(p, q, r)", 3)] {
+        let script = script(pattern);
+        let resolver = resolve(&script);
+
+        match &resolver.errors[..] {
+            [Error::PatternArity(item, 2, count)] => {
+                assert_eq!(*count, found);
+                assert_eq!(item.content, "Color.Pair");
+                assert_eq!(item.span.start, script.find(pattern).unwrap());
+            }
+            errors => panic!("unexpected errors: {errors:?}"),
+        }
+    }
+
+    // every field can be bound, or none of them to only match the variant
+    for pattern in ["Color.Pair(p, q)", "Color.Pair(_, q)", "Color.Pair"] {
+        let errors = resolve(&script(pattern)).errors;
+        assert!(errors.is_empty(), "{pattern}: {errors:?}");
+    }
+}
+
+#[test]
+fn native_arity() {
+    let script = "parse_number(\"1\", 2)";
+    let resolver = resolve(script);
+
+    assert_eq!(resolver.errors.len(), 1);
+    match &resolver.errors[0] {
+        Error::MismatchArgCount(expected, found, args) => {
+            assert_eq!(expected.content, "1");
+            assert_eq!(found.content, "2");
+            assert_eq!(found.span.start, 0);
+            assert_eq!(args.len(), 2);
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+
+    let resolver = resolve("format()");
+    match &resolver.errors[..] {
+        [Error::MismatchArgCount(expected, _, _)] => assert_eq!(expected.content, "at least 1"),
+        errors => panic!("unexpected errors: {errors:?}"),
+    }
+
+    // variadic natives take any number of extra arguments
+    assert!(resolve("format(\"{} {}\", 1, 2)\nprintln(1, 2, 3)").errors.is_empty());
+
+    // a local with the same name replaces the native
+    for script in [
+        "fun f() {\n    fun g() {\n        parse_number(1, 2)\n    }\n    var parse_number = 1\n}",
+        "fun f(parse_number) {\n    return parse_number(1, 2)\n}",
+    ] {
+        let errors = resolve(script).errors;
+        assert!(errors.is_empty(), "{script}: {errors:?}");
+    }
+}
@@ -0,0 +1,143 @@
+use kaon::common::serialize::{MAGIC, VERSION};
+use kaon::common::value::ValueList;
+use kaon::common::{Chunk, Function, Opcode, Value};
+use kaon::runtime::Vm;
+use kaon::Kaon;
+
+use std::rc::Rc;
+
+fn execute(chunk: Chunk) -> Value {
+    let mut vm = Vm::new();
+    vm.execute(Rc::new(Function::new("script".to_string(), 0, chunk, vec![])))
+        .unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let mut kaon = Kaon::new();
+    let function = kaon
+        .compile(
+            "fun add(a: f64, b: f64): f64 {
+                return a + b
+            }
+            var greeting = \"hello\"
+            add(1, 2) * 3",
+        )
+        .unwrap();
+
+    let bytes = function.chunk.serialize().unwrap();
+    let chunk = Chunk::deserialize(&bytes).unwrap();
+
+    assert_eq!(chunk, function.chunk);
+    assert_eq!(execute(chunk), execute(function.chunk));
+}
+
+#[test]
+fn enum_round_trip() {
+    let mut kaon = Kaon::new();
+    let function = kaon
+        .compile("enum Color { Red, Rgb(r, g, b) }\nColor.Rgb(1, 2, 3) == Color.Rgb(1, 2, 3)")
+        .unwrap();
+
+    let chunk = Chunk::deserialize(&function.chunk.serialize().unwrap()).unwrap();
+
+    assert_eq!(chunk, function.chunk);
+    assert_eq!(execute(chunk), Value::Boolean(true));
+}
+
+#[test]
+fn debug_info_round_trip() {
+    let mut kaon = Kaon::new();
+    let function = kaon.compile("1 + 2").unwrap();
+
+    let chunk = Chunk::deserialize(&function.chunk.serialize().unwrap()).unwrap();
+    let span = chunk.debug_info.span_at(0).unwrap();
+
+    assert_eq!(span.source.contents, "1 + 2");
+    assert_eq!(chunk.debug_info.source_map.len(), function.chunk.debug_info.source_map.len());
+}
+
+#[test]
+fn reject_mismatched_version() {
+    let mut kaon = Kaon::new();
+    let mut bytes = kaon.compile("1").unwrap().chunk.serialize().unwrap();
+
+    bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    assert!(Chunk::deserialize(&bytes).is_err());
+}
+
+#[test]
+fn reject_invalid_header() {
+    assert!(Chunk::deserialize(b"NOPE").is_err());
+    assert!(Chunk::deserialize(&[]).is_err());
+}
+
+#[test]
+fn reject_invalid_opcode() {
+    let chunk = Chunk {
+        opcodes: vec![Opcode::Const as u8, 0, u8::MAX, Opcode::Halt as u8],
+        ..Default::default()
+    };
+
+    let err = Chunk::deserialize(&chunk.serialize().unwrap()).unwrap_err();
+    assert_eq!(err, "invalid opcode 255 at offset 2");
+}
+
+#[test]
+fn reject_oversized_length() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    // no sources, then a chunk claiming more opcodes than could ever be read
+    bytes.extend(0_u64.to_le_bytes());
+    bytes.extend(u64::MAX.to_le_bytes());
+
+    assert!(Chunk::deserialize(&bytes).is_err());
+}
+
+#[test]
+fn reject_missing_operand() {
+    let chunk = Chunk {
+        opcodes: vec![Opcode::Halt as u8, Opcode::Jump as u8, 0],
+        ..Default::default()
+    };
+
+    let err = Chunk::deserialize(&chunk.serialize().unwrap()).unwrap_err();
+    assert_eq!(err, "missing operand for `Jump` at offset 1");
+}
+
+#[test]
+fn reject_invalid_operand() {
+    let chunks = [
+        // a constant that doesn't exist
+        (vec![Opcode::Const as u8, 0, Opcode::Halt as u8], "Const"),
+        // a name that doesn't exist
+        (vec![Opcode::GetGlobal as u8, 0, Opcode::Halt as u8], "GetGlobal"),
+        // a jump past the end of the chunk
+        (vec![Opcode::Jump as u8, 0, 9, Opcode::Halt as u8], "Jump"),
+        // a jump into the middle of an instruction
+        (vec![Opcode::Jump as u8, 0, 1, Opcode::PopN as u8, 0, Opcode::Halt as u8], "Jump"),
+        // a jump back past the start of the chunk
+        (vec![Opcode::Loop as u8, 0, 9, Opcode::Halt as u8], "Loop"),
+    ];
+
+    for (opcodes, opcode) in chunks {
+        let chunk = Chunk {
+            opcodes,
+            ..Default::default()
+        };
+
+        let err = Chunk::deserialize(&chunk.serialize().unwrap()).unwrap_err();
+        assert_eq!(err, format!("invalid operand for `{opcode}` at offset 0"));
+    }
+}
+
+#[test]
+fn reject_unserializable_constant() {
+    let chunk = Chunk {
+        constants: vec![Box::new(Value::List(ValueList::new()))],
+        ..Default::default()
+    };
+
+    assert!(chunk.serialize().is_err());
+}
@@ -1,7 +1,16 @@
-use kaon::common::{Chunk, Function, Opcode, Value};
-use kaon::runtime::Vm;
+use kaon::common::{
+    kaon_module, Chunk, Class, External, Function, Instance, KaonFile, KaonRead, KaonWrite, Map,
+    ModuleBuilder, Opcode, Span, Value,
+};
+use kaon::compiler::Symbol;
+use kaon::error::RuntimeError;
+use kaon::{Kaon, KaonError, KaonSettings, Scope, Source};
+use kaon::runtime::{Clock, DebugAction, Vm};
 
+use std::cell::{Cell, RefCell};
+use std::fmt;
 use std::rc::Rc;
+use std::time::Duration;
 
 fn new_chunk(opcodes: Vec<u8>, constants: Vec<Value>) -> Rc<Function> {
     let chunk = Chunk {
@@ -24,7 +33,7 @@ fn opcode_load() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(567.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(567.0));
 }
 
 #[test]
@@ -35,7 +44,53 @@ fn opcode_add() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(3.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(3.0));
+}
+
+#[test]
+fn eval_chunk() {
+    let mut vm = Vm::new();
+    let mut kaon = Kaon::new();
+
+    let script = kaon.compile("1 + 2").unwrap();
+    assert_eq!(vm.eval(script.chunk.clone()).unwrap(), Value::Float(3.0));
+
+    let script = kaon.compile("var x = 1").unwrap();
+    assert_eq!(vm.eval(script.chunk.clone()).unwrap(), Value::Unit);
+
+    let chunk = new_chunk(
+        vec![0, 0, 0, 1, Opcode::Add as u8, Opcode::Halt as u8],
+        vec![Value::Float(1.0), Value::Float(2.0)],
+    );
+    assert_eq!(vm.eval(chunk.chunk.clone()).unwrap(), Value::Float(3.0));
+}
+
+#[test]
+fn decode_opcodes() {
+    for byte in 0..=u8::MAX {
+        match Opcode::try_from(byte) {
+            Ok(opcode) => assert_eq!(opcode as u8, byte),
+            Err(err) => assert_eq!(err, byte),
+        }
+    }
+
+    assert_eq!(Opcode::try_from(Opcode::Halt as u8), Ok(Opcode::Halt));
+    assert_eq!(Opcode::try_from(255), Err(255));
+}
+
+#[test]
+fn invalid_opcode() {
+    let chunk = new_chunk(vec![0, 0, 255, Opcode::Halt as u8], vec![Value::Float(1.0)]);
+    let mut vm = Vm::new();
+
+    let trace = vm.execute(chunk).unwrap_err();
+    assert_eq!(
+        trace.error,
+        RuntimeError::InvalidOpcode {
+            byte: 255,
+            offset: 2
+        }
+    );
 }
 
 #[test]
@@ -46,7 +101,7 @@ fn opcode_sub() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(1.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(1.0));
 }
 
 #[test]
@@ -57,7 +112,7 @@ fn opcode_mul() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(6.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(6.0));
 }
 
 #[test]
@@ -68,7 +123,7 @@ fn opcode_div() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(3.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(3.0));
 }
 
 #[test]
@@ -79,5 +134,1811 @@ fn opcode_neg() {
     );
     let mut vm = Vm::new();
     vm.execute(chunk).unwrap();
-    assert_eq!(vm.stack.peek(), Value::Float(-2.0));
+    assert_eq!(vm.stack.peek(), &Value::Float(-2.0));
+}
+
+#[test]
+fn stack_underflow() {
+    // pop the script's own closure, leaving `Add` with an empty stack
+    let chunk = new_chunk(
+        vec![Opcode::Pop as u8, Opcode::Add as u8, Opcode::Halt as u8],
+        vec![],
+    );
+    let mut vm = Vm::new();
+    let trace = vm.execute(chunk).unwrap_err();
+
+    assert_eq!(
+        trace.error,
+        RuntimeError::StackUnderflow {
+            opcode: Opcode::Add,
+            offset: 1
+        }
+    );
+    assert!(trace
+        .to_string()
+        .starts_with("stack underflow in `Add` at offset 1"));
+}
+
+#[test]
+fn close_upvalue_underflow() {
+    let chunk = new_chunk(
+        vec![Opcode::Pop as u8, Opcode::CloseUpValue as u8, Opcode::Halt as u8],
+        vec![],
+    );
+    let trace = Vm::new().execute(chunk).unwrap_err();
+
+    assert_eq!(
+        trace.error,
+        RuntimeError::StackUnderflow {
+            opcode: Opcode::CloseUpValue,
+            offset: 1
+        }
+    );
+}
+
+#[test]
+fn jump_if_false_underflow() {
+    let chunk = new_chunk(
+        vec![Opcode::Pop as u8, Opcode::JumpIfFalse as u8, 0, 0, Opcode::Halt as u8],
+        vec![],
+    );
+    let trace = Vm::new().execute(chunk).unwrap_err();
+
+    assert_eq!(
+        trace.error,
+        RuntimeError::StackUnderflow {
+            opcode: Opcode::JumpIfFalse,
+            offset: 1
+        }
+    );
+}
+
+#[test]
+fn call_underflow() {
+    let chunk = new_chunk(
+        vec![Opcode::Pop as u8, Opcode::Call as u8, 0, Opcode::Halt as u8],
+        vec![],
+    );
+    let trace = Vm::new().execute(chunk).unwrap_err();
+
+    assert_eq!(
+        trace.error,
+        RuntimeError::StackUnderflow {
+            opcode: Opcode::Call,
+            offset: 1
+        }
+    );
+}
+
+#[test]
+fn inherit_underflow() {
+    // the parent class is popped, leaving nothing to inherit from it
+    let chunk = new_chunk(
+        vec![
+            Opcode::Pop as u8,
+            Opcode::Const as u8,
+            0,
+            Opcode::Inherit as u8,
+            Opcode::Halt as u8,
+        ],
+        vec![Value::Class(Class::new("Parent"))],
+    );
+    let trace = Vm::new().execute(chunk).unwrap_err();
+
+    assert_eq!(
+        trace.error,
+        RuntimeError::StackUnderflow {
+            opcode: Opcode::Inherit,
+            offset: 3
+        }
+    );
+}
+
+#[test]
+fn set_undefined_global() {
+    let chunk = Chunk {
+        opcodes: vec![Opcode::True as u8, Opcode::SetGlobal as u8, 0, Opcode::Halt as u8],
+        variables: vec!["x".into()],
+        ..Default::default()
+    };
+    let script = Rc::new(Function::new("script".to_string(), 0, chunk, vec![]));
+    let trace = Vm::new().execute(script).unwrap_err();
+
+    assert!(matches!(
+        trace.error,
+        RuntimeError::UndefinedVariable { name, .. } if name == "x"
+    ));
+}
+
+#[test]
+fn invalid_operands() {
+    let programs = [
+        // a constant that doesn't exist
+        (vec![Opcode::Const as u8, 3, Opcode::Halt as u8], Opcode::Const),
+        // a name that doesn't exist
+        (vec![Opcode::GetGlobal as u8, 0, Opcode::Halt as u8], Opcode::GetGlobal),
+        // a local slot above the top of the stack
+        (vec![Opcode::LoadLocal as u8, 9, Opcode::Halt as u8], Opcode::LoadLocal),
+        // an upvalue the script never captured
+        (vec![Opcode::LoadUpValue as u8, 0, Opcode::Halt as u8], Opcode::LoadUpValue),
+        // a jump back past the start of the chunk
+        (vec![Opcode::Loop as u8, 0, 9, Opcode::Halt as u8], Opcode::Loop),
+        // a jump missing the second byte of its offset
+        (vec![Opcode::Jump as u8, 0], Opcode::Jump),
+        // a call missing its arity
+        (vec![Opcode::Call as u8], Opcode::Call),
+    ];
+
+    for (opcodes, opcode) in programs {
+        let trace = Vm::new().execute(new_chunk(opcodes, vec![])).unwrap_err();
+
+        assert_eq!(trace.error, RuntimeError::InvalidOperand { opcode, offset: 0 });
+    }
+}
+
+#[test]
+fn missing_halt() {
+    let chunk = new_chunk(vec![Opcode::True as u8], vec![]);
+    let trace = Vm::new().execute(chunk).unwrap_err();
+
+    assert_eq!(trace.error, RuntimeError::UnexpectedEnd { offset: 1 });
+}
+
+#[test]
+fn runtime_error_location() {
+    let mut kaon = Kaon::new();
+    let source = Source::new("var x = 1\nvar y = 2\n\n1 + \"a\"\n", "test.kaon");
+
+    let error = kaon
+        .run_with_scope(&mut Scope::new(), source)
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains("mismatched types: expected `f64`, found `string`"));
+    assert!(error.contains("--> test.kaon:4:1"));
+    assert!(error.contains("4 | 1 + \"a\"\n  | ^^^^^^^"));
+}
+
+#[test]
+fn in_memory_error_location() {
+    let mut kaon = Kaon::new();
+    let source = Source::from_string("repl", "var x = 1\nx(2)");
+
+    let error = kaon
+        .run_with_scope(&mut Scope::new(), source)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("--> <repl>:2:1"));
+
+    let error = kaon
+        .run_with_scope(&mut Scope::new(), Source::contents("nil()"))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("--> <string>:1:1"));
+}
+
+/// Create a closure that runs scripts one after another on the same VM, each
+/// with an empty scope, and returns their values.
+fn script_runner() -> impl FnMut(&str) -> Result<Value, KaonError> {
+    let mut kaon = Kaon::new();
+    move |script| {
+        kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+            .map(|(value, _)| value)
+    }
+}
+
+fn run_native(script: &str) -> Result<Value, String> {
+    let mut kaon = Kaon::new();
+    kaon.vm.register_function("add", |a: f64, b: f64| a + b);
+    kaon.vm.register_function("sub", |a: f64, b: f64| a - b);
+
+    let mut scope = Scope::new();
+    scope.insert(Symbol("add".to_string(), Span::empty()));
+    scope.insert(Symbol("sub".to_string(), Span::empty()));
+
+    kaon.run_with_scope(&mut scope, Source::contents(script))
+        .map(|(value, _)| value)
+        .map_err(|err| match err {
+            KaonError::MultipleErrors(errors) => errors.0[0].message.clone(),
+            err => err.to_string(),
+        })
+}
+
+#[test]
+fn native_function() {
+    assert_eq!(run_native("add(1.5, 2.0)"), Ok(Value::Float(3.5)));
+    assert_eq!(run_native("sub(5.0, 3.0)"), Ok(Value::Float(2.0)));
+
+    // natives registered with the vm are arity-checked before the script runs
+    let error = run_native("add(1.5)").unwrap_err();
+    assert!(error.contains("this function take 2 arguments, found 1 arguments were supplied"));
+
+    let error = run_native("add(1.5, \"two\")").unwrap_err();
+    assert!(error.contains("in call to `add`: expected a value of type `f64`, found `two`"));
+}
+
+#[test]
+fn assertions() {
+    let mut run = script_runner();
+
+    assert_eq!(run("assert(1 < 2)").unwrap(), Value::Unit);
+    assert_eq!(run("assert_eq([1, 2], [1, 2])").unwrap(), Value::Unit);
+
+    let error = match run("assert(1 > 2, \"one is not greater\")") {
+        Err(KaonError::RuntimeError(trace)) => trace.error,
+        result => panic!("expected a runtime error, found {result:?}"),
+    };
+    assert_eq!(
+        error,
+        RuntimeError::AssertionFailed("one is not greater".to_string())
+    );
+
+    let error = match run("assert_eq(1, 2)") {
+        Err(KaonError::RuntimeError(trace)) => trace.error,
+        result => panic!("expected a runtime error, found {result:?}"),
+    };
+    assert_eq!(error.to_string(), "assertion failed: `1` != `2`");
+}
+
+#[test]
+fn inject_global() {
+    let mut kaon = Kaon::new();
+
+    let mut config = Map::new();
+    config.insert("name".to_string(), Value::String("kaon".into()));
+    config.insert("retries".to_string(), Value::Float(3.0));
+    kaon.vm.set_global("config", Value::Map(config));
+
+    let mut scope = Scope::new();
+    scope.insert(Symbol("config".to_string(), Span::empty()));
+
+    let source = Source::contents("config[\"retries\"] + 1");
+    let (value, _) = kaon.run_with_scope(&mut scope, source).unwrap();
+    assert_eq!(value, Value::Float(4.0));
+
+    let source = Source::contents("var config = 1");
+    let error = kaon.run_with_scope(&mut scope, source).unwrap_err();
+    match error {
+        KaonError::MultipleErrors(errors) => {
+            assert_eq!(errors.0[0].message, "duplicate identifier 'config'");
+        }
+        error => panic!("expected a duplicate identifier error, found {error:?}"),
+    }
+}
+
+#[test]
+fn call_from_rust() {
+    let mut kaon = Kaon::new();
+    let source = Source::contents("fun sub(a, b) {\n    return a - b\n}");
+    kaon.run_with_scope(&mut Scope::new(), source).unwrap();
+
+    let result = kaon.vm.call("sub", vec![Value::Float(5.0), Value::Float(3.0)]);
+    assert_eq!(result.unwrap(), Value::Float(2.0));
+    assert_eq!(kaon.vm.stack.len(), 0);
+
+    let error = kaon.vm.call("sub", vec![Value::Float(1.0)]).unwrap_err();
+    assert_eq!(
+        error.error,
+        RuntimeError::ArityMismatch {
+            name: "sub".to_string(),
+            expected: 2,
+            found: 1
+        }
+    );
+
+    assert!(kaon.vm.call("missing", vec![]).is_err());
+
+    kaon.vm.register_function("div", |a: f64, b: f64| a / b);
+    let result = kaon.vm.call("div", vec![Value::Float(6.0), Value::Float(3.0)]);
+    assert_eq!(result.unwrap(), Value::Float(2.0));
+}
+
+#[test]
+fn call_returned_callables() {
+    let mut kaon = Kaon::new();
+    let source = Source::contents(
+        "fun adder(a) {\n    fun add(b) {\n        return a + b\n    }\n    return add\n}\n\
+         class Counter {\n    var count = 0\n\
+             create new(count) {\n        self.count = count\n    }\n\
+             fun get() {\n        return self.count\n    }\n}\n\
+         fun make() {\n    return Counter.new\n}\n\
+         var result = adder(1)(2)\n\
+         var counter = make()(5).get()",
+    );
+    kaon.run_with_scope(&mut Scope::new(), source).unwrap();
+
+    let global = |kaon: &Kaon, name: &str| kaon.vm.context.borrow().globals.get(name).cloned();
+    assert_eq!(global(&kaon, "result"), Some(Value::Float(3.0)));
+    assert_eq!(global(&kaon, "counter"), Some(Value::Float(5.0)));
+
+    // the same dispatch is used when calling from rust
+    let add = kaon.vm.call("adder", vec![Value::Float(10.0)]).unwrap();
+    let sum = kaon.vm.call_function(add, vec![Value::Float(5.0)]).unwrap();
+    assert_eq!(sum, Value::Float(15.0));
+
+    let new = kaon.vm.call("make", vec![]).unwrap();
+    let counter = kaon.vm.call_function(new, vec![Value::Float(7.0)]).unwrap();
+    let get = match counter {
+        Value::Instance(instance) => Value::Method(Rc::new(Instance::bind(instance, "get"))),
+        value => panic!("expected an instance, found {value}"),
+    };
+    assert_eq!(kaon.vm.call_function(get, vec![]).unwrap(), Value::Float(7.0));
+
+    let class = global(&kaon, "Counter").unwrap();
+    assert!(matches!(kaon.vm.call_function(class.clone(), vec![]), Ok(Value::Instance(_))));
+    assert!(kaon.vm.call_function(class, vec![Value::Nil]).is_err());
+    assert!(kaon.vm.call_function(Value::Nil, vec![]).is_err());
+    assert_eq!(kaon.vm.stack.len(), 0);
+}
+
+#[test]
+fn external_methods() {
+    struct Counter(i64);
+
+    let counter = External::builder(Counter(0))
+        .method("increment", |counter: &mut Counter, args| {
+            counter.0 += args.len() as i64 + 1;
+            counter.0
+        })
+        .build();
+
+    let mut kaon = Kaon::new();
+    kaon.vm
+        .context
+        .borrow_mut()
+        .globals
+        .insert("counter".to_string(), Value::External(counter.clone()));
+
+    let mut scope = Scope::new();
+    scope.insert(Symbol("counter".to_string(), Span::empty()));
+
+    let source = Source::contents("counter.increment()\ncounter.increment(1)");
+    let (value, _) = kaon.run_with_scope(&mut scope, source).unwrap();
+
+    assert_eq!(value, Value::Integer(3));
+    assert_eq!(counter.borrow_mut::<Counter>().unwrap().0, 3);
+
+    let source = Source::contents("counter.decrement()");
+    let error = kaon.run_with_scope(&mut scope, source).unwrap_err();
+    assert!(error.to_string().contains("no method `decrement` found"));
+}
+
+#[test]
+fn external_downcast() {
+    struct Rect {
+        width: f64,
+        height: f64,
+    }
+
+    let mut kaon = Kaon::new();
+    kaon.vm.register_function("area", |shape: Rc<External>| {
+        shape
+            .downcast_ref::<Rect>()
+            .map(|rect| rect.width * rect.height)
+            .ok_or_else(|| format!("expected a rect, found `{shape}`"))
+    });
+    kaon.vm.context.borrow_mut().globals.insert(
+        "rect".to_string(),
+        Value::External(External::builder(Rect { width: 2.0, height: 3.5 }).build()),
+    );
+    kaon.vm.context.borrow_mut().globals.insert(
+        "other".to_string(),
+        Value::External(External::builder(0_u8).build()),
+    );
+
+    let mut scope = Scope::new();
+    for name in ["area", "rect", "other"] {
+        scope.insert(Symbol(name.to_string(), Span::empty()));
+    }
+
+    let source = Source::contents("area(rect)");
+    let (value, _) = kaon.run_with_scope(&mut scope, source).unwrap();
+    assert_eq!(value, Value::Float(7.0));
+
+    let source = Source::contents("area(other)");
+    let error = kaon.run_with_scope(&mut scope, source).unwrap_err();
+    assert!(error.to_string().contains("expected a rect, found `<external u8>`"));
+}
+
+#[test]
+fn instruction_limit() {
+    let mut kaon = Kaon::new();
+    kaon.vm.set_instruction_limit(1000);
+
+    let error = kaon
+        .run_with_scope(&mut Scope::new(), Source::contents("loop {}"))
+        .unwrap_err();
+    assert!(error.to_string().contains("instruction limit exceeded"));
+
+    let result = kaon.run_with_scope(&mut Scope::new(), Source::contents("1 + 2"));
+    assert!(result.is_ok());
+}
+
+#[test]
+fn timeout() {
+    let mut kaon = Kaon::new();
+    kaon.vm.set_timeout(Duration::from_millis(10));
+
+    let error = kaon
+        .run_with_scope(&mut Scope::new(), Source::contents("loop {}"))
+        .unwrap_err();
+    assert!(error.to_string().contains("time limit exceeded"));
+}
+
+#[test]
+fn structured_runtime_errors() {
+    let mut kaon = Kaon::new();
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("[1, 2][5]"));
+    match error {
+        Err(KaonError::RuntimeError(trace)) => assert_eq!(
+            trace.error,
+            RuntimeError::IndexOutOfBounds {
+                index: 5.0,
+                length: 2
+            }
+        ),
+        _ => panic!("expected an index out of bounds error"),
+    }
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("[1, 2][true]"));
+    match error {
+        Err(KaonError::RuntimeError(trace)) => assert!(matches!(
+            trace.error,
+            RuntimeError::TypeMismatch { ref expected, ref found, .. }
+                if expected == "f64" && found == "bool"
+        )),
+        _ => panic!("expected a type mismatch error"),
+    }
+}
+
+#[test]
+fn arithmetic_type_mismatch() {
+    let mut kaon = Kaon::new();
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("1 + true"));
+    match error {
+        Err(KaonError::RuntimeError(trace)) => assert!(matches!(
+            trace.error,
+            RuntimeError::TypeMismatch { ref expected, ref found, ref span }
+                if expected == "f64" && found == "bool" && span.start == 0
+        )),
+        _ => panic!("expected a type mismatch error"),
+    }
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("-\"x\""));
+    assert!(matches!(error, Err(KaonError::RuntimeError(_))));
+
+    assert_eq!(
+        Value::from("a").try_add(Value::from("b")),
+        Ok(Value::from("ab"))
+    );
+}
+
+#[test]
+fn nested_stack_trace() {
+    let mut kaon = Kaon::new();
+    let source = Source::contents(
+        "fun inner() {\n    return 1 + true\n}\nfun outer() {\n    return inner()\n}\nouter()",
+    );
+
+    let trace = match kaon.run_with_scope(&mut Scope::new(), source) {
+        Err(KaonError::RuntimeError(trace)) => trace,
+        _ => panic!("expected a runtime error"),
+    };
+
+    let names = trace
+        .frames
+        .iter()
+        .map(|frame| frame.closure.function.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["script", "outer", "inner"]);
+
+    let rendered = trace.to_string();
+    let inner = rendered.find("in inner (").unwrap();
+    let outer = rendered.find("in outer (").unwrap();
+    let script = rendered.find("in script (").unwrap();
+    assert!(inner < outer && outer < script);
+}
+
+#[test]
+fn try_catch() {
+    let mut kaon = Kaon::new();
+    let script = "fun divide(a, b) {\n    return a / b\n}\n\
+        var result = nil\n\
+        try {\n    result = divide(\"ab\".len(), \"\".len())\n} catch e {\n    result = e\n}\n\
+        var after = result + \"!\"";
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+        .unwrap();
+
+    let after = kaon.vm.context.borrow().globals.get("after").cloned();
+    assert_eq!(after, Some(Value::from("attempt to divide by zero!")));
+
+    let uncaught = "try {\n    1 + 1\n} catch e {\n    e\n}\n1 + true";
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents(uncaught));
+    assert!(matches!(
+        error,
+        Err(KaonError::RuntimeError(trace))
+            if matches!(trace.error, RuntimeError::TypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn closures_in_loop() {
+    let mut kaon = Kaon::new();
+    let script = r#"
+var first
+var second
+var third
+
+var i = 0
+while i < 3 {
+    var captured = i
+    fun get() {
+        return captured
+    }
+
+    if i == 0 {
+        first = get
+    } else if i == 1 {
+        second = get
+    } else {
+        third = get
+    }
+
+    i = i + 1
+}
+"#;
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+        .unwrap();
+
+    for (name, expected) in [("first", 0.0), ("second", 1.0), ("third", 2.0)] {
+        assert_eq!(kaon.vm.call(name, vec![]).unwrap(), Value::Float(expected));
+    }
+}
+
+#[test]
+fn arity_mismatch() {
+    let script = "fun add(a, b) {\n    return a + b\n}\n";
+
+    for (call, found) in [("add(1)", 1), ("add(1, 2, 3)", 3)] {
+        let mut kaon = Kaon::new();
+        let source = Source::contents(&format!("{script}{call}"));
+
+        match kaon.run_with_scope(&mut Scope::new(), source) {
+            Err(KaonError::RuntimeError(trace)) => assert_eq!(
+                trace.error,
+                RuntimeError::ArityMismatch {
+                    name: "add".to_string(),
+                    expected: 2,
+                    found
+                }
+            ),
+            _ => panic!("expected an arity mismatch"),
+        }
+    }
+
+    let mut kaon = Kaon::new();
+    let source = Source::contents(&format!("{script}add(1, 2)"));
+    let (value, _) = kaon.run_with_scope(&mut Scope::new(), source).unwrap();
+    assert_eq!(value, Value::Float(3.0));
+}
+
+#[test]
+fn method_arity_mismatch() {
+    let script = "class Calc {\n    fun add(a, b) {\n        return a + b\n    }\n}\n\
+        var calc = Calc()\n";
+
+    for (call, found) in [("calc.add(1)", 1), ("calc.add(1, 2, 3)", 3)] {
+        let mut kaon = Kaon::new();
+        let source = Source::contents(&format!("{script}{call}"));
+
+        match kaon.run_with_scope(&mut Scope::new(), source) {
+            Err(KaonError::RuntimeError(trace)) => assert_eq!(
+                trace.error,
+                RuntimeError::ArityMismatch {
+                    name: "add".to_string(),
+                    expected: 2,
+                    found
+                }
+            ),
+            _ => panic!("expected an arity mismatch"),
+        }
+    }
+
+    let mut kaon = Kaon::new();
+    let source = Source::contents(&format!("{script}calc.add(1, 2)"));
+    let (value, _) = kaon.run_with_scope(&mut Scope::new(), source).unwrap();
+    assert_eq!(value, Value::Float(3.0));
+}
+
+#[test]
+fn read_bytes_from_file() {
+    let path = std::env::temp_dir().join(format!("kaon_bytes_{}", std::process::id()));
+    std::fs::write(&path, [0x4b, 0x00, 0xff, 0x4e]).unwrap();
+
+    let mut kaon = Kaon::new();
+    let script = format!(
+        "var bytes = read_bytes({:?})\n\
+        var length = bytes.len()\n\
+        var first = bytes[0]\n\
+        var last = bytes[-1]\n\
+        var middle = bytes.slice(1, 3)",
+        path.to_string_lossy()
+    );
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(&script))
+        .unwrap();
+
+    let global = |name: &str| kaon.vm.context.borrow().globals.get(name).cloned();
+    assert_eq!(global("length"), Some(Value::Integer(4)));
+    assert_eq!(global("first"), Some(Value::Float(75.0)));
+    assert_eq!(global("last"), Some(Value::Float(78.0)));
+    assert_eq!(global("middle"), Some(Value::from(vec![0x00_u8, 0xff])));
+    assert_eq!(global("bytes").unwrap().to_string(), "b\"K\\x00\\xffN\"");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn power_operator() {
+    let mut kaon = Kaon::new();
+    let script = "var a = 2 ** 10\nvar b = 2 ** 3 ** 2\nvar c = (2 ** 3) ** 2\nvar d = 2 ** -1";
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+        .unwrap();
+
+    let global = |name: &str| kaon.vm.context.borrow().globals.get(name).cloned();
+    assert_eq!(global("a"), Some(Value::Float(1024.0)));
+    assert_eq!(global("b"), Some(Value::Float(512.0)));
+    assert_eq!(global("c"), Some(Value::Float(64.0)));
+    assert_eq!(global("d"), Some(Value::Float(0.5)));
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("\"2\" ** 2"));
+    assert!(matches!(
+        error,
+        Err(KaonError::RuntimeError(trace))
+            if matches!(trace.error, RuntimeError::TypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn index_assignment_errors() {
+    let mut kaon = Kaon::new();
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("var l = [1]\nl[1] = 2"));
+    assert!(matches!(
+        error,
+        Err(KaonError::RuntimeError(trace))
+            if trace.error == RuntimeError::IndexOutOfBounds { index: 1.0, length: 1 }
+    ));
+
+    let error = kaon.run_with_scope(
+        &mut Scope::new(),
+        Source::contents("var m = {a: 1}\nm[\"b\"]"),
+    );
+    assert!(matches!(error, Err(KaonError::RuntimeError(_))));
+}
+
+#[test]
+fn non_integral_index() {
+    let mut kaon = Kaon::new();
+    let scripts = [
+        "[1, 2][-0.5]",
+        "var l = [1, 2]\nl[-0.5] = 3",
+        "[][-0.0]",
+        "[1, 2][0 / 0]",
+        "var l = [1, 2]\nl[0 / 0] = 3",
+    ];
+
+    for script in scripts {
+        let error = kaon.run_with_scope(&mut Scope::new(), Source::contents(script));
+        assert!(
+            matches!(
+                &error,
+                Err(KaonError::RuntimeError(trace))
+                    if matches!(trace.error, RuntimeError::IndexOutOfBounds { .. })
+            ),
+            "{script}"
+        );
+    }
+
+    let value = kaon.run_with_scope(&mut Scope::new(), Source::contents("[1, 2][-0.0]"));
+    assert_eq!(value.unwrap().0, Value::Float(1.0));
+}
+
+#[test]
+fn field_assignment() {
+    let mut kaon = Kaon::new();
+    let script = "class Counter {\n    var count = 0\n}\n\
+        var counter = Counter()\n\
+        var alias = counter\n\
+        counter.count = 5\n\
+        var count = alias.count";
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+        .unwrap();
+
+    let count = kaon.vm.context.borrow().globals.get("count").cloned();
+    assert_eq!(count, Some(Value::Float(5.0)));
+
+    let error = kaon.run_with_scope(&mut Scope::new(), Source::contents("var n = 1\nn.x = 2"));
+    assert!(matches!(error, Err(KaonError::RuntimeError(_))));
+}
+
+#[test]
+fn class_inheritance() {
+    let mut kaon = Kaon::new();
+    let script = "class Shape {\n    var sides = 0\n\n    fun area() {\n        return 0\n    }\n\n    fun name() {\n        return \"shape\"\n    }\n}\n\
+        class Square : Shape {\n    var size = 3\n\n    fun area() {\n        return self.size * self.size + super.area()\n    }\n}\n\
+        var square = Square()\n\
+        var area = square.area()\n\
+        var name = square.name()\n\
+        var sides = square.sides";
+    kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+        .unwrap();
+
+    {
+        let globals = &kaon.vm.context.borrow().globals;
+        assert_eq!(globals.get("area").cloned(), Some(Value::Float(9.0)));
+        assert_eq!(globals.get("name").cloned(), Some(Value::from("shape")));
+        assert_eq!(globals.get("sides").cloned(), Some(Value::Float(0.0)));
+    }
+
+    let error = kaon.run_with_scope(
+        &mut Scope::new(),
+        Source::contents("var parent = 1\nclass Child : parent {}"),
+    );
+    assert!(matches!(error, Err(KaonError::RuntimeError(_))));
+}
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn monotonic(&self) -> f64 {
+        12.5
+    }
+
+    fn unix(&self) -> f64 {
+        1_700_000_000.0
+    }
+}
+
+#[test]
+fn injected_clock() {
+    let mut kaon = Kaon::with_settings(KaonSettings {
+        clock: Rc::new(FixedClock),
+        ..Default::default()
+    });
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    assert_eq!(run("clock()").unwrap(), Value::Float(12.5));
+    assert_eq!(run("now()").unwrap(), Value::Float(1_700_000_000.0));
+    assert_eq!(run("clock() - clock()").unwrap(), Value::Float(0.0));
+}
+
+#[test]
+fn seeded_random() {
+    let mut run = script_runner();
+
+    run("seed(42)").unwrap();
+    assert_eq!(run("random()").unwrap(), Value::Float(0.1941059175341826));
+    assert_eq!(run("random()").unwrap(), Value::Float(0.5626318272656207));
+
+    run("seed(42)").unwrap();
+    assert_eq!(run("random()").unwrap(), Value::Float(0.1941059175341826));
+
+    for _ in 0..100 {
+        match run("random_int(1, 6)").unwrap() {
+            Value::Float(n) => assert!((1.0..=6.0).contains(&n) && n.fract() == 0.0),
+            value => panic!("expected a number, found {value}"),
+        }
+    }
+
+    assert!(matches!(
+        run("random_int(3, 1)"),
+        Err(KaonError::RuntimeError(_))
+    ));
+    assert!(matches!(
+        run("random_int(1.5, 2)"),
+        Err(KaonError::RuntimeError(_))
+    ));
+}
+
+#[test]
+fn conversions() {
+    let mut run = script_runner();
+
+    assert_eq!(run("to_string(1.5)").unwrap(), Value::from("1.5"));
+    assert_eq!(
+        run("to_string([1, \"a\"])").unwrap(),
+        Value::from("[1, \"a\"]")
+    );
+
+    assert_eq!(
+        run("parse_number(\"1.5e3\")").unwrap(),
+        Value::Float(1500.0)
+    );
+    assert_eq!(
+        run("parse_number(\" -42\\n\")").unwrap(),
+        Value::Float(-42.0)
+    );
+    assert_eq!(run("parse_bool(\"true\")").unwrap(), Value::Boolean(true));
+    assert_eq!(run("parse_bool(\"false\")").unwrap(), Value::Boolean(false));
+
+    for script in [
+        "parse_number(\"abc\")",
+        "parse_number(\".5\")",
+        "parse_number(\"inf\")",
+        "parse_bool(\"yes\")",
+    ] {
+        assert!(
+            matches!(run(script), Err(KaonError::RuntimeError(_))),
+            "{script}"
+        );
+    }
+}
+
+/// Captures everything written to it.
+#[derive(Debug, Default)]
+struct Output(RefCell<String>);
+
+impl KaonFile for Output {}
+
+impl KaonRead for Output {}
+
+impl KaonWrite for Output {
+    fn write(&self, bytes: &[u8]) -> Result<(), String> {
+        self.0
+            .borrow_mut()
+            .push_str(std::str::from_utf8(bytes).unwrap());
+        Ok(())
+    }
+
+    fn writeln(&self, line: &str) -> Result<(), String> {
+        self.0.borrow_mut().push_str(line);
+        self.0.borrow_mut().push('\n');
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<output>")
+    }
+}
+
+#[test]
+fn print_and_format() {
+    let stdout = Rc::new(Output::default());
+    let mut kaon = Kaon::with_settings(KaonSettings {
+        stdout: stdout.clone(),
+        ..Default::default()
+    });
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    run("print(1, \"a\")\nprint(true)\nprintln()\nprintln(\"b\", nil)").unwrap();
+    assert_eq!(stdout.0.borrow().as_str(), "1 atrue\nb nil\n");
+
+    assert_eq!(
+        run("format(\"{} + {} = {}\", 1, 2, 1 + 2)").unwrap(),
+        Value::from("1 + 2 = 3")
+    );
+    assert_eq!(
+        run("format(\"{{{}}}\", [1])").unwrap(),
+        Value::from("{[1]}")
+    );
+    assert_eq!(
+        run("format(\"{:.2} in {:.1}\", 3.14159, 2)").unwrap(),
+        Value::from("3.14 in 2.0")
+    );
+
+    for script in [
+        "format(\"{} {}\", 1)",
+        "format(\"{}\", 1, 2)",
+        "format(\"{\")",
+        "format(\"{:2}\", 1)",
+        "format(\"{:.x}\", 1)",
+    ] {
+        assert!(
+            matches!(run(script), Err(KaonError::RuntimeError(_))),
+            "{script}"
+        );
+    }
+}
+
+#[test]
+fn native_module() {
+    let mut kaon = Kaon::new();
+
+    ModuleBuilder::new()
+        .function("add", |a: f64, b: f64| a + b)
+        .function("greet", |name: String| format!("hello, {name}"))
+        .function("len", |text: String| text.len() as f64)
+        .constant("version", 2.0)
+        .install(&mut kaon.vm, "util");
+
+    struct Geometry;
+
+    #[kaon_module]
+    impl Geometry {
+        fn square(x: f64) -> f64 {
+            x * x
+        }
+
+        fn double(x: f64) -> f64 {
+            x * 2.0
+        }
+    }
+
+    Geometry::module().install(&mut kaon.vm, "geometry");
+
+    let mut scope = Scope::new();
+    for name in ["util", "geometry"] {
+        scope.insert(Symbol(name.to_string(), Span::empty()));
+    }
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut scope, Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    assert_eq!(run("util.add(1, 2)").unwrap(), Value::Float(3.0));
+    assert_eq!(run("util.greet(\"kaon\")").unwrap(), Value::from("hello, kaon"));
+    assert_eq!(run("util.version").unwrap(), Value::Float(2.0));
+    assert_eq!(run("util.len(\"kaon\")").unwrap(), Value::Float(4.0));
+    assert_eq!(run("util.is_empty()").unwrap(), Value::Boolean(false));
+    assert_eq!(
+        run("geometry.square(geometry.double(3))").unwrap(),
+        Value::Float(36.0)
+    );
+
+    let error = run("util.missing").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("no method or entry `missing` found for map"));
+
+    // only modules shadow the builtin methods, other maps fall back to entries
+    assert_eq!(run("var m = {\"len\": 1}\nm.len()").unwrap(), Value::Integer(1));
+    assert_eq!(run("var n = {\"size\": 2}\nn.size").unwrap(), Value::Float(2.0));
+}
+
+#[test]
+fn display_external() {
+    struct Point(f64, f64);
+
+    let stdout = Rc::new(Output::default());
+    let mut kaon = Kaon::with_settings(KaonSettings {
+        stdout: stdout.clone(),
+        ..Default::default()
+    });
+
+    let point = External::builder(Point(1.0, 2.5))
+        .method("to_string", |point: &mut Point, _args| {
+            format!("Point({}, {})", point.0, point.1)
+        })
+        .build();
+    kaon.vm.set_global("point", Value::External(point));
+    kaon.vm.set_global(
+        "plain",
+        Value::External(External::builder(0_u8).name("Plain").build()),
+    );
+    kaon.vm.set_global(
+        "bad",
+        Value::External(
+            External::builder(0_u8)
+                .method("to_string", |_: &mut u8, _args| 1.0)
+                .build(),
+        ),
+    );
+
+    let mut scope = Scope::new();
+    for name in ["point", "plain", "bad"] {
+        scope.insert(Symbol(name.to_string(), Span::empty()));
+    }
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut scope, Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    run("println(point, plain)").unwrap();
+    assert_eq!(stdout.0.borrow().as_str(), "Point(1, 2.5) <external Plain>\n");
+
+    assert_eq!(
+        run("format(\"at {}\", point)").unwrap(),
+        Value::from("at Point(1, 2.5)")
+    );
+    assert_eq!(run("str(point)").unwrap(), Value::from("Point(1, 2.5)"));
+
+    let error = run("println(bad)").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("`to_string` must return a string, found `1`"));
+}
+
+#[test]
+fn reset_globals() {
+    let mut kaon = Kaon::new();
+
+    let (_, mut scope) = kaon
+        .run_with_scope(&mut Scope::new(), Source::contents("var x = 1"))
+        .unwrap();
+    let (value, mut scope) = kaon
+        .run_with_scope(&mut scope, Source::contents("x"))
+        .unwrap();
+    assert_eq!(value, Value::Float(1.0));
+
+    kaon.vm.reset();
+
+    match kaon.run_with_scope(&mut scope, Source::contents("x")) {
+        Err(KaonError::RuntimeError(trace)) => assert!(matches!(
+            trace.error,
+            RuntimeError::UndefinedVariable { ref name, .. } if name == "x"
+        )),
+        result => panic!("expected an undefined variable, found {result:?}"),
+    }
+}
+
+#[test]
+fn list_globals() {
+    let mut kaon = Kaon::new();
+    let mut count = 0;
+    kaon.vm.visit_globals(|_, _| count += 1);
+    assert_eq!(count, 0);
+
+    kaon.vm.set_global("host", Value::Boolean(true));
+
+    let mut scope = Scope::new();
+    scope.insert(Symbol("host".to_string(), Span::empty()));
+    let source = Source::contents("var name = \"kaon\"\nvar count = 1\ncount = count + 1");
+    kaon.run_with_scope(&mut scope, source).unwrap();
+
+    let mut globals = Vec::new();
+    kaon.vm
+        .visit_globals(|name, value| globals.push((name.to_string(), value.clone())));
+    assert_eq!(
+        globals,
+        [
+            ("count".to_string(), Value::Float(2.0)),
+            ("host".to_string(), Value::Boolean(true)),
+            ("name".to_string(), Value::from("kaon")),
+        ]
+    );
+
+    assert_eq!(kaon.vm.get_global("count"), Some(Value::Float(2.0)));
+    assert_eq!(kaon.vm.get_global("println"), None);
+}
+
+#[test]
+fn debug_hook_counts_instructions() {
+    let mut kaon = Kaon::new();
+    let count = Rc::new(Cell::new(0));
+
+    let counter = count.clone();
+    kaon.vm.set_debug_hook(Box::new(move |_| {
+        counter.set(counter.get() + 1);
+        DebugAction::Step
+    }));
+
+    // Const, Const, Add, Pop, Halt
+    kaon.run_from_script("1 + 2").unwrap();
+    assert_eq!(count.get(), 5);
+
+    count.set(0);
+    kaon.vm.set_debug_hook(Box::new({
+        let counter = count.clone();
+        move |_| {
+            counter.set(counter.get() + 1);
+            DebugAction::Continue
+        }
+    }));
+
+    kaon.run_from_script("1 + 2").unwrap();
+    assert_eq!(count.get(), 1);
+}
+
+#[test]
+fn debug_hook_pause_and_resume() {
+    let mut kaon = Kaon::new();
+    let function = Rc::new(kaon.compile("var x = 1 + 2").unwrap());
+    let seen = Rc::new(RefCell::new(Vec::new()));
+
+    let log = seen.clone();
+    kaon.vm.set_debug_hook(Box::new(move |state| {
+        let opcode = format!("{:?}", state.opcode);
+        let action = match opcode.as_str() {
+            "Add" => {
+                assert_eq!(state.stack.len(), 3);
+                assert_eq!(state.span().unwrap().start, 8);
+                DebugAction::Pause
+            }
+            _ => DebugAction::Step,
+        };
+
+        log.borrow_mut().push(opcode);
+        action
+    }));
+
+    match kaon.vm.execute(function) {
+        Err(trace) => assert_eq!(trace.error, RuntimeError::Paused),
+        result => panic!("expected the script to pause, found {result:?}"),
+    }
+    assert_eq!(*seen.borrow(), ["Const", "Const", "Add"]);
+
+    kaon.vm.resume().unwrap();
+    assert_eq!(
+        *seen.borrow(),
+        ["Const", "Const", "Add", "DefGlobal", "Halt"]
+    );
+    assert_eq!(kaon.vm.context.borrow().globals["x"], Value::Float(3.0));
+}
+
+#[test]
+fn shift_operators() {
+    let mut run = script_runner();
+
+    assert_eq!(run("1 << 4 == 16").unwrap(), Value::Boolean(true));
+    assert_eq!(run("1 << 4").unwrap(), Value::Float(16.0));
+    assert_eq!(run("-16 >> 2").unwrap(), Value::Float(-4.0));
+    assert_eq!(run("5.9 >> 1.5").unwrap(), Value::Float(2.0));
+    assert_eq!(run("1 << 64").unwrap(), Value::Float(0.0));
+    assert_eq!(run("-1 >> 64").unwrap(), Value::Float(-1.0));
+
+    assert!(matches!(run("1 << -1"), Err(KaonError::RuntimeError(_))));
+    assert!(matches!(run("\"a\" >> 1"), Err(KaonError::RuntimeError(_))));
+}
+
+/// Hands out canned lines of input.
+#[derive(Debug, Default)]
+struct Input(RefCell<Vec<String>>);
+
+impl KaonFile for Input {}
+
+impl KaonWrite for Input {}
+
+impl KaonRead for Input {
+    fn read_line(&self) -> Result<Option<String>, String> {
+        let mut lines = self.0.borrow_mut();
+        Ok((!lines.is_empty()).then(|| lines.remove(0)))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<input>")
+    }
+}
+
+#[test]
+fn read_input() {
+    let stdout = Rc::new(Output::default());
+    let stdin = Rc::new(Input(RefCell::new(vec![
+        "Ada\n".to_string(),
+        "42\r\n".to_string(),
+    ])));
+    let mut kaon = Kaon::with_settings(KaonSettings {
+        stdin,
+        stdout: stdout.clone(),
+        ..Default::default()
+    });
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    assert_eq!(run("input(\"name? \")").unwrap(), Value::from("Ada"));
+    assert_eq!(stdout.0.borrow().as_str(), "name? ");
+
+    assert_eq!(run("read_line()").unwrap(), Value::from("42"));
+    assert_eq!(run("read_line()").unwrap(), Value::Nil);
+}
+
+#[test]
+fn unique_elements() {
+    let mut run = script_runner();
+
+    assert_eq!(
+        run("unique([1, 2, 1, 3, 2])").unwrap().to_string(),
+        "[1, 2, 3]"
+    );
+    assert_eq!(
+        run("unique([\"b\", \"a\", \"b\"])").unwrap().to_string(),
+        "[\"b\", \"a\"]"
+    );
+    assert_eq!(run("unique([0, -0])").unwrap().to_string(), "[0]");
+    assert_eq!(run("unique([])").unwrap().to_string(), "[]");
+}
+
+#[test]
+fn list_aggregates() {
+    let mut runner = script_runner();
+    let mut run = |script: &str| runner(script).map(|value| value.to_string());
+
+    assert_eq!(run("sum([1, 2, 3.5])").unwrap(), "6.5");
+    assert_eq!(run("sum([])").unwrap(), "0");
+    assert_eq!(run("sum([\"a\", \"b\"])").unwrap(), "ab");
+
+    assert_eq!(run("min([3, -1, 2])").unwrap(), "-1");
+    assert_eq!(run("max([3, -1, 2])").unwrap(), "3");
+    assert_eq!(run("min([\"pear\", \"apple\"])").unwrap(), "apple");
+    assert_eq!(run("max([\"pear\", \"apple\"])").unwrap(), "pear");
+    assert!(run("min([])").is_err());
+    assert!(run("max([1, \"a\"])").is_err());
+
+    assert_eq!(run("sorted([3, 1, 2])").unwrap(), "[1, 2, 3]");
+    assert_eq!(
+        run("sorted([\"b\", \"c\", \"a\"])").unwrap(),
+        "[\"a\", \"b\", \"c\"]"
+    );
+
+    // mixed kinds are grouped by kind, with `NaN` after the other numbers
+    let script = "sorted([\"b\", 2, nil, [1], true, 0 / 0, \"a\", -1, [0, 5], false, 1.5])";
+    assert_eq!(
+        run(script).unwrap(),
+        "[nil, false, true, -1, 1.5, 2, NaN, \"a\", \"b\", [0, 5], [1]]"
+    );
+    assert_eq!(run("sorted([\"a\".len(), 1.5, 0])").unwrap(), "[0, 1, 1.5]");
+
+    // the list itself is left as it is
+    assert_eq!(run("var list = [2, 1]\nsorted(list)\nlist").unwrap(), "[2, 1]");
+
+    let script = "fun descending(a, b) {\n    return b - a\n}\nsorted([1, 3, 2], descending)";
+    assert_eq!(run(script).unwrap(), "[3, 2, 1]");
+
+    // the sort is stable
+    let script = "fun by_len(a, b) {\n    return a.len() - b.len()\n}\n\
+        sorted([\"bb\", \"a\", \"cc\", \"d\"], by_len)";
+    assert_eq!(run(script).unwrap(), "[\"a\", \"d\", \"bb\", \"cc\"]");
+}
+
+#[test]
+fn repeat_and_join() {
+    let mut runner = script_runner();
+    let mut run = |script: &str| runner(script).map(|value| value.to_string());
+
+    assert_eq!(run("repeat(\"ab\", 3)").unwrap(), "ababab");
+    assert_eq!(run("repeat(\"ab\", 0)").unwrap(), "");
+    assert_eq!(run("repeat([1, 2], 2)").unwrap(), "[1, 2, 1, 2]");
+    assert_eq!(run("repeat([], 1000000000000)").unwrap(), "[]");
+    assert_eq!(run("repeat(\"-\", \"abc\".len())").unwrap(), "---");
+    assert!(run("repeat(\"ab\", -1)").is_err());
+    assert!(run("repeat(\"ab\", 1.5)").is_err());
+    assert!(run("repeat([1], 1e300)").is_err());
+    assert!(run("repeat(1, 2)").is_err());
+
+    assert_eq!(run("join([\"a\", \"b\"], \"-\")").unwrap(), "a-b");
+    assert_eq!(run("join([\"a\"], \", \")").unwrap(), "a");
+    assert_eq!(run("join([], \", \")").unwrap(), "");
+    assert!(run("join([\"a\", 1], \"-\")").is_err());
+}
+
+#[test]
+fn deep_copy() {
+    let mut runner = script_runner();
+    let mut run = |script: &str| runner(script).map(|value| value.to_string());
+
+    let script = "var list = [1, [2, 3], {\"a\": 4}]\n\
+        var deep = copy(list)\n\
+        deep[0] = 10\n\
+        deep[1][0] = 20\n\
+        deep[2][\"a\"] = 40\n";
+    assert_eq!(
+        run(&format!("{script}list")).unwrap(),
+        "[1, [2, 3], {a: 4}]"
+    );
+    assert_eq!(
+        run(&format!("{script}deep")).unwrap(),
+        "[10, [20, 3], {a: 40}]"
+    );
+
+    let script = "var list = [1, 2]\n\
+        var alias = list\n\
+        alias[0] = 10\n\
+        list";
+    assert_eq!(run(script).unwrap(), "[10, 2]");
+
+    // a list that contains itself is copied into one that contains the copy
+    let list = Value::from(vec![Value::Nil]);
+    if let Value::List(inner) = &list {
+        inner.0.borrow_mut()[0] = list.clone();
+    }
+    let copy = list.deep_clone();
+    match (&list, &copy) {
+        (Value::List(list), Value::List(copy)) => {
+            assert!(!Rc::ptr_eq(&list.0, &copy.0));
+            assert!(
+                matches!(&copy.0.borrow()[0], Value::List(inner) if Rc::ptr_eq(&inner.0, &copy.0))
+            );
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn enum_variants() {
+    let mut runner = script_runner();
+    let mut run = |script: &str| runner(script).map(|value| value.to_string());
+
+    let color = "enum Color {\n    Red\n    Rgb(r, g, b)\n}\n";
+    assert_eq!(
+        run(&format!("{color}Color.Rgb(255, 0, 0)")).unwrap(),
+        "Color.Rgb(255, 0, 0)"
+    );
+    assert_eq!(run(&format!("{color}Color.Red")).unwrap(), "Color.Red");
+    assert_eq!(
+        run(&format!("{color}Color.Rgb(1, 2, 3) == Color.Rgb(1, 2, 3)")).unwrap(),
+        "true"
+    );
+    assert_eq!(
+        run(&format!("{color}Color.Rgb(1, 2, 3) == Color.Rgb(1, 2, 4)")).unwrap(),
+        "false"
+    );
+    assert_eq!(
+        run(&format!("{color}Color.Red == Color.Red")).unwrap(),
+        "true"
+    );
+
+    let script = "var color = Color.Rgb(255, 128, 0)\n\
+        var red = nil\n\
+        match color {\n\
+            Color.Red => red = 0\n\
+            Color.Rgb(r, _, _) => red = r\n\
+        }\n\
+        red";
+    assert_eq!(run(&format!("{color}{script}")).unwrap(), "255");
+
+    let script = "var kind = nil\n\
+        match Color.Red {\n\
+            Color.Rgb(r, g, b) => kind = \"rgb\"\n\
+            _ => kind = \"other\"\n\
+        }\n\
+        kind";
+    assert_eq!(run(&format!("{color}{script}")).unwrap(), "other");
+
+    assert!(run(&format!("{color}Color.Rgb(1)")).is_err());
+    assert!(run(&format!("{color}Color.Blue")).is_err());
+}
+
+#[test]
+fn loop_break_value() {
+    let mut run = script_runner();
+
+    assert_eq!(run("var x = loop { break 42 }\nx").unwrap(), Value::Float(42.0));
+    assert_eq!(run("var x = loop { break }\nx").unwrap(), Value::Unit);
+
+    let script = "fun first_square_over(n) {\n\
+            var i = 0\n\
+            return loop {\n\
+                var square = i * i\n\
+                i = i + 1\n\
+                if square > n { break square }\n\
+            }\n\
+        }\n\
+        first_square_over(10)";
+    assert_eq!(run(script).unwrap(), Value::Float(16.0));
+
+    // the locals declared in a loop are popped when breaking out of it
+    let script = "fun f() {\n\
+            var a = 7\n\
+            while true {\n\
+                var _b = 1\n\
+                break\n\
+            }\n\
+            return a\n\
+        }\n\
+        f()";
+    assert_eq!(run(script).unwrap(), Value::Float(7.0));
+}
+
+#[test]
+fn continue_skips_iteration() {
+    let mut run = script_runner();
+
+    let script = "var i = 0\n\
+        var odd = 0\n\
+        while i < 10 {\n\
+            i = i + 1\n\
+            if i % 2 == 0 {\n\
+                continue\n\
+            }\n\
+            odd = odd + 1\n\
+        }\n\
+        odd";
+    assert_eq!(run(script).unwrap(), Value::Float(5.0));
+
+    let script = "fun count() {\n\
+            var i = 0\n\
+            var skipped = 0\n\
+            loop {\n\
+                var next = i + 1\n\
+                i = next\n\
+                if i > 6 { break }\n\
+                if i < 4 {\n\
+                    skipped = skipped + 1\n\
+                    continue\n\
+                }\n\
+            }\n\
+            return skipped * 10 + i\n\
+        }\n\
+        count()";
+    assert_eq!(run(script).unwrap(), Value::Float(37.0));
+}
+
+#[test]
+fn labeled_break() {
+    let mut run = script_runner();
+
+    let script = "fun find() {\n\
+            var iterations = 0\n\
+            var i = 0\n\
+            outer: while i < 5 {\n\
+                var j = 0\n\
+                while j < 5 {\n\
+                    iterations = iterations + 1\n\
+                    if i * j == 6 { break outer }\n\
+                    j = j + 1\n\
+                }\n\
+                i = i + 1\n\
+            }\n\
+            return i * 100 + iterations\n\
+        }\n\
+        find()";
+    // breaking out of both loops leaves `i` at 2, after 5 + 5 + 4 iterations
+    assert_eq!(run(script).unwrap(), Value::Float(214.0));
+
+    let script = "var rows = 0\n\
+        var cells = 0\n\
+        rows: loop {\n\
+            rows = rows + 1\n\
+            if rows > 3 { break }\n\
+            loop {\n\
+                cells = cells + 1\n\
+                continue rows\n\
+            }\n\
+        }\n\
+        cells";
+    assert_eq!(run(script).unwrap(), Value::Float(3.0));
+}
+
+#[test]
+fn is_and_isnt() {
+    let mut run = script_runner();
+
+    let classes = "class Animal {\n    var legs = 4\n}\nclass Dog : Animal {}\nclass Car {}\n";
+    let check = |script: &str| format!("{classes}{script}");
+
+    assert_eq!(run(&check("Dog() is Animal")).unwrap(), Value::Boolean(true));
+    assert_eq!(run(&check("Dog() isnt Animal")).unwrap(), Value::Boolean(false));
+    assert_eq!(run(&check("Animal() isnt Dog")).unwrap(), Value::Boolean(true));
+    assert_eq!(run(&check("Dog() isnt Car")).unwrap(), Value::Boolean(true));
+
+    // values that aren't instances are never members of a class
+    assert_eq!(run(&check("1 is Animal")).unwrap(), Value::Boolean(false));
+    assert_eq!(run(&check("\"dog\" isnt Dog")).unwrap(), Value::Boolean(true));
+
+    // `is` checks membership, not equality, so the right-hand side must be a class
+    assert!(run(&check("Animal() is Animal()")).is_err());
+    assert!(run("1 isnt 1").is_err());
+
+    // `==` compares instances by their class and fields
+    assert_eq!(run(&check("Animal() == Animal()")).unwrap(), Value::Boolean(true));
+    let script = "var a = Animal()\nvar b = Animal()\nb.legs = 3\na == b";
+    assert_eq!(run(&check(script)).unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn tuple() {
+    let mut run = script_runner();
+
+    match run("(1, \"two\", true)").unwrap() {
+        Value::Tuple(tuple) => assert_eq!(
+            tuple.0.to_vec(),
+            vec![Value::Float(1.0), Value::from("two"), Value::Boolean(true)]
+        ),
+        value => panic!("expected a tuple, found {value}"),
+    }
+
+    assert!(matches!(run("(1,)").unwrap(), Value::Tuple(tuple) if tuple.0.len() == 1));
+    assert_eq!(run("(1)").unwrap(), Value::Float(1.0));
+
+    assert_eq!(run("(1, 2) == (1, 2)").unwrap(), Value::Boolean(true));
+    assert_eq!(run("(1, 2) == (2, 1)").unwrap(), Value::Boolean(false));
+    assert_eq!(run("(1, 2) == [1, 2]").unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn map_literal() {
+    let mut run = script_runner();
+
+    let map = "var m = {\"a\": 1, b: 2}\n";
+    assert_eq!(run(&format!("{map}m[\"b\"]")).unwrap(), Value::Float(2.0));
+    assert_eq!(run(&format!("{map}m.len()")).unwrap(), Value::Integer(2));
+
+    // the last value for a key wins
+    match run("({\"a\": 1, \"b\": 2, \"a\": 3})").unwrap() {
+        Value::Map(map) => {
+            assert_eq!(map.len(), 2);
+            assert_eq!(map.get("a"), Some(&Value::Float(3.0)));
+        }
+        value => panic!("expected a map, found {value}"),
+    }
+}
+
+#[test]
+fn map_copy_on_write() {
+    let mut map = Map::new();
+    map.insert("x".to_string(), Value::Float(1.0));
+
+    // copies share their entries until one of them is changed
+    let mut copy = map.clone();
+    *copy.get_mut("x").unwrap() = Value::Float(2.0);
+    copy.insert("y".to_string(), Value::Float(3.0));
+
+    assert_eq!(map.get("x"), Some(&Value::Float(1.0)));
+    assert_eq!(map.len(), 1);
+    assert_eq!(copy.get("x"), Some(&Value::Float(2.0)));
+    assert_eq!(
+        copy.keys().map(String::as_str).collect::<Vec<_>>(),
+        ["x", "y"]
+    );
+
+    // maps are values in scripts too, so assigning through an alias leaves the
+    // original unchanged
+    let mut run = script_runner();
+    let map = "var a = {x: 1}\nvar b = a\nb[\"x\"] = 2\n";
+    assert_eq!(run(&format!("{map}a[\"x\"]")).unwrap(), Value::Float(1.0));
+    assert_eq!(run(&format!("{map}b[\"x\"]")).unwrap(), Value::Float(2.0));
+    assert_eq!(run(&format!("{map}a == b")).unwrap(), Value::Boolean(false));
+    assert_eq!(run("var c = {x: 1}\nvar d = c\nc == d").unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn numeric_keys_and_equality() {
+    let mut run = script_runner();
+
+    assert_eq!(run("1 == 1.0").unwrap(), Value::Boolean(true));
+    assert_eq!(run("\"ab\".len() == 2").unwrap(), Value::Boolean(true));
+    assert_eq!(run("var nan = 0 / 0\nnan == nan").unwrap(), Value::Boolean(false));
+    assert!(run("var m = {}\nm[0 / 0] = 1").is_err());
+    assert!(run("({(0 / 0): 1})").is_err());
+
+    // a float key and an integer key with the same value are the same key
+    let map = "var m = {}\nm[2] = \"two\"\n";
+    assert_eq!(run(&format!("{map}m[\"ab\".len()]")).unwrap(), Value::from("two"));
+    assert_eq!(run(&format!("{map}m[\"ab\".len()] = \"2\"\nm.len()")).unwrap(), Value::Integer(1));
+}
+
+#[test]
+fn chained_assignment() {
+    let mut run = script_runner();
+
+    let script = "var a = 1\nvar b = 2\na = b = 0\n[a, b]";
+    assert_eq!(
+        run(script).unwrap(),
+        Value::from(vec![Value::Float(0.0), Value::Float(0.0)])
+    );
+
+    let script = "fun f() {\n    var a = 1\n    var list = [0]\n    a = list[0] = 5\n    \
+        return a == list[0]\n}\nf()";
+    assert_eq!(run(script).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn uninitialized_var() {
+    let mut run = script_runner();
+
+    assert_eq!(run("var x\nx").unwrap(), Value::Nil);
+    assert_eq!(run("var y: f64\ny").unwrap(), Value::Nil);
+    assert_eq!(run("var z\nz = 1\nz").unwrap(), Value::Float(1.0));
+
+    let script = "fun f() {\n    var local\n    return local\n}\nf()";
+    assert_eq!(run(script).unwrap(), Value::Nil);
+
+    let script = "class Point {\n    var x\n    var y = 2\n}\nvar p = Point()\n[p.x, p.y]";
+    assert_eq!(
+        run(script).unwrap(),
+        Value::from(vec![Value::Nil, Value::Float(2.0)])
+    );
+}
+
+#[test]
+fn spread() {
+    let mut run = script_runner();
+
+    let add = "fun add(a, b) {\n    return a + b\n}\n";
+    let check = |script: &str| format!("{add}{script}");
+
+    assert_eq!(run(&check("add(...[1, 2])")).unwrap(), Value::Float(3.0));
+    assert_eq!(run(&check("var args = [2]\nadd(1, ...args)")).unwrap(), Value::Float(3.0));
+    assert_eq!(
+        run("[...[1, 2], ...[3], 4] == [1, 2, 3, 4]").unwrap(),
+        Value::Boolean(true)
+    );
+    assert_eq!(run("[0, ...[]] == [0]").unwrap(), Value::Boolean(true));
+
+    // the number of arguments is only checked once the list is spread
+    assert!(run(&check("add(...[1, 2, 3])")).is_err());
+
+    match run("[...1]") {
+        Err(KaonError::RuntimeError(trace)) => assert!(matches!(
+            trace.error,
+            RuntimeError::TypeMismatch { ref expected, ref found, .. }
+                if expected == "List" && found == "f64"
+        )),
+        _ => panic!("expected a type mismatch error"),
+    }
+}
+
+#[test]
+fn for_loop() {
+    let mut run = script_runner();
+
+    let script = "var sum = 0\nfor x in [1, 2, 3] {\n    sum = sum + x\n}\nsum";
+    assert_eq!(run(script).unwrap(), Value::Float(6.0));
+
+    // `nil` is an element like any other, and doesn't end the loop
+    let script = "var count = 0\nfor x in [nil, 1, nil] {\n    count = count + 1\n}\ncount";
+    assert_eq!(run(script).unwrap(), Value::Float(3.0));
+
+    // a class is iterable if its `iter` method returns a value with a `next`
+    // method, which returns `StopIteration` once there are no more values
+    let range = "class Range {\n\
+            var lo = 0\n\
+            var hi = 0\n\
+            create new(lo, hi) {\n\
+                self.lo = lo\n\
+                self.hi = hi\n\
+            }\n\
+            fun iter() {\n\
+                return RangeIter.new(self.lo, self.hi)\n\
+            }\n\
+        }\n\
+        class RangeIter {\n\
+            var i = 0\n\
+            var hi = 0\n\
+            create new(lo, hi) {\n\
+                self.i = lo\n\
+                self.hi = hi\n\
+            }\n\
+            fun next() {\n\
+                if self.i >= self.hi { return StopIteration }\n\
+                self.i = self.i + 1\n\
+                return self.i - 1\n\
+            }\n\
+        }\n";
+    let script = format!(
+        "{range}var seen = 0\nfor i in Range.new(2, 5) {{\n    seen = seen * 10 + i\n}}\nseen"
+    );
+    assert_eq!(run(&script).unwrap(), Value::Float(234.0));
+
+    let script = format!(
+        "{range}var sum = 0\n\
+        for i in Range.new(0, 10) {{\n\
+            if i == 1 {{ continue }}\n\
+            if i == 4 {{ break }}\n\
+            sum = sum + i\n\
+        }}\n\
+        sum"
+    );
+    assert_eq!(run(&script).unwrap(), Value::Float(5.0));
+
+    match run("for x in 1 {}") {
+        Err(KaonError::RuntimeError(trace)) => assert!(matches!(
+            trace.error,
+            RuntimeError::TypeMismatch { ref expected, ref found, .. }
+                if expected == "iterable" && found == "f64"
+        )),
+        _ => panic!("expected a type mismatch error"),
+    }
+}
+
+#[test]
+fn else_if_chain() {
+    let mut kaon = Kaon::new();
+    let mut run = |script: &str| {
+        kaon.run_with_scope(&mut Scope::new(), Source::contents(script))
+            .map(|(value, _)| value)
+    };
+
+    let classify = "fun classify(n) {\n\
+            if n < 0 {\n\
+                return \"negative\"\n\
+            } else if n == 0 {\n\
+                return \"zero\"\n\
+            } else {\n\
+                return \"positive\"\n\
+            }\n\
+        }\n";
+    let check = |n: &str| format!("{classify}classify({n})");
+
+    assert_eq!(run(&check("-1")).unwrap(), Value::from("negative"));
+    assert_eq!(run(&check("0")).unwrap(), Value::from("zero"));
+    assert_eq!(run(&check("1")).unwrap(), Value::from("positive"));
+
+    // only the middle branch runs, and each condition's value is popped
+    let script = "var taken = \"\"\n\
+        var x = 2\n\
+        if x == 1 {\n\
+            taken = taken + \"a\"\n\
+        } else if x == 2 {\n\
+            taken = taken + \"b\"\n\
+        } else {\n\
+            taken = taken + \"c\"\n\
+        }\n\
+        taken";
+    assert_eq!(run(script).unwrap(), Value::from("b"));
+    assert_eq!(kaon.vm.stack.len(), 0);
+}
+
+#[test]
+fn blank_input() {
+    let mut kaon = Kaon::new();
+
+    for input in ["", "// comment", "\n\n\n"] {
+        let result = kaon.run_with_scope(&mut Scope::new(), Source::contents(input));
+        assert!(
+            matches!(result, Ok((Value::Unit, _))),
+            "expected `()` for {input:?}"
+        );
+        assert_eq!(kaon.vm.stack.len(), 0);
+    }
+}
+
+#[test]
+fn expression_statements_are_popped() {
+    let mut kaon = Kaon::new();
+    kaon.vm
+        .register_function("depth", |vm: &mut Vm| vm.stack.len() as f64);
+
+    let mut scope = Scope::new();
+    scope.insert(Symbol("depth".to_string(), Span::empty()));
+
+    let script = "
+        var before = depth()
+        var after = nil
+        var i = 0
+        while i < 1000 {
+            1 + 2
+            \"unused\"
+            [i, i]
+            depth()
+            after = depth()
+            i = i + 1
+        }
+        before == after";
+    let (value, _) = kaon
+        .run_with_scope(&mut scope, Source::contents(script))
+        .unwrap();
+    assert_eq!(value, Value::Boolean(true));
+}
+
+#[test]
+fn chained_comparison() {
+    let mut runner = script_runner();
+    let mut run = |script: &str| runner(script).unwrap();
+
+    assert_eq!(run("var x = 5\n1 < x < 10"), Value::Boolean(true));
+    assert_eq!(run("var x = 15\n1 < x < 10"), Value::Boolean(false));
+    assert_eq!(run("var x = 0\n1 < x < 10"), Value::Boolean(false));
+    assert_eq!(run("1 <= 1 < 2 <= 2 != 3 == 3"), Value::Boolean(true));
+    assert_eq!(run("3 > 2 > 2"), Value::Boolean(false));
+
+    // the middle operand is evaluated once, and the chain stops at the first
+    // comparison that fails
+    let script = "
+        var calls = 0
+        fun f(x) {
+            calls = calls + 1
+            return x
+        }
+        var inside = 1 < f(5) < f(10)
+        var outside = 1 < f(0) < f(20)
+        [inside, outside, calls]";
+    assert_eq!(run(script).to_string(), "[true, false, 3]");
 }